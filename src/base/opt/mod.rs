@@ -809,6 +809,7 @@ mod test {
         let record = OptRecord::from_record(record);
         assert_eq!(record.udp_payload_size(), 0x1234);
         assert_eq!(record.ext_rcode, OptRcode::BadVers.ext());
+        assert_eq!(record.ext_rcode, 1);
         assert_eq!(record.version(), 0xbd);
         assert!(record.dnssec_ok());
     }
@@ -1,21 +1,43 @@
 //! EDNS Options form RFC 7873
+//!
+//! This module also contains support for creating and checking
+//! interoperable server cookies as defined in [RFC 9018].
+//!
+//! [RFC 9018]: https://tools.ietf.org/html/rfc9018
 
 use super::super::iana::OptionCode;
 use super::super::message_builder::OptBuilder;
+use super::super::net::IpAddr;
 use super::super::octets::{
     Compose, OctetsBuilder, Parse, ParseError, Parser, ShortBuf
 };
+use super::super::serial::Serial;
 use super::CodeOptData;
+use core::hash::Hasher;
 
 
 //------------ Cookie --------------------------------------------------------
 
+/// The DNS cookie option.
+///
+/// The option always contains an eight octet client cookie and, in
+/// queries to a server the client has talked to before and in responses,
+/// a server cookie.
 #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-pub struct Cookie([u8; 8]);
+pub struct Cookie {
+    client: [u8; 8],
+    server: Option<ServerCookie>,
+}
 
 impl Cookie {
+    /// Creates a new cookie option with only a client cookie.
     pub fn new(cookie: [u8; 8]) -> Self {
-        Cookie(cookie)
+        Cookie { client: cookie, server: None }
+    }
+
+    /// Creates a new cookie option with both a client and server cookie.
+    pub fn with_server(cookie: [u8; 8], server: ServerCookie) -> Self {
+        Cookie { client: cookie, server: Some(server) }
     }
 
     pub fn push<Target: OctetsBuilder>(
@@ -25,8 +47,92 @@ impl Cookie {
         builder.push(&Self::new(cookie))
     }
 
+    /// Returns the client cookie.
     pub fn cookie(self) -> [u8; 8] {
-        self.0
+        self.client
+    }
+
+    /// Returns the server cookie if present.
+    pub fn server(self) -> Option<ServerCookie> {
+        self.server
+    }
+
+    /// Creates a response cookie with an interoperable server cookie.
+    ///
+    /// The returned option contains the client cookie of `self` and a new
+    /// server cookie as defined in [RFC 9018] for the given timestamp,
+    /// usually [`Serial::now()`], the IP address the query was received
+    /// from, and the server secret.
+    ///
+    /// [RFC 9018]: https://tools.ietf.org/html/rfc9018
+    pub fn create_response(
+        self,
+        timestamp: Serial,
+        client_ip: IpAddr,
+        secret: &[u8; 16],
+    ) -> Self {
+        let mut server = [0u8; 32];
+        server[0] = 1; // Version, followed by three reserved octets.
+        server[4..8].copy_from_slice(&timestamp.into_int().to_be_bytes());
+        let hash = self.server_hash(&server[..8], client_ip, secret);
+        server[8..16].copy_from_slice(&hash);
+        Cookie::with_server(self.client, ServerCookie::new(server, 16))
+    }
+
+    /// Checks that the server cookie is a valid interoperable cookie.
+    ///
+    /// Returns `true` if the option contains a server cookie as defined by
+    /// [RFC 9018] that was created by a server using `secret` for a query
+    /// from `client_ip` and with a timestamp that is neither more than one
+    /// hour in the past nor more than five minutes in the future relative
+    /// to `now`.
+    ///
+    /// [RFC 9018]: https://tools.ietf.org/html/rfc9018
+    pub fn check_server_hash(
+        self,
+        client_ip: IpAddr,
+        secret: &[u8; 16],
+        now: Serial,
+    ) -> bool {
+        let server = match self.server {
+            Some(server) => server,
+            None => return false,
+        };
+        let timestamp = match server.timestamp() {
+            Some(timestamp) => timestamp,
+            None => return false,
+        };
+        if timestamp.add(3600) < now || timestamp > now.add(300) {
+            return false
+        }
+        let server = server.as_slice();
+        self.server_hash(&server[..8], client_ip, secret) == server[8..]
+    }
+
+    /// Calculates the hash part of an interoperable server cookie.
+    ///
+    /// The `prefix` contains version, reserved octets, and timestamp.
+    #[allow(deprecated)] // SipHasher is guaranteed to be SipHash-2-4.
+    fn server_hash(
+        self,
+        prefix: &[u8],
+        client_ip: IpAddr,
+        secret: &[u8; 16],
+    ) -> [u8; 8] {
+        let mut k0 = [0u8; 8];
+        let mut k1 = [0u8; 8];
+        k0.copy_from_slice(&secret[..8]);
+        k1.copy_from_slice(&secret[8..]);
+        let mut hasher = core::hash::SipHasher::new_with_keys(
+            u64::from_le_bytes(k0), u64::from_le_bytes(k1)
+        );
+        hasher.write(&self.client);
+        hasher.write(prefix);
+        match client_ip {
+            IpAddr::V4(addr) => hasher.write(&addr.octets()),
+            IpAddr::V6(addr) => hasher.write(&addr.octets()),
+        }
+        hasher.finish().to_le_bytes()
     }
 }
 
@@ -35,13 +141,23 @@ impl Cookie {
 
 impl<Ref: AsRef<[u8]>> Parse<Ref> for Cookie {
     fn parse(parser: &mut Parser<Ref>) -> Result<Self, ParseError> {
-        let mut res = [0u8; 8];
-        parser.parse_buf(&mut res[..])?;
-        Ok(Self::new(res))
+        let mut client = [0u8; 8];
+        parser.parse_buf(&mut client[..])?;
+        let len = parser.remaining();
+        if len == 0 {
+            return Ok(Self::new(client))
+        }
+        if !(8..=32).contains(&len) {
+            return Err(ParseError::form_error("invalid server cookie length"))
+        }
+        let mut server = [0u8; 32];
+        parser.parse_buf(&mut server[..len])?;
+        Ok(Self::with_server(client, ServerCookie::new(server, len as u8)))
     }
 
     fn skip(parser: &mut Parser<Ref>) -> Result<(), ParseError> {
-        parser.advance(8)
+        parser.advance_to_end();
+        Ok(())
     }
 }
 
@@ -51,7 +167,13 @@ impl Compose for Cookie {
         &self,
         target: &mut T
     ) -> Result<(), ShortBuf> {
-        target.append_slice(&self.0[..])
+        target.append_all(|target| {
+            target.append_slice(&self.client[..])?;
+            if let Some(server) = self.server.as_ref() {
+                target.append_slice(server.as_slice())?;
+            }
+            Ok(())
+        })
     }
 }
 
@@ -62,3 +184,168 @@ impl CodeOptData for Cookie {
     const CODE: OptionCode = OptionCode::Cookie;
 }
 
+
+//------------ ServerCookie --------------------------------------------------
+
+/// A server cookie.
+///
+/// Server cookies are between 8 and 32 octets long. Their content is
+/// opaque to clients.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ServerCookie {
+    /// The octets of the cookie. Octets beyond `len` are always zero.
+    octets: [u8; 32],
+
+    /// The length of the cookie.
+    len: u8,
+}
+
+impl ServerCookie {
+    fn new(octets: [u8; 32], len: u8) -> Self {
+        ServerCookie { octets, len }
+    }
+
+    /// Creates a server cookie from a slice.
+    ///
+    /// Returns `None` if the slice is not between 8 and 32 octets long.
+    pub fn from_slice(slice: &[u8]) -> Option<Self> {
+        if !(8..=32).contains(&slice.len()) {
+            return None
+        }
+        let mut octets = [0u8; 32];
+        octets[..slice.len()].copy_from_slice(slice);
+        Some(Self::new(octets, slice.len() as u8))
+    }
+
+    /// Returns the cookie as a slice.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.octets[..usize::from(self.len)]
+    }
+
+    /// Returns the timestamp of an interoperable server cookie.
+    ///
+    /// Returns `None` if the cookie doesn’t look like a version 1 cookie
+    /// as defined in [RFC 9018].
+    ///
+    /// [RFC 9018]: https://tools.ietf.org/html/rfc9018
+    pub fn timestamp(&self) -> Option<Serial> {
+        if self.len != 16 || self.octets[0] != 1 {
+            return None
+        }
+        let mut timestamp = [0u8; 4];
+        timestamp.copy_from_slice(&self.octets[4..8]);
+        Some(Serial(u32::from_be_bytes(timestamp)))
+    }
+}
+
+impl AsRef<[u8]> for ServerCookie {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+
+//============ Testing =======================================================
+
+#[cfg(all(test, feature = "std"))]
+mod test {
+    use super::*;
+    use super::super::super::octets::Parser;
+    use std::vec::Vec;
+
+    fn hex(s: &str) -> Vec<u8> {
+        (0..s.len()).step_by(2).map(|i| {
+            u8::from_str_radix(&s[i..i + 2], 16).unwrap()
+        }).collect()
+    }
+
+    fn cookie(s: &str) -> Cookie {
+        let mut parser = Parser::from_ref(hex(s));
+        let res = Cookie::parse(&mut parser).unwrap();
+        assert_eq!(parser.remaining(), 0);
+        res
+    }
+
+    fn secret(s: &str) -> [u8; 16] {
+        let mut res = [0u8; 16];
+        res.copy_from_slice(&hex(s));
+        res
+    }
+
+    #[test]
+    fn parse_compose() {
+        let client = cookie("2464c4abcf10c957");
+        assert_eq!(client.cookie(), hex("2464c4abcf10c957").as_slice());
+        assert_eq!(client.server(), None);
+
+        let full = cookie("2464c4abcf10c957010000005cf79f111f8130c3eee29480");
+        assert_eq!(
+            full.server().unwrap().as_slice(),
+            hex("010000005cf79f111f8130c3eee29480").as_slice()
+        );
+        assert_eq!(
+            full.server().unwrap().timestamp(),
+            Some(Serial(1559731985))
+        );
+        let mut buf = Vec::new();
+        full.compose(&mut buf).unwrap();
+        assert_eq!(
+            buf, hex("2464c4abcf10c957010000005cf79f111f8130c3eee29480")
+        );
+
+        let mut parser = Parser::from_ref(hex("2464c4abcf10c9570100"));
+        assert!(Cookie::parse(&mut parser).is_err());
+    }
+
+    // Test vectors from RFC 9018, appendix A.
+    #[test]
+    fn rfc9018_server_cookies() {
+        let tests = [
+            (
+                "2464c4abcf10c957", "198.51.100.100",
+                "e5e973e5a6b2a43f48e7dc849e37bfcf", 1559731985,
+                "2464c4abcf10c957010000005cf79f111f8130c3eee29480",
+            ),
+            (
+                "2464c4abcf10c957", "198.51.100.100",
+                "e5e973e5a6b2a43f48e7dc849e37bfcf", 1559734385,
+                "2464c4abcf10c957010000005cf7a871d4a564a1442aca77",
+            ),
+            (
+                "fc93fc62807ddb86", "203.0.113.203",
+                "e5e973e5a6b2a43f48e7dc849e37bfcf", 1559734700,
+                "fc93fc62807ddb86010000005cf7a9acf73a7810aca2381e",
+            ),
+            (
+                "22681ab97d52c298", "2001:db8:220:1:59de:d0f4:8769:82b8",
+                "dd3bdf9344b678b185a6f5cb60fca715", 1559741817,
+                "22681ab97d52c298010000005cf7c57926556bd0934c72f8",
+            ),
+        ];
+        for &(client, ip, key, time, expected) in tests.iter() {
+            let ip: IpAddr = ip.parse().unwrap();
+            let key = secret(key);
+            let response = cookie(client).create_response(
+                Serial(time), ip, &key
+            );
+            assert_eq!(response, cookie(expected));
+            assert!(response.check_server_hash(ip, &key, Serial(time)));
+            assert!(
+                response.check_server_hash(ip, &key, Serial(time + 3600))
+            );
+            assert!(
+                !response.check_server_hash(ip, &key, Serial(time + 3601))
+            );
+            assert!(
+                response.check_server_hash(ip, &key, Serial(time - 300))
+            );
+            assert!(
+                !response.check_server_hash(ip, &key, Serial(time - 301))
+            );
+            assert!(!response.check_server_hash(
+                "192.0.2.1".parse().unwrap(), &key, Serial(time)
+            ));
+            assert!(!cookie(client).check_server_hash(ip, &key, Serial(time)));
+        }
+    }
+}
@@ -47,6 +47,13 @@
 //! * `chrono`: Adds the [chrono](https://github.com/chronotope/chrono)
 //!   crate as a dependency. This adds support for generating serial numbers
 //!   from time stamps.
+//! * `openssl`: Enables crypto functionality via the
+//!   [openssl](https://github.com/sfackler/rust-openssl) crate. Together
+//!   with the `sign` feature, this enables the `sign::openssl` module. If
+//!   the `validate` feature is enabled as well, the [validate] module uses
+//!   it to verify signatures ring refuses, i.e., Ed448 and RSA keys shorter
+//!   than 1024 bits. Note that this only happens if all three features are
+//!   enabled.
 //! * `master`: Zone file parsing and construction. This will enable the
 //!   [master] module and currently enables the `bytes`, `chrono`, and `std`
 //!   features. Note that feature and module are experimental and will soon
@@ -58,8 +65,7 @@
 //! * `sign`: basic DNSSEC signing support. This will enable the [sign]
 //!   module and requires the `std` feature. Note that this will not directly
 //!   enable actually signing. For that you will also need to pick a crypto
-//!   module via an additional feature. Currently we only support the `ring`
-//!   module, but support for OpenSSL is coming soon.
+//!   module via an additional feature, either `ring` or `openssl`.
 //! * `smallvec`: enables the use of the `Smallvec` type from the
 //!   [smallvec](https://github.com/servo/rust-smallvec) crate as octet
 //!   sequences.
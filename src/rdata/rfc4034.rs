@@ -1711,6 +1711,32 @@ impl std::error::Error for RtypeBitmapError {}
 
 //------------ Friendly Helper Functions -------------------------------------
 
+/// Calculates the digest of a DNSKEY record for a DS or CDS record.
+///
+/// Returns `None` if the digest algorithm isn’t supported. This is shared
+/// by signing and validation. See [RFC 4034, Section 5.1.4].
+///
+/// [RFC 4034, Section 5.1.4]: https://tools.ietf.org/html/rfc4034#section-5.1.4
+#[cfg(all(feature = "ring", feature = "std"))]
+pub(crate) fn dnskey_digest<N: ToDname, Octets: AsRef<[u8]>>(
+    owner: &N,
+    dnskey: &Dnskey<Octets>,
+    digest_type: DigestAlg,
+) -> Option<ring::digest::Digest> {
+    use ring::digest;
+
+    let algorithm = match digest_type {
+        DigestAlg::Sha1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        DigestAlg::Sha256 => &digest::SHA256,
+        DigestAlg::Sha384 => &digest::SHA384,
+        _ => return None,
+    };
+    let mut buf = Vec::new();
+    owner.compose_canonical(&mut buf).unwrap();
+    dnskey.compose_canonical(&mut buf).unwrap();
+    Some(digest::digest(algorithm, &buf))
+}
+
 /// Splits an Rtype value into window number, octet number, and octet mask.
 fn split_rtype(rtype: Rtype) -> (u8, usize, u8) {
     let rtype = rtype.to_int();
@@ -8,10 +8,14 @@ use super::rfc4034::RtypeBitmap;
 use crate::base::charstr::CharStr;
 use crate::base::cmp::CanonicalOrd;
 use crate::base::iana::{Nsec3HashAlg, Rtype};
+#[cfg(feature = "ring")]
+use crate::base::name::{Dname, ToDname};
 use crate::base::octets::{
     Compose, OctetsBuilder, OctetsFrom, OctetsRef, Parse, ParseError, Parser,
     ShortBuf,
 };
+#[cfg(feature = "ring")]
+use crate::base::octets::{EmptyBuilder, FromBuilder};
 use crate::base::rdata::RtypeRecordData;
 #[cfg(feature = "master")]
 use crate::master::scan::{
@@ -22,6 +26,8 @@ use crate::utils::base32;
 use bytes::Bytes;
 use core::cmp::Ordering;
 use core::{fmt, hash};
+#[cfg(feature = "ring")]
+use ring::digest;
 
 //------------ Nsec3 ---------------------------------------------------------
 
@@ -289,9 +295,11 @@ impl<Octets: AsRef<[u8]>> fmt::Display for Nsec3<Octets> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "{} {} {} {:X} ",
-            self.hash_algorithm, self.flags, self.iterations, self.salt
+            "{} {} {} ",
+            self.hash_algorithm, self.flags, self.iterations
         )?;
+        display_salt(&self.salt, f)?;
+        f.write_str(" ")?;
         base32::display_hex(&self.next_owner, f)?;
         write!(f, " {}", self.types)
     }
@@ -519,9 +527,24 @@ impl<Octets: AsRef<[u8]>> fmt::Display for Nsec3param<Octets> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "{} {} {} {:X}",
-            self.hash_algorithm, self.flags, self.iterations, self.salt
-        )
+            "{} {} {} ",
+            self.hash_algorithm, self.flags, self.iterations
+        )?;
+        display_salt(&self.salt, f)
+    }
+}
+
+/// Displays a salt in hex or as `-` if it is empty.
+///
+/// See RFC 5155, section 3.3.
+fn display_salt<Octets: AsRef<[u8]>>(
+    salt: &CharStr<Octets>,
+    f: &mut fmt::Formatter,
+) -> fmt::Result {
+    if salt.as_ref().is_empty() {
+        f.write_str("-")
+    } else {
+        write!(f, "{:X}", salt)
     }
 }
 
@@ -541,3 +564,272 @@ impl<Octets: AsRef<[u8]>> fmt::Debug for Nsec3param<Octets> {
 impl<Octets> RtypeRecordData for Nsec3param<Octets> {
     const RTYPE: Rtype = Rtype::Nsec3param;
 }
+
+//------------ nsec3_hash ----------------------------------------------------
+
+/// Calculates the NSEC3 hash of a domain name.
+///
+/// The hash is calculated as defined in [RFC 5155, Section 5] over the
+/// canonical wire format of `name` using the hash `algorithm`, appending the
+/// `salt`, and re-hashing `iterations` times.
+///
+/// The returned [`Nsec3Hash`] provides the hash in binary form for use in
+/// the next owner field of an NSEC3 record and can be turned into the hashed
+/// owner name of the record.
+///
+/// [RFC 5155, Section 5]: https://tools.ietf.org/html/rfc5155#section-5
+#[cfg(feature = "ring")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ring")))]
+pub fn nsec3_hash<N: ToDname>(
+    name: &N,
+    algorithm: Nsec3HashAlg,
+    iterations: u16,
+    salt: &[u8],
+) -> Result<Nsec3Hash, Nsec3HashError> {
+    let algorithm = match algorithm {
+        Nsec3HashAlg::Sha1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        _ => return Err(Nsec3HashError::UnsupportedAlgorithm),
+    };
+
+    let mut ctx = digest::Context::new(algorithm);
+    let mut buf = [0u8; 64];
+    for label in name.iter_labels() {
+        let len = label.len();
+        buf[0] = len as u8;
+        buf[1..=len].copy_from_slice(label.as_slice());
+        buf[1..=len].make_ascii_lowercase();
+        ctx.update(&buf[..=len]);
+    }
+    ctx.update(salt);
+    let mut res = ctx.finish();
+    for _ in 0..iterations {
+        let mut ctx = digest::Context::new(algorithm);
+        ctx.update(res.as_ref());
+        ctx.update(salt);
+        res = ctx.finish();
+    }
+    Ok(Nsec3Hash(res))
+}
+
+//------------ Nsec3Hash -----------------------------------------------------
+
+/// The NSEC3 hash of a domain name.
+///
+/// Values of this type are created via [`nsec3_hash`]. The hash can be
+/// accessed in binary form via [`as_slice`][Self::as_slice]. Its `Display`
+/// implementation prints the hash in lowercase base32hex which is the form
+/// used as the first label of the owner name of an NSEC3 record. The full
+/// owner name can be created via [`to_owner_name`][Self::to_owner_name].
+#[cfg(feature = "ring")]
+#[cfg_attr(docsrs, doc(cfg(feature = "ring")))]
+#[derive(Clone)]
+pub struct Nsec3Hash(digest::Digest);
+
+#[cfg(feature = "ring")]
+impl Nsec3Hash {
+    /// Returns the binary hash value.
+    pub fn as_slice(&self) -> &[u8] {
+        self.0.as_ref()
+    }
+
+    /// Returns the hashed owner name below the zone apex `apex`.
+    ///
+    /// The name consists of the hash in lowercase base32hex as its first
+    /// label followed by the labels of `apex`.
+    pub fn to_owner_name<Octets, N>(
+        &self,
+        apex: &N,
+    ) -> Result<Dname<Octets>, Nsec3HashError>
+    where
+        Octets: FromBuilder,
+        Octets::Builder: EmptyBuilder,
+        N: ToDname,
+    {
+        let mut label = [0u8; 64];
+        let len = self.write_label(&mut label[1..]);
+        label[0] = len as u8;
+        let mut builder = Octets::Builder::empty();
+        builder
+            .append_slice(&label[..=len])
+            .map_err(|_| Nsec3HashError::LongName)?;
+        for label in apex.iter_labels() {
+            label
+                .compose_canonical(&mut builder)
+                .map_err(|_| Nsec3HashError::LongName)?;
+        }
+        Dname::from_octets(builder.freeze())
+            .map_err(|_| Nsec3HashError::LongName)
+    }
+
+    /// Writes the hash in lowercase base32hex into `target`.
+    ///
+    /// Returns the number of octets written.
+    fn write_label(&self, target: &mut [u8]) -> usize {
+        struct Target<'a>(&'a mut [u8], usize);
+
+        impl<'a> fmt::Write for Target<'a> {
+            fn write_str(&mut self, s: &str) -> fmt::Result {
+                for ch in s.bytes() {
+                    *self.0.get_mut(self.1).ok_or(fmt::Error)? =
+                        ch.to_ascii_lowercase();
+                    self.1 += 1;
+                }
+                Ok(())
+            }
+        }
+
+        let mut target = Target(target, 0);
+        base32::display_hex(self.as_slice(), &mut target).unwrap();
+        target.1
+    }
+}
+
+//--- AsRef
+
+#[cfg(feature = "ring")]
+impl AsRef<[u8]> for Nsec3Hash {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+//--- PartialEq, Eq, PartialOrd, Ord, and Hash
+
+#[cfg(feature = "ring")]
+impl PartialEq for Nsec3Hash {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+#[cfg(feature = "ring")]
+impl Eq for Nsec3Hash {}
+
+#[cfg(feature = "ring")]
+impl PartialOrd for Nsec3Hash {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(feature = "ring")]
+impl Ord for Nsec3Hash {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_slice().cmp(other.as_slice())
+    }
+}
+
+#[cfg(feature = "ring")]
+impl hash::Hash for Nsec3Hash {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        self.as_slice().hash(state)
+    }
+}
+
+//--- Display and Debug
+
+#[cfg(feature = "ring")]
+impl fmt::Display for Nsec3Hash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut label = [0u8; 64];
+        let len = self.write_label(&mut label);
+        // base32hex only ever produces ASCII.
+        f.write_str(core::str::from_utf8(&label[..len]).unwrap())
+    }
+}
+
+#[cfg(feature = "ring")]
+impl fmt::Debug for Nsec3Hash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Nsec3Hash({})", self)
+    }
+}
+
+//------------ Nsec3HashError ------------------------------------------------
+
+/// An NSEC3 hash could not be calculated or used.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Nsec3HashError {
+    /// The hash algorithm is not supported.
+    UnsupportedAlgorithm,
+
+    /// The hashed owner name would be too long.
+    LongName,
+}
+
+//--- Display and Error
+
+impl fmt::Display for Nsec3HashError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Nsec3HashError::UnsupportedAlgorithm => {
+                f.write_str("unsupported NSEC3 hash algorithm")
+            }
+            Nsec3HashError::LongName => {
+                f.write_str("hashed owner name too long")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Nsec3HashError {}
+
+//============ Testing =======================================================
+
+#[cfg(all(test, feature = "ring"))]
+mod test {
+    use super::*;
+    use core::str::FromStr;
+    use std::string::{String, ToString};
+    use std::vec::Vec;
+
+    fn hash(name: &str) -> Nsec3Hash {
+        let salt = [0xaa, 0xbb, 0xcc, 0xdd];
+        let name = Dname::<Vec<u8>>::from_str(name).unwrap();
+        nsec3_hash(&name, Nsec3HashAlg::Sha1, 12, &salt).unwrap()
+    }
+
+    // Test vectors from RFC 5155, Appendix A.
+    #[test]
+    fn owner_hash() {
+        let example = hash("example");
+        assert_eq!(example.to_string(), "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom");
+        assert_eq!(example.as_slice().len(), 20);
+        let mut encoded = String::new();
+        base32::display_hex(example.as_slice(), &mut encoded).unwrap();
+        assert_eq!(encoded, "0P9MHAVEQVM6T7VBL5LOP2U3T2RP3TOM");
+
+        assert_eq!(
+            hash("a.example").to_string(),
+            "35mthgpgcu1qg68fab165klnsnk3dpvl"
+        );
+        assert_eq!(
+            hash("x.y.w.EXAMPLE").to_string(),
+            "2vptu5timamqttgl4luu9kg21e0aor3s"
+        );
+        assert_eq!(
+            hash("*.w.example").to_string(),
+            "r53bq7cc2uvmubfu5ocmm6pers9tk9en"
+        );
+        assert_eq!(
+            nsec3_hash(&Dname::root_vec(), Nsec3HashAlg::from_int(2), 0, &[])
+                .err(),
+            Some(Nsec3HashError::UnsupportedAlgorithm)
+        );
+    }
+
+    #[test]
+    fn owner_name() {
+        let apex = Dname::<Vec<u8>>::from_str("example").unwrap();
+        let owner: Dname<Vec<u8>> =
+            hash("ai.example").to_owner_name(&apex).unwrap();
+        assert_eq!(
+            owner,
+            Dname::<Vec<u8>>::from_str(
+                "gjeqe526plbf1g8mklp59enfd789njgi.example"
+            )
+            .unwrap()
+        );
+    }
+}
@@ -0,0 +1,897 @@
+//! A caching resolver.
+//!
+//! The [`CachingResolver`] wraps any other [`Resolver`] and keeps the
+//! answers it receives from it for as long as their records allow. Repeated
+//! questions are then answered from the cache without asking the wrapped
+//! resolver again.
+//!
+//! Positive answers are kept for the smallest TTL of the records in their
+//! answer section. Negative answers – both NXDOMAIN and answers without any
+//! records of the requested type – are kept for the time given by the SOA
+//! record in their authority section as described in [RFC 2308]. Answers
+//! without such an SOA record as well as answers with any other response
+//! code or with the TC bit set are not cached. When an answer is returned
+//! from the cache, the TTLs of its records are reduced by the time it has
+//! spent there.
+//!
+//! The number of cached answers is limited. If the limit is reached, the
+//! answer that has been used least recently is dropped.
+//!
+//! Optionally, expired answers can be kept for a while longer and used if
+//! the wrapped resolver fails to provide a fresh answer. This is known as
+//! _serve-stale_ and is described in [RFC 8767]. It is disabled by default
+//! and can be enabled via the [`max_stale`][CacheConf::max_stale] field of
+//! the configuration.
+//!
+//! [RFC 2308]: https://tools.ietf.org/html/rfc2308
+//! [RFC 8767]: https://tools.ietf.org/html/rfc8767
+
+use super::resolver::{QueryError, Resolver};
+use crate::base::iana::{Class, Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::name::{Dname, ParsedDname, ToDname};
+use crate::base::octets::{Parse, ParseError, Parser};
+use crate::base::question::Question;
+use crate::rdata::Soa;
+use bytes::{Bytes, BytesMut};
+use std::boxed::Box;
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+use std::{cmp, io};
+
+//------------ CachingResolver -----------------------------------------------
+
+/// A resolver that caches the answers of another resolver.
+///
+/// The resolver answers questions from its cache if possible and forwards
+/// them to the wrapped resolver otherwise. See the
+/// [module documentation][self] for the rules for caching answers.
+///
+/// Values can be cloned cheaply if the wrapped resolver can. All clones
+/// share the same cache.
+#[derive(Clone, Debug)]
+pub struct CachingResolver<R> {
+    /// The resolver asked for answers.
+    resolver: R,
+
+    /// The configuration of the cache.
+    conf: Arc<CacheConf>,
+
+    /// The cached answers.
+    cache: Arc<Mutex<Cache>>,
+}
+
+impl<R> CachingResolver<R> {
+    /// Creates a new caching resolver using the default configuration.
+    pub fn new(resolver: R) -> Self {
+        Self::with_conf(resolver, CacheConf::default())
+    }
+
+    /// Creates a new caching resolver using the given configuration.
+    pub fn with_conf(resolver: R, conf: CacheConf) -> Self {
+        CachingResolver {
+            resolver,
+            conf: Arc::new(conf),
+            cache: Default::default(),
+        }
+    }
+
+    /// Returns a reference to the wrapped resolver.
+    pub fn resolver(&self) -> &R {
+        &self.resolver
+    }
+
+    /// Returns a reference to the configuration of the cache.
+    pub fn conf(&self) -> &CacheConf {
+        &self.conf
+    }
+
+    /// Returns the number of answers currently in the cache.
+    ///
+    /// This includes expired answers that haven’t been removed yet.
+    pub fn len(&self) -> usize {
+        self.cache.lock().unwrap().len()
+    }
+
+    /// Returns whether the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all answers from the cache.
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear()
+    }
+}
+
+impl<R: Resolver> CachingResolver<R> {
+    /// Answers a question.
+    ///
+    /// Returns a cached answer if there is one. Otherwise asks the wrapped
+    /// resolver and caches its answer. Errors of the wrapped resolver are
+    /// passed through as is unless a stale answer can be used instead.
+    pub async fn query<N: ToDname, Q: Into<Question<N>>>(
+        &self,
+        question: Q,
+    ) -> Result<Answer, QueryError> {
+        let question = question.into();
+        self.run(Question::new(
+            question.qname().to_bytes(),
+            question.qtype(),
+            question.qclass(),
+        ))
+        .await
+    }
+
+    /// Answers a question with an owned name.
+    fn run<'s>(&'s self, question: Question<Dname<Bytes>>) -> QueryFuture<'s>
+    where
+        R::Query: 's,
+    {
+        Box::pin(async move {
+            let now = Instant::now();
+            let stale = match self.cache.lock().unwrap().get(&question, now) {
+                Lookup::Hit(answer) => return Ok(answer),
+                Lookup::Stale(answer) => Some(answer),
+                Lookup::Miss => None,
+            };
+            let answer = match self.resolver.query(question.clone()).await {
+                Ok(answer) => answer,
+                Err(err) => {
+                    return match stale {
+                        Some(answer) => {
+                            self.stale_used(&question);
+                            Ok(answer)
+                        }
+                        None => Err(err),
+                    }
+                }
+            };
+            let message = Message::from_octets(Bytes::copy_from_slice(
+                answer.as_ref().as_slice(),
+            ))
+            .map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "short message")
+            })?;
+            if let Some(answer) = stale {
+                if matches!(
+                    message.header().rcode(),
+                    Rcode::ServFail | Rcode::Refused
+                ) {
+                    self.stale_used(&question);
+                    return Ok(answer);
+                }
+            }
+            self.cache.lock().unwrap().insert(
+                &question,
+                &message,
+                Instant::now(),
+                &self.conf,
+            );
+            Ok(Answer::new(message, false, false))
+        })
+    }
+
+    /// Notes that a stale answer had to be used for a question.
+    fn stale_used(&self, question: &Question<Dname<Bytes>>) {
+        self.cache.lock().unwrap().failed(
+            question,
+            Instant::now(),
+            &self.conf,
+        )
+    }
+}
+
+//--- Resolver
+
+impl<'a, R: Resolver> Resolver for &'a CachingResolver<R> {
+    type Octets = Bytes;
+    type Answer = Answer;
+    type Query = QueryFuture<'a>;
+
+    fn query<N, Q>(&self, question: Q) -> Self::Query
+    where
+        N: ToDname,
+        Q: Into<Question<N>>,
+    {
+        let question = question.into();
+        self.run(Question::new(
+            question.qname().to_bytes(),
+            question.qtype(),
+            question.qclass(),
+        ))
+    }
+}
+
+/// The future returned by queries of a caching resolver.
+pub type QueryFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<Answer, QueryError>> + 'a>>;
+
+//------------ CacheConf -----------------------------------------------------
+
+/// Configuration for a caching resolver.
+///
+/// A default configuration can be created via [`CacheConf::new`]. All
+/// fields are public and can be changed afterwards.
+#[derive(Clone, Debug)]
+pub struct CacheConf {
+    /// The maximum number of answers kept in the cache.
+    ///
+    /// If the cache is full, the least recently used answer is dropped.
+    ///
+    /// This field defaults to 10,000.
+    pub max_entries: usize,
+
+    /// The minimum time in seconds a positive answer is kept.
+    ///
+    /// This field defaults to 0.
+    pub min_ttl: u32,
+
+    /// The maximum time in seconds a positive answer is kept.
+    ///
+    /// This field defaults to 86400 seconds, i.e., one day.
+    pub max_ttl: u32,
+
+    /// The maximum time in seconds a negative answer is kept.
+    ///
+    /// [RFC 2308] recommends a value between one and three hours.
+    ///
+    /// This field defaults to 10800 seconds, i.e., three hours.
+    ///
+    /// [RFC 2308]: https://tools.ietf.org/html/rfc2308
+    pub max_negative_ttl: u32,
+
+    /// The time in seconds an expired answer is kept for serve-stale.
+    ///
+    /// If this is not zero, an answer is kept for this long after it has
+    /// expired and is returned if the wrapped resolver fails to answer the
+    /// question or answers with SERVFAIL or REFUSED. [RFC 8767] recommends
+    /// a value between one and three days.
+    ///
+    /// This field defaults to 0, i.e., serve-stale is disabled.
+    ///
+    /// [RFC 8767]: https://tools.ietf.org/html/rfc8767
+    pub max_stale: u32,
+
+    /// The TTL in seconds of the records of a stale answer.
+    ///
+    /// Once a stale answer had to be used, the wrapped resolver isn’t
+    /// asked again for the same question for this long either.
+    ///
+    /// This field defaults to 30 seconds as recommended by RFC 8767.
+    pub stale_answer_ttl: u32,
+}
+
+impl CacheConf {
+    /// Creates a new configuration with default values.
+    pub fn new() -> Self {
+        CacheConf {
+            max_entries: 10_000,
+            min_ttl: 0,
+            max_ttl: 86400,
+            max_negative_ttl: 10800,
+            max_stale: 0,
+            stale_answer_ttl: 30,
+        }
+    }
+}
+
+impl Default for CacheConf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//------------ Answer --------------------------------------------------------
+
+/// The answer of a caching resolver.
+#[derive(Clone)]
+pub struct Answer {
+    /// The answer message.
+    message: Message<Bytes>,
+
+    /// Whether the answer was taken from the cache.
+    cached: bool,
+
+    /// Whether the answer was expired.
+    stale: bool,
+}
+
+impl Answer {
+    /// Creates a new answer.
+    fn new(message: Message<Bytes>, cached: bool, stale: bool) -> Self {
+        Answer {
+            message,
+            cached,
+            stale,
+        }
+    }
+
+    /// Returns whether the answer was taken from the cache.
+    pub fn is_cached(&self) -> bool {
+        self.cached
+    }
+
+    /// Returns whether the answer is a stale answer.
+    ///
+    /// A stale answer is an expired answer from the cache that was used
+    /// because the wrapped resolver couldn’t provide a fresh one.
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    /// Returns a reference to the answer message.
+    pub fn message(&self) -> &Message<Bytes> {
+        &self.message
+    }
+
+    /// Converts the answer into the answer message.
+    pub fn into_message(self) -> Message<Bytes> {
+        self.message
+    }
+}
+
+//--- AsRef
+
+impl AsRef<Message<Bytes>> for Answer {
+    fn as_ref(&self) -> &Message<Bytes> {
+        &self.message
+    }
+}
+
+//------------ Cache ---------------------------------------------------------
+
+/// The key of a cached answer.
+type Key = (Dname<Bytes>, Rtype, Class);
+
+/// The answers cached by a caching resolver.
+#[derive(Debug, Default)]
+struct Cache {
+    /// The cached answers.
+    entries: HashMap<Key, Entry>,
+
+    /// The keys of the cached answers ordered by their last use.
+    lru: BTreeMap<u64, Key>,
+
+    /// The counter for marking the last use of an answer.
+    tick: u64,
+}
+
+impl Cache {
+    /// Returns the number of cached answers.
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Removes all answers.
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.lru.clear();
+    }
+
+    /// Looks up the answer to a question.
+    fn get(
+        &mut self,
+        question: &Question<Dname<Bytes>>,
+        now: Instant,
+    ) -> Lookup {
+        let key = key(question);
+        let (expired, dead, recheck) = match self.entries.get(&key) {
+            Some(entry) => (
+                entry.expires <= now,
+                entry.stale_until <= now,
+                entry.recheck.map(|recheck| now < recheck).unwrap_or(false),
+            ),
+            None => return Lookup::Miss,
+        };
+        if dead {
+            self.remove(&key);
+            return Lookup::Miss;
+        }
+        self.touch(&key);
+        let entry = &self.entries[&key];
+        if !expired {
+            Lookup::Hit(entry.answer(now))
+        } else if recheck {
+            Lookup::Hit(entry.stale_answer())
+        } else {
+            Lookup::Stale(entry.stale_answer())
+        }
+    }
+
+    /// Caches the answer to a question.
+    ///
+    /// If the answer can’t be cached, a previously cached answer is kept.
+    fn insert(
+        &mut self,
+        question: &Question<Dname<Bytes>>,
+        message: &Message<Bytes>,
+        now: Instant,
+        conf: &CacheConf,
+    ) {
+        let entry = match Entry::new(question, message, now, conf) {
+            Some(entry) => entry,
+            None => return,
+        };
+        let key = key(question);
+        self.remove(&key);
+        while self.entries.len() >= cmp::max(conf.max_entries, 1) {
+            let oldest = match self.lru.keys().next() {
+                Some(tick) => *tick,
+                None => break,
+            };
+            if let Some(key) = self.lru.remove(&oldest) {
+                self.entries.remove(&key);
+            }
+        }
+        self.tick += 1;
+        self.lru.insert(self.tick, key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                tick: self.tick,
+                ..entry
+            },
+        );
+    }
+
+    /// Notes that the wrapped resolver failed to refresh an answer.
+    fn failed(
+        &mut self,
+        question: &Question<Dname<Bytes>>,
+        now: Instant,
+        conf: &CacheConf,
+    ) {
+        if let Some(entry) = self.entries.get_mut(&key(question)) {
+            entry.recheck =
+                Some(now + Duration::from_secs(conf.stale_answer_ttl.into()));
+        }
+    }
+
+    /// Marks an answer as used most recently.
+    fn touch(&mut self, key: &Key) {
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.lru.remove(&entry.tick);
+            self.tick += 1;
+            entry.tick = self.tick;
+            self.lru.insert(self.tick, key.clone());
+        }
+    }
+
+    /// Removes an answer.
+    fn remove(&mut self, key: &Key) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.lru.remove(&entry.tick);
+        }
+    }
+}
+
+/// Returns the cache key for a question.
+fn key(question: &Question<Dname<Bytes>>) -> Key {
+    (
+        question.qname().clone(),
+        question.qtype(),
+        question.qclass(),
+    )
+}
+
+//------------ Lookup --------------------------------------------------------
+
+/// The result of looking up a question in the cache.
+enum Lookup {
+    /// There is an answer that can be used.
+    Hit(Answer),
+
+    /// There is an expired answer that can be used if all else fails.
+    Stale(Answer),
+
+    /// There is no answer.
+    Miss,
+}
+
+//------------ Entry ---------------------------------------------------------
+
+/// A cached answer.
+#[derive(Debug)]
+struct Entry {
+    /// The answer message as it was received.
+    message: Bytes,
+
+    /// The positions and original values of the TTLs in the message.
+    ttls: Vec<(usize, u32)>,
+
+    /// The time the answer may be kept in the cache.
+    ttl: u32,
+
+    /// The time the answer was added to the cache.
+    inserted: Instant,
+
+    /// The time the answer expires.
+    expires: Instant,
+
+    /// The time the answer can’t be used as a stale answer any more.
+    stale_until: Instant,
+
+    /// Until when to use the stale answer without asking again.
+    recheck: Option<Instant>,
+
+    /// The TTL of the records of a stale answer.
+    stale_ttl: u32,
+
+    /// The last use of the answer.
+    tick: u64,
+}
+
+impl Entry {
+    /// Creates a new entry if the message can be cached.
+    fn new(
+        question: &Question<Dname<Bytes>>,
+        message: &Message<Bytes>,
+        now: Instant,
+        conf: &CacheConf,
+    ) -> Option<Self> {
+        let ttl = cache_ttl(message, question.qtype(), conf)?;
+        if ttl == 0 && conf.max_stale == 0 {
+            return None;
+        }
+        let ttls = ttl_positions(message.as_slice()).ok()?;
+        let expires = now + Duration::from_secs(ttl.into());
+        Some(Entry {
+            message: message.as_octets().clone(),
+            ttls,
+            ttl,
+            inserted: now,
+            expires,
+            stale_until: expires + Duration::from_secs(conf.max_stale.into()),
+            recheck: None,
+            stale_ttl: conf.stale_answer_ttl,
+            tick: 0,
+        })
+    }
+
+    /// Returns the answer at `now` with the TTLs adjusted.
+    fn answer(&self, now: Instant) -> Answer {
+        let elapsed = now
+            .checked_duration_since(self.inserted)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or(0);
+        let remaining = u64::from(self.ttl).saturating_sub(elapsed) as u32;
+        self.make_answer(|ttl| cmp::min(ttl, remaining), false)
+    }
+
+    /// Returns the stale answer.
+    ///
+    /// As suggested by RFC 8767, the TTLs are set to the stale answer TTL.
+    fn stale_answer(&self) -> Answer {
+        let ttl = self.stale_ttl;
+        self.make_answer(|_| ttl, true)
+    }
+
+    /// Creates an answer with the TTLs changed via `op`.
+    fn make_answer<F: Fn(u32) -> u32>(&self, op: F, stale: bool) -> Answer {
+        let mut message = BytesMut::from(self.message.as_ref());
+        for &(pos, ttl) in &self.ttls {
+            message[pos..pos + 4].copy_from_slice(&op(ttl).to_be_bytes());
+        }
+        Answer::new(
+            Message::from_octets(message.freeze()).unwrap(),
+            true,
+            stale,
+        )
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns how long a response may be cached.
+///
+/// Returns `None` if the response must not be cached at all.
+fn cache_ttl(
+    message: &Message<Bytes>,
+    qtype: Rtype,
+    conf: &CacheConf,
+) -> Option<u32> {
+    let header = message.header();
+    if header.tc() {
+        return None;
+    }
+    let rcode = header.rcode();
+    if rcode != Rcode::NoError && rcode != Rcode::NXDomain {
+        return None;
+    }
+    let mut ttl: Option<u32> = None;
+    let mut answered = false;
+    for record in message.answer().ok()? {
+        let record = record.ok()?;
+        if record.rtype() == qtype || qtype == Rtype::Any {
+            answered = true;
+        }
+        ttl = Some(match ttl {
+            Some(ttl) => cmp::min(ttl, record.ttl()),
+            None => record.ttl(),
+        });
+    }
+    if rcode == Rcode::NoError && answered {
+        let ttl = ttl?;
+        return Some(cmp::min(cmp::max(ttl, conf.min_ttl), conf.max_ttl));
+    }
+
+    // A negative answer. RFC 2308, section 5 says to use the smaller of
+    // the SOA’s TTL and its minimum field.
+    let soa = message
+        .authority()
+        .ok()?
+        .limit_to::<Soa<ParsedDname<&Bytes>>>()
+        .filter_map(Result::ok)
+        .next()?;
+    let negative = cmp::min(
+        cmp::min(soa.ttl(), soa.data().minimum()),
+        conf.max_negative_ttl,
+    );
+    Some(match ttl {
+        Some(ttl) => cmp::min(ttl, negative),
+        None => negative,
+    })
+}
+
+/// Returns the positions and values of the TTLs of all records.
+///
+/// The TTL field of an OPT record is skipped since it doesn’t contain a
+/// TTL.
+fn ttl_positions(message: &[u8]) -> Result<Vec<(usize, u32)>, ParseError> {
+    let mut parser = Parser::from_ref(message);
+    parser.advance(4)?;
+    let qdcount = parser.parse_u16()?;
+    let rrcount = usize::from(parser.parse_u16()?)
+        + usize::from(parser.parse_u16()?)
+        + usize::from(parser.parse_u16()?);
+    for _ in 0..qdcount {
+        Question::<ParsedDname<&[u8]>>::skip(&mut parser)?;
+    }
+    let mut res = Vec::with_capacity(rrcount);
+    for _ in 0..rrcount {
+        ParsedDname::skip(&mut parser)?;
+        let rtype = parser.parse_u16()?;
+        parser.advance(2)?;
+        let pos = parser.pos();
+        let ttl = parser.parse_u32()?;
+        if rtype != Rtype::Opt.to_int() {
+            res.push((pos, ttl))
+        }
+        let len = parser.parse_u16()?;
+        parser.advance(len.into())?;
+    }
+    Ok(res)
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::message_builder::{AnswerBuilder, MessageBuilder};
+    use crate::base::serial::Serial;
+    use crate::rdata::A;
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    fn name(s: &str) -> Dname<Bytes> {
+        Dname::from_str(s).unwrap()
+    }
+
+    fn question(qname: &str, qtype: Rtype) -> Question<Dname<Bytes>> {
+        Question::new_in(name(qname), qtype)
+    }
+
+    fn response(
+        qname: &str,
+        qtype: Rtype,
+        rcode: Rcode,
+    ) -> AnswerBuilder<BytesMut> {
+        let mut msg = MessageBuilder::new_bytes();
+        msg.header_mut().set_qr(true);
+        msg.header_mut().set_rcode(rcode);
+        let mut msg = msg.question();
+        msg.push((name(qname), qtype)).unwrap();
+        msg.answer()
+    }
+
+    fn positive(ttl: u32) -> Message<Bytes> {
+        let mut msg = response("www.example.com", Rtype::A, Rcode::NoError);
+        msg.push((
+            name("www.example.com"),
+            ttl,
+            A::new(Ipv4Addr::new(192, 0, 2, 1)),
+        ))
+        .unwrap();
+        let mut msg = msg.additional();
+        msg.opt(|opt| {
+            opt.set_dnssec_ok(true);
+            Ok(())
+        })
+        .unwrap();
+        msg.into_message()
+    }
+
+    fn negative(rcode: Rcode, soa_ttl: u32, minimum: u32) -> Message<Bytes> {
+        let mut msg =
+            response("www.example.com", Rtype::Aaaa, rcode).authority();
+        msg.push((
+            name("example.com"),
+            soa_ttl,
+            Soa::new(
+                name("ns.example.com"),
+                name("hostmaster.example.com"),
+                Serial(1),
+                3600,
+                600,
+                86400,
+                minimum,
+            ),
+        ))
+        .unwrap();
+        msg.into_message()
+    }
+
+    fn ttls(answer: &Answer) -> Vec<u32> {
+        answer
+            .message()
+            .answer()
+            .unwrap()
+            .chain(answer.message().authority().unwrap())
+            .map(|record| record.unwrap().ttl())
+            .collect()
+    }
+
+    fn hit(lookup: Lookup) -> Answer {
+        match lookup {
+            Lookup::Hit(answer) => answer,
+            _ => panic!("expected cache hit"),
+        }
+    }
+
+    #[test]
+    fn positive_answers() {
+        let conf = CacheConf::new();
+        let mut cache = Cache::default();
+        let question = question("www.example.com", Rtype::A);
+        let now = Instant::now();
+        cache.insert(&question, &positive(300), now, &conf);
+
+        let answer = hit(cache.get(&question, now));
+        assert!(answer.is_cached());
+        assert_eq!(ttls(&answer), [300]);
+        let answer =
+            hit(cache.get(&question, now + Duration::from_secs(100)));
+        assert_eq!(ttls(&answer), [200]);
+        assert!(answer.message().opt().unwrap().dnssec_ok());
+        assert!(matches!(
+            cache.get(&question, now + Duration::from_secs(300)),
+            Lookup::Miss
+        ));
+        assert_eq!(cache.len(), 0);
+
+        // The TTL is capped by max_ttl.
+        let conf = CacheConf {
+            max_ttl: 60,
+            ..Default::default()
+        };
+        cache.insert(&question, &positive(300), now, &conf);
+        let answer = hit(cache.get(&question, now + Duration::from_secs(10)));
+        assert_eq!(ttls(&answer), [50]);
+
+        // Names are compared case-insensitively.
+        assert!(matches!(
+            cache.get(&self::question("WWW.example.COM", Rtype::A), now),
+            Lookup::Hit(_)
+        ));
+        assert!(matches!(
+            cache.get(&self::question("www.example.com", Rtype::Aaaa), now),
+            Lookup::Miss
+        ));
+    }
+
+    #[test]
+    fn negative_answers() {
+        let conf = CacheConf::new();
+        let mut cache = Cache::default();
+        let question = question("www.example.com", Rtype::Aaaa);
+        let now = Instant::now();
+
+        // NXDOMAIN uses the SOA minimum if it is smaller than the TTL.
+        cache.insert(
+            &question,
+            &negative(Rcode::NXDomain, 3600, 300),
+            now,
+            &conf,
+        );
+        let answer = hit(cache.get(&question, now + Duration::from_secs(60)));
+        assert_eq!(answer.message().header().rcode(), Rcode::NXDomain);
+        assert_eq!(ttls(&answer), [240]);
+
+        // NODATA uses the SOA TTL if it is smaller than the minimum.
+        cache.insert(
+            &question,
+            &negative(Rcode::NoError, 120, 300),
+            now,
+            &conf,
+        );
+        let answer = hit(cache.get(&question, now + Duration::from_secs(60)));
+        assert_eq!(ttls(&answer), [60]);
+
+        // Negative answers without SOA aren’t cached.
+        cache.clear();
+        let msg = response("www.example.com", Rtype::Aaaa, Rcode::NXDomain)
+            .into_message();
+        cache.insert(&question, &msg, now, &conf);
+        assert_eq!(cache.len(), 0);
+
+        // Neither are other errors.
+        let msg = response("www.example.com", Rtype::Aaaa, Rcode::ServFail)
+            .into_message();
+        cache.insert(&question, &msg, now, &conf);
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn lru_eviction() {
+        let conf = CacheConf {
+            max_entries: 2,
+            ..Default::default()
+        };
+        let mut cache = Cache::default();
+        let now = Instant::now();
+        let first = question("www.example.com", Rtype::A);
+        let second = question("www.example.com", Rtype::Aaaa);
+        let third = question("www.example.com", Rtype::Mx);
+        cache.insert(&first, &positive(300), now, &conf);
+        cache.insert(
+            &second,
+            &negative(Rcode::NoError, 300, 300),
+            now,
+            &conf,
+        );
+        hit(cache.get(&first, now));
+        cache.insert(&third, &negative(Rcode::NoError, 300, 300), now, &conf);
+        assert_eq!(cache.len(), 2);
+        assert!(matches!(cache.get(&second, now), Lookup::Miss));
+        hit(cache.get(&first, now));
+        hit(cache.get(&third, now));
+    }
+
+    #[test]
+    fn serve_stale() {
+        let conf = CacheConf {
+            max_stale: 3600,
+            ..Default::default()
+        };
+        let mut cache = Cache::default();
+        let question = question("www.example.com", Rtype::A);
+        let now = Instant::now();
+        cache.insert(&question, &positive(300), now, &conf);
+
+        let later = now + Duration::from_secs(600);
+        let answer = match cache.get(&question, later) {
+            Lookup::Stale(answer) => answer,
+            _ => panic!("expected stale answer"),
+        };
+        assert!(answer.is_stale());
+        assert_eq!(ttls(&answer), [30]);
+
+        // After a failure, the stale answer is used right away.
+        cache.failed(&question, later, &conf);
+        assert!(hit(cache.get(&question, later)).is_stale());
+        assert!(matches!(
+            cache.get(&question, later + Duration::from_secs(30)),
+            Lookup::Stale(_)
+        ));
+        assert!(matches!(
+            cache.get(&question, now + Duration::from_secs(3900)),
+            Lookup::Miss
+        ));
+    }
+}
@@ -5,8 +5,7 @@ use crate::base::message::RecordIter;
 use crate::base::name::{Dname, DnameBuilder, ParsedDname};
 use crate::base::octets::{Octets128, OctetsRef};
 use crate::rdata::Ptr;
-use crate::resolv::resolver::Resolver;
-use std::io;
+use crate::resolv::resolver::{QueryError, Resolver};
 use std::net::IpAddr;
 use std::str::FromStr;
 
@@ -23,7 +22,7 @@ use std::str::FromStr;
 pub async fn lookup_addr<R: Resolver>(
     resolv: &R,
     addr: IpAddr,
-) -> Result<FoundAddrs<R>, io::Error> {
+) -> Result<FoundAddrs<R>, QueryError> {
     let name = dname_from_addr(addr);
     resolv.query((name, Rtype::Ptr)).await.map(FoundAddrs)
 }
@@ -37,6 +36,11 @@ pub async fn lookup_addr<R: Resolver>(
 pub struct FoundAddrs<R: Resolver>(R::Answer);
 
 impl<R: Resolver> FoundAddrs<R> {
+    /// Creates a new value from the answer to a PTR query.
+    pub fn new(answer: R::Answer) -> Self {
+        FoundAddrs(answer)
+    }
+
     /// Returns an iterator over the host names.
     pub fn iter(&self) -> FoundAddrsIter<&R::Octets>
     where
@@ -94,7 +98,7 @@ impl<Ref: OctetsRef> Iterator for FoundAddrsIter<Ref> {
 //------------ Helper Functions ---------------------------------------------
 
 /// Translates an IP address into a domain name.
-fn dname_from_addr(addr: IpAddr) -> Dname<Octets128> {
+pub(crate) fn dname_from_addr(addr: IpAddr) -> Dname<Octets128> {
     match addr {
         IpAddr::V4(addr) => {
             let octets = addr.octets();
@@ -5,9 +5,10 @@ use crate::base::message::RecordIter;
 use crate::base::name::{ParsedDname, ToDname, ToRelativeDname};
 use crate::base::octets::OctetsRef;
 use crate::rdata::{Aaaa, A};
-use crate::resolv::resolver::{Resolver, SearchNames};
+use crate::resolv::resolver::{QueryError, Resolver, SearchNames};
 use std::io;
 use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::vec::{self, Vec};
 
 //------------ lookup_host ---------------------------------------------------
 
@@ -23,7 +24,7 @@ use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
 pub async fn lookup_host<R: Resolver>(
     resolver: &R,
     qname: impl ToDname,
-) -> Result<FoundHosts<R>, io::Error> {
+) -> Result<FoundHosts<R>, QueryError> {
     let (a, aaaa) = tokio::join!(
         resolver.query((&qname, Rtype::A)),
         resolver.query((&qname, Rtype::Aaaa)),
@@ -36,7 +37,7 @@ pub async fn lookup_host<R: Resolver>(
 pub async fn search_host<R: Resolver + SearchNames>(
     resolver: &R,
     qname: impl ToRelativeDname,
-) -> Result<FoundHosts<R>, io::Error> {
+) -> Result<FoundHosts<R>, QueryError> {
     for suffix in resolver.search_iter() {
         if let Ok(name) = (&qname).chain(suffix) {
             if let Ok(answer) = lookup_host(resolver, name).await {
@@ -62,17 +63,22 @@ pub async fn search_host<R: Resolver + SearchNames>(
 #[derive(Debug)]
 pub struct FoundHosts<R: Resolver> {
     /// The answer to the AAAA query.
-    aaaa: Result<R::Answer, io::Error>,
+    aaaa: Result<R::Answer, QueryError>,
 
     /// The answer to the A query.
-    a: Result<R::Answer, io::Error>,
+    a: Result<R::Answer, QueryError>,
+
+    /// The addresses if they have been reordered or mapped.
+    ///
+    /// If this is `None`, the addresses are taken from the answers.
+    addrs: Option<Vec<IpAddr>>,
 }
 
 impl<R: Resolver> FoundHosts<R> {
     pub fn new(
-        aaaa: Result<R::Answer, io::Error>,
-        a: Result<R::Answer, io::Error>,
-    ) -> Result<Self, io::Error> {
+        aaaa: Result<R::Answer, QueryError>,
+        a: Result<R::Answer, QueryError>,
+    ) -> Result<Self, QueryError> {
         if aaaa.is_err() && a.is_err() {
             match aaaa {
                 Err(err) => return Err(err),
@@ -80,7 +86,11 @@ impl<R: Resolver> FoundHosts<R> {
             }
         }
 
-        Ok(FoundHosts { aaaa, a })
+        Ok(FoundHosts {
+            aaaa,
+            a,
+            addrs: None,
+        })
     }
 
     pub fn is_empty(&self) -> bool {
@@ -130,6 +140,15 @@ where
 
     /// Returns an iterator over the IP addresses returned by the lookup.
     pub fn iter(&self) -> FoundHostsIter<&R::Octets> {
+        if let Some(ref addrs) = self.addrs {
+            return FoundHostsIter {
+                aaaa_name: None,
+                a_name: None,
+                aaaa: None,
+                a: None,
+                addrs: Some(addrs.clone().into_iter()),
+            };
+        }
         FoundHostsIter {
             aaaa_name: self
                 .aaaa
@@ -155,9 +174,37 @@ where
                     .and_then(|msg| msg.as_ref().answer().ok())
                     .map(|answer| answer.limit_to::<A>())
             },
+            addrs: None,
         }
     }
 
+    /// Sorts the IP addresses with a key extraction function.
+    ///
+    /// The sort is stable, i.e., addresses with equal keys keep their
+    /// order. Iterators created afterwards return the addresses in the new
+    /// order.
+    pub fn sort_by_key<K, F>(&mut self, f: F)
+    where
+        K: Ord,
+        F: FnMut(&IpAddr) -> K,
+    {
+        let mut addrs: Vec<_> = self.iter().collect();
+        addrs.sort_by_key(f);
+        self.addrs = Some(addrs);
+    }
+
+    /// Converts all IPv4 addresses into IPv4-mapped IPv6 addresses.
+    pub fn map_ipv4_to_ipv6(&mut self) {
+        let addrs = self
+            .iter()
+            .map(|addr| match addr {
+                IpAddr::V4(addr) => IpAddr::V6(addr.to_ipv6_mapped()),
+                addr => addr,
+            })
+            .collect();
+        self.addrs = Some(addrs);
+    }
+
     /// Returns an iterator over socket addresses gained from the lookup.
     ///
     /// The socket addresses are gained by combining the IP addresses with
@@ -180,12 +227,16 @@ pub struct FoundHostsIter<Ref: OctetsRef> {
     a_name: Option<ParsedDname<Ref>>,
     aaaa: Option<RecordIter<Ref, Aaaa>>,
     a: Option<RecordIter<Ref, A>>,
+    addrs: Option<vec::IntoIter<IpAddr>>,
 }
 
 impl<Ref: OctetsRef> Iterator for FoundHostsIter<Ref> {
     type Item = IpAddr;
 
     fn next(&mut self) -> Option<IpAddr> {
+        if let Some(addrs) = self.addrs.as_mut() {
+            return addrs.next();
+        }
         while let Some(res) = self.aaaa.as_mut().and_then(Iterator::next) {
             if let Ok(record) = res {
                 if Some(*record.owner()) == self.aaaa_name {
@@ -6,7 +6,7 @@ use crate::base::message::Message;
 use crate::base::name::{Dname, ToDname, ToRelativeDname};
 use crate::base::octets::{OctetsRef, OctetsVec, ParseError};
 use crate::rdata::{Aaaa, Srv, A};
-use crate::resolv::resolver::Resolver;
+use crate::resolv::resolver::{QueryError, Resolver};
 use futures::stream;
 use futures::stream::{Stream, StreamExt};
 use rand::distributions::{Distribution, Uniform};
@@ -80,7 +80,7 @@ impl FoundSrvs {
     pub fn into_stream<R: Resolver>(
         self,
         resolver: &R,
-    ) -> impl Stream<Item = Result<ResolvedSrvItem, io::Error>> + '_
+    ) -> impl Stream<Item = Result<ResolvedSrvItem, QueryError>> + '_
     where
         R::Octets: OctetsRef,
     {
@@ -269,7 +269,7 @@ impl SrvItem {
     pub async fn resolve<R: Resolver>(
         self,
         resolver: &R,
-    ) -> Result<ResolvedSrvItem, io::Error>
+    ) -> Result<ResolvedSrvItem, QueryError>
     where
         for<'a> &'a R::Octets: OctetsRef,
     {
@@ -346,12 +346,18 @@ impl ops::Deref for ResolvedSrvItem {
 pub enum SrvError {
     LongName,
     MalformedAnswer,
-    Query(io::Error),
+    Query(QueryError),
+}
+
+impl From<QueryError> for SrvError {
+    fn from(err: QueryError) -> SrvError {
+        SrvError::Query(err)
+    }
 }
 
 impl From<io::Error> for SrvError {
     fn from(err: io::Error) -> SrvError {
-        SrvError::Query(err)
+        SrvError::Query(err.into())
     }
 }
 
@@ -19,6 +19,25 @@
 //!    See the [stub] module for more information on how to use the stub
 //!    resolver.
 //!
+//! *  [`RecursiveResolver`] does all the work itself. Starting from the
+//!    root name servers, it follows referrals until it finds a server
+//!    that is authoritative for the name in question. It doesn’t need any
+//!    upstream resolvers at all.
+//!
+//!    See the [recursive] module for more information.
+//!
+//! *  [`ValidatingResolver`] wraps any of the other resolvers and checks
+//!    their answers using DNSSEC. It is only available if the `validate`
+//!    feature is enabled.
+//!
+//!    See the [validating] module for more information.
+//!
+//! *  [`CachingResolver`] wraps any of the other resolvers and keeps their
+//!    answers for as long as the records allow, including negative answers
+//!    and, optionally, expired answers for when the wrapped resolver fails.
+//!
+//!    See the [cache] module for more information.
+//!
 //! The lookups implemented by the crate are generic over the particular
 //! resolver, so you can pick the resolver most suitable for your own
 //! application or even implement your own specialised resolver. All
@@ -26,16 +45,29 @@
 //! convenience, they are also available as methods on the [`Resolver`]
 //! trait.
 //!
+//! [cache]: cache/index.html
 //! [lookup]: lookup/index.html
+//! [recursive]: recursive/index.html
 //! [stub]: stub/index.html
+//! [validating]: validating/index.html
+//! [`CachingResolver`]: cache/struct.CachingResolver.html
 //! [`Resolver`]: resolver/trait.Resolver.html
+//! [`RecursiveResolver`]: recursive/struct.RecursiveResolver.html
 //! [`StubResolver`]: stub/struct.StubResolver.html
+//! [`ValidatingResolver`]: validating/struct.ValidatingResolver.html
 #![cfg(feature = "resolv")]
 #![cfg_attr(docsrs, doc(cfg(feature = "resolv")))]
 
-pub use self::resolver::Resolver;
+pub use self::cache::CachingResolver;
+pub use self::recursive::RecursiveResolver;
+pub use self::resolver::{QueryError, Resolver};
 pub use self::stub::StubResolver;
+#[cfg(feature = "validate")]
+pub use self::validating::ValidatingResolver;
 
+pub mod cache;
 pub mod lookup;
+pub mod recursive;
 pub mod resolver;
 pub mod stub;
+pub mod validating;
@@ -0,0 +1,159 @@
+//! Recursive resolver configuration.
+//!
+//! Unlike the stub resolver, a recursive resolver doesn’t need to be told
+//! which upstream servers to ask. Instead, it starts out with the addresses
+//! of the root name servers – the so-called _root hints_ – and works its
+//! way down the delegation tree from there. This module contains the
+//! configuration type [`RecursiveConf`] that collects these hints and a few
+//! limits that keep the resolver from being led astray by broken or
+//! malicious zones.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Duration;
+use std::vec::Vec;
+
+//------------ RecursiveConf -------------------------------------------------
+
+/// Configuration for a recursive resolver.
+///
+/// A default configuration with the current root hints and conservative
+/// limits can be created via [`RecursiveConf::new`]. All fields are public
+/// and can be changed afterwards.
+#[derive(Clone, Debug)]
+pub struct RecursiveConf {
+    /// The addresses of the root name servers.
+    ///
+    /// Resolution of every name starts with asking one of these servers
+    /// unless the resolver already knows a closer delegation.
+    pub root_hints: Vec<IpAddr>,
+
+    /// Whether to use IPv6 addresses of name servers.
+    ///
+    /// If this is `false`, the resolver will neither use IPv6 root hints nor
+    /// IPv6 glue nor look up AAAA records for glue-less name servers.
+    ///
+    /// This field defaults to `true`.
+    pub use_ipv6: bool,
+
+    /// How long to wait for a response from a single server.
+    ///
+    /// This field defaults to 2 seconds.
+    pub request_timeout: Duration,
+
+    /// Size of the message receive buffer in bytes for UDP.
+    ///
+    /// This field defaults to 1232 bytes.
+    pub recv_size: usize,
+
+    /// Advertised UDP payload size.
+    ///
+    /// This field defaults to 1232 bytes.
+    pub udp_payload_size: u16,
+
+    /// The maximum number of referrals to follow for a single name.
+    ///
+    /// This field defaults to 16.
+    pub max_referrals: usize,
+
+    /// The maximum number of CNAME or DNAME redirections to follow.
+    ///
+    /// This field defaults to 8.
+    pub max_cname_chain: usize,
+
+    /// The maximum nesting depth of look-ups for name server addresses.
+    ///
+    /// Whenever a delegation doesn’t come with glue, the resolver needs to
+    /// resolve the name server names first which may in turn require the
+    /// resolution of yet other name server names. This limits how deep
+    /// this can go.
+    ///
+    /// This field defaults to 6.
+    pub max_depth: usize,
+
+    /// Whether to request DNSSEC records by setting the DO bit.
+    ///
+    /// This needs to be enabled if the answers are to be validated, for
+    /// instance, by a validating resolver wrapped around this resolver.
+    ///
+    /// This field defaults to `false`.
+    pub dnssec_ok: bool,
+}
+
+impl RecursiveConf {
+    /// Creates a new configuration with default values.
+    ///
+    /// The root hints are set to the addresses of the IANA root servers.
+    pub fn new() -> Self {
+        RecursiveConf {
+            root_hints: iana_root_hints(),
+            use_ipv6: true,
+            request_timeout: Duration::from_secs(2),
+            recv_size: 1232,
+            udp_payload_size: 1232,
+            max_referrals: 16,
+            max_cname_chain: 8,
+            max_depth: 6,
+            dnssec_ok: false,
+        }
+    }
+
+    /// Returns an iterator over the root hints that should be used.
+    ///
+    /// This takes the `use_ipv6` option into account.
+    pub fn usable_root_hints(&self) -> impl Iterator<Item = IpAddr> + '_ {
+        let use_ipv6 = self.use_ipv6;
+        self.root_hints
+            .iter()
+            .copied()
+            .filter(move |addr| use_ipv6 || addr.is_ipv4())
+    }
+}
+
+impl Default for RecursiveConf {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the addresses of the IANA root servers.
+///
+/// These are the addresses from `a.root-servers.net` to `m.root-servers.net`
+/// as published by IANA in the `named.root` file.
+fn iana_root_hints() -> Vec<IpAddr> {
+    let v4: [[u8; 4]; 13] = [
+        [198, 41, 0, 4],
+        [170, 247, 170, 2],
+        [192, 33, 4, 12],
+        [199, 7, 91, 13],
+        [192, 203, 230, 10],
+        [192, 5, 5, 241],
+        [192, 112, 36, 4],
+        [198, 97, 190, 53],
+        [192, 36, 148, 17],
+        [192, 58, 128, 30],
+        [193, 0, 14, 129],
+        [199, 7, 83, 42],
+        [202, 12, 27, 33],
+    ];
+    let v6: [[u16; 8]; 13] = [
+        [0x2001, 0x503, 0xba3e, 0, 0, 0, 0x2, 0x30],
+        [0x2801, 0x1b8, 0x10, 0, 0, 0, 0, 0xb],
+        [0x2001, 0x500, 0x2, 0, 0, 0, 0, 0xc],
+        [0x2001, 0x500, 0x2d, 0, 0, 0, 0, 0xd],
+        [0x2001, 0x500, 0xa8, 0, 0, 0, 0, 0xe],
+        [0x2001, 0x500, 0x2f, 0, 0, 0, 0, 0xf],
+        [0x2001, 0x500, 0x12, 0, 0, 0, 0, 0xd0d],
+        [0x2001, 0x500, 0x1, 0, 0, 0, 0, 0x53],
+        [0x2001, 0x7fe, 0, 0, 0, 0, 0, 0x53],
+        [0x2001, 0x503, 0xc27, 0, 0, 0, 0x2, 0x30],
+        [0x2001, 0x7fd, 0, 0, 0, 0, 0, 0x1],
+        [0x2001, 0x500, 0x9f, 0, 0, 0, 0, 0x42],
+        [0x2001, 0xdc3, 0, 0, 0, 0, 0, 0x35],
+    ];
+    let mut res = Vec::with_capacity(v4.len() + v6.len());
+    res.extend(v4.iter().map(|addr| IpAddr::V4(Ipv4Addr::from(*addr))));
+    res.extend(v6.iter().map(|addr| IpAddr::V6(Ipv6Addr::from(*addr))));
+    res
+}
@@ -0,0 +1,1061 @@
+//! A recursive resolver.
+//!
+//! A recursive resolver – sometimes also called an iterative resolver –
+//! answers questions by itself instead of relaying them to an upstream
+//! resolver. It starts by asking one of the root name servers. Unless that
+//! server knows the answer, it will refer the resolver to the name servers
+//! of a zone closer to the name in question. The resolver follows these
+//! referrals until it reaches a server that is authoritative for the name
+//! and then returns that server’s answer.
+//!
+//! Along the way, the resolver follows CNAME and DNAME redirections and
+//! resolves the addresses of name servers that were given to it without
+//! glue records.
+//!
+//! The main type is [`RecursiveResolver`] which implements the [`Resolver`]
+//! trait and can thus be used with all the lookup functions in the
+//! [lookup][crate::resolv::lookup] module.
+
+use self::conf::RecursiveConf;
+use super::resolver::{QueryError, Resolver};
+use super::stub::{Answer, QueryMessage, ServerInfo};
+use crate::base::iana::{Class, Rcode, Rtype};
+use crate::base::message::Message;
+use crate::base::message_builder::MessageBuilder;
+use crate::base::name::{Dname, ParsedDname, ToDname};
+use crate::base::question::Question;
+use crate::rdata::{Aaaa, AllRecordData, Cname, Ns, A};
+use bytes::Bytes;
+use futures::future::{FutureExt, TryFutureExt};
+use std::boxed::Box;
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+use tokio::time::timeout;
+
+//------------ Sub-modules ---------------------------------------------------
+
+pub mod conf;
+
+//------------ Module Configuration ------------------------------------------
+
+/// The port name servers are expected to listen on.
+const NAME_SERVER_PORT: u16 = 53;
+
+/// The maximum time we keep a delegation in the cache.
+///
+/// This caps the TTL of the NS records of a delegation.
+const MAX_DELEGATION_TTL: u32 = 86400;
+
+//------------ RecursiveResolver ---------------------------------------------
+
+/// A DNS resolver resolving names by itself.
+///
+/// The resolver starts with the root hints given in its configuration and
+/// follows referrals down to an authoritative server. It keeps the
+/// delegations it learns along the way so that later queries can start
+/// closer to the name in question.
+///
+/// Values can be cloned cheaply. All clones share the same delegation
+/// cache.
+#[derive(Clone, Debug)]
+pub struct RecursiveResolver {
+    /// The resolver configuration.
+    conf: Arc<RecursiveConf>,
+
+    /// The delegations we have learned so far.
+    delegations: Arc<Mutex<DelegationCache>>,
+
+    /// Socket addresses to use instead of those of certain name servers.
+    ///
+    /// This allows tests to send queries to mock servers.
+    #[cfg(test)]
+    server_addrs: HashMap<IpAddr, SocketAddr>,
+}
+
+impl RecursiveResolver {
+    /// Creates a new resolver using the default configuration.
+    pub fn new() -> Self {
+        Self::from_conf(RecursiveConf::default())
+    }
+
+    /// Creates a new resolver using the given configuration.
+    pub fn from_conf(conf: RecursiveConf) -> Self {
+        RecursiveResolver {
+            conf: Arc::new(conf),
+            delegations: Default::default(),
+            #[cfg(test)]
+            server_addrs: HashMap::new(),
+        }
+    }
+
+    /// Returns a reference to the resolver’s configuration.
+    pub fn conf(&self) -> &RecursiveConf {
+        &self.conf
+    }
+
+    /// Answers a question.
+    ///
+    /// The returned answer is the response of the authoritative server for
+    /// the final name. If CNAME or DNAME records had to be followed, their
+    /// records are included in the answer section before the records of the
+    /// final response.
+    pub async fn query<N: ToDname, Q: Into<Question<N>>>(
+        &self,
+        question: Q,
+    ) -> Result<Answer, QueryError> {
+        let question = question.into();
+        self.resolve(
+            question.qname().to_bytes(),
+            question.qtype(),
+            question.qclass(),
+            0,
+        )
+        .await
+        .map_err(Into::into)
+    }
+
+    /// Resolves a question including following redirections.
+    ///
+    /// The method returns a boxed future since it may end up calling itself
+    /// when it needs to resolve the addresses of name servers.
+    fn resolve(
+        &self,
+        qname: Dname<Bytes>,
+        qtype: Rtype,
+        qclass: Class,
+        depth: usize,
+    ) -> Pin<Box<dyn Future<Output = Result<Answer, io::Error>> + Send + '_>>
+    {
+        async move {
+            if depth > self.conf.max_depth {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "maximum resolution depth exceeded",
+                ));
+            }
+            let mut responses = Vec::new();
+            let mut name = qname.clone();
+            loop {
+                let response =
+                    self.resolve_name(&name, qtype, qclass, depth).await?;
+                let next = if qtype == Rtype::Cname {
+                    None
+                } else {
+                    redirection(&response, &name, qtype)
+                };
+                responses.push(response);
+                match next {
+                    Some(next) => {
+                        if responses.len() > self.conf.max_cname_chain {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                "CNAME chain too long",
+                            ));
+                        }
+                        name = next;
+                    }
+                    None => break,
+                }
+            }
+            if responses.len() == 1 {
+                Ok(responses.pop().unwrap().into())
+            } else {
+                combine_responses(
+                    &Question::new(qname, qtype, qclass),
+                    &responses,
+                )
+                .map(Into::into)
+            }
+        }
+        .boxed()
+    }
+
+    /// Resolves a single name without following redirections.
+    ///
+    /// DS records live in the parent zone of their owner, so for DS queries
+    /// the resolver starts at and follows referrals only down to the
+    /// delegation of the parent of `qname`.
+    async fn resolve_name(
+        &self,
+        qname: &Dname<Bytes>,
+        qtype: Rtype,
+        qclass: Class,
+        depth: usize,
+    ) -> Result<Message<Bytes>, io::Error> {
+        let mut target = qname.clone();
+        if qtype == Rtype::Ds {
+            target.parent();
+        }
+        let mut delegation = self.closest_delegation(&target);
+        for _ in 0..self.conf.max_referrals {
+            if !delegation.has_addrs() {
+                self.resolve_server_addrs(&mut delegation, qclass, depth)
+                    .await?;
+                self.delegations.lock().unwrap().insert(&delegation);
+            }
+            let response = self
+                .query_delegation(&delegation, qname, qtype, qclass)
+                .await?;
+            match Referral::from_response(
+                &response,
+                &target,
+                &delegation.zone,
+            ) {
+                Some(referral) => {
+                    delegation = referral.into_delegation(self.conf.use_ipv6);
+                    self.delegations.lock().unwrap().insert(&delegation);
+                }
+                None => return Ok(response),
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "too many referrals",
+        ))
+    }
+
+    /// Returns the closest delegation we know of for the given name.
+    fn closest_delegation(&self, qname: &Dname<Bytes>) -> Delegation {
+        if let Some(res) = self.delegations.lock().unwrap().closest(qname) {
+            return res;
+        }
+        Delegation {
+            zone: Dname::root_bytes(),
+            servers: vec![NameServer {
+                name: Dname::root_bytes(),
+                addrs: self.conf.usable_root_hints().collect(),
+            }],
+            ttl: MAX_DELEGATION_TTL,
+        }
+    }
+
+    /// Resolves the addresses of a delegation’s glue-less name servers.
+    ///
+    /// Stops as soon as one name server produced some addresses.
+    async fn resolve_server_addrs(
+        &self,
+        delegation: &mut Delegation,
+        qclass: Class,
+        depth: usize,
+    ) -> Result<(), io::Error> {
+        let mut rtypes = vec![Rtype::A];
+        if self.conf.use_ipv6 {
+            rtypes.push(Rtype::Aaaa);
+        }
+        for server in &mut delegation.servers {
+            // A name server within the delegated zone but without glue
+            // can never be resolved.
+            if server.name.ends_with(&delegation.zone) {
+                continue;
+            }
+            for &rtype in &rtypes {
+                let answer = match self
+                    .resolve(server.name.clone(), rtype, qclass, depth + 1)
+                    .await
+                {
+                    Ok(answer) => answer,
+                    Err(_) => continue,
+                };
+                server.addrs.extend(answer_addrs(&answer));
+            }
+            if !server.addrs.is_empty() {
+                return Ok(());
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "cannot resolve name server addresses",
+        ))
+    }
+
+    /// Sends a question to the servers of a delegation.
+    ///
+    /// Tries all addresses of all servers in turn until one of them gives a
+    /// usable response.
+    async fn query_delegation(
+        &self,
+        delegation: &Delegation,
+        qname: &Dname<Bytes>,
+        qtype: Rtype,
+        qclass: Class,
+    ) -> Result<Message<Bytes>, io::Error> {
+        let message = self.create_message(qname, qtype, qclass);
+        let mut error =
+            io::Error::new(io::ErrorKind::TimedOut, "all timed out");
+        for addr in delegation.addrs() {
+            let addr = self.server_addr(addr);
+            match self.query_server(&message, addr).await {
+                Ok(response) => {
+                    let rcode = response.header().rcode();
+                    if rcode == Rcode::NoError || rcode == Rcode::NXDomain {
+                        return Ok(response);
+                    }
+                    error = io::Error::new(
+                        io::ErrorKind::ConnectionRefused,
+                        format!("server {} responded with {}", addr, rcode),
+                    );
+                }
+                Err(err) => {
+                    if err.kind() != io::ErrorKind::TimedOut {
+                        error = err
+                    }
+                }
+            }
+        }
+        Err(error)
+    }
+
+    /// Sends a query to a single server.
+    ///
+    /// Starts with UDP and switches to TCP if the response is truncated.
+    async fn query_server(
+        &self,
+        message: &QueryMessage,
+        addr: SocketAddr,
+    ) -> Result<Message<Bytes>, io::Error> {
+        let answer = self
+            .with_timeout(ServerInfo::udp_query(
+                message,
+                addr,
+                self.conf.recv_size,
+            ))
+            .await?;
+        if !answer.is_truncated() {
+            return Ok(answer.into_message());
+        }
+        self.with_timeout(ServerInfo::tcp_query(message, addr))
+            .await
+            .map(Answer::into_message)
+    }
+
+    /// Returns the socket address to send queries for a name server to.
+    fn server_addr(&self, addr: IpAddr) -> SocketAddr {
+        #[cfg(test)]
+        {
+            if let Some(addr) = self.server_addrs.get(&addr) {
+                return *addr;
+            }
+        }
+        SocketAddr::new(addr, NAME_SERVER_PORT)
+    }
+
+    /// Applies the request timeout to a query future.
+    async fn with_timeout(
+        &self,
+        fut: impl Future<Output = Result<Answer, io::Error>>,
+    ) -> Result<Answer, io::Error> {
+        match timeout(self.conf.request_timeout, fut).await {
+            Ok(res) => res,
+            Err(_) => Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "request timed out",
+            )),
+        }
+    }
+
+    /// Creates a non-recursive query message.
+    fn create_message(
+        &self,
+        qname: &Dname<Bytes>,
+        qtype: Rtype,
+        qclass: Class,
+    ) -> QueryMessage {
+        let mut message = MessageBuilder::new_stream_vec();
+        message.header_mut().set_random_id();
+        let mut message = message.question();
+        message.push((qname, qtype, qclass)).unwrap();
+        let mut message = message.additional();
+        message
+            .opt(|opt| {
+                opt.set_udp_payload_size(self.conf.udp_payload_size);
+                opt.set_dnssec_ok(self.conf.dnssec_ok);
+                Ok(())
+            })
+            .unwrap();
+        message
+    }
+}
+
+impl Default for RecursiveResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> Resolver for &'a RecursiveResolver {
+    type Octets = Bytes;
+    type Answer = Answer;
+    type Query =
+        Pin<Box<dyn Future<Output = Result<Answer, QueryError>> + Send + 'a>>;
+
+    fn query<N, Q>(&self, question: Q) -> Self::Query
+    where
+        N: ToDname,
+        Q: Into<Question<N>>,
+    {
+        let question = question.into();
+        self.resolve(
+            question.qname().to_bytes(),
+            question.qtype(),
+            question.qclass(),
+            0,
+        )
+        .map_err(Into::into)
+        .boxed()
+    }
+}
+
+//------------ Delegation ----------------------------------------------------
+
+/// The name servers responsible for a zone.
+#[derive(Clone, Debug)]
+struct Delegation {
+    /// The apex of the delegated zone.
+    zone: Dname<Bytes>,
+
+    /// The name servers for the zone.
+    servers: Vec<NameServer>,
+
+    /// The TTL of the NS records.
+    ttl: u32,
+}
+
+impl Delegation {
+    /// Returns whether any of the servers has an address.
+    fn has_addrs(&self) -> bool {
+        self.servers.iter().any(|server| !server.addrs.is_empty())
+    }
+
+    /// Returns an iterator over all server addresses.
+    fn addrs(&self) -> impl Iterator<Item = IpAddr> + '_ {
+        self.servers
+            .iter()
+            .flat_map(|server| server.addrs.iter().copied())
+    }
+}
+
+//------------ NameServer ----------------------------------------------------
+
+/// A single name server of a delegation.
+#[derive(Clone, Debug)]
+struct NameServer {
+    /// The host name of the name server.
+    name: Dname<Bytes>,
+
+    /// The addresses of the name server we know of.
+    addrs: Vec<IpAddr>,
+}
+
+//------------ DelegationCache -----------------------------------------------
+
+/// The delegations learned from referrals.
+#[derive(Debug, Default)]
+struct DelegationCache {
+    delegations: HashMap<Dname<Bytes>, (Instant, Delegation)>,
+}
+
+impl DelegationCache {
+    /// Adds a delegation to the cache.
+    ///
+    /// Delegations without any server addresses are skipped. Those of
+    /// glue-less delegations are added once the addresses of their name
+    /// servers have been resolved.
+    fn insert(&mut self, delegation: &Delegation) {
+        if !delegation.has_addrs() {
+            return;
+        }
+        let ttl = delegation.ttl.min(MAX_DELEGATION_TTL);
+        let expires = Instant::now() + Duration::from_secs(ttl.into());
+        self.delegations
+            .insert(delegation.zone.clone(), (expires, delegation.clone()));
+    }
+
+    /// Returns the closest unexpired delegation for a name.
+    fn closest(&mut self, qname: &Dname<Bytes>) -> Option<Delegation> {
+        let now = Instant::now();
+        self.delegations.retain(|_, (expires, _)| *expires > now);
+        let mut name = qname.clone();
+        loop {
+            if let Some((_, delegation)) = self.delegations.get(&name) {
+                return Some(delegation.clone());
+            }
+            if !name.parent() {
+                return None;
+            }
+        }
+    }
+}
+
+//------------ Referral ------------------------------------------------------
+
+/// A referral to the name servers of a zone closer to the query name.
+#[derive(Clone, Debug)]
+struct Referral {
+    /// The delegation learned from the referral.
+    delegation: Delegation,
+}
+
+impl Referral {
+    /// Extracts a referral from a response if there is one.
+    ///
+    /// A response is a referral if it has no answer records, has a NOERROR
+    /// rcode, and contains NS records in its authority section that are
+    /// for a zone below `zone` and at or above `qname`. Any other NS
+    /// records are ignored for being out of bailiwick.
+    ///
+    /// Address records from the additional section are only used as glue
+    /// if their owner is at or below the delegated zone. The addresses of
+    /// all other name servers have to be resolved separately.
+    fn from_response(
+        response: &Message<Bytes>,
+        qname: &Dname<Bytes>,
+        zone: &Dname<Bytes>,
+    ) -> Option<Self> {
+        if response.header().rcode() != Rcode::NoError
+            || response.header().aa()
+            || response.header_counts().ancount() > 0
+        {
+            return None;
+        }
+        let authority = response.authority().ok()?;
+        let mut cut: Option<Dname<Bytes>> = None;
+        let mut servers = Vec::new();
+        let mut ttl = MAX_DELEGATION_TTL;
+        for record in authority.limit_to::<Ns<_>>() {
+            let record = match record {
+                Ok(record) => record,
+                Err(_) => continue,
+            };
+            let owner = record.owner();
+            if owner.name_eq(zone)
+                || !owner.ends_with(zone)
+                || !qname.ends_with(owner)
+            {
+                continue;
+            }
+            match cut {
+                Some(ref cut) if !cut.name_eq(owner) => continue,
+                Some(_) => {}
+                None => cut = Some(owner.to_bytes()),
+            }
+            ttl = ttl.min(record.ttl());
+            servers.push(NameServer {
+                name: record.data().nsdname().to_bytes(),
+                addrs: Vec::new(),
+            });
+        }
+        let zone = cut?;
+        if let Ok(additional) = response.additional() {
+            for record in additional {
+                let record = match record {
+                    Ok(record) => record,
+                    Err(_) => continue,
+                };
+                if !record.owner().ends_with(&zone) {
+                    continue;
+                }
+                let server = match servers
+                    .iter_mut()
+                    .find(|server| server.name.name_eq(record.owner()))
+                {
+                    Some(server) => server,
+                    None => continue,
+                };
+                if let Ok(Some(record)) = record.to_record::<A>() {
+                    server.addrs.push(record.data().addr().into())
+                } else if let Ok(Some(record)) = record.to_record::<Aaaa>() {
+                    server.addrs.push(record.data().addr().into())
+                }
+            }
+        }
+        Some(Referral {
+            delegation: Delegation { zone, servers, ttl },
+        })
+    }
+
+    /// Converts the referral into a delegation.
+    fn into_delegation(mut self, use_ipv6: bool) -> Delegation {
+        if !use_ipv6 {
+            for server in &mut self.delegation.servers {
+                server.addrs.retain(IpAddr::is_ipv4);
+            }
+        }
+        self.delegation
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Determines whether a response redirects the query to another name.
+///
+/// Follows the CNAME chain in the answer section starting at `qname`. If
+/// the end of the chain has records of the requested type, there is no
+/// redirection. Otherwise, returns the last name of the chain. If there
+/// isn’t a CNAME, checks for a DNAME record that applies to the name and
+/// returns the name synthesized from it.
+fn redirection(
+    response: &Message<Bytes>,
+    qname: &Dname<Bytes>,
+    qtype: Rtype,
+) -> Option<Dname<Bytes>> {
+    if response.header().rcode() != Rcode::NoError {
+        return None;
+    }
+    let answer = response.answer().ok()?;
+    let mut name = qname.clone();
+    for _ in 0..=response.header_counts().ancount() {
+        // If there are records of the type we want, we are done.
+        let found = answer.clone().any(|record| match record {
+            Ok(record) => {
+                record.rtype() == qtype && record.owner().name_eq(&name)
+            }
+            Err(_) => false,
+        });
+        if found {
+            return None;
+        }
+
+        // Follow a CNAME.
+        let cname = answer.limit_to::<Cname<_>>().find_map(|record| {
+            let record = record.ok()?;
+            if record.owner().name_eq(&name) {
+                Some(record.data().cname().to_bytes())
+            } else {
+                None
+            }
+        });
+        if let Some(cname) = cname {
+            name = cname;
+            continue;
+        }
+
+        // Without a CNAME, try a DNAME.
+        if let Some(target) = dname_target(response, &name) {
+            return Some(target);
+        }
+
+        // No further redirection in this message. If we have moved, that’s
+        // a redirection.
+        if name.name_eq(qname) {
+            return None;
+        } else {
+            return Some(name);
+        }
+    }
+
+    // CNAME loop.
+    None
+}
+
+/// Returns the target of a DNAME redirection for a name, if any.
+fn dname_target(
+    response: &Message<Bytes>,
+    name: &Dname<Bytes>,
+) -> Option<Dname<Bytes>> {
+    let answer = response.answer().ok()?;
+    for record in answer.limit_to::<crate::rdata::Dname<_>>() {
+        let record = match record {
+            Ok(record) => record,
+            Err(_) => continue,
+        };
+        if record.owner().name_eq(name) || !name.ends_with(record.owner()) {
+            continue;
+        }
+        let prefix = match name.clone().strip_suffix(record.owner()) {
+            Ok(prefix) => prefix,
+            Err(_) => continue,
+        };
+        if let Ok(target) = prefix.chain(record.data().dname()) {
+            return target.to_dname().ok();
+        }
+    }
+    None
+}
+
+/// Returns all the addresses in the answer section of an answer.
+fn answer_addrs(answer: &Answer) -> Vec<IpAddr> {
+    let mut res = Vec::new();
+    let name = match answer.canonical_name() {
+        Some(name) => name,
+        None => return res,
+    };
+    let section = match answer.answer() {
+        Ok(section) => section,
+        Err(_) => return res,
+    };
+    for record in section {
+        let record = match record {
+            Ok(record) => record,
+            Err(_) => continue,
+        };
+        if *record.owner() != name {
+            continue;
+        }
+        if let Ok(Some(record)) = record.to_record::<A>() {
+            res.push(record.data().addr().into())
+        } else if let Ok(Some(record)) = record.to_record::<Aaaa>() {
+            res.push(record.data().addr().into())
+        }
+    }
+    res
+}
+
+/// Combines the responses of a redirection chain into a single message.
+///
+/// The message has the original question, the answer records of all
+/// responses, and the authority section of the last response.
+fn combine_responses(
+    question: &Question<Dname<Bytes>>,
+    responses: &[Message<Bytes>],
+) -> Result<Message<Bytes>, io::Error> {
+    let last = match responses.last() {
+        Some(last) => last,
+        None => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "no response",
+            ))
+        }
+    };
+    let mut builder = MessageBuilder::new_bytes();
+    {
+        let header = builder.header_mut();
+        *header = last.header();
+        header.set_qr(true);
+        header.set_aa(false);
+        header.set_ra(true);
+    }
+    let mut builder = builder.question();
+    builder.push(question).map_err(short_buf)?;
+    let mut builder = builder.answer();
+    for response in responses {
+        for record in response.answer().map_err(parse_err)? {
+            let record = record.map_err(parse_err)?;
+            if let Some(record) = record
+                .into_record::<AllRecordData<Bytes, ParsedDname<&Bytes>>>()
+                .map_err(parse_err)?
+            {
+                builder.push(record).map_err(short_buf)?;
+            }
+        }
+    }
+    let mut builder = builder.authority();
+    for record in last.authority().map_err(parse_err)? {
+        let record = record.map_err(parse_err)?;
+        if let Some(record) = record
+            .into_record::<AllRecordData<Bytes, ParsedDname<&Bytes>>>()
+            .map_err(parse_err)?
+        {
+            builder.push(record).map_err(short_buf)?;
+        }
+    }
+    Ok(builder.into_message())
+}
+
+fn short_buf<T>(_: T) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "response too long")
+}
+
+fn parse_err<T>(_: T) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "malformed response")
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::message_builder::AnswerBuilder;
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+    use tokio::net::UdpSocket;
+    use tokio::runtime;
+
+    fn name(s: &str) -> Dname<Bytes> {
+        Dname::from_str(s).unwrap()
+    }
+
+    fn response_builder(
+        qname: &str,
+        qtype: Rtype,
+    ) -> crate::base::message_builder::AnswerBuilder<bytes::BytesMut> {
+        let mut msg = MessageBuilder::new_bytes();
+        msg.header_mut().set_qr(true);
+        let mut msg = msg.question();
+        msg.push((name(qname), qtype)).unwrap();
+        msg.answer()
+    }
+
+    #[test]
+    fn referral() {
+        let mut msg =
+            response_builder("www.example.com.", Rtype::A).authority();
+        msg.push((
+            name("example.com."),
+            3600,
+            Ns::new(name("ns1.example.com.")),
+        ))
+        .unwrap();
+        msg.push((
+            name("example.com."),
+            3600,
+            Ns::new(name("ns.example.net.")),
+        ))
+        .unwrap();
+        // Out of bailiwick.
+        msg.push((name("example.org."), 3600, Ns::new(name("ns.evil."))))
+            .unwrap();
+        let mut msg = msg.additional();
+        msg.push((
+            name("ns1.example.com."),
+            3600,
+            A::new(Ipv4Addr::new(192, 0, 2, 1)),
+        ))
+        .unwrap();
+        // Out of bailiwick glue.
+        msg.push((
+            name("ns.example.net."),
+            3600,
+            A::new(Ipv4Addr::new(192, 0, 2, 66)),
+        ))
+        .unwrap();
+        let msg = msg.into_message();
+
+        let referral = Referral::from_response(
+            &msg,
+            &name("www.example.com."),
+            &name("com."),
+        )
+        .unwrap()
+        .into_delegation(true);
+        assert_eq!(referral.zone, name("example.com."));
+        assert_eq!(referral.servers.len(), 2);
+        assert_eq!(
+            referral.addrs().collect::<Vec<_>>(),
+            vec![IpAddr::from(Ipv4Addr::new(192, 0, 2, 1))]
+        );
+
+        // A referral sideways or up isn’t one.
+        assert!(Referral::from_response(
+            &msg,
+            &name("www.example.com."),
+            &name("example.com."),
+        )
+        .is_none());
+
+        // A DS query for example.com. only follows referrals down to com.
+        assert!(Referral::from_response(&msg, &name("com."), &name("com."),)
+            .is_none());
+    }
+
+    #[test]
+    fn cname_redirection() {
+        let mut msg = response_builder("www.example.com.", Rtype::A);
+        msg.push((
+            name("www.example.com."),
+            3600,
+            Cname::new(name("host.example.com.")),
+        ))
+        .unwrap();
+        msg.push((
+            name("host.example.com."),
+            3600,
+            Cname::new(name("host.example.net.")),
+        ))
+        .unwrap();
+        let msg = msg.into_message();
+        assert_eq!(
+            redirection(&msg, &name("www.example.com."), Rtype::A),
+            Some(name("host.example.net."))
+        );
+
+        let mut msg = response_builder("www.example.com.", Rtype::A);
+        msg.push((
+            name("www.example.com."),
+            3600,
+            Cname::new(name("host.example.com.")),
+        ))
+        .unwrap();
+        msg.push((
+            name("host.example.com."),
+            3600,
+            A::new(Ipv4Addr::new(192, 0, 2, 1)),
+        ))
+        .unwrap();
+        let msg = msg.into_message();
+        assert_eq!(
+            redirection(&msg, &name("www.example.com."), Rtype::A),
+            None
+        );
+    }
+
+    #[test]
+    fn dname_redirection() {
+        let mut msg = response_builder("www.example.com.", Rtype::A);
+        msg.push((
+            name("example.com."),
+            3600,
+            crate::rdata::Dname::new(name("example.net.")),
+        ))
+        .unwrap();
+        let msg = msg.into_message();
+        assert_eq!(
+            redirection(&msg, &name("www.example.com."), Rtype::A),
+            Some(name("www.example.net."))
+        );
+    }
+
+    /// The log of queries received by the mock servers.
+    type QueryLog = Arc<Mutex<Vec<(IpAddr, Dname<Bytes>)>>>;
+
+    /// Returns the address of mock server `n`.
+    fn mock_addr(n: u8) -> IpAddr {
+        Ipv4Addr::new(192, 0, 2, n).into()
+    }
+
+    /// Starts mock server `n` and returns the address it listens on.
+    async fn mock_server(n: u8, log: QueryLog) -> SocketAddr {
+        let sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = sock.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = vec![0; 512];
+            loop {
+                let (len, peer) = sock.recv_from(&mut buf).await.unwrap();
+                let query =
+                    Message::from_octets(Bytes::copy_from_slice(&buf[..len]))
+                        .unwrap();
+                let qname = query.sole_question().unwrap().qname().to_bytes();
+                log.lock().unwrap().push((mock_addr(n), qname.clone()));
+                let response = mock_response(n, &query, &qname);
+                sock.send_to(&response, peer).await.unwrap();
+            }
+        });
+        addr
+    }
+
+    /// Creates the response of mock server `n`.
+    ///
+    /// Server 1 is the root server, servers 2 and 4 are the servers for
+    /// com. and net., respectively. Server 3 is ns.example.net. which is
+    /// authoritative for both example.com. and example.net. Since it is
+    /// out of bailiwick for example.com., that delegation has no glue.
+    fn mock_response(
+        n: u8,
+        query: &Message<Bytes>,
+        qname: &Dname<Bytes>,
+    ) -> Vec<u8> {
+        let mut msg = MessageBuilder::new_vec()
+            .start_answer(query, Rcode::NoError)
+            .unwrap();
+        match n {
+            1 if qname.ends_with(&name("com.")) => {
+                mock_referral(msg, "com.", "ns.nic.com.", Some(2))
+            }
+            1 => mock_referral(msg, "net.", "ns.nic.net.", Some(4)),
+            2 => mock_referral(msg, "example.com.", "ns.example.net.", None),
+            4 => {
+                mock_referral(msg, "example.net.", "ns.example.net.", Some(3))
+            }
+            _ => {
+                msg.header_mut().set_aa(true);
+                if *qname == name("www.example.com.") {
+                    msg.push((
+                        qname,
+                        3600,
+                        Cname::new(name("host.example.net.")),
+                    ))
+                    .unwrap();
+                } else if *qname == name("ns.example.net.") {
+                    msg.push((
+                        qname,
+                        3600,
+                        A::new(Ipv4Addr::new(192, 0, 2, 3)),
+                    ))
+                    .unwrap();
+                } else {
+                    msg.push((
+                        qname,
+                        3600,
+                        A::new(Ipv4Addr::new(192, 0, 2, 80)),
+                    ))
+                    .unwrap();
+                }
+                msg.finish()
+            }
+        }
+    }
+
+    /// Finishes a mock response as a referral.
+    fn mock_referral(
+        msg: AnswerBuilder<Vec<u8>>,
+        zone: &str,
+        ns: &str,
+        glue: Option<u8>,
+    ) -> Vec<u8> {
+        let mut msg = msg.authority();
+        msg.push((name(zone), 3600, Ns::new(name(ns)))).unwrap();
+        let mut msg = msg.additional();
+        if let Some(glue) = glue {
+            msg.push((
+                name(ns),
+                3600,
+                A::new(Ipv4Addr::new(192, 0, 2, glue)),
+            ))
+            .unwrap();
+        }
+        msg.finish()
+    }
+
+    #[test]
+    fn iterative_resolution() {
+        runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(async {
+                let log = QueryLog::default();
+                let mut conf = RecursiveConf::new();
+                conf.root_hints = vec![mock_addr(1)];
+                conf.use_ipv6 = false;
+                let mut resolver = RecursiveResolver::from_conf(conf);
+                for n in 1..5 {
+                    let addr = mock_server(n, log.clone()).await;
+                    resolver.server_addrs.insert(mock_addr(n), addr);
+                }
+
+                // Referrals down to example.com., the glue-less name server
+                // resolved via net., and the CNAME into example.net.
+                let answer = resolver
+                    .query((name("www.example.com."), Rtype::A))
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    answer.canonical_name().unwrap().to_bytes(),
+                    name("host.example.net.")
+                );
+                assert_eq!(
+                    answer_addrs(&answer),
+                    vec![IpAddr::from(Ipv4Addr::new(192, 0, 2, 80))]
+                );
+
+                // The glue-less delegation has been cached with the
+                // resolved address, so the next query goes straight to
+                // its server.
+                log.lock().unwrap().clear();
+                resolver
+                    .query((name("mail.example.com."), Rtype::A))
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    *log.lock().unwrap(),
+                    vec![(mock_addr(3), name("mail.example.com."))]
+                );
+            })
+    }
+}
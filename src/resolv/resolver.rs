@@ -1,10 +1,13 @@
 //! The trait defining an abstract resolver.
 
+use super::stub::conf::Transport;
 use crate::base::message::Message;
 use crate::base::name::ToDname;
 use crate::base::question::Question;
 use futures::future::Future;
-use std::io;
+use std::net::SocketAddr;
+use std::vec::Vec;
+use std::{error, fmt, io};
 
 //----------- Resolver -------------------------------------------------------
 
@@ -12,7 +15,7 @@ use std::io;
 ///
 /// A resolver is anything that tries to answer questions using the DNS. The
 /// `query` method takes a single question and returns a future that will
-/// eventually resolve into either an answer or an IO error.
+/// eventually resolve into either an answer or a [`QueryError`].
 pub trait Resolver {
     type Octets: AsRef<[u8]>;
 
@@ -25,7 +28,7 @@ pub trait Resolver {
     type Answer: AsRef<Message<Self::Octets>>;
 
     /// The future resolving into an answer.
-    type Query: Future<Output = Result<Self::Answer, io::Error>>;
+    type Query: Future<Output = Result<Self::Answer, QueryError>>;
 
     /// Returns a future answering a question.
     ///
@@ -54,3 +57,236 @@ pub trait SearchNames {
     /// Returns an iterator over the search suffixes.
     fn search_iter(&self) -> Self::Iter;
 }
+
+//------------ QueryError ----------------------------------------------------
+
+/// An error happened while trying to answer a question.
+///
+/// Resolvers that send queries to a set of upstream servers, such as the
+/// stub resolver, report what happened with each of them via the
+/// [`AllServersFailed`][QueryError::AllServersFailed] variant. Other
+/// errors are wrapped in the [`Io`][QueryError::Io] variant.
+#[derive(Debug)]
+pub enum QueryError {
+    /// There are no servers the query could be sent to.
+    NoServers,
+
+    /// The query message exceeds the maximum message size.
+    QueryTooLong,
+
+    /// None of the servers provided a usable answer.
+    ///
+    /// The vector contains one element for each attempt in the order the
+    /// servers were tried. This includes servers that answered with
+    /// SERVFAIL, so such answers are never returned.
+    AllServersFailed(Vec<ServerError>),
+
+    /// An answer contained a name that isn’t a valid host name.
+    ///
+    /// The stub resolver checks the names in the answers to host and
+    /// address lookups unless its `no_check_name` option is set.
+    InvalidName,
+
+    /// Some other error happened.
+    Io(io::Error),
+}
+
+impl QueryError {
+    /// Returns the errors of the individual servers that were tried.
+    pub fn servers(&self) -> &[ServerError] {
+        match *self {
+            QueryError::AllServersFailed(ref servers) => servers,
+            _ => &[],
+        }
+    }
+
+    /// Returns whether the error was caused by timeouts only.
+    pub fn is_timeout(&self) -> bool {
+        match *self {
+            QueryError::AllServersFailed(ref servers) => {
+                !servers.is_empty()
+                    && servers.iter().all(|err| {
+                        matches!(err.kind(), ServerErrorKind::Timeout)
+                    })
+            }
+            QueryError::Io(ref err) => err.kind() == io::ErrorKind::TimedOut,
+            _ => false,
+        }
+    }
+
+    /// Returns the IO error kind that best describes the error.
+    fn io_kind(&self) -> io::ErrorKind {
+        match *self {
+            QueryError::NoServers => io::ErrorKind::NotFound,
+            QueryError::QueryTooLong => io::ErrorKind::InvalidInput,
+            QueryError::AllServersFailed(ref servers) => {
+                if self.is_timeout() {
+                    return io::ErrorKind::TimedOut;
+                }
+                servers
+                    .iter()
+                    .rev()
+                    .find_map(|err| match err.kind() {
+                        ServerErrorKind::Io(err) => Some(err.kind()),
+                        _ => None,
+                    })
+                    .unwrap_or(io::ErrorKind::InvalidData)
+            }
+            QueryError::InvalidName => io::ErrorKind::InvalidData,
+            QueryError::Io(ref err) => err.kind(),
+        }
+    }
+}
+
+//--- From
+
+impl From<io::Error> for QueryError {
+    fn from(err: io::Error) -> Self {
+        QueryError::Io(err)
+    }
+}
+
+impl From<QueryError> for io::Error {
+    fn from(err: QueryError) -> Self {
+        match err {
+            QueryError::Io(err) => err,
+            err => io::Error::new(err.io_kind(), err),
+        }
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            QueryError::NoServers => f.write_str("no servers available"),
+            QueryError::QueryTooLong => f.write_str("query message too long"),
+            QueryError::AllServersFailed(ref servers) => {
+                f.write_str("all servers failed")?;
+                for (i, err) in servers.iter().enumerate() {
+                    f.write_str(if i == 0 { ": " } else { ", " })?;
+                    err.fmt(f)?;
+                }
+                Ok(())
+            }
+            QueryError::InvalidName => {
+                f.write_str("invalid host name in answer")
+            }
+            QueryError::Io(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for QueryError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            QueryError::Io(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+//------------ ServerError ---------------------------------------------------
+
+/// What went wrong when sending a query to a single server.
+#[derive(Debug)]
+pub struct ServerError {
+    /// The address of the server.
+    addr: SocketAddr,
+
+    /// The transport used for the query.
+    transport: Transport,
+
+    /// What happened.
+    kind: ServerErrorKind,
+}
+
+impl ServerError {
+    /// Creates a new server error.
+    pub fn new(
+        addr: SocketAddr,
+        transport: Transport,
+        kind: ServerErrorKind,
+    ) -> Self {
+        ServerError {
+            addr,
+            transport,
+            kind,
+        }
+    }
+
+    /// Returns the address of the server.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Returns the transport used to query the server.
+    pub fn transport(&self) -> Transport {
+        self.transport
+    }
+
+    /// Returns what happened.
+    pub fn kind(&self) -> &ServerErrorKind {
+        &self.kind
+    }
+}
+
+//--- Display
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({:?}): {}", self.addr, self.transport, self.kind)
+    }
+}
+
+//------------ ServerErrorKind -----------------------------------------------
+
+/// The reason a server didn’t provide a usable answer.
+#[derive(Debug)]
+pub enum ServerErrorKind {
+    /// The server didn’t answer in time.
+    Timeout,
+
+    /// Communicating with the server failed.
+    ///
+    /// This includes unreachable networks and refused connections as well
+    /// as TLS and HTTP errors for encrypted transports.
+    Io(io::Error),
+
+    /// The server answered with FORMERR even without EDNS.
+    FormErr,
+
+    /// The server answered with SERVFAIL.
+    ///
+    /// A validating server answers with SERVFAIL if the answer is bogus.
+    ServFail,
+
+    /// The answer was truncated and retrying via a stream transport
+    /// didn’t produce an answer.
+    Truncated,
+
+    /// The server kept answering with BADCOOKIE.
+    BadCookie,
+
+    /// The answer wasn’t authoritative but the `aa_only` option is set.
+    NotAuthoritative,
+}
+
+//--- Display
+
+impl fmt::Display for ServerErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ServerErrorKind::Timeout => f.write_str("timed out"),
+            ServerErrorKind::Io(ref err) => err.fmt(f),
+            ServerErrorKind::FormErr => f.write_str("FORMERR without EDNS"),
+            ServerErrorKind::ServFail => f.write_str("SERVFAIL"),
+            ServerErrorKind::Truncated => f.write_str("answer truncated"),
+            ServerErrorKind::BadCookie => f.write_str("repeated BADCOOKIE"),
+            ServerErrorKind::NotAuthoritative => {
+                f.write_str("answer not authoritative")
+            }
+        }
+    }
+}
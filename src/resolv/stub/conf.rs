@@ -9,13 +9,17 @@
 //! Both parts are modeled along the lines of glibc’s resolver.
 
 use crate::base::name::{self, Dname};
+#[cfg(feature = "resolv-tls")]
+use crate::utils::base64;
 use smallvec::SmallVec;
 use std::cmp::Ordering;
 use std::default::Default;
 use std::io::Read;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::{self, FromStr, SplitWhitespace};
+#[cfg(feature = "resolv-tls")]
+use std::string::String;
 use std::time::Duration;
 use std::vec::Vec;
 use std::{convert, error, fmt, fs, io, ops};
@@ -33,8 +37,11 @@ pub struct ResolvOptions {
     /// Search list for host-name lookup.
     pub search: SearchList,
 
-    /// TODO Sortlist
-    /// sortlist: ??
+    /// Networks to sort the IPv4 addresses of host lookups by.
+    ///
+    /// This option is implemented by the stub resolver’s `lookup_host` and
+    /// `search_host` methods for addresses received from the DNS.
+    pub sortlist: SortList,
 
     /// Number of dots before an initial absolute query is made.
     pub ndots: usize,
@@ -50,8 +57,7 @@ pub struct ResolvOptions {
     /// Only responses with the AA bit set will be considered. If there
     /// aren’t any, the query will fail.
     ///
-    /// This option is not currently implemented. It is likely to be
-    /// eventually implemented by the query.
+    /// This option is implemented by the query.
     pub aa_only: bool,
 
     /// Always use TCP.
@@ -88,7 +94,15 @@ pub struct ResolvOptions {
 
     /// Keep TCP connections open between queries.
     ///
-    /// This is not currently implemented.
+    /// If this option is enabled, there is only one TCP connection to each
+    /// server which is used for all queries to that server. Queries are
+    /// pipelined, i.e., sent without waiting for the answers to earlier
+    /// queries. An idle connection is closed after the time announced by
+    /// the server via the edns-tcp-keepalive option or, if the server
+    /// doesn’t do that, after the time given by the server’s
+    /// [`idle_timeout`][ServerConf::idle_timeout].
+    ///
+    /// This option is implemented by the query.
     pub stay_open: bool,
 
     /// Search hostnames in the current domain and parent domains.
@@ -102,8 +116,12 @@ pub struct ResolvOptions {
 
     /// Try AAAA query before A query and map IPv4 responses to tunnel form.
     ///
-    /// This option is not currently implemented. It is only relevant for
-    /// `lookup_host`.
+    /// If this option is enabled, host lookups send the AAAA query first.
+    /// Only if its answer contains no AAAA records is the A query sent, and
+    /// the IPv4 addresses found are returned as IPv4-mapped IPv6 addresses.
+    ///
+    /// This option is implemented by the stub resolver’s `lookup_host` and
+    /// `search_host` methods.
     pub use_inet6: bool,
 
     /// Use round-robin selection of name servers.
@@ -113,8 +131,16 @@ pub struct ResolvOptions {
 
     /// Disable checking of incoming hostname and mail names.
     ///
-    /// This is not currently implemented. Or rather, this is currently
-    /// always on—there is no name checking as yet.
+    /// Unless this option is enabled, host and address lookups check that
+    /// the host names in the answers consist of letters, digits, hyphens,
+    /// and underscores only and that they don’t start with a hyphen. An
+    /// answer with any other names is treated as if the query had failed
+    /// with [`QueryError::InvalidName`].
+    ///
+    /// This option is implemented by the stub resolver’s `lookup_host`,
+    /// `search_host`, and `lookup_addr` methods.
+    ///
+    /// [`QueryError::InvalidName`]: crate::resolv::QueryError::InvalidName
     pub no_check_name: bool,
 
     /// Do not strip TSIG records.
@@ -125,7 +151,10 @@ pub struct ResolvOptions {
 
     /// Send each query simultaneously to all name servers.
     ///
-    /// This is not currently implemented. It would be a query option.
+    /// In each attempt, the query is sent to all servers at once and the
+    /// first usable answer is returned.
+    ///
+    /// This option is implemented by the query.
     pub blast: bool,
 
     /// Use bit-label format for IPv6 reverse lookups.
@@ -149,21 +178,54 @@ pub struct ResolvOptions {
 
     /// Perform IPv4 and IPv6 lookups sequentially instead of in parallel.
     ///
-    /// This is not yet implemented but would be an option for
-    /// `lookup_host()`.
+    /// The A query is sent first and the AAAA query only once it has been
+    /// answered.
+    ///
+    /// This option is implemented by the stub resolver’s `lookup_host` and
+    /// `search_host` methods.
     pub single_request: bool,
 
     /// Open a new socket for each request.
     ///
-    /// This is not currently implemented.
+    /// This implies `single_request`. In addition, persistent TCP
+    /// connections are not used even if `stay_open` is enabled. UDP
+    /// queries always use a new socket.
+    ///
+    /// This option is implemented by the query and the stub resolver’s
+    /// `lookup_host` and `search_host` methods.
     pub single_request_reopen: bool,
 
     /// Don’t look up unqualified names as top-level-domain.
     ///
-    /// This is not currently implemented. Instead, the resolver config’s
-    /// `search` and `ndots` fields govern resolution of relative names of
-    /// all kinds.
+    /// If this option is enabled, the stub resolver’s `search_host` method
+    /// doesn’t look up a name consisting of a single label in the root
+    /// domain, neither if the root domain is in the search list nor as a
+    /// last resort.
     pub no_tld_query: bool,
+
+    /// Request DNSSEC records by setting the DO bit in queries.
+    ///
+    /// This corresponds to glibc’s `RES_USE_DNSSEC` option. There is no
+    /// option in the configuration file for it. It is implemented by the
+    /// query.
+    pub use_dnssec: bool,
+
+    /// Select name servers based on their past performance.
+    ///
+    /// If this option is enabled, the resolver keeps track of the smoothed
+    /// round trip time and the number of failed queries for each server.
+    /// Queries are sent to the fastest server that has recently answered
+    /// first. A server that fails to answer is backed off from for an
+    /// exponentially growing time during which it is only tried after all
+    /// other servers. Once that time has passed, the next query is used to
+    /// probe whether it has come back.
+    ///
+    /// If the option is disabled, servers are always tried in the order
+    /// given. The `rotate` option takes precedence over this option.
+    ///
+    /// There is no equivalent in glibc. The option is enabled by default.
+    /// It is implemented by the query.
+    pub select_by_rtt: bool,
 }
 
 impl Default for ResolvOptions {
@@ -171,7 +233,7 @@ impl Default for ResolvOptions {
         ResolvOptions {
             // non-flags:
             search: SearchList::new(),
-            //sortlist,
+            sortlist: SortList::new(),
             ndots: 1,
             timeout: Duration::new(5, 0),
             attempts: 2,
@@ -180,6 +242,7 @@ impl Default for ResolvOptions {
             recurse: true,
             default_names: true,
             dn_search: true,
+            select_by_rtt: true,
 
             // everthing else is not:
             aa_only: false,
@@ -198,6 +261,7 @@ impl Default for ResolvOptions {
             single_request: false,
             single_request_reopen: false,
             no_tld_query: false,
+            use_dnssec: false,
         }
     }
 }
@@ -205,13 +269,41 @@ impl Default for ResolvOptions {
 //------------ Transport -----------------------------------------------------
 
 /// The transport protocol to be used for a server.
+///
+/// Which variants exist depends on the enabled features, so the type is
+/// marked as non-exhaustive.
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
 pub enum Transport {
     /// Unencrypted UDP transport.
     Udp,
 
     /// Unencrypted TCP transport.
     Tcp,
+
+    /// DNS over TLS as defined in [RFC 7858].
+    ///
+    /// Since this is a streaming transport, servers using it are only
+    /// considered if there are no UDP servers or if an answer was
+    /// truncated. If you want all your queries to be encrypted, only
+    /// configure servers with this transport.
+    ///
+    /// [RFC 7858]: https://tools.ietf.org/html/rfc7858
+    #[cfg(feature = "resolv-tls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "resolv-tls")))]
+    Tls,
+
+    /// DNS over HTTPS as defined in [RFC 8484].
+    ///
+    /// Queries are sent over HTTP/2 to the URI given by the server’s URI
+    /// template. Like with TLS, servers using this transport are only
+    /// considered if there are no UDP servers or if an answer was
+    /// truncated.
+    ///
+    /// [RFC 8484]: https://tools.ietf.org/html/rfc8484
+    #[cfg(feature = "resolv-https")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "resolv-https")))]
+    Https,
 }
 
 impl Transport {
@@ -224,6 +316,10 @@ impl Transport {
         match self {
             Transport::Udp => true,
             Transport::Tcp => false,
+            #[cfg(feature = "resolv-tls")]
+            Transport::Tls => false,
+            #[cfg(feature = "resolv-https")]
+            Transport::Https => false,
         }
     }
 
@@ -232,6 +328,35 @@ impl Transport {
         match self {
             Transport::Udp => false,
             Transport::Tcp => true,
+            #[cfg(feature = "resolv-tls")]
+            Transport::Tls => true,
+            #[cfg(feature = "resolv-https")]
+            Transport::Https => true,
+        }
+    }
+
+    /// Returns whether the transport is encrypted.
+    pub fn is_encrypted(self) -> bool {
+        match self {
+            Transport::Udp | Transport::Tcp => false,
+            #[cfg(feature = "resolv-tls")]
+            Transport::Tls => true,
+            #[cfg(feature = "resolv-https")]
+            Transport::Https => true,
+        }
+    }
+
+    /// Returns the default port for the transport.
+    ///
+    /// This is port 53 for unencrypted transports, port 853 for DNS
+    /// over TLS, and port 443 for DNS over HTTPS.
+    pub fn default_port(self) -> u16 {
+        match self {
+            Transport::Udp | Transport::Tcp => 53,
+            #[cfg(feature = "resolv-tls")]
+            Transport::Tls => 853,
+            #[cfg(feature = "resolv-https")]
+            Transport::Https => 443,
         }
     }
 }
@@ -277,6 +402,73 @@ pub struct ServerConf {
     /// server. It will be included both for datagram and streaming transport
     /// but really only matters for UDP.
     pub udp_payload_size: u16,
+
+    /// How long to keep an idle TCP connection open.
+    ///
+    /// This is only used if the `stay_open` option is enabled and the
+    /// server doesn’t announce its own timeout via the edns-tcp-keepalive
+    /// option of [RFC 7828].
+    ///
+    /// This field defaults to 10 seconds.
+    ///
+    /// [RFC 7828]: https://tools.ietf.org/html/rfc7828
+    pub idle_timeout: Duration,
+
+    /// The name to authenticate a DNS-over-TLS server with.
+    ///
+    /// This name is sent to the server via SNI and the certificate
+    /// presented by the server has to be valid for it. If this field is
+    /// `None`, the certificate has to be valid for the server’s IP address
+    /// instead or, for DNS over HTTPS, for the host of the server’s URI.
+    ///
+    /// The field is ignored if `tls_pins` is not empty and for other
+    /// transports. It defaults to `None`.
+    #[cfg(feature = "resolv-tls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "resolv-tls")))]
+    pub tls_auth_name: Option<String>,
+
+    /// The SPKI pins to authenticate a DNS-over-TLS server with.
+    ///
+    /// If this list is not empty, the server is authenticated by the
+    /// public key of its certificate only as described in section 4.2 of
+    /// [RFC 7858]: the key has to match one of the pins while the
+    /// certificate itself isn’t validated at all.
+    ///
+    /// The field is ignored for other transports. It defaults to an empty
+    /// list.
+    ///
+    /// [RFC 7858]: https://tools.ietf.org/html/rfc7858
+    #[cfg(feature = "resolv-tls")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "resolv-tls")))]
+    pub tls_pins: Vec<SpkiPin>,
+
+    /// The URI template of a DNS-over-HTTPS server.
+    ///
+    /// This is a URI template as described in section 4.1 of [RFC 8484],
+    /// such as `https://dns.example.com/dns-query{?dns}`. Only the `{?dns}`
+    /// and `{&dns}` expressions are supported. If the template doesn’t
+    /// contain either, the `dns` variable is appended for GET requests.
+    ///
+    /// Note that the host of the URI is not resolved. Connections always
+    /// go to the address given in `addr`.
+    ///
+    /// The field is ignored for other transports. If it is `None`, which
+    /// is the default, `https://<host>/dns-query{?dns}` is used with the
+    /// host being `tls_auth_name` if present or the IP address of `addr`
+    /// otherwise.
+    ///
+    /// [RFC 8484]: https://tools.ietf.org/html/rfc8484
+    #[cfg(feature = "resolv-https")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "resolv-https")))]
+    pub https_template: Option<String>,
+
+    /// The HTTP method to use for a DNS-over-HTTPS server.
+    ///
+    /// The field is ignored for other transports. It defaults to GET which
+    /// allows HTTP caches to work better.
+    #[cfg(feature = "resolv-https")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "resolv-https")))]
+    pub https_method: HttpsMethod,
 }
 
 impl ServerConf {
@@ -291,7 +483,109 @@ impl ServerConf {
             request_timeout: Duration::from_secs(2),
             recv_size: 1232,
             udp_payload_size: 1232,
+            idle_timeout: Duration::from_secs(10),
+            #[cfg(feature = "resolv-tls")]
+            tls_auth_name: None,
+            #[cfg(feature = "resolv-tls")]
+            tls_pins: Vec::new(),
+            #[cfg(feature = "resolv-https")]
+            https_template: None,
+            #[cfg(feature = "resolv-https")]
+            https_method: HttpsMethod::Get,
+        }
+    }
+
+    /// Returns a new default server config using the transport’s port.
+    ///
+    /// The port is the one given by [`Transport::default_port`].
+    pub fn with_default_port(addr: IpAddr, transport: Transport) -> Self {
+        Self::new(SocketAddr::new(addr, transport.default_port()), transport)
+    }
+}
+
+//------------ HttpsMethod ---------------------------------------------------
+
+/// The HTTP method used for DNS over HTTPS.
+#[cfg(feature = "resolv-https")]
+#[cfg_attr(docsrs, doc(cfg(feature = "resolv-https")))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum HttpsMethod {
+    /// The query is sent Base 64 encoded as a parameter of the URI.
+    Get,
+
+    /// The query is sent as the body of the request.
+    Post,
+}
+
+//------------ SpkiPin -------------------------------------------------------
+
+/// A pin for the public key of a DNS-over-TLS server.
+///
+/// The pin is the SHA-256 digest of the DER encoded SubjectPublicKeyInfo of
+/// the server’s certificate as described in [RFC 7469]. Its textual
+/// representation used by the `FromStr` and `Display` implementations is
+/// the Base 64 encoding of the digest. This is the format used by RFC 7469
+/// for `pin-sha256` and by [RFC 7858] in its examples.
+///
+/// [RFC 7469]: https://tools.ietf.org/html/rfc7469
+/// [RFC 7858]: https://tools.ietf.org/html/rfc7858
+#[cfg(feature = "resolv-tls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "resolv-tls")))]
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SpkiPin([u8; 32]);
+
+#[cfg(feature = "resolv-tls")]
+impl SpkiPin {
+    /// Creates a pin from the SHA-256 digest of a public key.
+    pub fn from_digest(digest: [u8; 32]) -> Self {
+        SpkiPin(digest)
+    }
+
+    /// Creates a pin for a DER encoded SubjectPublicKeyInfo.
+    pub fn from_spki(spki: &[u8]) -> Self {
+        let mut res = [0u8; 32];
+        res.copy_from_slice(
+            ring::digest::digest(&ring::digest::SHA256, spki).as_ref(),
+        );
+        SpkiPin(res)
+    }
+
+    /// Returns the digest of the pin.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+//--- AsRef
+
+#[cfg(feature = "resolv-tls")]
+impl AsRef<[u8]> for SpkiPin {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+//--- FromStr and Display
+
+#[cfg(feature = "resolv-tls")]
+impl FromStr for SpkiPin {
+    type Err = SpkiPinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digest = base64::decode(s).map_err(|_| SpkiPinError)?;
+        if digest.len() != 32 {
+            return Err(SpkiPinError);
         }
+        let mut res = [0u8; 32];
+        res.copy_from_slice(digest.as_ref());
+        Ok(SpkiPin(res))
+    }
+}
+
+#[cfg(feature = "resolv-tls")]
+impl fmt::Display for SpkiPin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        base64::display(&self.0, f)
     }
 }
 
@@ -323,6 +617,11 @@ pub struct ResolvConf {
 
     /// Default options.
     pub options: ResolvOptions,
+
+    /// The path of the hosts file to consult for host lookups.
+    ///
+    /// If this is `None`, host and address lookups only go to the DNS.
+    pub hosts_file: Option<PathBuf>,
 }
 
 /// # Management
@@ -331,11 +630,13 @@ impl ResolvConf {
     /// Creates a new, empty configuration.
     ///
     /// Using an empty configuration will fail since it does not contain
-    /// any name servers. Call `self.finalize()` to make it usable.
+    /// any name servers. Call `self.finalize()` to make it usable. The
+    /// configuration does not use a hosts file.
     pub fn new() -> Self {
         ResolvConf {
             servers: Vec::new(),
             options: ResolvOptions::default(),
+            hosts_file: None,
         }
     }
 
@@ -364,9 +665,12 @@ impl ResolvConf {
 
     /// Creates a default configuration for this system.
     ///
+    /// The configuration uses `/etc/hosts` as its hosts file.
+    ///
     /// XXX This currently only works for Unix-y systems.
     pub fn default() -> Self {
         let mut res = ResolvConf::new();
+        res.hosts_file = Some("/etc/hosts".into());
         let _ = res.parse_file("/etc/resolv.conf");
         res.finalize();
         res
@@ -408,8 +712,7 @@ impl ResolvConf {
                 Some("nameserver") => self.parse_nameserver(words)?,
                 Some("domain") => self.parse_domain(words)?,
                 Some("search") => self.parse_search(words)?,
-                Some("sortlist") => { /* TODO: self.parse_sortlist(words)? */
-                }
+                Some("sortlist") => self.parse_sortlist(words),
                 Some("options") => self.parse_options(words)?,
                 _ => return Err(Error::ParseError),
             }
@@ -456,14 +759,34 @@ impl ResolvConf {
         Ok(())
     }
 
-    /*
-    fn parse_sortlist(
-        &mut self,
-        _words: SplitWhitespace
-    ) -> Result<(), Error> {
-        // XXX TODO
+    /// Parses a sortlist line.
+    ///
+    /// Each entry is an IPv4 address optionally followed by a slash or
+    /// ampersand and a netmask in dotted-quad notation. Like glibc, we
+    /// ignore entries we can’t parse and anything beyond the first
+    /// `SortList::MAX_LEN` entries.
+    fn parse_sortlist(&mut self, words: SplitWhitespace) {
+        let mut sortlist = SortList::new();
+        for word in words {
+            if sortlist.len() == SortList::MAX_LEN {
+                break;
+            }
+            let (addr, mask) = match word.find(&['/', '&'][..]) {
+                Some(idx) => (&word[..idx], Some(&word[idx + 1..])),
+                None => (word, None),
+            };
+            let addr = match Ipv4Addr::from_str(addr) {
+                Ok(addr) => addr,
+                Err(_) => continue,
+            };
+            let item = match mask.map(Ipv4Addr::from_str) {
+                Some(Ok(mask)) => SortListItem::new(addr, mask),
+                _ => SortListItem::with_natural_mask(addr),
+            };
+            sortlist.push(item);
+        }
+        self.options.sortlist = sortlist;
     }
-    */
 
     #[allow(clippy::match_same_arms)]
     fn parse_options(&mut self, words: SplitWhitespace) -> Result<(), Error> {
@@ -512,6 +835,12 @@ impl fmt::Display for ResolvConf {
     #[allow(clippy::cognitive_complexity)]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         for server in &self.servers {
+            // There is no way to express encrypted transports in the file,
+            // so we leave them out rather than have them turn into
+            // unencrypted servers.
+            if server.transport.is_encrypted() {
+                continue;
+            }
             let server = server.addr;
             f.write_str("nameserver ")?;
             if server.port() == 53 {
@@ -534,6 +863,13 @@ impl fmt::Display for ResolvConf {
             }
             Ordering::Less => {}
         }
+        if !self.options.sortlist.is_empty() {
+            "sortlist".fmt(f)?;
+            for item in self.options.sortlist.as_slice() {
+                write!(f, " {}/{}", item.addr(), item.mask())?;
+            }
+            "\n".fmt(f)?;
+        }
 
         // Collect options so we only print them if there are any non-default
         // ones.
@@ -677,6 +1013,115 @@ impl ops::Deref for SearchList {
     }
 }
 
+//------------ SortList ------------------------------------------------------
+
+/// A list of networks to sort the IPv4 addresses of host lookups by.
+///
+/// Addresses in the network of an earlier item are sorted before those in
+/// the networks of later items. Addresses that aren’t in any of the
+/// networks come last.
+#[derive(Clone, Debug, Default)]
+pub struct SortList {
+    list: Vec<SortListItem>,
+}
+
+impl SortList {
+    /// The maximum number of items glibc considers.
+    pub const MAX_LEN: usize = 10;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, item: SortListItem) {
+        self.list.push(item)
+    }
+
+    pub fn as_slice(&self) -> &[SortListItem] {
+        self.as_ref()
+    }
+
+    /// Returns the sort position of an address.
+    ///
+    /// This is the index of the first item whose network contains the
+    /// address or the length of the list if there is none.
+    pub fn position(&self, addr: Ipv4Addr) -> usize {
+        self.list
+            .iter()
+            .position(|item| item.contains(addr))
+            .unwrap_or(self.list.len())
+    }
+}
+
+//--- AsRef and Deref
+
+impl AsRef<[SortListItem]> for SortList {
+    fn as_ref(&self) -> &[SortListItem] {
+        self.list.as_ref()
+    }
+}
+
+impl ops::Deref for SortList {
+    type Target = [SortListItem];
+
+    fn deref(&self) -> &Self::Target {
+        self.as_ref()
+    }
+}
+
+//------------ SortListItem --------------------------------------------------
+
+/// An IPv4 network in a sort list.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SortListItem {
+    /// The network address.
+    addr: Ipv4Addr,
+
+    /// The netmask.
+    mask: Ipv4Addr,
+}
+
+impl SortListItem {
+    /// Creates a new item from a network address and netmask.
+    pub fn new(addr: Ipv4Addr, mask: Ipv4Addr) -> Self {
+        SortListItem { addr, mask }
+    }
+
+    /// Creates a new item using the netmask of the address’s class.
+    ///
+    /// This is what glibc does if the netmask is missing: it uses `/8` for
+    /// class A, `/16` for class B, and `/24` for all other addresses.
+    pub fn with_natural_mask(addr: Ipv4Addr) -> Self {
+        let bits = u32::from(addr);
+        let mask = if bits & 0x8000_0000 == 0 {
+            0xFF00_0000
+        } else if bits & 0xC000_0000 == 0x8000_0000 {
+            0xFFFF_0000
+        } else {
+            0xFFFF_FF00
+        };
+        Self::new(addr, mask.into())
+    }
+
+    /// Returns the network address.
+    pub fn addr(self) -> Ipv4Addr {
+        self.addr
+    }
+
+    /// Returns the netmask.
+    pub fn mask(self) -> Ipv4Addr {
+        self.mask
+    }
+
+    /// Returns whether the network contains the given address.
+    ///
+    /// As in glibc, the address masked with the netmask has to be equal
+    /// to the network address.
+    pub fn contains(self, addr: Ipv4Addr) -> bool {
+        u32::from(addr) & u32::from(self.mask) == u32::from(self.addr)
+    }
+}
+
 //------------ Private Helpers -----------------------------------------------
 //
 // These are here to wrap stuff into Results.
@@ -754,6 +1199,24 @@ impl fmt::Display for Error {
     }
 }
 
+//------------ SpkiPinError --------------------------------------------------
+
+/// An SPKI pin couldn’t be parsed.
+#[cfg(feature = "resolv-tls")]
+#[cfg_attr(docsrs, doc(cfg(feature = "resolv-tls")))]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SpkiPinError;
+
+#[cfg(feature = "resolv-tls")]
+impl fmt::Display for SpkiPinError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid SPKI pin")
+    }
+}
+
+#[cfg(feature = "resolv-tls")]
+impl error::Error for SpkiPinError {}
+
 //============ Testing ======================================================
 
 #[cfg(test)]
@@ -773,4 +1236,53 @@ mod test {
         assert!(conf.options.use_vc);
         assert_eq!(conf.options.ndots, 122);
     }
+
+    #[test]
+    fn parse_sortlist() {
+        let mut conf = ResolvConf::new();
+        let data = "sortlist 130.155.160.0/255.255.240.0 130.155.0.0 \
+                    192.0.2.1&255.255.255.0 bogus 10.0.0.0/8\n"
+            .to_string();
+        assert!(conf.parse(&mut io::Cursor::new(data)).is_ok());
+        assert_eq!(
+            conf.options.sortlist.as_slice(),
+            &[
+                SortListItem::new(
+                    Ipv4Addr::new(130, 155, 160, 0),
+                    Ipv4Addr::new(255, 255, 240, 0)
+                ),
+                SortListItem::new(
+                    Ipv4Addr::new(130, 155, 0, 0),
+                    Ipv4Addr::new(255, 255, 0, 0)
+                ),
+                SortListItem::new(
+                    Ipv4Addr::new(192, 0, 2, 1),
+                    Ipv4Addr::new(255, 255, 255, 0)
+                ),
+                SortListItem::new(
+                    Ipv4Addr::new(10, 0, 0, 0),
+                    Ipv4Addr::new(255, 0, 0, 0)
+                ),
+            ]
+        );
+        let sortlist = &conf.options.sortlist;
+        assert_eq!(sortlist.position(Ipv4Addr::new(130, 155, 170, 1)), 0);
+        assert_eq!(sortlist.position(Ipv4Addr::new(130, 155, 1, 1)), 1);
+        assert_eq!(sortlist.position(Ipv4Addr::new(192, 0, 2, 1)), 4);
+        assert_eq!(sortlist.position(Ipv4Addr::new(10, 1, 2, 3)), 3);
+        assert_eq!(sortlist.position(Ipv4Addr::new(198, 51, 100, 1)), 4);
+        assert_eq!(
+            SortListItem::with_natural_mask(Ipv4Addr::new(192, 0, 2, 0))
+                .mask(),
+            Ipv4Addr::new(255, 255, 255, 0)
+        );
+        assert!(conf.to_string().contains(
+            "sortlist 130.155.160.0/255.255.240.0 130.155.0.0/255.255.0.0 "
+        ));
+
+        let mut conf = ResolvConf::new();
+        let data = format!("sortlist{}\n", " 10.0.0.0".repeat(12));
+        assert!(conf.parse(&mut io::Cursor::new(data)).is_ok());
+        assert_eq!(conf.options.sortlist.len(), SortList::MAX_LEN);
+    }
 }
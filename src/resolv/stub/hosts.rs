@@ -0,0 +1,334 @@
+//! The hosts file.
+//!
+//! Before asking the DNS, the C library consults the hosts file, normally
+//! `/etc/hosts`, which contains a static mapping between host names and
+//! IP addresses. This module provides the [`Hosts`] table holding such a
+//! mapping as well as [`HostsFile`] which keeps a table in sync with the
+//! content of a hosts file.
+
+use crate::base::name::{Dname, ToDname};
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::str::{self, FromStr};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use std::vec::Vec;
+use std::{fs, io, iter};
+
+//------------ Hosts ---------------------------------------------------------
+
+/// A table mapping host names to IP addresses and back.
+///
+/// The table can be filled from data in the format of the hosts file
+/// through the `parse()` and `parse_file()` methods. In this format, each
+/// line contains an IP address followed by the canonical name of the host
+/// and any number of aliases. Everything following a `#` is a comment.
+/// Lines that cannot be parsed are ignored, just like the C library does.
+///
+/// Host names are compared ignoring ASCII case. If a name appears on more
+/// than one line, the addresses of all these lines are collected and the
+/// canonical name is taken from the first line.
+#[derive(Clone, Debug, Default)]
+pub struct Hosts {
+    /// The entries for all canonical names and aliases.
+    names: HashMap<Dname<Bytes>, HostsEntry>,
+
+    /// The host names for each address in the order they were added.
+    addrs: HashMap<IpAddr, Vec<Dname<Bytes>>>,
+}
+
+impl Hosts {
+    /// Creates a new, empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns whether the table is empty.
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    /// Adds a host to the table.
+    ///
+    /// The first element of `names` is the canonical name of the host, all
+    /// others are its aliases. If `names` is empty, nothing happens.
+    pub fn insert<I>(&mut self, addr: IpAddr, names: I)
+    where
+        I: IntoIterator<Item = Dname<Bytes>>,
+    {
+        let mut names = names.into_iter();
+        let canonical = match names.next() {
+            Some(name) => name,
+            None => return,
+        };
+        for name in iter::once(canonical.clone()).chain(names) {
+            let entry = self.names.entry(name.clone()).or_insert_with(|| {
+                HostsEntry {
+                    canonical: canonical.clone(),
+                    addrs: Vec::new(),
+                }
+            });
+            if !entry.addrs.contains(&addr) {
+                entry.addrs.push(addr)
+            }
+            let names = self.addrs.entry(addr).or_default();
+            if !names.contains(&name) {
+                names.push(name)
+            }
+        }
+    }
+
+    /// Looks up the entry for a host name.
+    pub fn lookup_host(&self, name: &impl ToDname) -> Option<&HostsEntry> {
+        self.names.get(&name.to_bytes())
+    }
+
+    /// Looks up the host names for an address.
+    ///
+    /// The first name returned is the canonical name of the first host
+    /// added for the address.
+    pub fn lookup_addr(&self, addr: IpAddr) -> Option<&[Dname<Bytes>]> {
+        self.addrs.get(&addr).map(Vec::as_slice)
+    }
+}
+
+/// # Parsing Hosts Files
+///
+impl Hosts {
+    /// Adds the hosts contained in a file.
+    pub fn parse_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<(), io::Error> {
+        let mut file = fs::File::open(path)?;
+        self.parse(&mut file)
+    }
+
+    /// Adds the hosts read from a reader.
+    ///
+    /// The format is that of the /etc/hosts file.
+    pub fn parse<R: Read>(
+        &mut self,
+        reader: &mut R,
+    ) -> Result<(), io::Error> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        for line in data.split(|&ch| ch == b'\n') {
+            // A line that isn’t valid UTF-8 can’t contain valid host names,
+            // so we skip it.
+            if let Ok(line) = str::from_utf8(line) {
+                self.parse_line(line)
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_line(&mut self, line: &str) {
+        let line = match line.find('#') {
+            Some(pos) => &line[..pos],
+            None => line,
+        };
+        let mut words = line.split_whitespace();
+        let addr = match words.next().map(IpAddr::from_str) {
+            Some(Ok(addr)) => addr,
+            _ => return,
+        };
+        let names: Result<Vec<_>, _> = words.map(Dname::from_str).collect();
+        if let Ok(names) = names {
+            self.insert(addr, names)
+        }
+    }
+}
+
+//------------ HostsEntry ----------------------------------------------------
+
+/// The addresses of a host name in a hosts table.
+#[derive(Clone, Debug)]
+pub struct HostsEntry {
+    /// The canonical name of the host.
+    canonical: Dname<Bytes>,
+
+    /// The addresses of the host.
+    addrs: Vec<IpAddr>,
+}
+
+impl HostsEntry {
+    /// Returns the canonical name of the host.
+    pub fn canonical_name(&self) -> &Dname<Bytes> {
+        &self.canonical
+    }
+
+    /// Returns the addresses of the host.
+    pub fn addrs(&self) -> &[IpAddr] {
+        &self.addrs
+    }
+}
+
+//------------ HostsFile -----------------------------------------------------
+
+/// A hosts table kept in sync with a hosts file.
+///
+/// The file is read when the table is first needed. Afterwards, every
+/// access checks the modification time and size of the file and reads it
+/// again if either of them has changed. If the file is missing or cannot
+/// be read, the table is empty.
+#[derive(Debug)]
+pub struct HostsFile {
+    /// The path of the hosts file.
+    path: PathBuf,
+
+    /// The table and the stamp of the file it was read from.
+    ///
+    /// This is `None` if the file hasn’t been read yet.
+    state: Mutex<Option<(Arc<Hosts>, FileStamp)>>,
+}
+
+/// The modification time and size of a file if it could be determined.
+type FileStamp = Option<(Option<SystemTime>, u64)>;
+
+impl HostsFile {
+    /// Creates a new value for the hosts file at the given path.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        HostsFile {
+            path: path.into(),
+            state: Mutex::new(None),
+        }
+    }
+
+    /// Returns the path of the hosts file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the current hosts table.
+    ///
+    /// Reads the file again if it has changed since it was last read.
+    pub fn hosts(&self) -> Arc<Hosts> {
+        let stamp = fs::metadata(&self.path)
+            .ok()
+            .map(|meta| (meta.modified().ok(), meta.len()));
+        let mut state = self.state.lock().unwrap();
+        if let Some((ref hosts, ref old)) = *state {
+            if *old == stamp {
+                return hosts.clone();
+            }
+        }
+        let mut hosts = Hosts::new();
+        // A file we can’t read simply results in an empty table.
+        let _ = hosts.parse_file(&self.path);
+        let hosts = Arc::new(hosts);
+        *state = Some((hosts.clone(), stamp));
+        hosts
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::string::String;
+    use std::{env, process};
+
+    fn name(s: &str) -> Dname<Bytes> {
+        Dname::from_str(s).unwrap()
+    }
+
+    fn addr(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn parse(s: &str) -> Hosts {
+        let mut hosts = Hosts::new();
+        hosts.parse(&mut s.as_bytes()).unwrap();
+        hosts
+    }
+
+    #[test]
+    fn parse_and_lookup() {
+        let hosts = parse(
+            "# The loopback.\n\
+             127.0.0.1\tlocalhost\n\
+             ::1 localhost ip6-localhost # comment\n\
+             \n\
+             192.0.2.1 Host.Example.com host alias.example.com\n\
+             192.0.2.2 other.example.com host\n\
+             not-an-address foo.example.com\n\
+             fe80::1%eth0 link.example.com\n\
+             192.0.2.3 bad..example.com\n\
+             192.0.2.4\n",
+        );
+
+        let entry = hosts.lookup_host(&name("localhost")).unwrap();
+        assert_eq!(entry.canonical_name(), &name("localhost"));
+        assert_eq!(entry.addrs(), &[addr("127.0.0.1"), addr("::1")]);
+        let entry = hosts.lookup_host(&name("ip6-localhost")).unwrap();
+        assert_eq!(entry.canonical_name(), &name("localhost"));
+        assert_eq!(entry.addrs(), &[addr("::1")]);
+
+        let entry = hosts.lookup_host(&name("HOST")).unwrap();
+        assert_eq!(entry.canonical_name(), &name("host.example.com"));
+        assert_eq!(entry.addrs(), &[addr("192.0.2.1"), addr("192.0.2.2")]);
+        let entry = hosts.lookup_host(&name("alias.example.com")).unwrap();
+        assert_eq!(entry.canonical_name(), &name("host.example.com"));
+        assert_eq!(entry.addrs(), &[addr("192.0.2.1")]);
+
+        assert!(hosts.lookup_host(&name("foo.example.com")).is_none());
+        assert!(hosts.lookup_host(&name("link.example.com")).is_none());
+        assert!(hosts.lookup_host(&name("example.com")).is_none());
+
+        assert_eq!(
+            hosts.lookup_addr(addr("192.0.2.1")).unwrap(),
+            &[
+                name("host.example.com"),
+                name("host"),
+                name("alias.example.com")
+            ]
+        );
+        assert_eq!(
+            hosts.lookup_addr(addr("::1")).unwrap(),
+            &[name("localhost"), name("ip6-localhost")]
+        );
+        assert!(hosts.lookup_addr(addr("192.0.2.3")).is_none());
+        assert!(hosts.lookup_addr(addr("192.0.2.4")).is_none());
+    }
+
+    #[test]
+    fn reload_file() {
+        let path = env::temp_dir()
+            .join(format!("domain-hosts-test-{}", process::id()));
+        let _ = fs::remove_file(&path);
+        let file = HostsFile::new(&path);
+        assert!(file.hosts().is_empty());
+
+        fs::write(&path, "192.0.2.1 host.example.com\n").unwrap();
+        let hosts = file.hosts();
+        assert_eq!(
+            hosts
+                .lookup_host(&name("host.example.com"))
+                .unwrap()
+                .addrs(),
+            &[addr("192.0.2.1")]
+        );
+        assert!(Arc::ptr_eq(&hosts, &file.hosts()));
+
+        let mut content = String::from("192.0.2.2 host.example.com\n");
+        content.push_str("192.0.2.3 other.example.com\n");
+        fs::write(&path, content).unwrap();
+        let hosts = file.hosts();
+        assert_eq!(
+            hosts
+                .lookup_host(&name("host.example.com"))
+                .unwrap()
+                .addrs(),
+            &[addr("192.0.2.2")]
+        );
+        assert!(hosts.lookup_host(&name("other.example.com")).is_some());
+
+        fs::remove_file(&path).unwrap();
+        assert!(file.hosts().is_empty());
+    }
+}
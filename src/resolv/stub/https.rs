@@ -0,0 +1,476 @@
+//! The DNS-over-HTTPS transport.
+//!
+//! This module implements the HTTPS transport of [RFC 8484] for the stub
+//! resolver. Queries are sent over HTTP/2 as either GET or POST requests.
+//! There is only one connection to each server which is shared by all
+//! queries to that server and kept open for as long as the server allows.
+//!
+//! [RFC 8484]: https://tools.ietf.org/html/rfc8484
+#![cfg(feature = "resolv-https")]
+
+use super::conf::{HttpsMethod, ServerConf};
+use super::tls;
+use super::{Answer, QueryMessage};
+use crate::base::message::Message;
+use crate::utils::base64;
+use bytes::{Bytes, BytesMut};
+use h2::client::SendRequest;
+use http::header::{ACCEPT, CONTENT_TYPE};
+use http::{Method, Request, StatusCode, Uri};
+use std::string::{String, ToString};
+use std::sync::Mutex;
+use std::{fmt, io};
+use tokio_native_tls::TlsConnector;
+
+//------------ Module Configuration ------------------------------------------
+
+/// The media type of DNS messages.
+const MEDIA_TYPE: &str = "application/dns-message";
+
+/// The largest response body we accept.
+const MAX_BODY_LEN: usize = 0xFFFF;
+
+//------------ HttpsConn -----------------------------------------------------
+
+/// The HTTP/2 connection to a single server.
+#[derive(Default)]
+pub(super) struct HttpsConn {
+    /// The connector for creating new connections.
+    ///
+    /// This is created when it is needed for the first time.
+    connector: Mutex<Option<TlsConnector>>,
+
+    /// The handle for sending requests over the current connection.
+    send: Mutex<Option<SendRequest<Bytes>>>,
+}
+
+impl HttpsConn {
+    /// Sends a query to the server and returns the answer.
+    ///
+    /// Uses the existing connection if there is one and it is still
+    /// usable. Otherwise opens a new connection.
+    pub async fn query(
+        &self,
+        query: &QueryMessage,
+        conf: &ServerConf,
+    ) -> Result<Answer, io::Error> {
+        let message = query.as_target().as_dgram_slice();
+        let uri = request_uri(conf, message)?;
+        let current = self.send.lock().unwrap().clone();
+        let send = match current {
+            Some(send) => match send.ready().await {
+                Ok(send) => send,
+                Err(_) => self.connect(conf, &uri).await?,
+            },
+            None => self.connect(conf, &uri).await?,
+        };
+        let answer = request(send, conf.https_method, uri, message).await?;
+        if !answer.is_answer(&query.as_message()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unexpected answer",
+            ));
+        }
+        Ok(answer.into())
+    }
+
+    /// Opens a new connection to the server.
+    ///
+    /// The new connection replaces the current one.
+    async fn connect(
+        &self,
+        conf: &ServerConf,
+        uri: &Uri,
+    ) -> Result<SendRequest<Bytes>, io::Error> {
+        let connector = self.connector(conf)?;
+        let domain = match conf.tls_auth_name {
+            Some(ref name) => name.clone(),
+            None => match uri.host() {
+                Some(host) => host.to_string(),
+                None => return Err(uri_error()),
+            },
+        };
+        let sock = tls::connect(&connector, conf, &domain).await?;
+        if sock.get_ref().negotiated_alpn().map_err(tls::tls_error)?
+            != Some(b"h2".to_vec())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "server does not support HTTP/2",
+            ));
+        }
+        let (send, connection) =
+            h2::client::handshake(sock).await.map_err(h2_error)?;
+        tokio::spawn(async move {
+            // The connection ends with an error if the server goes away.
+            // Since we notice that when we try to use it next time, we can
+            // ignore the error here.
+            let _ = connection.await;
+        });
+        let send = send.ready().await.map_err(h2_error)?;
+        *self.send.lock().unwrap() = Some(send.clone());
+        Ok(send)
+    }
+
+    /// Returns the connector, creating it if necessary.
+    fn connector(
+        &self,
+        conf: &ServerConf,
+    ) -> Result<TlsConnector, io::Error> {
+        let mut connector = self.connector.lock().unwrap();
+        if let Some(ref connector) = *connector {
+            return Ok(connector.clone());
+        }
+        let mut builder = tls::connector_builder(conf);
+        builder.request_alpns(&["h2"]);
+        let res =
+            TlsConnector::from(builder.build().map_err(tls::tls_error)?);
+        *connector = Some(res.clone());
+        Ok(res)
+    }
+}
+
+//--- Debug
+
+impl fmt::Debug for HttpsConn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HttpsConn")
+            .field("connected", &self.send.lock().unwrap().is_some())
+            .finish()
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Sends a query as an HTTP request and returns the response message.
+async fn request(
+    mut send: SendRequest<Bytes>,
+    method: HttpsMethod,
+    uri: Uri,
+    message: &[u8],
+) -> Result<Message<Bytes>, io::Error> {
+    let request = Request::builder().uri(uri).header(ACCEPT, MEDIA_TYPE);
+    let request = match method {
+        HttpsMethod::Get => request.method(Method::GET),
+        HttpsMethod::Post => request
+            .method(Method::POST)
+            .header(CONTENT_TYPE, MEDIA_TYPE),
+    };
+    let request = request.body(()).map_err(|_| uri_error())?;
+    let (response, mut stream) = send
+        .send_request(request, method == HttpsMethod::Get)
+        .map_err(h2_error)?;
+    if method == HttpsMethod::Post {
+        stream
+            .send_data(Bytes::copy_from_slice(message), true)
+            .map_err(h2_error)?;
+    }
+    let response = response.await.map_err(h2_error)?;
+    if response.status() != StatusCode::OK {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "server responded with HTTP status {}",
+                response.status()
+            ),
+        ));
+    }
+    if let Some(content_type) = response.headers().get(CONTENT_TYPE) {
+        if content_type.as_bytes() != MEDIA_TYPE.as_bytes() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unexpected content type",
+            ));
+        }
+    }
+    let mut body = response.into_body();
+    let mut buf = BytesMut::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(h2_error)?;
+        let _ = body.flow_control().release_capacity(chunk.len());
+        buf.extend_from_slice(&chunk);
+        if buf.len() > MAX_BODY_LEN {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "response too long",
+            ));
+        }
+    }
+    Message::from_octets(buf.freeze()).map_err(|_| {
+        io::Error::new(io::ErrorKind::InvalidData, "short message")
+    })
+}
+
+/// Returns the URI for a request to the server.
+fn request_uri(conf: &ServerConf, message: &[u8]) -> Result<Uri, io::Error> {
+    let dns = match conf.https_method {
+        HttpsMethod::Get => Some(encode_base64url(message)),
+        HttpsMethod::Post => None,
+    };
+    let uri = match conf.https_template {
+        Some(ref template) => expand_template(template, dns.as_deref())?,
+        None => {
+            let host = match conf.tls_auth_name {
+                Some(ref name) => name.clone(),
+                None => match conf.addr.ip() {
+                    std::net::IpAddr::V4(addr) => addr.to_string(),
+                    std::net::IpAddr::V6(addr) => format!("[{}]", addr),
+                },
+            };
+            expand_template(
+                &format!("https://{}/dns-query{{?dns}}", host),
+                dns.as_deref(),
+            )?
+        }
+    };
+    uri.parse().map_err(|_| uri_error())
+}
+
+/// Expands a URI template.
+///
+/// We only support the `{?dns}` and `{&dns}` expressions of [RFC 6570]
+/// which is what [RFC 8484] uses. If there is no expression in the
+/// template, the variable is appended.
+///
+/// [RFC 6570]: https://tools.ietf.org/html/rfc6570
+/// [RFC 8484]: https://tools.ietf.org/html/rfc8484
+fn expand_template(
+    template: &str,
+    dns: Option<&str>,
+) -> Result<String, io::Error> {
+    let start = match template.find('{') {
+        Some(start) => start,
+        None => {
+            let mut res = String::from(template);
+            if let Some(dns) = dns {
+                res.push(if template.contains('?') { '&' } else { '?' });
+                res.push_str("dns=");
+                res.push_str(dns);
+            }
+            return Ok(res);
+        }
+    };
+    let end = match template[start..].find('}') {
+        Some(end) => start + end,
+        None => return Err(uri_error()),
+    };
+    let operator = match &template[start + 1..end] {
+        "?dns" => '?',
+        "&dns" => '&',
+        _ => return Err(uri_error()),
+    };
+    if template[end + 1..].contains('{') {
+        return Err(uri_error());
+    }
+    let mut res = String::from(&template[..start]);
+    if let Some(dns) = dns {
+        res.push(operator);
+        res.push_str("dns=");
+        res.push_str(dns);
+    }
+    res.push_str(&template[end + 1..]);
+    Ok(res)
+}
+
+/// Encodes data in unpadded *base64url* as required by RFC 8484.
+fn encode_base64url(data: &[u8]) -> String {
+    base64::encode_string(data)
+        .chars()
+        .filter_map(|ch| match ch {
+            '+' => Some('-'),
+            '/' => Some('_'),
+            '=' => None,
+            ch => Some(ch),
+        })
+        .collect()
+}
+
+/// Converts an HTTP/2 error into an IO error.
+fn h2_error(err: h2::Error) -> io::Error {
+    if err.is_io() {
+        if let Some(err) = err.into_io() {
+            return err;
+        }
+        return io::Error::new(io::ErrorKind::ConnectionAborted, "I/O error");
+    }
+    io::Error::new(io::ErrorKind::ConnectionAborted, err)
+}
+
+/// Returns the error for an invalid URI template.
+fn uri_error() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, "invalid URI template")
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Rcode, Rtype};
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::{Dname, ToDname};
+    use crate::base::question::Question;
+    use crate::rdata::A;
+    use crate::resolv::stub::conf::{ResolvConf, SpkiPin, Transport};
+    use crate::resolv::StubResolver;
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::vec::Vec;
+    use tokio::net::TcpListener;
+    use tokio::runtime;
+    use tokio_native_tls::native_tls;
+    use tokio_native_tls::TlsAcceptor;
+
+    /// The pin for the test certificate.
+    const PIN: &str = "5omdzeHTu+XEyq5kx0ztpPoLfBsSZGJmYGpeIPxFw5Y=";
+
+    /// Starts a DoH server that answers all A queries with 192.0.2.1.
+    ///
+    /// Returns the server’s address and the number of accepted connections.
+    async fn server() -> (SocketAddr, Arc<AtomicUsize>) {
+        let identity = native_tls::Identity::from_pkcs8(
+            include_bytes!("../../../test-data/tls/cert.pem"),
+            include_bytes!("../../../test-data/tls/key.pem"),
+        )
+        .unwrap();
+        let acceptor = TlsAcceptor::from(
+            native_tls::TlsAcceptor::builder(identity)
+                .accept_alpn(&["h2"])
+                .build()
+                .unwrap(),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let count = Arc::new(AtomicUsize::new(0));
+        let accepted = count.clone();
+        tokio::spawn(async move {
+            loop {
+                let (sock, _) = listener.accept().await.unwrap();
+                accepted.fetch_add(1, Ordering::SeqCst);
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    let sock = acceptor.accept(sock).await.unwrap();
+                    let mut conn = h2::server::handshake(sock).await.unwrap();
+                    while let Some(Ok((request, mut respond))) =
+                        conn.accept().await
+                    {
+                        let query = query(request).await;
+                        let response = http::Response::builder()
+                            .header(CONTENT_TYPE, MEDIA_TYPE)
+                            .body(())
+                            .unwrap();
+                        let mut stream =
+                            respond.send_response(response, false).unwrap();
+                        stream.send_data(answer(query), true).unwrap();
+                    }
+                });
+            }
+        });
+        (addr, count)
+    }
+
+    /// Extracts the query from a request.
+    async fn query(request: Request<h2::RecvStream>) -> Vec<u8> {
+        assert_eq!(request.uri().path(), "/dns-query");
+        assert_eq!(request.headers()[ACCEPT], MEDIA_TYPE);
+        if request.method() == Method::GET {
+            let dns = request.uri().query().unwrap();
+            let dns = dns.strip_prefix("dns=").unwrap();
+            assert!(!dns.contains('='));
+            let mut dns: String = dns
+                .chars()
+                .map(|ch| match ch {
+                    '-' => '+',
+                    '_' => '/',
+                    ch => ch,
+                })
+                .collect();
+            while dns.len() % 4 != 0 {
+                dns.push('=')
+            }
+            base64::decode(&dns).unwrap().to_vec()
+        } else {
+            assert_eq!(request.method(), Method::POST);
+            assert_eq!(request.headers()[CONTENT_TYPE], MEDIA_TYPE);
+            let mut body = request.into_body();
+            let mut res = Vec::new();
+            while let Some(chunk) = body.data().await {
+                res.extend_from_slice(&chunk.unwrap());
+            }
+            res
+        }
+    }
+
+    fn answer(query: Vec<u8>) -> Bytes {
+        let query = Message::from_octets(query).unwrap();
+        let mut msg = MessageBuilder::new_bytes()
+            .start_answer(&query, Rcode::NoError)
+            .unwrap();
+        let qname = query.first_question().unwrap().qname().to_bytes();
+        msg.push((qname, 3600, A::new(Ipv4Addr::new(192, 0, 2, 1))))
+            .unwrap();
+        msg.into_message().into_octets()
+    }
+
+    fn resolver(addr: SocketAddr, method: HttpsMethod) -> StubResolver {
+        let mut server = ServerConf::new(addr, Transport::Https);
+        server.tls_pins.push(SpkiPin::from_str(PIN).unwrap());
+        server.https_method = method;
+        server.https_template =
+            Some("https://dns.example/dns-query{?dns}".into());
+        let mut conf = ResolvConf::new();
+        conf.servers.push(server);
+        conf.options.attempts = 1;
+        StubResolver::from_conf(conf)
+    }
+
+    fn question() -> Question<Dname<Vec<u8>>> {
+        Question::new_in(Dname::from_str("dns.example").unwrap(), Rtype::A)
+    }
+
+    #[test]
+    fn template() {
+        assert_eq!(
+            expand_template("https://dns.example/q{?dns}", Some("AAAB"))
+                .unwrap(),
+            "https://dns.example/q?dns=AAAB"
+        );
+        assert_eq!(
+            expand_template("https://dns.example/q{?dns}", None).unwrap(),
+            "https://dns.example/q"
+        );
+        assert_eq!(
+            expand_template("https://dns.example/q?ct{&dns}", Some("AAAB"))
+                .unwrap(),
+            "https://dns.example/q?ct&dns=AAAB"
+        );
+        assert_eq!(
+            expand_template("https://dns.example/q", Some("AAAB")).unwrap(),
+            "https://dns.example/q?dns=AAAB"
+        );
+        assert!(expand_template("https://dns.example/{x}", None).is_err());
+        assert_eq!(encode_base64url(&[0xfb, 0xff]), "-_8");
+    }
+
+    #[test]
+    fn query_get_and_post() {
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let (addr, count) = server().await;
+            for &method in &[HttpsMethod::Get, HttpsMethod::Post] {
+                let resolver = resolver(addr, method);
+                for _ in 0..3 {
+                    let answer = resolver.query(question()).await.unwrap();
+                    assert_eq!(answer.header().rcode(), Rcode::NoError);
+                    assert_eq!(answer.header_counts().ancount(), 1);
+                }
+            }
+            assert_eq!(count.load(Ordering::SeqCst), 2);
+        })
+    }
+}
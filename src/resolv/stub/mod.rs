@@ -1,26 +1,33 @@
 use self::conf::{
     ResolvConf, ResolvOptions, SearchSuffix, ServerConf, Transport,
 };
-use crate::base::iana::Rcode;
+use self::hosts::{Hosts, HostsEntry, HostsFile};
+use crate::base::iana::{OptRcode, OptionCode, Rcode, Rtype};
 use crate::base::message::Message;
 use crate::base::message_builder::{
     AdditionalBuilder, MessageBuilder, StreamTarget,
 };
-use crate::base::name::{ToDname, ToRelativeDname};
-use crate::base::octets::Octets512;
+use crate::base::name::{Dname, ParsedDname, ToDname, ToRelativeDname};
+use crate::base::opt::{Cookie, OptData, OptRecord, UnknownOptData};
 use crate::base::question::Question;
-use crate::resolv::lookup::addr::{lookup_addr, FoundAddrs};
-use crate::resolv::lookup::host::{lookup_host, search_host, FoundHosts};
+use crate::rdata::{Aaaa, Cname, Ptr, A};
+use crate::resolv::lookup::addr::{dname_from_addr, FoundAddrs};
+use crate::resolv::lookup::host::FoundHosts;
 use crate::resolv::lookup::srv::{lookup_srv, FoundSrvs, SrvError};
-use crate::resolv::resolver::{Resolver, SearchNames};
+use crate::resolv::resolver::{
+    QueryError, Resolver, SearchNames, ServerError, ServerErrorKind,
+};
 use bytes::Bytes;
 use futures::future::FutureExt;
+use futures::stream::{FuturesUnordered, StreamExt};
 use std::boxed::Box;
+use std::collections::HashMap;
 use std::future::Future;
 use std::net::{IpAddr, SocketAddr};
 use std::pin::Pin;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use std::vec::Vec;
 /// A stub resolver.
 ///
@@ -33,7 +40,7 @@ use std::vec::Vec;
 /// The main type is [`StubResolver`] that implements the [`Resolver`] trait
 /// and thus can be used with the various lookup functions.
 use std::{io, ops};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::{TcpStream, UdpSocket};
 #[cfg(feature = "resolv-sync")]
 use tokio::runtime;
@@ -42,12 +49,24 @@ use tokio::time::timeout;
 //------------ Sub-modules ---------------------------------------------------
 
 pub mod conf;
+pub mod hosts;
+mod https;
+mod tcp;
+mod tls;
 
 //------------ Module Configuration ------------------------------------------
 
 /// How many times do we try a new random port if we get ‘address in use.’
 const RETRY_RANDOM_PORT: usize = 10;
 
+/// How long to back off from a server after its first failed query.
+///
+/// The time doubles with every further consecutive failure.
+const MIN_SERVER_BACKOFF: Duration = Duration::from_secs(1);
+
+/// The maximum time to back off from a failing server.
+const MAX_SERVER_BACKOFF: Duration = Duration::from_secs(120);
+
 //------------ StubResolver --------------------------------------------------
 
 /// A DNS stub resolver.
@@ -60,6 +79,9 @@ const RETRY_RANDOM_PORT: usize = 10;
 /// Stub resolver values can be cloned relatively cheaply as they keep all
 /// information behind an arc.
 ///
+/// Host and address lookups consult the hosts file given in the
+/// configuration before asking the DNS, just like the C library does.
+///
 /// If you want to run a single query or lookup on a resolver synchronously,
 /// you can do so simply by using the [`run()`] or [`run_with_conf()`]
 /// associated functions.
@@ -79,6 +101,9 @@ pub struct StubResolver {
 
     /// Resolver options.
     options: ResolvOptions,
+
+    /// The hosts file if we use one.
+    hosts: Option<Arc<HostsFile>>,
 }
 
 impl StubResolver {
@@ -89,12 +114,16 @@ impl StubResolver {
 
     /// Creates a new resolver using the given configuraiton.
     pub fn from_conf(conf: ResolvConf) -> Self {
+        let mut cookies = HashMap::new();
         StubResolver {
-            preferred: ServerList::from_conf(&conf, |s| {
+            preferred: ServerList::from_conf(&conf, &mut cookies, |s| {
                 s.transport.is_preferred()
             }),
-            stream: ServerList::from_conf(&conf, |s| s.transport.is_stream()),
+            stream: ServerList::from_conf(&conf, &mut cookies, |s| {
+                s.transport.is_stream()
+            }),
             options: conf.options,
+            hosts: conf.hosts_file.map(|path| Arc::new(HostsFile::new(path))),
         }
     }
 
@@ -102,11 +131,28 @@ impl StubResolver {
         &self.options
     }
 
+    /// Returns the current content of the hosts file.
+    ///
+    /// Returns `None` if the resolver doesn’t use a hosts file.
+    pub fn hosts(&self) -> Option<Arc<Hosts>> {
+        self.hosts.as_ref().map(|file| file.hosts())
+    }
+
     pub async fn query<N: ToDname, Q: Into<Question<N>>>(
         &self,
         question: Q,
-    ) -> Result<Answer, io::Error> {
-        Query::new(self)?
+    ) -> Result<Answer, QueryError> {
+        self.query_with_options(question, &QueryOptions::default())
+            .await
+    }
+
+    /// Starts a query using the given per-query options.
+    pub async fn query_with_options<N: ToDname, Q: Into<Question<N>>>(
+        &self,
+        question: Q,
+        options: &QueryOptions,
+    ) -> Result<Answer, QueryError> {
+        Query::new(self, options)?
             .run(Query::create_message(question.into()))
             .await
     }
@@ -114,31 +160,92 @@ impl StubResolver {
     async fn query_message(
         &self,
         message: QueryMessage,
-    ) -> Result<Answer, io::Error> {
-        Query::new(self)?.run(message).await
+    ) -> Result<Answer, QueryError> {
+        Query::new(self, &QueryOptions::default())?
+            .run(message)
+            .await
     }
 }
 
 impl StubResolver {
+    /// Looks up the host names of an address.
+    ///
+    /// If the address is in the hosts file, the names from there are
+    /// returned without asking the DNS. Unless the `no_check_name` option
+    /// is set, the names received from the DNS have to be valid host
+    /// names.
     pub async fn lookup_addr(
         &self,
         addr: IpAddr,
-    ) -> Result<FoundAddrs<&Self>, io::Error> {
-        lookup_addr(&self, addr).await
+    ) -> Result<FoundAddrs<&Self>, QueryError> {
+        if let Some(hosts) = self.hosts() {
+            if let Some(names) = hosts.lookup_addr(addr) {
+                return Ok(FoundAddrs::new(Answer::from_hosts_names(
+                    addr, names,
+                )));
+            }
+        }
+        let answer = self.query((dname_from_addr(addr), Rtype::Ptr)).await;
+        self.check_names(answer).map(FoundAddrs::new)
     }
 
+    /// Looks up the addresses of a host.
+    ///
+    /// If the host is in the hosts file, the addresses from there are
+    /// returned without asking the DNS. Otherwise, the `single_request`,
+    /// `single_request_reopen`, `use_inet6`, `no_check_name`, and
+    /// `sortlist` options are applied to the DNS lookup.
     pub async fn lookup_host(
         &self,
         qname: impl ToDname,
-    ) -> Result<FoundHosts<&Self>, io::Error> {
-        lookup_host(&self, qname).await
+    ) -> Result<FoundHosts<&Self>, QueryError> {
+        if let Some(found) = self.lookup_hosts_file(&qname) {
+            return Ok(found);
+        }
+        self.lookup_host_dns(qname).await
     }
 
+    /// Looks up the addresses of a host using the search list.
+    ///
+    /// The name is first looked up as an absolute name in the hosts file.
+    /// Only if it isn’t found there, the DNS is asked for the name
+    /// combined with each suffix in the search list and finally for the
+    /// name as an absolute name. If the `no_tld_query` option is set, a
+    /// name with a single label is never looked up in the root domain.
     pub async fn search_host(
         &self,
         qname: impl ToRelativeDname,
-    ) -> Result<FoundHosts<&Self>, io::Error> {
-        search_host(&self, qname).await
+    ) -> Result<FoundHosts<&Self>, QueryError> {
+        if let Some(found) = self.lookup_hosts_file(&(&qname).chain_root()) {
+            return Ok(found);
+        }
+        let no_tld =
+            self.options.no_tld_query && qname.iter_labels().count() <= 1;
+        let mut last = None;
+        for suffix in self.options.search.iter() {
+            if no_tld && suffix.is_root() {
+                continue;
+            }
+            if let Ok(name) = (&qname).chain(suffix) {
+                let res = self.lookup_host_dns(name).await;
+                if let Ok(ref found) = res {
+                    if !found.is_empty() {
+                        return res;
+                    }
+                }
+                last = Some(res);
+            }
+        }
+        if !no_tld {
+            return self.lookup_host_dns(qname.chain_root()).await;
+        }
+        match last {
+            Some(res) => res,
+            None => Err(QueryError::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no search domain for single-label name",
+            ))),
+        }
     }
 
     pub async fn lookup_srv(
@@ -149,6 +256,85 @@ impl StubResolver {
     ) -> Result<Option<FoundSrvs>, SrvError> {
         lookup_srv(&self, service, name, fallback_port).await
     }
+
+    /// Looks up the addresses of a host in the DNS.
+    async fn lookup_host_dns(
+        &self,
+        qname: impl ToDname,
+    ) -> Result<FoundHosts<&Self>, QueryError> {
+        let options = &self.options;
+        let (aaaa, a) = if options.use_inet6 {
+            // Like glibc, only fall back to A records if there are no AAAA
+            // records. These are then mapped to IPv6 below.
+            let aaaa = self.query((&qname, Rtype::Aaaa)).await;
+            let has_aaaa = match aaaa {
+                Ok(ref answer) => answer
+                    .answer()
+                    .map(|section| section.limit_to::<Aaaa>().next())
+                    .map(|record| record.is_some())
+                    .unwrap_or(false),
+                Err(_) => false,
+            };
+            if has_aaaa {
+                let skipped = io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "A query skipped due to use_inet6",
+                );
+                (aaaa, Err(QueryError::Io(skipped)))
+            } else {
+                (aaaa, self.query((&qname, Rtype::A)).await)
+            }
+        } else if options.single_request || options.single_request_reopen {
+            let a = self.query((&qname, Rtype::A)).await;
+            (self.query((&qname, Rtype::Aaaa)).await, a)
+        } else {
+            tokio::join!(
+                self.query((&qname, Rtype::Aaaa)),
+                self.query((&qname, Rtype::A)),
+            )
+        };
+        let mut found =
+            FoundHosts::new(self.check_names(aaaa), self.check_names(a))?;
+        if !options.sortlist.is_empty() {
+            // The sort list only applies to IPv4 addresses. IPv6
+            // addresses stay in front of them.
+            found.sort_by_key(|addr| match *addr {
+                IpAddr::V4(addr) => options.sortlist.position(addr) + 1,
+                IpAddr::V6(_) => 0,
+            });
+        }
+        if options.use_inet6 {
+            found.map_ipv4_to_ipv6();
+        }
+        Ok(found)
+    }
+
+    /// Checks the host names in an answer unless told not to.
+    fn check_names(
+        &self,
+        answer: Result<Answer, QueryError>,
+    ) -> Result<Answer, QueryError> {
+        let answer = answer?;
+        if self.options.no_check_name || answer.has_valid_host_names() {
+            Ok(answer)
+        } else {
+            Err(QueryError::InvalidName)
+        }
+    }
+
+    /// Looks up a host in the hosts file.
+    fn lookup_hosts_file(
+        &self,
+        qname: &impl ToDname,
+    ) -> Option<FoundHosts<&Self>> {
+        let hosts = self.hosts()?;
+        let entry = hosts.lookup_host(qname)?;
+        FoundHosts::new(
+            Ok(Answer::from_hosts_entry(qname, entry, Rtype::Aaaa)),
+            Ok(Answer::from_hosts_entry(qname, entry, Rtype::A)),
+        )
+        .ok()
+    }
 }
 
 #[cfg(feature = "resolv-sync")]
@@ -204,7 +390,7 @@ impl<'a> Resolver for &'a StubResolver {
     type Octets = Bytes;
     type Answer = Answer;
     type Query =
-        Pin<Box<dyn Future<Output = Result<Answer, io::Error>> + 'a>>;
+        Pin<Box<dyn Future<Output = Result<Answer, QueryError>> + 'a>>;
 
     fn query<N, Q>(&self, question: Q) -> Self::Query
     where
@@ -234,6 +420,9 @@ pub struct Query<'a> {
     /// The resolver whose configuration we are using.
     resolver: &'a StubResolver,
 
+    /// The options for this query.
+    options: &'a QueryOptions,
+
     /// Are we still in the preferred server list or have gone streaming?
     preferred: bool,
 
@@ -243,58 +432,78 @@ pub struct Query<'a> {
     /// The index in the server list we currently trying.
     counter: ServerListCounter,
 
-    /// The preferred error to return.
-    ///
-    /// Every time we finish a single query, we see if we can update this with
-    /// a better one. If we finally have to fail, we return this result. This
-    /// is a result so we can return a servfail answer if that is the only
-    /// answer we get. (Remember, SERVFAIL is returned for a bogus answer, so
-    /// you might want to know.)
-    error: Result<Answer, io::Error>,
+    /// Have we already retried the current server after a BADCOOKIE?
+    cookie_retried: bool,
+
+    /// What went wrong with each server we tried.
+    failures: Vec<ServerError>,
 }
 
 impl<'a> Query<'a> {
-    pub fn new(resolver: &'a StubResolver) -> Result<Self, io::Error> {
-        let (preferred, counter) =
-            if resolver.options().use_vc || resolver.preferred.is_empty() {
-                if resolver.stream.is_empty() {
-                    return Err(io::Error::new(
-                        io::ErrorKind::NotFound,
-                        "no servers available",
-                    ));
-                }
-                (false, resolver.stream.counter(resolver.options().rotate))
-            } else {
-                (true, resolver.preferred.counter(resolver.options().rotate))
-            };
+    pub fn new(
+        resolver: &'a StubResolver,
+        options: &'a QueryOptions,
+    ) -> Result<Self, QueryError> {
+        let preferred = match options.transport {
+            Some(transport) => transport.is_preferred(),
+            None => {
+                !resolver.options().use_vc && !resolver.preferred.is_empty()
+            }
+        };
+        let counter = Self::new_counter(resolver, options, preferred);
+        if counter.is_empty() {
+            return Err(QueryError::NoServers);
+        }
         Ok(Query {
             resolver,
+            options,
             preferred,
             attempt: 0,
             counter,
-            error: Err(io::Error::new(
-                io::ErrorKind::TimedOut,
-                "all timed out",
-            )),
+            cookie_retried: false,
+            failures: Vec::new(),
         })
     }
 
     pub async fn run(
         mut self,
         mut message: QueryMessage,
-    ) -> Result<Answer, io::Error> {
+    ) -> Result<Answer, QueryError> {
+        if self.resolver.options().blast {
+            return self.run_blast(message).await;
+        }
         loop {
-            match self.run_query(&mut message).await {
+            self.current_server().prepare_message(
+                &mut message,
+                self.resolver.options(),
+                self.options,
+            )?;
+            match self.run_query(&message).await {
                 Ok(answer) => {
-                    if answer.header().rcode() == Rcode::FormErr
-                        && self.current_server().does_edns()
-                    {
-                        // FORMERR with EDNS: turn off EDNS and try again.
-                        self.current_server().disable_edns();
-                        continue;
+                    if answer.header().rcode() == Rcode::FormErr {
+                        if self.current_server().does_edns() {
+                            // FORMERR with EDNS: turn off EDNS and try
+                            // again.
+                            self.current_server().disable_edns();
+                            continue;
+                        }
+                        // FORMERR without EDNS: go to next server.
+                        self.add_failure(ServerErrorKind::FormErr);
                     } else if answer.header().rcode() == Rcode::ServFail {
                         // SERVFAIL: go to next server.
-                        self.update_error_servfail(answer);
+                        self.add_failure(ServerErrorKind::ServFail);
+                    } else if answer.is_bad_cookie() {
+                        // BADCOOKIE: We have learned the new server cookie
+                        // from the answer, so try again once. If that
+                        // doesn’t help, RFC 7873 suggests TCP.
+                        if !self.cookie_retried {
+                            self.cookie_retried = true;
+                            continue;
+                        }
+                        self.add_failure(ServerErrorKind::BadCookie);
+                        if self.switch_to_stream() {
+                            continue;
+                        }
                     } else if answer.header().tc()
                         && self.preferred
                         && !self.resolver.options().ign_tc
@@ -302,29 +511,123 @@ impl<'a> Query<'a> {
                         // Truncated. If we can, switch to stream transports
                         // and try again. Otherwise return the truncated
                         // answer.
-                        if self.switch_to_stream() {
+                        if self.can_switch_to_stream() {
+                            self.add_failure(ServerErrorKind::Truncated);
+                            self.switch_to_stream();
                             continue;
                         } else {
                             return Ok(answer);
                         }
+                    } else if self.resolver.options().aa_only
+                        && !answer.header().aa()
+                    {
+                        // Not authoritative but we want that: go to next
+                        // server.
+                        self.add_failure(ServerErrorKind::NotAuthoritative);
                     } else {
                         // I guess we have an answer ...
                         return Ok(answer);
                     }
                 }
-                Err(err) => self.update_error(err),
+                Err(err) => self.add_failure(Self::io_failure(err)),
             }
             if !self.next_server() {
-                return self.error;
+                return Err(QueryError::AllServersFailed(self.failures));
             }
         }
     }
 
+    /// Runs the query by sending it to all servers at once.
+    ///
+    /// This implements the `blast` option. In each attempt, the query is
+    /// sent to all servers of the current list simultaneously and the
+    /// first usable answer is returned. Answers that make `run` retry the
+    /// same server count as a failure here and the retry happens with the
+    /// next attempt.
+    async fn run_blast(
+        mut self,
+        message: QueryMessage,
+    ) -> Result<Answer, QueryError> {
+        let resolver = self.resolver;
+        let options = self.options;
+        loop {
+            let list = if self.preferred {
+                &resolver.preferred
+            } else {
+                &resolver.stream
+            };
+            let mut queries = FuturesUnordered::new();
+            for &idx in &self.counter.order {
+                let server = &list[idx];
+                let mut message = message.clone();
+                server.prepare_message(
+                    &mut message,
+                    resolver.options(),
+                    options,
+                )?;
+                queries.push(async move {
+                    let start = Instant::now();
+                    let res = server
+                        .query(&message, resolver.options(), options)
+                        .await;
+                    (server, start.elapsed(), res)
+                });
+            }
+
+            let mut truncated = false;
+            while let Some((server, rtt, res)) = queries.next().await {
+                let kind = match res {
+                    Ok(answer) => {
+                        server.record_response(&answer, rtt);
+                        let header = answer.header();
+                        if header.rcode() == Rcode::FormErr {
+                            if server.does_edns() {
+                                server.disable_edns();
+                            }
+                            ServerErrorKind::FormErr
+                        } else if header.rcode() == Rcode::ServFail {
+                            ServerErrorKind::ServFail
+                        } else if answer.is_bad_cookie() {
+                            ServerErrorKind::BadCookie
+                        } else if header.tc()
+                            && self.preferred
+                            && !resolver.options().ign_tc
+                            && self.can_switch_to_stream()
+                        {
+                            truncated = true;
+                            ServerErrorKind::Truncated
+                        } else if resolver.options().aa_only && !header.aa() {
+                            ServerErrorKind::NotAuthoritative
+                        } else {
+                            return Ok(answer);
+                        }
+                    }
+                    Err(err) => {
+                        server.record_failure(Instant::now());
+                        Self::io_failure(err)
+                    }
+                };
+                self.failures.push(ServerError::new(
+                    server.conf.addr,
+                    server.conf.transport,
+                    kind,
+                ));
+            }
+
+            if truncated && self.switch_to_stream() {
+                continue;
+            }
+            self.attempt += 1;
+            if self.attempt >= resolver.options().attempts {
+                return Err(QueryError::AllServersFailed(self.failures));
+            }
+            self.counter =
+                Self::new_counter(resolver, options, self.preferred);
+        }
+    }
+
     fn create_message(question: Question<impl ToDname>) -> QueryMessage {
-        let mut message = MessageBuilder::from_target(
-            StreamTarget::new(Octets512::new()).unwrap(),
-        )
-        .unwrap();
+        let mut message = MessageBuilder::new_stream_vec();
         message.header_mut().set_rd(true);
         let mut message = message.question();
         message.push(question).unwrap();
@@ -332,12 +635,19 @@ impl<'a> Query<'a> {
     }
 
     async fn run_query(
-        &mut self,
-        message: &mut QueryMessage,
+        &self,
+        message: &QueryMessage,
     ) -> Result<Answer, io::Error> {
         let server = self.current_server();
-        server.prepare_message(message);
-        server.query(message).await
+        let start = Instant::now();
+        let res = server
+            .query(message, self.resolver.options(), self.options)
+            .await;
+        match res {
+            Ok(ref answer) => server.record_response(answer, start.elapsed()),
+            Err(_) => server.record_failure(Instant::now()),
+        }
+        res
     }
 
     fn current_server(&self) -> &ServerInfo {
@@ -349,32 +659,45 @@ impl<'a> Query<'a> {
         self.counter.info(list)
     }
 
-    fn update_error(&mut self, err: io::Error) {
-        // We keep the last error except for timeouts or if we have a servfail
-        // answer already. Since we start with a timeout, we still get a that
-        // if everything times out.
-        if err.kind() != io::ErrorKind::TimedOut && self.error.is_err() {
-            self.error = Err(err)
+    /// Notes that the current server failed to provide an answer.
+    fn add_failure(&mut self, kind: ServerErrorKind) {
+        let conf = &self.current_server().conf;
+        let err = ServerError::new(conf.addr, conf.transport, kind);
+        self.failures.push(err)
+    }
+
+    /// Returns the server error kind for an IO error.
+    fn io_failure(err: io::Error) -> ServerErrorKind {
+        if err.kind() == io::ErrorKind::TimedOut {
+            ServerErrorKind::Timeout
+        } else {
+            ServerErrorKind::Io(err)
         }
     }
 
-    fn update_error_servfail(&mut self, answer: Answer) {
-        self.error = Ok(answer)
+    /// Returns whether there are stream servers we can switch to.
+    fn can_switch_to_stream(&self) -> bool {
+        // We may have done this already or must stick to the requested
+        // transport.
+        self.preferred
+            && self.options.transport.is_none()
+            && !Self::new_counter(self.resolver, self.options, false)
+                .is_empty()
     }
 
     fn switch_to_stream(&mut self) -> bool {
-        if !self.preferred {
-            // We already did this.
+        if !self.can_switch_to_stream() {
             return false;
         }
         self.preferred = false;
         self.attempt = 0;
-        self.counter =
-            self.resolver.stream.counter(self.resolver.options().rotate);
+        self.cookie_retried = false;
+        self.counter = Self::new_counter(self.resolver, self.options, false);
         true
     }
 
     fn next_server(&mut self) -> bool {
+        self.cookie_retried = false;
         if self.counter.next() {
             return true;
         }
@@ -382,21 +705,124 @@ impl<'a> Query<'a> {
         if self.attempt >= self.resolver.options().attempts {
             return false;
         }
-        self.counter = if self.preferred {
-            self.resolver
-                .preferred
-                .counter(self.resolver.options().rotate)
+        self.counter =
+            Self::new_counter(self.resolver, self.options, self.preferred);
+        true
+    }
+
+    /// Creates a counter over the preferred or stream servers.
+    ///
+    /// If the query options ask for a specific transport, only servers
+    /// using that transport are included.
+    fn new_counter(
+        resolver: &StubResolver,
+        options: &QueryOptions,
+        preferred: bool,
+    ) -> ServerListCounter {
+        let list = if preferred {
+            &resolver.preferred
         } else {
-            self.resolver.stream.counter(self.resolver.options().rotate)
+            &resolver.stream
         };
-        true
+        let mut counter = list.counter(resolver.options());
+        if let Some(transport) = options.transport {
+            counter.retain(list, |server| server.conf.transport == transport);
+        }
+        counter
+    }
+}
+
+//------------ QueryOptions --------------------------------------------------
+
+/// Options for an individual query.
+///
+/// These options complement the resolver’s [`ResolvOptions`] for a single
+/// query started via [`StubResolver::query_with_options`]. The default
+/// value leaves everything as configured for the resolver.
+#[derive(Clone, Debug)]
+pub struct QueryOptions {
+    /// Set the recursion desired (RD) bit.
+    ///
+    /// The bit is only set if the resolver’s `recurse` option is enabled,
+    /// too. The default is `true`.
+    pub recursion_desired: bool,
+
+    /// Set the DNSSEC OK (DO) bit.
+    ///
+    /// The bit is also set if the resolver’s `use_dnssec` option is
+    /// enabled. The default is `false`.
+    pub dnssec_ok: bool,
+
+    /// Set the checking disabled (CD) bit.
+    ///
+    /// The default is `false`.
+    pub checking_disabled: bool,
+
+    /// Set the authentic data (AD) bit.
+    ///
+    /// As described in [RFC 6840], this asks the server to indicate in its
+    /// response whether it considers the data authentic. The default is
+    /// `false`.
+    ///
+    /// [RFC 6840]: https://tools.ietf.org/html/rfc6840
+    pub authentic_data: bool,
+
+    /// The EDNS UDP payload size to announce.
+    ///
+    /// If this is `None`, the server’s configured
+    /// [`udp_payload_size`][ServerConf::udp_payload_size] is used.
+    pub udp_payload_size: Option<u16>,
+
+    /// Additional EDNS options to include in the query.
+    ///
+    /// Use [`push_option`][Self::push_option] to add options of any of the
+    /// types in [`opt`][crate::base::opt]. The options are only sent to
+    /// servers that support EDNS.
+    pub edns_options: Vec<UnknownOptData<Bytes>>,
+
+    /// The time to wait for a response from a server.
+    ///
+    /// If this is `None`, the server’s configured
+    /// [`request_timeout`][ServerConf::request_timeout] is used.
+    pub timeout: Option<Duration>,
+
+    /// Only use servers with this transport.
+    ///
+    /// If this is `None`, the resolver picks transports as usual, i.e., it
+    /// uses UDP and switches to stream transports if an answer is
+    /// truncated. Otherwise, the query fails if there are no servers with
+    /// the given transport.
+    pub transport: Option<Transport>,
+}
+
+impl QueryOptions {
+    /// Adds an EDNS option to be included in the query.
+    pub fn push_option(&mut self, option: &impl OptData) {
+        let mut data = Vec::new();
+        option.compose(&mut data).unwrap(); // Vec<u8> never runs short.
+        self.edns_options
+            .push(UnknownOptData::from_octets(option.code(), data.into()));
+    }
+}
+
+impl Default for QueryOptions {
+    fn default() -> Self {
+        QueryOptions {
+            recursion_desired: true,
+            dnssec_ok: false,
+            checking_disabled: false,
+            authentic_data: false,
+            udp_payload_size: None,
+            edns_options: Vec::new(),
+            timeout: None,
+            transport: None,
+        }
     }
 }
 
 //------------ QueryMessage --------------------------------------------------
 
-// XXX This needs to be re-evaluated if we start adding OPTtions to the query.
-pub(super) type QueryMessage = AdditionalBuilder<StreamTarget<Octets512>>;
+pub(super) type QueryMessage = AdditionalBuilder<StreamTarget<Vec<u8>>>;
 
 //------------ Answer --------------------------------------------------------
 
@@ -422,9 +848,128 @@ impl Answer {
         self.message.header().tc()
     }
 
+    /// Returns the OPT record of the answer if there is one.
+    pub fn opt(&self) -> Option<OptRecord<Bytes>> {
+        self.message.opt()
+    }
+
+    /// Returns the extended rcode of the answer.
+    ///
+    /// If the answer doesn’t have an OPT record, this is the rcode from
+    /// the header.
+    pub fn opt_rcode(&self) -> OptRcode {
+        match self.opt() {
+            Some(opt) => opt.rcode(self.message.header()),
+            None => self.message.header().rcode().into(),
+        }
+    }
+
+    /// Returns whether all host names in the answer section are valid.
+    ///
+    /// These are the owners of A, AAAA, and CNAME records as well as the
+    /// targets of CNAME and PTR records.
+    fn has_valid_host_names(&self) -> bool {
+        let answer = match self.message.answer() {
+            Ok(answer) => answer,
+            Err(_) => return false,
+        };
+        for record in answer {
+            let record = match record {
+                Ok(record) => record,
+                Err(_) => return false,
+            };
+            let valid = match record.rtype() {
+                Rtype::A | Rtype::Aaaa => is_host_name(record.owner()),
+                Rtype::Cname => {
+                    is_host_name(record.owner())
+                        && match record.to_record::<Cname<ParsedDname<_>>>() {
+                            Ok(Some(record)) => {
+                                is_host_name(record.data().cname())
+                            }
+                            _ => false,
+                        }
+                }
+                Rtype::Ptr => match record.to_record::<Ptr<ParsedDname<_>>>()
+                {
+                    Ok(Some(record)) => {
+                        is_host_name(record.data().ptrdname())
+                    }
+                    _ => false,
+                },
+                _ => true,
+            };
+            if !valid {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Returns whether the answer has the extended rcode BADCOOKIE.
+    fn is_bad_cookie(&self) -> bool {
+        matches!(self.opt_rcode(), OptRcode::BadCookie)
+    }
+
     pub fn into_message(self) -> Message<Bytes> {
         self.message
     }
+
+    /// Creates an answer to an A or AAAA query from a hosts file entry.
+    ///
+    /// If `qname` is an alias, the answer starts with a CNAME record
+    /// pointing to the canonical name.
+    fn from_hosts_entry(
+        qname: &impl ToDname,
+        entry: &HostsEntry,
+        qtype: Rtype,
+    ) -> Self {
+        let mut message = MessageBuilder::new_bytes();
+        message.header_mut().set_qr(true);
+        message.header_mut().set_ra(true);
+        let mut message = message.question();
+        message.push((qname, qtype)).unwrap();
+        let mut message = message.answer();
+        let canonical = entry.canonical_name();
+        if qname.to_bytes() != *canonical {
+            message
+                .push((qname, 0, Cname::new(canonical.clone())))
+                .unwrap();
+        }
+        for addr in entry.addrs() {
+            // The message can grow up to 64k. If a host has too many
+            // addresses to fit, we quietly leave out the rest.
+            let res = match (*addr, qtype) {
+                (IpAddr::V4(addr), Rtype::A) => {
+                    message.push((canonical, 0, A::new(addr)))
+                }
+                (IpAddr::V6(addr), Rtype::Aaaa) => {
+                    message.push((canonical, 0, Aaaa::new(addr)))
+                }
+                _ => continue,
+            };
+            if res.is_err() {
+                break;
+            }
+        }
+        message.into_message().into()
+    }
+
+    /// Creates an answer to a PTR query from the host names for `addr`.
+    fn from_hosts_names(addr: IpAddr, names: &[Dname<Bytes>]) -> Self {
+        let qname = dname_from_addr(addr);
+        let mut message = MessageBuilder::new_bytes();
+        message.header_mut().set_qr(true);
+        message.header_mut().set_ra(true);
+        let mut message = message.question();
+        message.push((&qname, Rtype::Ptr)).unwrap();
+        let mut message = message.answer();
+        for name in names {
+            if message.push((&qname, 0, Ptr::new(name.clone()))).is_err() {
+                break;
+            }
+        }
+        message.into_message().into()
+    }
 }
 
 impl From<Message<Bytes>> for Answer {
@@ -433,10 +978,24 @@ impl From<Message<Bytes>> for Answer {
     }
 }
 
+/// Returns whether a domain name is a valid host name.
+///
+/// Like glibc, we only allow ASCII letters, digits, hyphens, and
+/// underscores in labels and don’t allow the name to start with a hyphen.
+fn is_host_name(name: &impl ToDname) -> bool {
+    name.iter_labels().enumerate().all(|(idx, label)| {
+        let label = label.as_slice();
+        !(idx == 0 && label.first() == Some(&b'-'))
+            && label.iter().all(|&ch| {
+                ch.is_ascii_alphanumeric() || ch == b'-' || ch == b'_'
+            })
+    })
+}
+
 //------------ ServerInfo ----------------------------------------------------
 
 #[derive(Clone, Debug)]
-struct ServerInfo {
+pub(super) struct ServerInfo {
     /// The basic server configuration.
     conf: ServerConf,
 
@@ -444,6 +1003,28 @@ struct ServerInfo {
     ///
     /// We start out with assuming it does and unset it if we get a FORMERR.
     edns: Arc<AtomicBool>,
+
+    /// What we have learned about the server from past queries.
+    stats: Arc<Mutex<ServerStats>>,
+
+    /// The DNS cookie to send to the server.
+    ///
+    /// The client cookie is chosen randomly for each server address. The
+    /// server cookie is the one most recently received from the server.
+    /// Since the cookie belongs to the address, all entries for the same
+    /// address share it, regardless of their transport.
+    cookie: Arc<Mutex<Cookie>>,
+
+    /// The persistent TCP connection to the server.
+    tcp: Arc<tcp::TcpPool>,
+
+    /// The open TLS connections to the server.
+    #[cfg(feature = "resolv-tls")]
+    tls: Arc<tls::TlsPool>,
+
+    /// The HTTP/2 connection to the server.
+    #[cfg(feature = "resolv-https")]
+    https: Arc<https::HttpsConn>,
 }
 
 impl ServerInfo {
@@ -455,49 +1036,190 @@ impl ServerInfo {
         self.edns.store(false, Ordering::Relaxed);
     }
 
-    pub fn prepare_message(&self, query: &mut QueryMessage) {
+    /// Records that the server answered a query after `rtt`.
+    pub fn record_answer(&self, rtt: Duration) {
+        self.stats.lock().unwrap().answer(rtt)
+    }
+
+    /// Records the response the server gave to a query after `rtt`.
+    ///
+    /// A response with an rcode of SERVFAIL, REFUSED, or FORMERR counts
+    /// as a failure since the server wasn’t able to answer the query.
+    pub fn record_response(&self, answer: &Answer, rtt: Duration) {
+        match answer.header().rcode() {
+            Rcode::ServFail | Rcode::Refused | Rcode::FormErr => {
+                self.record_failure(Instant::now())
+            }
+            _ => self.record_answer(rtt),
+        }
+    }
+
+    /// Returns whether we use DNS cookies with this server.
+    ///
+    /// Cookies are only useful without transport security. They also
+    /// require EDNS.
+    fn uses_cookies(&self) -> bool {
+        matches!(self.conf.transport, Transport::Udp | Transport::Tcp)
+            && self.does_edns()
+    }
+
+    /// Processes the cookie option of an answer from the server.
+    ///
+    /// Remembers the server cookie if the answer contains one. Returns
+    /// whether the answer is acceptable, i.e., contains our client cookie
+    /// or, if we haven’t learned a server cookie yet, no cookie at all.
+    fn process_cookie(&self, answer: &Message<Bytes>) -> bool {
+        if !self.uses_cookies() {
+            return true;
+        }
+        let received = match answer.opt() {
+            Some(opt) => opt.as_opt().iter::<Cookie>().next(),
+            None => None,
+        };
+        let mut cookie = self.cookie.lock().unwrap();
+        match received {
+            Some(Ok(received)) if received.cookie() == cookie.cookie() => {
+                if let Some(server) = received.server() {
+                    *cookie = Cookie::with_server(cookie.cookie(), server);
+                }
+                true
+            }
+            Some(_) => false,
+            None => cookie.server().is_none(),
+        }
+    }
+
+    /// Forgets the server cookie.
+    ///
+    /// We do this when the server fails to answer in time, so we recover
+    /// if the server stopped supporting cookies.
+    fn forget_server_cookie(&self) {
+        let mut cookie = self.cookie.lock().unwrap();
+        *cookie = Cookie::new(cookie.cookie());
+    }
+
+    /// Records that a query to the server failed at `now`.
+    pub fn record_failure(&self, now: Instant) {
+        self.stats.lock().unwrap().failure(now)
+    }
+
+    pub fn prepare_message(
+        &self,
+        query: &mut QueryMessage,
+        options: &ResolvOptions,
+        query_options: &QueryOptions,
+    ) -> Result<(), QueryError> {
         query.rewind();
+        let header = query.header_mut();
+        header.set_rd(options.recurse && query_options.recursion_desired);
+        header.set_cd(query_options.checking_disabled);
+        header.set_ad(query_options.authentic_data);
         if self.does_edns() {
             query
                 .opt(|opt| {
-                    opt.set_udp_payload_size(self.conf.udp_payload_size);
+                    opt.set_udp_payload_size(
+                        self.udp_payload_size(query_options),
+                    );
+                    opt.set_dnssec_ok(
+                        options.use_dnssec || query_options.dnssec_ok,
+                    );
+                    if self.stays_open(options) {
+                        // RFC 7828: The option is empty in queries.
+                        opt.push_raw_option(
+                            OptionCode::TcpKeepalive,
+                            |_| Ok(()),
+                        )?;
+                    }
+                    if self.uses_cookies() {
+                        opt.push(&*self.cookie.lock().unwrap())?;
+                    }
+                    for option in &query_options.edns_options {
+                        opt.push(option)?;
+                    }
                     Ok(())
                 })
-                .unwrap();
+                .map_err(|_| QueryError::QueryTooLong)?;
         }
+        Ok(())
+    }
+
+    /// Returns the UDP payload size to announce in a query.
+    fn udp_payload_size(&self, query_options: &QueryOptions) -> u16 {
+        query_options
+            .udp_payload_size
+            .unwrap_or(self.conf.udp_payload_size)
+    }
+
+    /// Returns whether the server uses a persistent TCP connection.
+    fn stays_open(&self, options: &ResolvOptions) -> bool {
+        options.stay_open
+            && !options.single_request_reopen
+            && self.conf.transport == Transport::Tcp
     }
 
     pub async fn query(
         &self,
         query: &QueryMessage,
+        options: &ResolvOptions,
+        query_options: &QueryOptions,
     ) -> Result<Answer, io::Error> {
+        let request_timeout =
+            query_options.timeout.unwrap_or(self.conf.request_timeout);
         let res = match self.conf.transport {
             Transport::Udp => {
                 timeout(
-                    self.conf.request_timeout,
-                    Self::udp_query(
+                    request_timeout,
+                    Self::udp_query_filtered(
                         query,
                         self.conf.addr,
-                        self.conf.recv_size,
+                        self.conf.recv_size.max(usize::from(
+                            self.udp_payload_size(query_options),
+                        )),
+                        |answer| self.process_cookie(answer),
                     ),
                 )
                 .await
             }
+            Transport::Tcp if self.stays_open(options) => {
+                timeout(request_timeout, self.tcp.query(query, &self.conf))
+                    .await
+            }
             Transport::Tcp => {
                 timeout(
-                    self.conf.request_timeout,
+                    request_timeout,
                     Self::tcp_query(query, self.conf.addr),
                 )
                 .await
             }
+            #[cfg(feature = "resolv-tls")]
+            Transport::Tls => {
+                timeout(request_timeout, self.tls.query(query, &self.conf))
+                    .await
+            }
+            #[cfg(feature = "resolv-https")]
+            Transport::Https => {
+                timeout(request_timeout, self.https.query(query, &self.conf))
+                    .await
+            }
         };
         match res {
-            Ok(Ok(answer)) => Ok(answer),
+            Ok(Ok(answer)) => {
+                // Answers via UDP have been checked already. Over stream
+                // transports, spoofing isn’t a concern, we only want to
+                // learn the server cookie.
+                if self.conf.transport != Transport::Udp {
+                    self.process_cookie(&answer);
+                }
+                Ok(answer)
+            }
             Ok(Err(err)) => Err(err),
-            Err(_) => Err(io::Error::new(
-                io::ErrorKind::TimedOut,
-                "request timed out",
-            )),
+            Err(_) => {
+                self.forget_server_cookie();
+                Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    "request timed out",
+                ))
+            }
         }
     }
 
@@ -506,6 +1228,17 @@ impl ServerInfo {
         addr: SocketAddr,
     ) -> Result<Answer, io::Error> {
         let mut sock = TcpStream::connect(&addr).await?;
+        Self::stream_query(&mut sock, query).await
+    }
+
+    /// Sends a query over a stream socket and waits for the answer.
+    pub async fn stream_query<S>(
+        sock: &mut S,
+        query: &QueryMessage,
+    ) -> Result<Answer, io::Error>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
         sock.write_all(query.as_target().as_stream_slice()).await?;
 
         // This loop can be infinite because we have a timeout on this whole
@@ -513,7 +1246,7 @@ impl ServerInfo {
         loop {
             let mut buf = Vec::new();
             let len = sock.read_u16().await? as u64;
-            AsyncReadExt::take(&mut sock, len)
+            AsyncReadExt::take(&mut *sock, len)
                 .read_to_end(&mut buf)
                 .await?;
             if let Ok(answer) = Message::from_octets(buf.into()) {
@@ -535,6 +1268,21 @@ impl ServerInfo {
         addr: SocketAddr,
         recv_size: usize,
     ) -> Result<Answer, io::Error> {
+        Self::udp_query_filtered(query, addr, recv_size, |_| true).await
+    }
+
+    /// Sends a query over UDP and waits for an acceptable answer.
+    ///
+    /// Answers for which `accept` returns `false` are ignored.
+    async fn udp_query_filtered<F>(
+        query: &QueryMessage,
+        addr: SocketAddr,
+        recv_size: usize,
+        accept: F,
+    ) -> Result<Answer, io::Error>
+    where
+        F: Fn(&Message<Bytes>) -> bool,
+    {
         let sock = Self::udp_bind(addr.is_ipv4()).await?;
         sock.connect(addr).await?;
         let sent = sock.send(query.as_target().as_dgram_slice()).await?;
@@ -554,7 +1302,7 @@ impl ServerInfo {
                 Ok(answer) => answer,
                 Err(_) => continue,
             };
-            if !answer.is_answer(&query.as_message()) {
+            if !answer.is_answer(&query.as_message()) || !accept(&answer) {
                 continue;
             }
             return Ok(answer.into());
@@ -588,6 +1336,13 @@ impl From<ServerConf> for ServerInfo {
         ServerInfo {
             conf,
             edns: Arc::new(AtomicBool::new(true)),
+            stats: Default::default(),
+            cookie: Arc::new(Mutex::new(Cookie::new(::rand::random()))),
+            tcp: Default::default(),
+            #[cfg(feature = "resolv-tls")]
+            tls: Default::default(),
+            #[cfg(feature = "resolv-https")]
+            https: Default::default(),
         }
     }
 }
@@ -617,7 +1372,15 @@ struct ServerList {
 }
 
 impl ServerList {
-    pub fn from_conf<F>(conf: &ResolvConf, filter: F) -> Self
+    /// Creates the list from the servers in `conf` selected by `filter`.
+    ///
+    /// Servers share the cookie state in `cookies` by address. Addresses
+    /// not yet present are added with the new server’s cookie.
+    pub fn from_conf<F>(
+        conf: &ResolvConf,
+        cookies: &mut HashMap<SocketAddr, Arc<Mutex<Cookie>>>,
+        filter: F,
+    ) -> Self
     where
         F: Fn(&ServerConf) -> bool,
     {
@@ -626,7 +1389,14 @@ impl ServerList {
                 conf.servers
                     .iter()
                     .filter(|f| filter(*f))
-                    .map(Into::into)
+                    .map(|conf| {
+                        let mut info = ServerInfo::from(conf);
+                        info.cookie = cookies
+                            .entry(conf.addr)
+                            .or_insert_with(|| info.cookie.clone())
+                            .clone();
+                        info
+                    })
                     .collect()
             },
             start: Arc::new(AtomicUsize::new(0)),
@@ -637,12 +1407,16 @@ impl ServerList {
         self.servers.is_empty()
     }
 
-    pub fn counter(&self, rotate: bool) -> ServerListCounter {
-        let res = ServerListCounter::new(self);
-        if rotate {
-            self.rotate()
+    pub fn counter(&self, options: &ResolvOptions) -> ServerListCounter {
+        if options.rotate {
+            let res = ServerListCounter::new(self);
+            self.rotate();
+            res
+        } else if options.select_by_rtt {
+            ServerListCounter::by_stats(self, Instant::now())
+        } else {
+            ServerListCounter::new(self)
         }
-        res
     }
 
     pub fn iter(&self) -> ServerListIter {
@@ -675,29 +1449,73 @@ impl ops::Deref for ServerList {
 
 #[derive(Clone, Debug)]
 struct ServerListCounter {
+    /// The indexes of the servers in the order they should be tried.
+    order: Vec<usize>,
+
+    /// The current position in `order`.
     cur: usize,
-    end: usize,
 }
 
 impl ServerListCounter {
     fn new(list: &ServerList) -> Self {
         if list.servers.is_empty() {
-            return ServerListCounter { cur: 0, end: 0 };
+            return ServerListCounter {
+                order: Vec::new(),
+                cur: 0,
+            };
         }
 
         // We modulo the start value here to prevent hick-ups towards the
         // end of usize’s range.
-        let start = list.start.load(Ordering::Relaxed) % list.servers.len();
+        let len = list.servers.len();
+        let start = list.start.load(Ordering::Relaxed) % len;
         ServerListCounter {
-            cur: start,
-            end: start + list.servers.len(),
+            order: (start..start + len).map(|idx| idx % len).collect(),
+            cur: 0,
         }
     }
 
+    /// Creates a counter that orders the servers by their statistics.
+    ///
+    /// Servers that are due for a probe come first, followed by the
+    /// healthy servers ordered by their smoothed RTT and finally the
+    /// servers we are backing off from ordered by when they can be tried
+    /// again. Servers with equal keys are kept in configuration order.
+    fn by_stats(list: &ServerList, now: Instant) -> Self {
+        let keys: Vec<_> = list
+            .servers
+            .iter()
+            .map(|server| server.stats.lock().unwrap().key(now))
+            .collect();
+        let mut order: Vec<_> = (0..list.servers.len()).collect();
+        order.sort_by_key(|&idx| keys[idx]);
+
+        // Only the first query after the back-off time should go to a
+        // failing server. Other queries keep avoiding it until we know.
+        if let Some(&first) = order.first() {
+            list.servers[first].stats.lock().unwrap().claim_probe(now);
+        }
+        ServerListCounter { order, cur: 0 }
+    }
+
+    /// Removes all servers for which `keep` returns `false`.
+    ///
+    /// This must be called before the counter is used.
+    fn retain<F>(&mut self, list: &ServerList, keep: F)
+    where
+        F: Fn(&ServerInfo) -> bool,
+    {
+        self.order.retain(|&idx| keep(&list[idx]));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
     #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> bool {
         let next = self.cur + 1;
-        if next < self.end {
+        if next < self.order.len() {
             self.cur = next;
             true
         } else {
@@ -706,7 +1524,73 @@ impl ServerListCounter {
     }
 
     pub fn info<'a>(&self, list: &'a ServerList) -> &'a ServerInfo {
-        &list[self.cur % list.servers.len()]
+        &list[self.order[self.cur]]
+    }
+}
+
+//------------ ServerStats ---------------------------------------------------
+
+/// What we know about the performance of a server.
+#[derive(Clone, Debug, Default)]
+struct ServerStats {
+    /// The smoothed round trip time.
+    ///
+    /// This is `None` if the server hasn’t answered yet.
+    srtt: Option<Duration>,
+
+    /// The number of consecutive failed queries.
+    failures: u32,
+
+    /// The time before which we don’t send queries to a failing server.
+    retry_at: Option<Instant>,
+}
+
+impl ServerStats {
+    /// Updates the stats for an answer received after `rtt`.
+    fn answer(&mut self, rtt: Duration) {
+        // As in RFC 6298, new measurements enter with a weight of 1/8. If
+        // the server has been failing, the old value is meaningless.
+        self.srtt = match self.srtt {
+            Some(srtt) if self.failures == 0 => Some(srtt * 7 / 8 + rtt / 8),
+            _ => Some(rtt),
+        };
+        self.failures = 0;
+        self.retry_at = None;
+    }
+
+    /// Updates the stats for a query that failed at `now`.
+    fn failure(&mut self, now: Instant) {
+        self.failures = self.failures.saturating_add(1);
+        self.retry_at = Some(now + self.backoff());
+    }
+
+    /// Returns the time to back off from the server.
+    fn backoff(&self) -> Duration {
+        let shift = self.failures.saturating_sub(1).min(16);
+        (MIN_SERVER_BACKOFF * (1u32 << shift)).min(MAX_SERVER_BACKOFF)
+    }
+
+    /// Returns the sort key of the server for server selection.
+    ///
+    /// Servers that haven’t answered yet get an RTT of zero so they are
+    /// tried early on and we learn about them.
+    fn key(&self, now: Instant) -> (u8, Duration) {
+        match self.retry_at {
+            None => (1, self.srtt.unwrap_or_default()),
+            Some(retry_at) => match retry_at.checked_duration_since(now) {
+                Some(wait) if wait > Duration::from_secs(0) => (2, wait),
+                _ => (0, Duration::from_secs(0)),
+            },
+        }
+    }
+
+    /// Marks a failing server as being probed at `now`, if it is due.
+    ///
+    /// The server goes back into back-off until the probe’s result is in.
+    fn claim_probe(&mut self, now: Instant) {
+        if let (0, _) = self.key(now) {
+            self.retry_at = Some(now + self.backoff());
+        }
     }
 }
 
@@ -773,3 +1657,836 @@ impl<'a> Iterator for SearchIter<'a> {
         }
     }
 }
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::conf::SortListItem;
+    use super::*;
+    use crate::base::iana::Rtype;
+    use crate::base::name::{Dname, RelativeDname};
+    use crate::base::opt::Nsid;
+    use crate::base::serial::Serial;
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+    use tokio::runtime;
+
+    fn server_list(count: u8) -> ServerList {
+        let mut conf = ResolvConf::new();
+        for i in 0..count {
+            conf.servers.push(ServerConf::new(
+                ([192, 0, 2, i + 1], 53).into(),
+                Transport::Udp,
+            ));
+        }
+        ServerList::from_conf(&conf, &mut HashMap::new(), |_| true)
+    }
+
+    fn order(list: &ServerList, now: Instant) -> Vec<usize> {
+        ServerListCounter::by_stats(list, now).order
+    }
+
+    #[test]
+    fn select_by_rtt() {
+        let list = server_list(3);
+        let now = Instant::now();
+
+        // Without any knowledge, we keep the configured order.
+        assert_eq!(order(&list, now), [0, 1, 2]);
+
+        // Servers that haven’t answered yet are tried first.
+        list[0].record_answer(Duration::from_millis(30));
+        assert_eq!(order(&list, now), [1, 2, 0]);
+
+        list[1].record_answer(Duration::from_millis(50));
+        list[2].record_answer(Duration::from_millis(10));
+        assert_eq!(order(&list, now), [2, 0, 1]);
+
+        // The RTT is smoothed.
+        list[2].record_answer(Duration::from_millis(90));
+        assert_eq!(
+            list[2].stats.lock().unwrap().srtt,
+            Some(Duration::from_millis(20))
+        );
+        assert_eq!(order(&list, now), [2, 0, 1]);
+
+        // The rotate option overrides selection, turning it off falls back
+        // to configured order.
+        let mut options = ResolvOptions::default();
+        options.select_by_rtt = false;
+        let counter = list.counter(&options);
+        assert_eq!(counter.order, [0, 1, 2]);
+        options.rotate = true;
+        let _ = list.counter(&options);
+        assert_eq!(list.counter(&options).order, [1, 2, 0]);
+    }
+
+    #[test]
+    fn back_off_and_probe() {
+        let list = server_list(3);
+        let now = Instant::now();
+        list[0].record_answer(Duration::from_millis(10));
+        list[1].record_answer(Duration::from_millis(20));
+        list[2].record_answer(Duration::from_millis(30));
+
+        // A failing server goes last, behind servers failing longer.
+        list[1].record_failure(now);
+        list[1].record_failure(now);
+        list[0].record_failure(now);
+        assert_eq!(order(&list, now), [2, 0, 1]);
+        assert_eq!(
+            list[1].stats.lock().unwrap().backoff(),
+            MIN_SERVER_BACKOFF * 2
+        );
+
+        // Once its back-off time has passed, exactly one query probes it.
+        let later = now + MIN_SERVER_BACKOFF;
+        assert_eq!(order(&list, later), [0, 2, 1]);
+        assert_eq!(order(&list, later), [2, 0, 1]);
+
+        // A failed probe doubles the back-off time.
+        list[0].record_failure(later);
+        assert_eq!(
+            list[0].stats.lock().unwrap().retry_at,
+            Some(later + MIN_SERVER_BACKOFF * 2)
+        );
+
+        // A successful probe makes the server healthy again and forgets
+        // the old RTT.
+        list[1].record_answer(Duration::from_millis(5));
+        assert_eq!(order(&list, later), [1, 2, 0]);
+
+        // The back-off time is limited.
+        for _ in 0..100 {
+            list[2].record_failure(now);
+        }
+        assert_eq!(
+            list[2].stats.lock().unwrap().backoff(),
+            MAX_SERVER_BACKOFF
+        );
+    }
+
+    #[test]
+    fn error_rcodes_are_failures() {
+        let list = server_list(1);
+        let response = |rcode| -> Answer {
+            let mut msg = MessageBuilder::new_bytes();
+            msg.header_mut().set_rcode(rcode);
+            msg.into_message().into()
+        };
+        list[0].record_response(
+            &response(Rcode::NXDomain),
+            Duration::from_millis(10),
+        );
+        assert_eq!(list[0].stats.lock().unwrap().failures, 0);
+        for rcode in &[Rcode::ServFail, Rcode::Refused, Rcode::FormErr] {
+            list[0].record_response(
+                &response(*rcode),
+                Duration::from_millis(10),
+            );
+        }
+        let stats = list[0].stats.lock().unwrap();
+        assert_eq!(stats.failures, 3);
+        assert_eq!(stats.srtt, Some(Duration::from_millis(10)));
+    }
+
+    const SECRET: [u8; 16] = [7; 16];
+
+    /// Starts a UDP server that answers queries via `respond`.
+    ///
+    /// The closure receives the query and its cookie, if it has one, and
+    /// returns the responses to send.
+    async fn udp_server<F>(mut respond: F) -> SocketAddr
+    where
+        F: FnMut(&Message<Bytes>, Option<Cookie>) -> Vec<Vec<u8>>
+            + Send
+            + 'static,
+    {
+        let sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = sock.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = vec![0; 512];
+            loop {
+                let (len, peer) = sock.recv_from(&mut buf).await.unwrap();
+                let query =
+                    Message::from_octets(Bytes::copy_from_slice(&buf[..len]))
+                        .unwrap();
+                let cookie = query.opt().map(|opt| {
+                    opt.as_opt().iter::<Cookie>().next().unwrap().unwrap()
+                });
+                for response in respond(&query, cookie) {
+                    sock.send_to(&response, peer).await.unwrap();
+                }
+            }
+        });
+        addr
+    }
+
+    /// Creates a response with the given rcode and cookie.
+    fn cookie_response(
+        query: &Message<Bytes>,
+        rcode: OptRcode,
+        cookie: Cookie,
+    ) -> Vec<u8> {
+        let mut msg = MessageBuilder::new_vec()
+            .start_answer(query, Rcode::NoError)
+            .unwrap()
+            .additional();
+        msg.opt(|opt| {
+            opt.set_rcode(rcode);
+            opt.push(&cookie)
+        })
+        .unwrap();
+        msg.finish()
+    }
+
+    /// Returns a fresh server cookie for `cookie`.
+    fn server_cookie(cookie: Cookie) -> Cookie {
+        cookie.create_response(
+            Serial(1_000_000),
+            Ipv4Addr::LOCALHOST.into(),
+            &SECRET,
+        )
+    }
+
+    fn cookie_resolver(addr: SocketAddr) -> StubResolver {
+        let mut conf = ResolvConf::new();
+        conf.servers.push(ServerConf::new(addr, Transport::Udp));
+        conf.options.attempts = 1;
+        StubResolver::from_conf(conf)
+    }
+
+    #[test]
+    fn shared_cookies() {
+        let mut conf = ResolvConf::new();
+        let addr = SocketAddr::from(([192, 0, 2, 1], 53));
+        conf.servers.push(ServerConf::new(addr, Transport::Udp));
+        conf.servers.push(ServerConf::new(addr, Transport::Tcp));
+        conf.servers.push(ServerConf::new(
+            ([192, 0, 2, 2], 53).into(),
+            Transport::Tcp,
+        ));
+        let resolver = StubResolver::from_conf(conf);
+        let udp = &resolver.preferred.servers[0];
+        let tcp = &resolver.stream.servers[0];
+        let other = &resolver.stream.servers[1];
+        assert!(Arc::ptr_eq(&udp.cookie, &tcp.cookie));
+        assert!(!Arc::ptr_eq(&udp.cookie, &other.cookie));
+    }
+
+    fn question() -> Question<Dname<Vec<u8>>> {
+        Question::new_in(Dname::from_str("example.com").unwrap(), Rtype::A)
+    }
+
+    #[test]
+    fn cookies() {
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let received = Arc::new(Mutex::new(Vec::new()));
+            let seen = received.clone();
+            let addr = udp_server(move |query, cookie| {
+                let cookie = cookie.unwrap();
+                seen.lock().unwrap().push(cookie);
+                vec![cookie_response(
+                    query,
+                    OptRcode::NoError,
+                    server_cookie(cookie),
+                )]
+            })
+            .await;
+            let resolver = cookie_resolver(addr);
+            resolver.query(question()).await.unwrap();
+            resolver.query(question()).await.unwrap();
+
+            // The first query only has a client cookie, the second one the
+            // server cookie from the first answer.
+            let received = received.lock().unwrap();
+            assert_eq!(received.len(), 2);
+            assert_eq!(received[0].server(), None);
+            assert_eq!(received[1], server_cookie(received[0]));
+            assert!(received[1].check_server_hash(
+                Ipv4Addr::LOCALHOST.into(),
+                &SECRET,
+                Serial(1_000_000)
+            ));
+        })
+    }
+
+    #[test]
+    fn bad_cookie() {
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let addr = udp_server(|query, cookie| {
+                let cookie = cookie.unwrap();
+                // Only accept queries with our server cookie.
+                let rcode = if cookie.server().is_some() {
+                    OptRcode::NoError
+                } else {
+                    OptRcode::BadCookie
+                };
+                vec![cookie_response(query, rcode, server_cookie(cookie))]
+            })
+            .await;
+            let answer =
+                cookie_resolver(addr).query(question()).await.unwrap();
+            assert_eq!(answer.header().rcode(), Rcode::NoError);
+            assert!(!answer.is_bad_cookie());
+        })
+    }
+
+    #[test]
+    fn spoofed_cookie() {
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let addr = udp_server(|query, cookie| {
+                let cookie = cookie.unwrap();
+                // First send a response with the wrong client cookie.
+                let mut spoofed = cookie.cookie();
+                spoofed[0] ^= 0xff;
+                vec![
+                    cookie_response(
+                        query,
+                        OptRcode::Refused,
+                        Cookie::new(spoofed),
+                    ),
+                    cookie_response(
+                        query,
+                        OptRcode::NoError,
+                        server_cookie(cookie),
+                    ),
+                ]
+            })
+            .await;
+            let answer =
+                cookie_resolver(addr).query(question()).await.unwrap();
+            assert_eq!(answer.header().rcode(), Rcode::NoError);
+        })
+    }
+
+    #[test]
+    fn query_options() {
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let received = Arc::new(Mutex::new(Vec::new()));
+            let seen = received.clone();
+            let addr = udp_server(move |query, cookie| {
+                let cookie = cookie.unwrap();
+                seen.lock().unwrap().push(query.clone());
+                let mut msg = MessageBuilder::new_vec()
+                    .start_answer(query, Rcode::NoError)
+                    .unwrap()
+                    .additional();
+                msg.opt(|opt| {
+                    opt.push(&Nsid::from_octets(b"ns1"))?;
+                    opt.push(&server_cookie(cookie))
+                })
+                .unwrap();
+                vec![msg.finish()]
+            })
+            .await;
+
+            let mut options = QueryOptions::default();
+            options.recursion_desired = false;
+            options.dnssec_ok = true;
+            options.checking_disabled = true;
+            options.authentic_data = true;
+            options.udp_payload_size = Some(1232);
+            options.push_option(&Nsid::from_octets(b""));
+            let answer = cookie_resolver(addr)
+                .query_with_options(question(), &options)
+                .await
+                .unwrap();
+
+            let query = received.lock().unwrap().pop().unwrap();
+            assert!(!query.header().rd());
+            assert!(query.header().cd());
+            assert!(query.header().ad());
+            let opt = query.opt().unwrap();
+            assert!(opt.dnssec_ok());
+            assert_eq!(opt.udp_payload_size(), 1232);
+            assert_eq!(
+                opt.as_opt().iter::<Nsid<Bytes>>().next().unwrap().unwrap(),
+                Nsid::from_octets(Bytes::new())
+            );
+
+            assert_eq!(answer.opt_rcode().to_int(), 0);
+            assert_eq!(
+                answer
+                    .opt()
+                    .unwrap()
+                    .as_opt()
+                    .iter::<Nsid<Bytes>>()
+                    .next()
+                    .unwrap()
+                    .unwrap(),
+                Nsid::from_octets(Bytes::from_static(b"ns1"))
+            );
+        })
+    }
+
+    #[test]
+    fn query_options_transport() {
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let resolver =
+                cookie_resolver(SocketAddr::from(([127, 0, 0, 1], 53)));
+            let mut options = QueryOptions::default();
+            options.transport = Some(Transport::Tcp);
+            let err =
+                match resolver.query_with_options(question(), &options).await
+                {
+                    Ok(_) => panic!("query without TCP servers succeeded"),
+                    Err(err) => err,
+                };
+            assert!(matches!(err, QueryError::NoServers));
+        })
+    }
+
+    #[test]
+    fn query_errors() {
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            // A server that doesn’t understand our query even without EDNS.
+            let addr = udp_server(|query, _| {
+                vec![MessageBuilder::new_vec()
+                    .start_answer(query, Rcode::FormErr)
+                    .unwrap()
+                    .finish()]
+            })
+            .await;
+            let err = match cookie_resolver(addr).query(question()).await {
+                Ok(_) => panic!("query to broken server succeeded"),
+                Err(err) => err,
+            };
+            assert_eq!(err.servers().len(), 1);
+            assert_eq!(err.servers()[0].addr(), addr);
+            assert!(matches!(
+                err.servers()[0].kind(),
+                ServerErrorKind::FormErr
+            ));
+
+            // A server that fails to answer, both with and without blast.
+            let addr = udp_server(|query, _| {
+                vec![MessageBuilder::new_vec()
+                    .start_answer(query, Rcode::ServFail)
+                    .unwrap()
+                    .finish()]
+            })
+            .await;
+            for &blast in &[false, true] {
+                let mut conf = ResolvConf::new();
+                conf.servers.push(ServerConf::new(addr, Transport::Udp));
+                conf.options.attempts = 1;
+                conf.options.blast = blast;
+                let resolver = StubResolver::from_conf(conf);
+                let err = match resolver.query(question()).await {
+                    Ok(_) => panic!("query to failing server succeeded"),
+                    Err(err) => err,
+                };
+                assert_eq!(err.servers().len(), 1);
+                assert!(matches!(
+                    err.servers()[0].kind(),
+                    ServerErrorKind::ServFail
+                ));
+            }
+
+            // A truncated answer and nobody listening on TCP.
+            let udp_addr = udp_server(|query, _| {
+                let mut msg = MessageBuilder::new_vec();
+                msg.header_mut().set_tc(true);
+                vec![msg
+                    .start_answer(query, Rcode::NoError)
+                    .unwrap()
+                    .finish()]
+            })
+            .await;
+            let tcp_addr = {
+                let sock =
+                    std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+                sock.local_addr().unwrap()
+            };
+            let mut conf = ResolvConf::new();
+            conf.servers.push(ServerConf::new(udp_addr, Transport::Udp));
+            conf.servers.push(ServerConf::new(tcp_addr, Transport::Tcp));
+            conf.options.attempts = 1;
+            let resolver = StubResolver::from_conf(conf);
+            let err = match resolver.query(question()).await {
+                Ok(_) => panic!("query without TCP server succeeded"),
+                Err(err) => err,
+            };
+            assert_eq!(err.servers().len(), 2);
+            assert_eq!(err.servers()[0].transport(), Transport::Udp);
+            assert!(matches!(
+                err.servers()[0].kind(),
+                ServerErrorKind::Truncated
+            ));
+            assert_eq!(err.servers()[1].addr(), tcp_addr);
+            assert!(matches!(
+                err.servers()[1].kind(),
+                ServerErrorKind::Io(err)
+                    if err.kind() == io::ErrorKind::ConnectionRefused
+            ));
+
+            // A server that never answers.
+            let sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let mut conf = ResolvConf::new();
+            let mut server =
+                ServerConf::new(sock.local_addr().unwrap(), Transport::Udp);
+            server.request_timeout = Duration::from_millis(50);
+            conf.servers.push(server);
+            conf.options.attempts = 2;
+            let resolver = StubResolver::from_conf(conf);
+            let err = match resolver.query(question()).await {
+                Ok(_) => panic!("query to silent server succeeded"),
+                Err(err) => err,
+            };
+            assert_eq!(err.servers().len(), 2);
+            assert!(err.is_timeout());
+            assert_eq!(io::Error::from(err).kind(), io::ErrorKind::TimedOut);
+        })
+    }
+
+    #[test]
+    fn hosts_file() {
+        use std::{env, fs, process};
+
+        let path = env::temp_dir()
+            .join(format!("domain-stub-hosts-{}", process::id()));
+        fs::write(
+            &path,
+            "192.0.2.1 host.example.com host alias.example.com\n\
+             2001:db8::1 host.example.com\n",
+        )
+        .unwrap();
+
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            // A server that never answers so that only the hosts file
+            // can provide results.
+            let sock = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let mut conf = ResolvConf::new();
+            let mut server =
+                ServerConf::new(sock.local_addr().unwrap(), Transport::Udp);
+            server.request_timeout = Duration::from_millis(50);
+            conf.servers.push(server);
+            conf.options.attempts = 1;
+            conf.hosts_file = Some(path.clone());
+            let resolver = StubResolver::from_conf(conf);
+
+            let alias =
+                Dname::<Vec<u8>>::from_str("Alias.example.com").unwrap();
+            let found = resolver.lookup_host(&alias).await.unwrap();
+            assert_eq!(found.qname(), alias);
+            assert_eq!(
+                found.canonical_name(),
+                Dname::<Vec<u8>>::from_str("host.example.com").unwrap()
+            );
+            assert_eq!(
+                found.iter().collect::<Vec<_>>(),
+                ["192.0.2.1".parse::<IpAddr>().unwrap()]
+            );
+
+            let name =
+                Dname::<Vec<u8>>::from_str("HOST.example.com").unwrap();
+            let found = resolver.lookup_host(&name).await.unwrap();
+            assert_eq!(
+                found.iter().collect::<Vec<_>>(),
+                [
+                    "2001:db8::1".parse::<IpAddr>().unwrap(),
+                    "192.0.2.1".parse().unwrap()
+                ]
+            );
+
+            let host =
+                RelativeDname::from_octets(b"\x04host".as_ref()).unwrap();
+            let found = resolver.search_host(host).await.unwrap();
+            assert_eq!(
+                found.iter().collect::<Vec<_>>(),
+                ["192.0.2.1".parse::<IpAddr>().unwrap()]
+            );
+
+            let found = resolver
+                .lookup_addr("192.0.2.1".parse().unwrap())
+                .await
+                .unwrap();
+            assert_eq!(
+                found.iter().map(|name| name.to_bytes()).collect::<Vec<_>>(),
+                [
+                    Dname::<Bytes>::from_str("host.example.com").unwrap(),
+                    Dname::from_str("host").unwrap(),
+                    Dname::from_str("alias.example.com").unwrap(),
+                ]
+            );
+
+            let unknown =
+                Dname::<Vec<u8>>::from_str("other.example.com").unwrap();
+            assert!(resolver.lookup_host(&unknown).await.is_err());
+        });
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn aa_only_and_blast() {
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let respond = |aa| {
+                move |query: &Message<Bytes>, _| {
+                    let mut msg = MessageBuilder::new_vec()
+                        .start_answer(query, Rcode::NoError)
+                        .unwrap();
+                    msg.header_mut().set_aa(aa);
+                    vec![msg.finish()]
+                }
+            };
+            let plain = udp_server(respond(false)).await;
+            let auth = udp_server(respond(true)).await;
+            let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let silent = silent.local_addr().unwrap();
+
+            let resolver =
+                |servers: &[SocketAddr], set: fn(&mut ResolvOptions)| {
+                    let mut conf = ResolvConf::new();
+                    for &addr in servers {
+                        let mut server =
+                            ServerConf::new(addr, Transport::Udp);
+                        server.request_timeout = Duration::from_secs(2);
+                        conf.servers.push(server);
+                    }
+                    conf.options.attempts = 1;
+                    conf.options.select_by_rtt = false;
+                    set(&mut conf.options);
+                    StubResolver::from_conf(conf)
+                };
+
+            // Without aa_only, the first answer is taken.
+            let answer = resolver(&[plain, auth], |_| {})
+                .query(question())
+                .await
+                .unwrap();
+            assert!(!answer.header().aa());
+
+            // With aa_only, non-authoritative answers are skipped.
+            let answer = resolver(&[plain, auth], |opts| opts.aa_only = true)
+                .query(question())
+                .await
+                .unwrap();
+            assert!(answer.header().aa());
+            let err = match resolver(&[plain], |opts| opts.aa_only = true)
+                .query(question())
+                .await
+            {
+                Ok(_) => panic!("non-authoritative answer accepted"),
+                Err(err) => err,
+            };
+            assert!(matches!(
+                err.servers()[0].kind(),
+                ServerErrorKind::NotAuthoritative
+            ));
+
+            // With blast, the silent server doesn’t hold us up.
+            let start = Instant::now();
+            let answer = resolver(&[silent, auth], |opts| opts.blast = true)
+                .query(question())
+                .await
+                .unwrap();
+            assert!(answer.header().aa());
+            assert!(start.elapsed() < Duration::from_secs(1));
+
+            // With blast and aa_only, all answers are considered.
+            let answer = resolver(&[plain, silent, auth], |opts| {
+                opts.blast = true;
+                opts.aa_only = true;
+            })
+            .query(question())
+            .await
+            .unwrap();
+            assert!(answer.header().aa());
+        })
+    }
+
+    /// Answers queries for the host lookup test.
+    fn host_response(query: &Message<Bytes>) -> Vec<u8> {
+        let question = query.first_question().unwrap();
+        let qname = question.qname().to_bytes();
+        let name = |s| Dname::<Bytes>::from_str(s).unwrap();
+        let mut msg = MessageBuilder::new_vec()
+            .start_answer(query, Rcode::NoError)
+            .unwrap();
+        if qname == name("bad.example.com") {
+            let target = name("bad!name.example.com");
+            msg.push((&qname, 0, Cname::new(target.clone()))).unwrap();
+            if question.qtype() == Rtype::A {
+                msg.push((&target, 0, A::from_octets(192, 0, 2, 9)))
+                    .unwrap();
+            }
+        } else if qname == name("host.example.com") || qname == name("tld") {
+            match question.qtype() {
+                Rtype::A => {
+                    msg.push((&qname, 0, A::from_octets(10, 0, 0, 1)))
+                        .unwrap();
+                    msg.push((&qname, 0, A::from_octets(192, 0, 2, 1)))
+                        .unwrap();
+                }
+                Rtype::Aaaa => {
+                    let addr = "2001:db8::1".parse().unwrap();
+                    msg.push((&qname, 0, Aaaa::new(addr))).unwrap();
+                }
+                _ => {}
+            }
+        } else if qname == name("v4.example.com") {
+            if question.qtype() == Rtype::A {
+                msg.push((&qname, 0, A::from_octets(192, 0, 2, 4))).unwrap();
+            }
+        } else if qname == name("1.2.0.192.in-addr.arpa") {
+            msg.push((&qname, 0, Ptr::new(name("host.example.com"))))
+                .unwrap();
+        } else if qname == name("66.2.0.192.in-addr.arpa") {
+            msg.push((&qname, 0, Ptr::new(name("-host.example.com"))))
+                .unwrap();
+        }
+        msg.finish()
+    }
+
+    #[test]
+    fn host_lookup_options() {
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let received = Arc::new(Mutex::new(Vec::new()));
+            let seen = received.clone();
+            let addr = udp_server(move |query, _| {
+                let question = query.first_question().unwrap();
+                seen.lock()
+                    .unwrap()
+                    .push((question.qname().to_bytes(), question.qtype()));
+                vec![host_response(query)]
+            })
+            .await;
+            let resolver = |set: &dyn Fn(&mut ResolvOptions)| {
+                let mut conf = ResolvConf::new();
+                conf.servers.push(ServerConf::new(addr, Transport::Udp));
+                conf.options.attempts = 1;
+                conf.options.search =
+                    SearchSuffix::from_str("example.com").unwrap().into();
+                conf.options.search.push_root();
+                set(&mut conf.options);
+                StubResolver::from_conf(conf)
+            };
+            let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+            let host =
+                Dname::<Vec<u8>>::from_str("host.example.com").unwrap();
+
+            let plain = resolver(&|_| {});
+            let sorted = resolver(&|opts| {
+                opts.sortlist.push(SortListItem::new(
+                    Ipv4Addr::new(192, 0, 2, 0),
+                    Ipv4Addr::new(255, 255, 255, 0),
+                ))
+            });
+            let inet6 = resolver(&|opts| opts.use_inet6 = true);
+            let single = resolver(&|opts| opts.single_request = true);
+            let unchecked = resolver(&|opts| opts.no_check_name = true);
+            let no_tld = resolver(&|opts| opts.no_tld_query = true);
+
+            let found = plain.lookup_host(&host).await.unwrap();
+            assert_eq!(
+                found.iter().collect::<Vec<_>>(),
+                [ip("2001:db8::1"), ip("10.0.0.1"), ip("192.0.2.1")]
+            );
+
+            let found = sorted.lookup_host(&host).await.unwrap();
+            assert_eq!(
+                found.iter().collect::<Vec<_>>(),
+                [ip("2001:db8::1"), ip("192.0.2.1"), ip("10.0.0.1")]
+            );
+
+            let qtypes = || {
+                received
+                    .lock()
+                    .unwrap()
+                    .drain(..)
+                    .map(|item: (Dname<Bytes>, Rtype)| item.1)
+                    .collect::<Vec<_>>()
+            };
+            qtypes();
+            // With use_inet6, A records are only used if there are no
+            // AAAA records.
+            let found = inet6.lookup_host(&host).await.unwrap();
+            assert_eq!(found.iter().collect::<Vec<_>>(), [ip("2001:db8::1")]);
+            assert_eq!(qtypes(), [Rtype::Aaaa]);
+            let v4 = Dname::<Vec<u8>>::from_str("v4.example.com").unwrap();
+            let found = inet6.lookup_host(&v4).await.unwrap();
+            assert_eq!(
+                found.iter().collect::<Vec<_>>(),
+                [ip("::ffff:192.0.2.4")]
+            );
+            assert_eq!(qtypes(), [Rtype::Aaaa, Rtype::A]);
+            single.lookup_host(&host).await.unwrap();
+            assert_eq!(qtypes(), [Rtype::A, Rtype::Aaaa]);
+
+            // Names are checked unless no_check_name is set.
+            let bad = Dname::<Vec<u8>>::from_str("bad.example.com").unwrap();
+            assert!(matches!(
+                plain.lookup_host(&bad).await,
+                Err(QueryError::InvalidName)
+            ));
+            let found = unchecked.lookup_host(&bad).await.unwrap();
+            assert_eq!(found.iter().collect::<Vec<_>>(), [ip("192.0.2.9")]);
+
+            let found = plain.lookup_addr(ip("192.0.2.1")).await.unwrap();
+            assert_eq!(
+                found.iter().map(|name| name.to_bytes()).collect::<Vec<_>>(),
+                [Dname::<Bytes>::from_str("host.example.com").unwrap()]
+            );
+            assert!(matches!(
+                plain.lookup_addr(ip("192.0.2.66")).await,
+                Err(QueryError::InvalidName)
+            ));
+            assert!(unchecked.lookup_addr(ip("192.0.2.66")).await.is_ok());
+
+            // A single label name is only looked up as a TLD without
+            // no_tld_query.
+            let tld =
+                || RelativeDname::from_octets(b"\x03tld".as_ref()).unwrap();
+            let found = plain.search_host(tld()).await.unwrap();
+            assert!(!found.is_empty());
+            qtypes();
+            let found = no_tld.search_host(tld()).await.unwrap();
+            assert!(found.is_empty());
+            let tld_example =
+                Dname::<Bytes>::from_str("tld.example.com").unwrap();
+            assert!(received
+                .lock()
+                .unwrap()
+                .iter()
+                .all(|item| item.0 == tld_example));
+        })
+    }
+}
@@ -0,0 +1,588 @@
+//! Persistent TCP connections.
+//!
+//! This module implements TCP connections for the stub resolver that stay
+//! open after a query and are used for later queries to the same server.
+//! As recommended by [RFC 7766], queries are pipelined: all queries to a
+//! server are sent over the same connection without waiting for answers to
+//! earlier queries, and answers are matched to their queries via the
+//! message ID, so they can arrive in any order.
+//!
+//! An idle connection is closed after a while. The server can tell us how
+//! long this should be via the edns-tcp-keepalive option defined in
+//! [RFC 7828]. If it announces a timeout of zero, no new queries are sent
+//! over the connection and it is closed once all outstanding answers have
+//! arrived.
+//!
+//! [RFC 7766]: https://tools.ietf.org/html/rfc7766
+//! [RFC 7828]: https://tools.ietf.org/html/rfc7828
+
+use super::conf::ServerConf;
+use super::{Answer, QueryMessage};
+use crate::base::message::Message;
+use crate::base::opt::TcpKeepalive;
+use bytes::{Buf, Bytes, BytesMut};
+use futures::channel::{mpsc, oneshot};
+use futures::StreamExt;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+use std::{fmt, io};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+//------------ Constants -----------------------------------------------------
+
+/// The minimum time to wait for data before checking the idle timeout.
+///
+/// This keeps a zero or very short idle timeout from making the reading
+/// task spin.
+const MIN_IDLE_WAIT: Duration = Duration::from_millis(100);
+
+//------------ TcpPool -------------------------------------------------------
+
+/// The persistent TCP connection to a single server.
+#[derive(Default)]
+pub(super) struct TcpPool {
+    /// The current connection if there is one.
+    conn: Mutex<Option<Arc<TcpConn>>>,
+}
+
+impl TcpPool {
+    /// Sends a query to the server and returns the answer.
+    ///
+    /// Uses the current connection if it is still open. Since the server
+    /// may close it at any time, the query is tried again on a new
+    /// connection if that fails.
+    pub async fn query(
+        &self,
+        query: &QueryMessage,
+        conf: &ServerConf,
+    ) -> Result<Answer, io::Error> {
+        let (conn, reused) = self.conn(conf);
+        match conn.query(query).await {
+            Err(_) if reused => self.conn(conf).0.query(query).await,
+            res => res,
+        }
+    }
+
+    /// Returns the current connection, opening a new one if necessary.
+    ///
+    /// Also returns whether the connection has been used before.
+    fn conn(&self, conf: &ServerConf) -> (Arc<TcpConn>, bool) {
+        let mut conn = self.conn.lock().unwrap();
+        if let Some(ref conn) = *conn {
+            if conn.is_usable() {
+                return (conn.clone(), true);
+            }
+        }
+        let res = TcpConn::open(conf);
+        *conn = Some(res.clone());
+        (res, false)
+    }
+
+    /// Returns whether there currently is an open connection.
+    #[cfg(test)]
+    fn is_connected(&self) -> bool {
+        match *self.conn.lock().unwrap() {
+            Some(ref conn) => conn.is_usable(),
+            None => false,
+        }
+    }
+}
+
+//--- Debug
+
+impl fmt::Debug for TcpPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TcpPool")
+            .field("conn", &self.conn.lock().unwrap())
+            .finish()
+    }
+}
+
+//------------ TcpConn -------------------------------------------------------
+
+/// A single TCP connection.
+///
+/// The connection is driven by two tasks: one writes the queries handed to
+/// it via a channel, the other reads the answers and dispatches them to the
+/// waiting queries. Using a separate task for writing makes sure that a
+/// query that is dropped half way through doesn’t leave a partial message
+/// on the connection.
+///
+/// The connection is established by the reading task. Queries can be
+/// handed to the connection right away and are sent once it is there.
+struct TcpConn {
+    /// The channel to the writing task.
+    write: mpsc::UnboundedSender<Vec<u8>>,
+
+    /// The state shared with the reading task.
+    state: Mutex<ConnState>,
+}
+
+/// The mutable state of a TCP connection.
+struct ConnState {
+    /// The queries waiting for an answer by their message ID.
+    pending: HashMap<u16, oneshot::Sender<Message<Bytes>>>,
+
+    /// The time the last query was sent or the last answer received.
+    last_used: Instant,
+
+    /// The time after which an idle connection is closed.
+    idle_timeout: Duration,
+
+    /// Whether the connection has been closed.
+    closed: bool,
+
+    /// Whether the server asked us to close the connection.
+    ///
+    /// This happens if it announces an idle timeout of zero. No new queries
+    /// are accepted and the connection is closed once `pending` is empty.
+    draining: bool,
+
+    /// The kind of error that happened while connecting.
+    error: Option<io::ErrorKind>,
+}
+
+impl TcpConn {
+    /// Opens a new connection to the server.
+    fn open(conf: &ServerConf) -> Arc<Self> {
+        let (tx, rx) = mpsc::unbounded();
+        let conn = Arc::new(TcpConn {
+            write: tx,
+            state: Mutex::new(ConnState {
+                pending: HashMap::new(),
+                last_used: Instant::now(),
+                idle_timeout: conf.idle_timeout,
+                closed: false,
+                draining: false,
+                error: None,
+            }),
+        });
+        tokio::spawn(conn.clone().run(conf.addr, conf.request_timeout, rx));
+        conn
+    }
+
+    /// Returns whether new queries can be sent over the connection.
+    fn is_usable(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        !state.closed && !state.draining
+    }
+
+    /// Sends a query over the connection and waits for the answer.
+    ///
+    /// The query is sent with a message ID that is unique among the
+    /// queries currently waiting for an answer on this connection.
+    async fn query(&self, query: &QueryMessage) -> Result<Answer, io::Error> {
+        let mut message = Vec::from(query.as_target().as_stream_slice());
+        let (id, rx) = {
+            let mut state = self.state.lock().unwrap();
+            if state.closed || state.draining {
+                return Err(state.closed_error());
+            }
+            let id = loop {
+                let id = ::rand::random();
+                if !state.pending.contains_key(&id) {
+                    break id;
+                }
+            };
+            let (tx, rx) = oneshot::channel();
+            state.pending.insert(id, tx);
+            state.last_used = Instant::now();
+            (id, rx)
+        };
+        let _guard = PendingGuard { conn: self, id };
+        message[2..4].copy_from_slice(&id.to_be_bytes());
+        if self.write.unbounded_send(message.clone()).is_err() {
+            return Err(self.state.lock().unwrap().closed_error());
+        }
+        let answer = match rx.await {
+            Ok(answer) => answer,
+            Err(_) => return Err(self.state.lock().unwrap().closed_error()),
+        };
+        if !answer.is_answer(&Message::from_octets(&message[2..]).unwrap()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "unexpected answer",
+            ));
+        }
+        Ok(answer.into())
+    }
+
+    /// Establishes the connection and then runs it.
+    async fn run(
+        self: Arc<Self>,
+        addr: SocketAddr,
+        connect_timeout: Duration,
+        rx: mpsc::UnboundedReceiver<Vec<u8>>,
+    ) {
+        let sock =
+            match timeout(connect_timeout, TcpStream::connect(&addr)).await {
+                Ok(Ok(sock)) => sock,
+                Ok(Err(err)) => return self.close(Some(err.kind())),
+                Err(_) => return self.close(Some(io::ErrorKind::TimedOut)),
+            };
+        let (read, write) = sock.into_split();
+        tokio::spawn(Self::write_loop(write, rx));
+        self.read_loop(read).await
+    }
+
+    /// Writes the queries received via `rx` to the socket.
+    async fn write_loop(
+        mut sock: OwnedWriteHalf,
+        mut rx: mpsc::UnboundedReceiver<Vec<u8>>,
+    ) {
+        while let Some(message) = rx.next().await {
+            if sock.write_all(&message).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    /// Reads answers from the socket and dispatches them.
+    ///
+    /// Closes the connection if it has been idle for too long, if the
+    /// server asked us to and all answers have arrived, or if reading
+    /// fails.
+    async fn read_loop(self: Arc<Self>, mut sock: OwnedReadHalf) {
+        let mut buf = BytesMut::new();
+        loop {
+            loop {
+                match next_message(&mut buf) {
+                    Ok(Some(answer)) => self.dispatch(answer),
+                    Ok(None) => break,
+                    Err(_) => return self.close(None),
+                }
+            }
+
+            let idle_timeout = {
+                let state = self.state.lock().unwrap();
+                if state.draining && state.pending.is_empty() {
+                    break;
+                }
+                state.idle_timeout.max(MIN_IDLE_WAIT)
+            };
+
+            // Reading into a buffer can safely be interrupted by the
+            // timeout, reading a message directly could not.
+            match timeout(idle_timeout, sock.read_buf(&mut buf)).await {
+                Ok(Ok(0)) | Ok(Err(_)) => break,
+                Ok(Ok(_)) => {}
+                Err(_) => {
+                    let state = self.state.lock().unwrap();
+                    if state.pending.is_empty()
+                        && state.last_used.elapsed() >= state.idle_timeout
+                    {
+                        break;
+                    }
+                }
+            }
+        }
+        self.close(None)
+    }
+
+    /// Hands an answer to the query waiting for it.
+    fn dispatch(&self, answer: Message<Bytes>) {
+        let mut state = self.state.lock().unwrap();
+        state.last_used = Instant::now();
+        match keepalive(&answer) {
+            Some(timeout) if timeout == Duration::from_secs(0) => {
+                state.draining = true
+            }
+            Some(timeout) => state.idle_timeout = timeout,
+            None => {}
+        }
+        if let Some(tx) = state.pending.remove(&answer.header().id()) {
+            let _ = tx.send(answer);
+        }
+    }
+
+    /// Marks the connection as closed.
+    fn close(&self, error: Option<io::ErrorKind>) {
+        let mut state = self.state.lock().unwrap();
+        state.closed = true;
+        state.error = error;
+
+        // Dropping the senders wakes up all remaining queries with an
+        // error.
+        state.pending.clear();
+        self.write.close_channel();
+    }
+}
+
+//--- Debug
+
+impl fmt::Debug for TcpConn {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let state = self.state.lock().unwrap();
+        f.debug_struct("TcpConn")
+            .field("pending", &state.pending.len())
+            .field("idle_timeout", &state.idle_timeout)
+            .field("closed", &state.closed)
+            .field("draining", &state.draining)
+            .finish()
+    }
+}
+
+impl ConnState {
+    /// Returns the error for a query on a closed connection.
+    fn closed_error(&self) -> io::Error {
+        match self.error {
+            Some(kind) => io::Error::new(kind, "connecting failed"),
+            None => io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "connection closed",
+            ),
+        }
+    }
+}
+
+//------------ PendingGuard --------------------------------------------------
+
+/// Removes a pending query when the query is dropped.
+///
+/// This makes sure the entry is removed if the query times out.
+struct PendingGuard<'a> {
+    conn: &'a TcpConn,
+    id: u16,
+}
+
+impl<'a> Drop for PendingGuard<'a> {
+    fn drop(&mut self) {
+        self.conn.state.lock().unwrap().pending.remove(&self.id);
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Takes the next complete length-prefixed message from the buffer.
+fn next_message(
+    buf: &mut BytesMut,
+) -> Result<Option<Message<Bytes>>, io::Error> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    let len = usize::from(u16::from_be_bytes([buf[0], buf[1]]));
+    if buf.len() < len + 2 {
+        return Ok(None);
+    }
+    buf.advance(2);
+    Message::from_octets(buf.split_to(len).freeze())
+        .map(Some)
+        .map_err(|_| {
+            io::Error::new(io::ErrorKind::InvalidData, "short message")
+        })
+}
+
+/// Returns the idle timeout announced by the server in an answer.
+///
+/// The timeout of the edns-tcp-keepalive option is given in units of
+/// 100 milliseconds.
+fn keepalive(answer: &Message<Bytes>) -> Option<Duration> {
+    let opt = answer.opt()?;
+    let keepalive = opt.as_opt().iter::<TcpKeepalive>().next()?.ok()?;
+    Some(Duration::from_millis(u64::from(keepalive.timeout()) * 100))
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{OptionCode, Rcode, Rtype};
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::{Dname, ToDname};
+    use crate::base::opt::UnknownOptData;
+    use crate::base::question::Question;
+    use crate::rdata::A;
+    use crate::resolv::stub::conf::{ResolvConf, Transport};
+    use crate::resolv::StubResolver;
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::net::TcpListener;
+    use tokio::runtime;
+
+    /// Reads a single length-prefixed message.
+    async fn read_message(
+        sock: &mut OwnedReadHalf,
+    ) -> Result<Message<Bytes>, io::Error> {
+        let len = sock.read_u16().await?;
+        let mut buf = vec![0; usize::from(len)];
+        sock.read_exact(&mut buf).await?;
+        Ok(Message::from_octets(buf.into()).unwrap())
+    }
+
+    /// Starts a server that answers queries for `<n>.example` with
+    /// 192.0.2.n.
+    ///
+    /// The server reads `batch` queries before answering them in reverse
+    /// order. It announces a keepalive timeout of `keepalive` times 100ms.
+    ///
+    /// Returns the server’s address and the number of accepted connections.
+    async fn server(
+        batch: usize,
+        keepalive: u16,
+    ) -> (SocketAddr, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let count = Arc::new(AtomicUsize::new(0));
+        let accepted = count.clone();
+        tokio::spawn(async move {
+            loop {
+                let (sock, _) = listener.accept().await.unwrap();
+                accepted.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let (mut read, mut write) = sock.into_split();
+                    loop {
+                        let mut queries = Vec::new();
+                        for _ in 0..batch {
+                            match read_message(&mut read).await {
+                                Ok(query) => queries.push(query),
+                                Err(_) => return,
+                            }
+                        }
+                        for query in queries.into_iter().rev() {
+                            let answer = answer(query, keepalive);
+                            let len = answer.len() as u16;
+                            write
+                                .write_all(&len.to_be_bytes())
+                                .await
+                                .unwrap();
+                            write.write_all(&answer).await.unwrap();
+                        }
+                    }
+                });
+            }
+        });
+        (addr, count)
+    }
+
+    fn answer(query: Message<Bytes>, keepalive: u16) -> Bytes {
+        let mut msg = MessageBuilder::new_bytes()
+            .start_answer(&query, Rcode::NoError)
+            .unwrap();
+        let qname = query.first_question().unwrap().qname().to_bytes();
+        let last = qname.first().as_slice()[0] - b'0';
+        msg.push((qname, 3600, A::new(Ipv4Addr::new(192, 0, 2, last))))
+            .unwrap();
+        let mut msg = msg.additional();
+        // The query has to contain an empty keepalive option.
+        let wants_keepalive = query.opt().map(|opt| {
+            opt.as_opt().iter::<UnknownOptData<Bytes>>().any(|opt| {
+                let opt = opt.unwrap();
+                opt.code() == OptionCode::TcpKeepalive
+                    && opt.as_slice().is_empty()
+            })
+        });
+        assert_eq!(wants_keepalive, Some(true));
+        msg.opt(|opt| TcpKeepalive::push(opt, keepalive)).unwrap();
+        msg.into_message().into_octets()
+    }
+
+    fn resolver(addr: SocketAddr) -> StubResolver {
+        let mut conf = ResolvConf::new();
+        conf.servers.push(ServerConf::new(addr, Transport::Tcp));
+        conf.options.stay_open = true;
+        conf.options.attempts = 1;
+        StubResolver::from_conf(conf)
+    }
+
+    fn question(n: u8) -> Question<Dname<Vec<u8>>> {
+        Question::new_in(
+            Dname::from_str(&format!("{}.example", n)).unwrap(),
+            Rtype::A,
+        )
+    }
+
+    fn address(answer: &Answer) -> Ipv4Addr {
+        answer
+            .answer()
+            .unwrap()
+            .limit_to::<A>()
+            .next()
+            .unwrap()
+            .unwrap()
+            .data()
+            .addr()
+    }
+
+    #[test]
+    fn pipelined_queries() {
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let (addr, count) = server(3, 100).await;
+            let resolver = resolver(addr);
+            let (one, two, three) = tokio::join!(
+                resolver.query(question(1)),
+                resolver.query(question(2)),
+                resolver.query(question(3)),
+            );
+            assert_eq!(address(&one.unwrap()), Ipv4Addr::new(192, 0, 2, 1));
+            assert_eq!(address(&two.unwrap()), Ipv4Addr::new(192, 0, 2, 2));
+            assert_eq!(address(&three.unwrap()), Ipv4Addr::new(192, 0, 2, 3));
+            assert_eq!(count.load(Ordering::SeqCst), 1);
+        })
+    }
+
+    #[test]
+    fn keepalive_timeout() {
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            // The server asks us to close idle connections after 100ms.
+            let (addr, count) = server(1, 1).await;
+            let resolver = resolver(addr);
+            resolver.query(question(1)).await.unwrap();
+            resolver.query(question(2)).await.unwrap();
+            assert_eq!(count.load(Ordering::SeqCst), 1);
+            assert!(resolver.stream[0].tcp.is_connected());
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            assert!(!resolver.stream[0].tcp.is_connected());
+            resolver.query(question(3)).await.unwrap();
+            assert_eq!(count.load(Ordering::SeqCst), 2);
+        })
+    }
+
+    #[test]
+    fn zero_keepalive() {
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            // The server asks us to close the connection right away.
+            let (addr, count) = server(1, 0).await;
+            let resolver = resolver(addr);
+            resolver.query(question(1)).await.unwrap();
+            assert!(!resolver.stream[0].tcp.is_connected());
+            resolver.query(question(2)).await.unwrap();
+            assert_eq!(count.load(Ordering::SeqCst), 2);
+        })
+    }
+
+    #[test]
+    fn keepalive_option() {
+        let mut msg = MessageBuilder::new_bytes().additional();
+        msg.opt(|opt| {
+            opt.push_raw_option(OptionCode::TcpKeepalive, |_| Ok(()))
+        })
+        .unwrap();
+        assert_eq!(keepalive(&msg.into_message()), None);
+        let mut msg = MessageBuilder::new_bytes().additional();
+        msg.opt(|opt| TcpKeepalive::push(opt, 25)).unwrap();
+        assert_eq!(
+            keepalive(&msg.into_message()),
+            Some(Duration::from_millis(2500))
+        );
+    }
+}
@@ -0,0 +1,399 @@
+//! The DNS-over-TLS transport.
+//!
+//! This module implements the TLS transport of [RFC 7858] for the stub
+//! resolver. Connections to a server are kept open after a query and are
+//! used again for later queries to the same server.
+//!
+//! [RFC 7858]: https://tools.ietf.org/html/rfc7858
+#![cfg(feature = "resolv-tls")]
+
+use super::conf::{ServerConf, SpkiPin};
+use super::{Answer, QueryMessage, ServerInfo};
+use std::string::{String, ToString};
+use std::sync::Mutex;
+use std::vec::Vec;
+use std::{fmt, io};
+use tokio::net::TcpStream;
+use tokio_native_tls::native_tls::{self, Protocol};
+use tokio_native_tls::{TlsConnector, TlsStream};
+
+//------------ Module Configuration ------------------------------------------
+
+/// The maximum number of idle connections kept open per server.
+const MAX_IDLE: usize = 4;
+
+//------------ TlsPool -------------------------------------------------------
+
+/// The TLS connections to a single server.
+#[derive(Default)]
+pub(super) struct TlsPool {
+    /// The connector for creating new connections.
+    ///
+    /// This is created when it is needed for the first time.
+    connector: Mutex<Option<TlsConnector>>,
+
+    /// Connections currently not in use.
+    idle: Mutex<Vec<TlsStream<TcpStream>>>,
+}
+
+impl TlsPool {
+    /// Sends a query to the server and returns the answer.
+    ///
+    /// Uses an idle connection if there is one. Since the server may have
+    /// closed such a connection in the meantime, the query is tried again
+    /// on a new connection if that fails.
+    pub async fn query(
+        &self,
+        query: &QueryMessage,
+        conf: &ServerConf,
+    ) -> Result<Answer, io::Error> {
+        let idle = self.idle.lock().unwrap().pop();
+        if let Some(mut sock) = idle {
+            if let Ok(answer) =
+                ServerInfo::stream_query(&mut sock, query).await
+            {
+                self.release(sock);
+                return Ok(answer);
+            }
+        }
+        let mut sock = self.connect(conf).await?;
+        let answer = ServerInfo::stream_query(&mut sock, query).await?;
+        self.release(sock);
+        Ok(answer)
+    }
+
+    /// Returns the number of idle connections.
+    #[cfg(test)]
+    fn idle_len(&self) -> usize {
+        self.idle.lock().unwrap().len()
+    }
+
+    /// Keeps a connection for later use.
+    fn release(&self, sock: TlsStream<TcpStream>) {
+        let mut idle = self.idle.lock().unwrap();
+        if idle.len() < MAX_IDLE {
+            idle.push(sock)
+        }
+    }
+
+    /// Opens a new connection to the server.
+    async fn connect(
+        &self,
+        conf: &ServerConf,
+    ) -> Result<TlsStream<TcpStream>, io::Error> {
+        let connector = self.connector(conf)?;
+        let domain = match conf.tls_auth_name {
+            Some(ref name) => name.clone(),
+            None => conf.addr.ip().to_string(),
+        };
+        connect(&connector, conf, &domain).await
+    }
+
+    /// Returns the connector, creating it if necessary.
+    fn connector(
+        &self,
+        conf: &ServerConf,
+    ) -> Result<TlsConnector, io::Error> {
+        let mut connector = self.connector.lock().unwrap();
+        if let Some(ref connector) = *connector {
+            return Ok(connector.clone());
+        }
+        let res = TlsConnector::from(
+            connector_builder(conf).build().map_err(tls_error)?,
+        );
+        *connector = Some(res.clone());
+        Ok(res)
+    }
+}
+
+//--- Debug
+
+impl fmt::Debug for TlsPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TlsPool")
+            .field("idle", &self.idle.lock().unwrap().len())
+            .finish()
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns a builder for a connector to the given server.
+pub(super) fn connector_builder(
+    conf: &ServerConf,
+) -> native_tls::TlsConnectorBuilder {
+    let mut builder = native_tls::TlsConnector::builder();
+
+    // RFC 8310, section 9 requires TLS 1.2 or later.
+    builder.min_protocol_version(Some(Protocol::Tlsv12));
+    if !conf.tls_pins.is_empty() {
+        builder.danger_accept_invalid_certs(true);
+        builder.danger_accept_invalid_hostnames(true);
+    }
+    builder
+}
+
+/// Opens a new TLS connection to the given server.
+///
+/// The server is authenticated via the SPKI pins if there are any or via
+/// `domain` otherwise.
+pub(super) async fn connect(
+    connector: &TlsConnector,
+    conf: &ServerConf,
+    domain: &str,
+) -> Result<TlsStream<TcpStream>, io::Error> {
+    let sock = TcpStream::connect(&conf.addr).await?;
+    let sock = connector.connect(domain, sock).await.map_err(tls_error)?;
+    if !conf.tls_pins.is_empty() {
+        check_pins(&sock, &conf.tls_pins)?;
+    }
+    Ok(sock)
+}
+
+/// Checks that the server’s public key matches one of the pins.
+fn check_pins(
+    sock: &TlsStream<TcpStream>,
+    pins: &[SpkiPin],
+) -> Result<(), io::Error> {
+    let cert = sock
+        .get_ref()
+        .peer_certificate()
+        .map_err(tls_error)?
+        .ok_or_else(|| auth_error("server did not present a certificate"))?;
+    let cert = cert.to_der().map_err(tls_error)?;
+    let spki = certificate_spki(&cert)
+        .ok_or_else(|| auth_error("invalid server certificate"))?;
+    if pins.contains(&SpkiPin::from_spki(spki)) {
+        Ok(())
+    } else {
+        Err(auth_error("server key does not match SPKI pins"))
+    }
+}
+
+/// Returns the DER encoded SubjectPublicKeyInfo of a certificate.
+///
+/// The certificate needs to be DER encoded, too. See section 4.1 of
+/// RFC 5280 for its structure.
+fn certificate_spki(cert: &[u8]) -> Option<&[u8]> {
+    let cert = der_content(cert, 0x30)?;
+    let mut tbs = der_content(cert, 0x30)?;
+
+    // The version is optional and carries a context specific tag.
+    if tbs.first() == Some(&0xa0) {
+        tbs = der_skip(tbs)?;
+    }
+
+    // Skip serial number, signature, issuer, validity, and subject.
+    for _ in 0..5 {
+        tbs = der_skip(tbs)?;
+    }
+    let (tag, header, len) = der_header(tbs)?;
+    if tag != 0x30 {
+        return None;
+    }
+    Some(&tbs[..header + len])
+}
+
+/// Returns the tag, header length, and content length of a DER element.
+fn der_header(data: &[u8]) -> Option<(u8, usize, usize)> {
+    let tag = *data.first()?;
+    let first = *data.get(1)?;
+    let (header, len) = if first < 0x80 {
+        (2, usize::from(first))
+    } else {
+        let octets = usize::from(first & 0x7f);
+        if octets == 0 || octets > 4 {
+            return None;
+        }
+        let len = data
+            .get(2..2 + octets)?
+            .iter()
+            .fold(0usize, |len, octet| (len << 8) | usize::from(*octet));
+        (2 + octets, len)
+    };
+    if data.len() < header + len {
+        None
+    } else {
+        Some((tag, header, len))
+    }
+}
+
+/// Returns the content of a DER element with the given tag.
+fn der_content(data: &[u8], expected: u8) -> Option<&[u8]> {
+    let (tag, header, len) = der_header(data)?;
+    if tag != expected {
+        return None;
+    }
+    Some(&data[header..header + len])
+}
+
+/// Returns the data following the first DER element.
+fn der_skip(data: &[u8]) -> Option<&[u8]> {
+    let (_, header, len) = der_header(data)?;
+    Some(&data[header + len..])
+}
+
+/// Converts a TLS error into an IO error.
+///
+/// Most of these errors happen during the handshake, so we treat them as
+/// if the server sent something we didn’t like.
+pub(super) fn tls_error(err: native_tls::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Creates an IO error for a failed server authentication.
+fn auth_error(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, String::from(msg))
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Rcode, Rtype};
+    use crate::base::message::Message;
+    use crate::base::message_builder::MessageBuilder;
+    use crate::base::name::{Dname, ToDname};
+    use crate::base::question::Question;
+    use crate::rdata::A;
+    use crate::resolv::resolver::ServerErrorKind;
+    use crate::resolv::stub::conf::{ResolvConf, Transport};
+    use crate::resolv::StubResolver;
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::str::FromStr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::runtime;
+    use tokio_native_tls::TlsAcceptor;
+
+    /// The pin for the test certificate as produced by OpenSSL.
+    const PIN: &str = "5omdzeHTu+XEyq5kx0ztpPoLfBsSZGJmYGpeIPxFw5Y=";
+
+    /// Starts a DoT server that answers all A queries with 192.0.2.1.
+    ///
+    /// Returns the server’s address and the number of accepted connections.
+    async fn server() -> (SocketAddr, Arc<AtomicUsize>) {
+        let identity = native_tls::Identity::from_pkcs8(
+            include_bytes!("../../../test-data/tls/cert.pem"),
+            include_bytes!("../../../test-data/tls/key.pem"),
+        )
+        .unwrap();
+        let acceptor = TlsAcceptor::from(
+            native_tls::TlsAcceptor::new(identity).unwrap(),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let count = Arc::new(AtomicUsize::new(0));
+        let accepted = count.clone();
+        tokio::spawn(async move {
+            loop {
+                let (sock, _) = listener.accept().await.unwrap();
+                accepted.fetch_add(1, Ordering::SeqCst);
+                let acceptor = acceptor.clone();
+                tokio::spawn(async move {
+                    if let Ok(mut sock) = acceptor.accept(sock).await {
+                        while let Ok(len) = sock.read_u16().await {
+                            let mut buf = vec![0; usize::from(len)];
+                            if sock.read_exact(&mut buf).await.is_err() {
+                                break;
+                            }
+                            let answer = answer(buf);
+                            let len = answer.as_slice().len() as u16;
+                            sock.write_all(&len.to_be_bytes()).await.unwrap();
+                            sock.write_all(answer.as_slice()).await.unwrap();
+                        }
+                    }
+                });
+            }
+        });
+        (addr, count)
+    }
+
+    fn answer(query: Vec<u8>) -> Message<Vec<u8>> {
+        let query = Message::from_octets(query).unwrap();
+        let mut msg = MessageBuilder::new_vec()
+            .start_answer(&query, Rcode::NoError)
+            .unwrap();
+        let qname = query
+            .first_question()
+            .unwrap()
+            .qname()
+            .to_dname::<Vec<u8>>();
+        msg.push((qname.unwrap(), 3600, A::new(Ipv4Addr::new(192, 0, 2, 1))))
+            .unwrap();
+        msg.into_message()
+    }
+
+    fn resolver(addr: SocketAddr, pin: &str) -> StubResolver {
+        let mut server = ServerConf::new(addr, Transport::Tls);
+        server.tls_pins.push(SpkiPin::from_str(pin).unwrap());
+        let mut conf = ResolvConf::new();
+        conf.servers.push(server);
+        conf.options.attempts = 1;
+        StubResolver::from_conf(conf)
+    }
+
+    fn question() -> Question<Dname<Vec<u8>>> {
+        Question::new_in(Dname::from_str("dns.example").unwrap(), Rtype::A)
+    }
+
+    #[test]
+    fn spki() {
+        let cert = native_tls::Certificate::from_pem(include_bytes!(
+            "../../../test-data/tls/cert.pem"
+        ))
+        .unwrap()
+        .to_der()
+        .unwrap();
+        let spki = certificate_spki(&cert).unwrap();
+        assert_eq!(SpkiPin::from_spki(spki), SpkiPin::from_str(PIN).unwrap());
+        assert_eq!(format!("{}", SpkiPin::from_spki(spki)), PIN);
+        assert!(certificate_spki(&cert[..cert.len() - 1]).is_none());
+    }
+
+    #[test]
+    fn query_reuses_connection() {
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let (addr, count) = server().await;
+            let resolver = resolver(addr, PIN);
+            for _ in 0..3 {
+                let answer = resolver.query(question()).await.unwrap();
+                assert_eq!(answer.header().rcode(), Rcode::NoError);
+                assert_eq!(answer.header_counts().ancount(), 1);
+            }
+            assert_eq!(count.load(Ordering::SeqCst), 1);
+            assert_eq!(resolver.stream[0].tls.idle_len(), 1);
+        })
+    }
+
+    #[test]
+    fn query_wrong_pin() {
+        let rt = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let (addr, _) = server().await;
+            let resolver = resolver(
+                addr,
+                "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA=",
+            );
+            match resolver.query(question()).await {
+                Ok(_) => panic!("query with wrong pin succeeded"),
+                Err(err) => match err.servers()[0].kind() {
+                    ServerErrorKind::Io(err) => {
+                        assert_eq!(err.kind(), io::ErrorKind::InvalidData)
+                    }
+                    kind => panic!("unexpected error {}", kind),
+                },
+            }
+        })
+    }
+}
@@ -0,0 +1,1590 @@
+//! A DNSSEC-validating resolver.
+//!
+//! The [`ValidatingResolver`] wraps any other [`Resolver`] and checks the
+//! answers it receives from it using DNSSEC. Starting from a set of trust
+//! anchors – by default the anchor for the root zone – it fetches the DNSKEY
+//! and DS records necessary to build a chain of trust down to the records
+//! of an answer and verifies their signatures.
+//!
+//! The outcome of this process is available via the [`Answer`] returned by
+//! the resolver. Its [`status`][Answer::status] is one of the four states
+//! defined by DNSSEC: secure, insecure, bogus, or indeterminate. If the
+//! answer is not secure, [`reason`][Answer::reason] may provide some
+//! insight as to why.
+//!
+//! For validation to work, the wrapped resolver needs to request DNSSEC
+//! records from its upstream servers. For the stub resolver, this means
+//! setting the `use_dnssec` option. For the recursive resolver, the
+//! `dnssec_ok` field of its configuration needs to be set.
+//!
+//! Negative answers and answers synthesized from wildcards are checked
+//! using the NSEC or NSEC3 records included in the response via the
+//! functions in [`validate::denial`][crate::validate::denial]. Answers
+//! relying on NSEC3 opt-out are considered insecure.
+#![cfg(feature = "validate")]
+#![cfg_attr(docsrs, doc(cfg(all(feature = "resolv", feature = "validate"))))]
+
+use super::resolver::{QueryError, Resolver};
+use crate::base::iana::{Rcode, Rtype};
+use crate::base::message::{Message, RecordSection};
+use crate::base::name::{Dname, ParsedDname, ToDname};
+use crate::base::octets::ParseError;
+use crate::base::question::Question;
+use crate::base::rdata::ParseRecordData;
+use crate::base::record::Record;
+use crate::base::serial::Serial;
+use crate::rdata::{Dnskey, Ds, MasterRecordData, Rrsig};
+use crate::validate::anchor::TrustAnchors;
+use crate::validate::denial::{
+    verify_denial, verify_wildcard, Denial, DenialError,
+};
+use crate::validate::rrset::{verify_dnskeys, verify_rrset, RrsetError};
+use crate::validate::{
+    supports_algorithm, supports_digest, ValidationStatus,
+};
+use bytes::Bytes;
+use std::boxed::Box;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+//------------ Module Configuration ------------------------------------------
+
+/// The maximum time we keep the validation state of a zone.
+///
+/// This caps the TTL of the DNSKEY, DS, or NSEC records that determined the
+/// state.
+const MAX_ZONE_TTL: u32 = 86400;
+
+/// The time we keep a bogus zone state.
+///
+/// See [RFC 4035, Section 4.7].
+///
+/// [RFC 4035, Section 4.7]: https://tools.ietf.org/html/rfc4035#section-4.7
+const BOGUS_TTL: u32 = 60;
+
+/// The maximum number of zones we follow when building a chain of trust.
+///
+/// Since each step needs to move to a strict ancestor of the previous
+/// zone, this is only reached for absurdly deep names.
+const MAX_CHAIN_DEPTH: usize = 32;
+
+//------------ ValidatingResolver --------------------------------------------
+
+/// A resolver that validates the answers of another resolver.
+///
+/// The resolver forwards all questions to the wrapped resolver. It then
+/// tries to establish a chain of trust from one of its trust anchors to the
+/// records in the answer, asking the wrapped resolver for the DNSKEY and DS
+/// records it needs along the way.
+///
+/// The validation state of each zone it encounters is kept in a cache, so
+/// that later answers from the same zones can be validated quicker. Values
+/// can be cloned cheaply if the wrapped resolver can. All clones share the
+/// same cache.
+#[derive(Clone, Debug)]
+pub struct ValidatingResolver<R> {
+    /// The resolver asked for answers.
+    resolver: R,
+
+    /// The trust anchors.
+    anchors: Arc<Mutex<TrustAnchors>>,
+
+    /// The validation state of the zones we have seen so far.
+    zones: Arc<Mutex<ZoneCache>>,
+}
+
+impl<R> ValidatingResolver<R> {
+    /// Creates a new validating resolver using the root trust anchor.
+    pub fn new(resolver: R) -> Self {
+        Self::with_anchors(resolver, TrustAnchors::root())
+    }
+
+    /// Creates a new validating resolver using the given trust anchors.
+    ///
+    /// Whenever the resolver fetches the DNSKEY RRset for the apex of one
+    /// of the anchors, it updates the anchors as described in RFC 5011.
+    pub fn with_anchors(
+        resolver: R,
+        anchors: impl Into<TrustAnchors>,
+    ) -> Self {
+        ValidatingResolver {
+            resolver,
+            anchors: Arc::new(Mutex::new(anchors.into())),
+            zones: Default::default(),
+        }
+    }
+
+    /// Returns a reference to the wrapped resolver.
+    pub fn resolver(&self) -> &R {
+        &self.resolver
+    }
+
+    /// Returns a copy of the current trust anchors of the resolver.
+    ///
+    /// Since the anchors are updated during validation, this can be used
+    /// to persist their state via [`TrustAnchors::save`].
+    pub fn anchors(&self) -> TrustAnchors {
+        self.anchors.lock().unwrap().clone()
+    }
+
+    /// Returns the apex of the closest trust anchor covering `name`.
+    fn find_anchor(&self, name: &impl ToDname) -> Option<Dname<Bytes>> {
+        self.anchors
+            .lock()
+            .unwrap()
+            .find(name)
+            .map(|anchor| anchor.owner().to_bytes())
+    }
+
+    /// Returns the cached state of the given zone if there is one.
+    fn cached(&self, zone: &Dname<Bytes>) -> Option<ZoneState> {
+        self.zones.lock().unwrap().get(zone)
+    }
+
+    /// Caches the state of a zone and returns it again.
+    fn cache(
+        &self,
+        zone: &Dname<Bytes>,
+        state: ZoneState,
+        ttl: u32,
+    ) -> ZoneState {
+        let ttl = match state {
+            ZoneState::Secure(_) | ZoneState::Insecure => {
+                ttl.min(MAX_ZONE_TTL)
+            }
+            ZoneState::Failed(ValidationStatus::Bogus, _) => BOGUS_TTL,
+            ZoneState::Failed(..) => return state,
+        };
+        self.zones
+            .lock()
+            .unwrap()
+            .insert(zone.clone(), state.clone(), ttl);
+        state
+    }
+}
+
+impl<R: Resolver> ValidatingResolver<R> {
+    /// Answers a question.
+    ///
+    /// Asks the wrapped resolver and validates its answer. Errors of the
+    /// wrapped resolver are passed through as is.
+    pub async fn query<N: ToDname, Q: Into<Question<N>>>(
+        &self,
+        question: Q,
+    ) -> Result<Answer<R::Answer>, QueryError> {
+        let question = question.into();
+        self.run(Question::new(
+            question.qname().to_bytes(),
+            question.qtype(),
+            question.qclass(),
+        ))
+        .await
+    }
+
+    /// Answers a question with an owned name.
+    fn run<'s>(
+        &'s self,
+        question: Question<Dname<Bytes>>,
+    ) -> QueryFuture<'s, R::Answer>
+    where
+        R::Query: 's,
+    {
+        Box::pin(async move {
+            let answer = self.resolver.query(question.clone()).await?;
+            let outcome = match to_bytes_message(answer.as_ref()) {
+                Some(message) => self.validate(&question, &message).await,
+                None => Outcome::failed(
+                    ValidationStatus::Indeterminate,
+                    Reason::Malformed,
+                ),
+            };
+            Ok(Answer {
+                answer,
+                status: outcome.status,
+                reason: outcome.reason,
+            })
+        })
+    }
+
+    /// Validates a response.
+    async fn validate(
+        &self,
+        question: &Question<Dname<Bytes>>,
+        message: &Message<Bytes>,
+    ) -> Outcome {
+        let rcode = message.header().rcode();
+        if rcode != Rcode::NoError && rcode != Rcode::NXDomain {
+            return Outcome::failed(
+                ValidationStatus::Indeterminate,
+                Reason::ErrorResponse(rcode),
+            );
+        }
+        let (mut rrsets, rrsigs) = match message.answer().and_then(rrsets) {
+            Ok(some) => some,
+            Err(_) => {
+                return Outcome::failed(
+                    ValidationStatus::Indeterminate,
+                    Reason::Malformed,
+                )
+            }
+        };
+
+        let mut res = Outcome::secure();
+        let mut answered = false;
+        for rrset in &mut rrsets {
+            let rtype = rrset[0].rtype();
+            if rtype == question.qtype() || question.qtype() == Rtype::Any {
+                answered = true;
+            }
+            res = res
+                .combine(self.validate_rrset(rrset, &rrsigs, message).await);
+            if res.status.is_bogus() {
+                return res;
+            }
+        }
+        if !answered {
+            let name = match message.canonical_name() {
+                Some(name) => name.to_bytes(),
+                None => question.qname().clone(),
+            };
+            res = res.combine(
+                self.validate_negative(name, question.qtype(), message)
+                    .await,
+            );
+        }
+        res
+    }
+
+    /// Validates a single RRset from the answer section.
+    async fn validate_rrset(
+        &self,
+        rrset: &mut [AnyRecord<'_>],
+        rrsigs: &[RrsigRecord<'_>],
+        message: &Message<Bytes>,
+    ) -> Outcome {
+        let owner = rrset[0].owner().to_bytes();
+        let rtype = rrset[0].rtype();
+        let sigs: Vec<_> = rrsigs
+            .iter()
+            .filter(|sig| {
+                sig.owner().name_eq(&owner)
+                    && sig.data().type_covered() == rtype
+            })
+            .map(|sig| sig.data())
+            .collect();
+        let signer = match sigs
+            .iter()
+            .find(|sig| owner.ends_with(sig.signer_name()))
+        {
+            Some(sig) => sig.signer_name().to_bytes(),
+            None => {
+                return match self.name_state(owner.clone(), 0).await {
+                    ZoneState::Insecure => Outcome::insecure(),
+                    ZoneState::Secure(_) => Outcome::failed(
+                        ValidationStatus::Bogus,
+                        Reason::Unsigned(owner, rtype),
+                    ),
+                    ZoneState::Failed(status, reason) => {
+                        Outcome::failed(status, reason)
+                    }
+                }
+            }
+        };
+        let keys = match self.zone_keys(signer.clone(), 0).await {
+            ZoneState::Secure(keys) => keys,
+            ZoneState::Insecure => return Outcome::insecure(),
+            ZoneState::Failed(status, reason) => {
+                return Outcome::failed(status, reason)
+            }
+        };
+        let now = Serial::now();
+        match verify_rrset(&signer, rrset, sigs, &keys, now) {
+            Ok(sig) => {
+                if sig.labels() < owner.rrsig_label_count() {
+                    // Wildcard expansion. We need a proof that there is no
+                    // closer match for the owner.
+                    let res = verify_wildcard(
+                        message,
+                        &owner,
+                        sig.labels(),
+                        &signer,
+                        &keys,
+                        now,
+                    );
+                    denial_outcome(owner, res)
+                } else {
+                    Outcome::secure()
+                }
+            }
+            Err(err) => Outcome::failed(
+                ValidationStatus::Bogus,
+                Reason::Rrset(owner, rtype, err),
+            ),
+        }
+    }
+
+    /// Validates a negative answer for `name` and `qtype`.
+    async fn validate_negative(
+        &self,
+        name: Dname<Bytes>,
+        qtype: Rtype,
+        message: &Message<Bytes>,
+    ) -> Outcome {
+        let signer = match denial_signer(message, &name) {
+            Some(signer) => signer,
+            None => {
+                return match self.name_state(name.clone(), 0).await {
+                    ZoneState::Insecure => Outcome::insecure(),
+                    ZoneState::Secure(_) => Outcome::failed(
+                        ValidationStatus::Bogus,
+                        Reason::MissingProof(name),
+                    ),
+                    ZoneState::Failed(status, reason) => {
+                        Outcome::failed(status, reason)
+                    }
+                }
+            }
+        };
+        let keys = match self.zone_keys(signer.clone(), 0).await {
+            ZoneState::Secure(keys) => keys,
+            ZoneState::Insecure => return Outcome::insecure(),
+            ZoneState::Failed(status, reason) => {
+                return Outcome::failed(status, reason)
+            }
+        };
+        let res = verify_denial(
+            message,
+            &name,
+            qtype,
+            &signer,
+            &keys,
+            Serial::now(),
+        );
+        denial_outcome(name, res)
+    }
+
+    /// Determines the state of the zone `name` belongs to.
+    ///
+    /// This walks down from the closest trust anchor, checking for each
+    /// label whether there is a secure or insecure delegation.
+    ///
+    /// The method returns a boxed future since it may be called again for
+    /// an ancestor of `name` when checking the absence of DS records.
+    fn name_state<'s>(
+        &'s self,
+        name: Dname<Bytes>,
+        depth: usize,
+    ) -> Pin<Box<dyn Future<Output = ZoneState> + 's>>
+    where
+        R::Query: 's,
+    {
+        Box::pin(async move {
+            if depth > MAX_CHAIN_DEPTH {
+                return ZoneState::Failed(
+                    ValidationStatus::Indeterminate,
+                    Reason::ChainTooLong(name),
+                );
+            }
+            let zone = match self.find_anchor(&name) {
+                Some(zone) => zone,
+                None => {
+                    return ZoneState::Failed(
+                        ValidationStatus::Indeterminate,
+                        Reason::NoTrustAnchor(name),
+                    )
+                }
+            };
+            let mut state = self.zone_keys(zone.clone(), depth + 1).await;
+            let mut names: Vec<_> = name
+                .iter_suffixes()
+                .take_while(|item| !item.name_eq(&zone))
+                .collect();
+            names.reverse();
+            for name in names {
+                if !matches!(state, ZoneState::Secure(_)) {
+                    return state;
+                }
+                if let Some(cached) = self.cached(&name) {
+                    state = cached;
+                    continue;
+                }
+                let message = match self.lookup(&name, Rtype::Ds).await {
+                    Ok(message) => message,
+                    Err(reason) => {
+                        return ZoneState::Failed(
+                            ValidationStatus::Indeterminate,
+                            reason,
+                        )
+                    }
+                };
+                let has_ds = match message.answer() {
+                    Ok(answer) => {
+                        answer.limit_to::<Ds<Bytes>>().any(|record| {
+                            match record {
+                                Ok(record) => record.owner().name_eq(&name),
+                                Err(_) => false,
+                            }
+                        })
+                    }
+                    Err(_) => false,
+                };
+                if has_ds {
+                    state = self
+                        .zone_keys_from_ds(&name, &message, depth + 1)
+                        .await;
+                    continue;
+                }
+                match self.ds_denial(&name, &message, depth + 1).await {
+                    DsDenial::Insecure(ttl) => {
+                        return self.cache(&name, ZoneState::Insecure, ttl)
+                    }
+                    DsDenial::NoDelegation => {}
+                    DsDenial::Failed(status, reason) => {
+                        return ZoneState::Failed(status, reason)
+                    }
+                }
+            }
+            state
+        })
+    }
+
+    /// Determines the keys of a zone.
+    ///
+    /// The method returns a boxed future since it calls itself for the
+    /// parent zone.
+    fn zone_keys<'s>(
+        &'s self,
+        zone: Dname<Bytes>,
+        depth: usize,
+    ) -> Pin<Box<dyn Future<Output = ZoneState> + 's>>
+    where
+        R::Query: 's,
+    {
+        Box::pin(async move {
+            if let Some(state) = self.cached(&zone) {
+                return state;
+            }
+            if depth > MAX_CHAIN_DEPTH {
+                return ZoneState::Failed(
+                    ValidationStatus::Indeterminate,
+                    Reason::ChainTooLong(zone),
+                );
+            }
+            let apex = match self.find_anchor(&zone) {
+                Some(apex) => apex,
+                None => {
+                    return ZoneState::Failed(
+                        ValidationStatus::Indeterminate,
+                        Reason::NoTrustAnchor(zone),
+                    )
+                }
+            };
+            if apex.name_eq(&zone) {
+                return self.fetch_dnskeys(&zone, KeySource::Anchor).await;
+            }
+            match self.lookup(&zone, Rtype::Ds).await {
+                Ok(message) => {
+                    self.zone_keys_from_ds(&zone, &message, depth).await
+                }
+                Err(reason) => {
+                    ZoneState::Failed(ValidationStatus::Indeterminate, reason)
+                }
+            }
+        })
+    }
+
+    /// Determines the keys of a zone given the response to a DS query.
+    async fn zone_keys_from_ds(
+        &self,
+        zone: &Dname<Bytes>,
+        message: &Message<Bytes>,
+        depth: usize,
+    ) -> ZoneState {
+        let (mut ds_set, sigs) = match message
+            .answer()
+            .and_then(|answer| signed_rrset::<Ds<Bytes>>(answer, zone))
+        {
+            Ok(some) => some,
+            Err(_) => {
+                return ZoneState::Failed(
+                    ValidationStatus::Indeterminate,
+                    Reason::Malformed,
+                )
+            }
+        };
+        if ds_set.is_empty() {
+            return match self.ds_denial(zone, message, depth).await {
+                DsDenial::Insecure(ttl) => {
+                    self.cache(zone, ZoneState::Insecure, ttl)
+                }
+                DsDenial::NoDelegation => self.cache(
+                    zone,
+                    ZoneState::Failed(
+                        ValidationStatus::Bogus,
+                        Reason::Dnskey(zone.clone(), RrsetError::NoKey),
+                    ),
+                    0,
+                ),
+                DsDenial::Failed(status, reason) => {
+                    ZoneState::Failed(status, reason)
+                }
+            };
+        }
+
+        let signer = match sigs.iter().find(|sig| {
+            !zone.name_eq(sig.signer_name())
+                && zone.ends_with(sig.signer_name())
+        }) {
+            Some(sig) => sig.signer_name().to_bytes(),
+            None => {
+                return self.cache(
+                    zone,
+                    ZoneState::Failed(
+                        ValidationStatus::Bogus,
+                        Reason::Rrset(
+                            zone.clone(),
+                            Rtype::Ds,
+                            RrsetError::NoSignature,
+                        ),
+                    ),
+                    0,
+                )
+            }
+        };
+        let keys = match self.zone_keys(signer.clone(), depth + 1).await {
+            ZoneState::Secure(keys) => keys,
+            state => return state,
+        };
+        if let Err(err) =
+            verify_rrset(&signer, &mut ds_set, &sigs, &keys, Serial::now())
+        {
+            return self.cache(
+                zone,
+                ZoneState::Failed(
+                    ValidationStatus::Bogus,
+                    Reason::Rrset(zone.clone(), Rtype::Ds, err),
+                ),
+                0,
+            );
+        }
+
+        let ttl = min_ttl(&ds_set);
+        let ds_set: Vec<_> = ds_set
+            .into_iter()
+            .map(Record::into_data)
+            .filter(|ds| {
+                supports_algorithm(ds.algorithm())
+                    && supports_digest(ds.digest_type())
+            })
+            .collect();
+        if ds_set.is_empty() {
+            // None of the DS records is usable, so the zone is to be
+            // treated as unsigned. See RFC 4035, section 5.2.
+            return self.cache(zone, ZoneState::Insecure, ttl);
+        }
+        self.fetch_dnskeys(zone, KeySource::Ds(&ds_set)).await
+    }
+
+    /// Fetches and verifies the DNSKEY RRset of a zone.
+    async fn fetch_dnskeys(
+        &self,
+        zone: &Dname<Bytes>,
+        source: KeySource<'_>,
+    ) -> ZoneState {
+        let message = match self.lookup(zone, Rtype::Dnskey).await {
+            Ok(message) => message,
+            Err(reason) => {
+                return ZoneState::Failed(
+                    ValidationStatus::Indeterminate,
+                    reason,
+                )
+            }
+        };
+        let (mut keys, sigs) = match message
+            .answer()
+            .and_then(|answer| signed_rrset::<Dnskey<Bytes>>(answer, zone))
+        {
+            Ok(some) => some,
+            Err(_) => {
+                return ZoneState::Failed(
+                    ValidationStatus::Indeterminate,
+                    Reason::Malformed,
+                )
+            }
+        };
+        let now = Serial::now();
+        let res = match source {
+            KeySource::Anchor => {
+                self.anchors.lock().unwrap().update(&mut keys, &sigs, now)
+            }
+            KeySource::Ds(ds) => verify_dnskeys(&mut keys, &sigs, ds, now),
+        };
+        match res {
+            Ok(_) => {
+                let ttl = min_ttl(&keys);
+                let keys = keys.into_iter().map(Record::into_data).collect();
+                self.cache(zone, ZoneState::Secure(Arc::new(keys)), ttl)
+            }
+            Err(err) => self.cache(
+                zone,
+                ZoneState::Failed(
+                    ValidationStatus::Bogus,
+                    Reason::Dnskey(zone.clone(), err),
+                ),
+                0,
+            ),
+        }
+    }
+
+    /// Checks the proof that there is no DS record for `name`.
+    ///
+    /// The proof has to be signed by a zone above `name` since the DS
+    /// records live on the parent side of a delegation. Without such a
+    /// proof, the answer is bogus if the parent zone is secure and insecure
+    /// if it is insecure.
+    async fn ds_denial(
+        &self,
+        name: &Dname<Bytes>,
+        message: &Message<Bytes>,
+        depth: usize,
+    ) -> DsDenial {
+        let ttl = authority_ttl(message);
+        let mut parent = name.clone();
+        let signer = if parent.parent() {
+            denial_signer(message, &parent)
+        } else {
+            None
+        };
+        let signer = match signer {
+            Some(signer) => signer,
+            None => {
+                return match self.name_state(parent, depth + 1).await {
+                    ZoneState::Insecure => DsDenial::Insecure(ttl),
+                    ZoneState::Secure(_) => DsDenial::Failed(
+                        ValidationStatus::Bogus,
+                        Reason::MissingProof(name.clone()),
+                    ),
+                    ZoneState::Failed(status, reason) => {
+                        DsDenial::Failed(status, reason)
+                    }
+                }
+            }
+        };
+        let keys = match self.zone_keys(signer.clone(), depth + 1).await {
+            ZoneState::Secure(keys) => keys,
+            ZoneState::Insecure => return DsDenial::Insecure(ttl),
+            ZoneState::Failed(status, reason) => {
+                return DsDenial::Failed(status, reason)
+            }
+        };
+        match verify_denial(
+            message,
+            name,
+            Rtype::Ds,
+            &signer,
+            &keys,
+            Serial::now(),
+        ) {
+            Ok(Denial::InsecureDelegation) | Ok(Denial::OptOut) => {
+                DsDenial::Insecure(ttl)
+            }
+            Ok(_) => DsDenial::NoDelegation,
+            Err(err) if err.is_insecure() => DsDenial::Insecure(ttl),
+            Err(err) => DsDenial::Failed(
+                ValidationStatus::Bogus,
+                Reason::Denial(name.clone(), err),
+            ),
+        }
+    }
+
+    /// Asks the wrapped resolver for the given records.
+    async fn lookup(
+        &self,
+        name: &Dname<Bytes>,
+        rtype: Rtype,
+    ) -> Result<Message<Bytes>, Reason> {
+        let answer = match self.resolver.query((name.clone(), rtype)).await {
+            Ok(answer) => answer,
+            Err(_) => return Err(Reason::Lookup(name.clone(), rtype)),
+        };
+        let message = match to_bytes_message(answer.as_ref()) {
+            Some(message) => message,
+            None => return Err(Reason::Malformed),
+        };
+        match message.header().rcode() {
+            Rcode::NoError | Rcode::NXDomain => Ok(message),
+            _ => Err(Reason::Lookup(name.clone(), rtype)),
+        }
+    }
+}
+
+//--- Resolver
+
+impl<'a, R: Resolver> Resolver for &'a ValidatingResolver<R> {
+    type Octets = R::Octets;
+    type Answer = Answer<R::Answer>;
+    type Query = QueryFuture<'a, R::Answer>;
+
+    fn query<N, Q>(&self, question: Q) -> Self::Query
+    where
+        N: ToDname,
+        Q: Into<Question<N>>,
+    {
+        let question = question.into();
+        self.run(Question::new(
+            question.qname().to_bytes(),
+            question.qtype(),
+            question.qclass(),
+        ))
+    }
+}
+
+/// The future returned by queries of a validating resolver.
+pub type QueryFuture<'a, A> =
+    Pin<Box<dyn Future<Output = Result<Answer<A>, QueryError>> + 'a>>;
+
+//------------ Answer --------------------------------------------------------
+
+/// The answer of a validating resolver.
+///
+/// This wraps the answer of the underlying resolver and adds the outcome
+/// of validating it.
+#[derive(Clone, Debug)]
+pub struct Answer<A> {
+    /// The answer of the underlying resolver.
+    answer: A,
+
+    /// The validation status of the answer.
+    status: ValidationStatus,
+
+    /// The reason for the status if it isn’t secure or insecure.
+    reason: Option<Reason>,
+}
+
+impl<A> Answer<A> {
+    /// Returns the validation status of the answer.
+    pub fn status(&self) -> ValidationStatus {
+        self.status
+    }
+
+    /// Returns whether the answer has been validated as secure.
+    pub fn is_secure(&self) -> bool {
+        self.status.is_secure()
+    }
+
+    /// Returns the reason why the answer is bogus or indeterminate.
+    pub fn reason(&self) -> Option<&Reason> {
+        self.reason.as_ref()
+    }
+
+    /// Returns a reference to the answer of the underlying resolver.
+    pub fn inner(&self) -> &A {
+        &self.answer
+    }
+
+    /// Converts the value into the answer of the underlying resolver.
+    pub fn into_inner(self) -> A {
+        self.answer
+    }
+}
+
+//--- AsRef
+
+impl<A: AsRef<Message<Octets>>, Octets> AsRef<Message<Octets>> for Answer<A> {
+    fn as_ref(&self) -> &Message<Octets> {
+        self.answer.as_ref()
+    }
+}
+
+//------------ Reason --------------------------------------------------------
+
+/// The reason why an answer is bogus or indeterminate.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Reason {
+    /// There is no trust anchor covering the name.
+    NoTrustAnchor(Dname<Bytes>),
+
+    /// The response had an error response code.
+    ErrorResponse(Rcode),
+
+    /// A response was malformed.
+    Malformed,
+
+    /// Looking up records needed for validation failed.
+    Lookup(Dname<Bytes>, Rtype),
+
+    /// The DNSKEY RRset of a zone could not be verified.
+    Dnskey(Dname<Bytes>, RrsetError),
+
+    /// An RRset could not be verified.
+    Rrset(Dname<Bytes>, Rtype, RrsetError),
+
+    /// An RRset in a signed zone was not signed.
+    Unsigned(Dname<Bytes>, Rtype),
+
+    /// A proof of non-existence for a name was missing.
+    MissingProof(Dname<Bytes>),
+
+    /// A proof of non-existence for a name could not be verified.
+    Denial(Dname<Bytes>, DenialError),
+
+    /// The chain of trust for a name was too long.
+    ChainTooLong(Dname<Bytes>),
+}
+
+//--- Display
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Reason::NoTrustAnchor(ref name) => {
+                write!(f, "no trust anchor for {}", name)
+            }
+            Reason::ErrorResponse(rcode) => {
+                write!(f, "error response {}", rcode)
+            }
+            Reason::Malformed => f.write_str("malformed response"),
+            Reason::Lookup(ref name, rtype) => {
+                write!(f, "failed to look up {} {}", name, rtype)
+            }
+            Reason::Dnskey(ref name, err) => {
+                write!(f, "DNSKEY RRset for {}: {}", name, err)
+            }
+            Reason::Rrset(ref name, rtype, err) => {
+                write!(f, "{} RRset for {}: {}", rtype, name, err)
+            }
+            Reason::Unsigned(ref name, rtype) => {
+                write!(f, "unsigned {} RRset for {}", rtype, name)
+            }
+            Reason::MissingProof(ref name) => {
+                write!(f, "missing proof of non-existence for {}", name)
+            }
+            Reason::Denial(ref name, err) => {
+                write!(f, "proof of non-existence for {}: {}", name, err)
+            }
+            Reason::ChainTooLong(ref name) => {
+                write!(f, "chain of trust for {} too long", name)
+            }
+        }
+    }
+}
+
+//------------ Outcome -------------------------------------------------------
+
+/// The outcome of validating part of an answer.
+#[derive(Clone, Debug)]
+struct Outcome {
+    status: ValidationStatus,
+    reason: Option<Reason>,
+}
+
+impl Outcome {
+    fn secure() -> Self {
+        Outcome {
+            status: ValidationStatus::Secure,
+            reason: None,
+        }
+    }
+
+    fn insecure() -> Self {
+        Outcome {
+            status: ValidationStatus::Insecure,
+            reason: None,
+        }
+    }
+
+    fn failed(status: ValidationStatus, reason: Reason) -> Self {
+        Outcome {
+            status,
+            reason: Some(reason),
+        }
+    }
+
+    /// Combines two outcomes keeping the reason of the weaker one.
+    fn combine(self, other: Self) -> Self {
+        let status = self.status.combine(other.status);
+        if status == self.status {
+            self
+        } else {
+            other
+        }
+    }
+}
+
+//------------ ZoneState -----------------------------------------------------
+
+/// The validation state of a zone.
+#[derive(Clone, Debug)]
+enum ZoneState {
+    /// The zone is secure and these are its trusted keys.
+    Secure(Arc<Vec<Dnskey<Bytes>>>),
+
+    /// The zone is provably unsigned.
+    Insecure,
+
+    /// The state of the zone couldn’t be determined.
+    Failed(ValidationStatus, Reason),
+}
+
+//------------ ZoneCache -----------------------------------------------------
+
+/// A cache for the validation state of zones.
+#[derive(Debug, Default)]
+struct ZoneCache {
+    zones: HashMap<Dname<Bytes>, (Instant, ZoneState)>,
+}
+
+impl ZoneCache {
+    /// Returns the state of the zone if it is present and not expired.
+    fn get(&mut self, zone: &Dname<Bytes>) -> Option<ZoneState> {
+        let expired = match self.zones.get(zone) {
+            Some((expires, state)) => {
+                if *expires > Instant::now() {
+                    return Some(state.clone());
+                }
+                true
+            }
+            None => false,
+        };
+        if expired {
+            self.zones.remove(zone);
+        }
+        None
+    }
+
+    /// Inserts the state of a zone.
+    fn insert(&mut self, zone: Dname<Bytes>, state: ZoneState, ttl: u32) {
+        let expires = Instant::now() + Duration::from_secs(ttl.into());
+        self.zones.insert(zone, (expires, state));
+    }
+}
+
+//------------ KeySource -----------------------------------------------------
+
+/// Where the trust for the DNSKEY RRset of a zone comes from.
+enum KeySource<'a> {
+    /// The zone has a trust anchor.
+    Anchor,
+
+    /// The zone has these validated DS records.
+    Ds(&'a [Ds<Bytes>]),
+}
+
+//------------ DsDenial ------------------------------------------------------
+
+/// The result of checking a proof of the absence of DS records.
+enum DsDenial {
+    /// The name is an insecure delegation.
+    ///
+    /// The value is the TTL of the proof.
+    Insecure(u32),
+
+    /// There is no delegation at the name.
+    NoDelegation,
+
+    /// The proof could not be checked.
+    Failed(ValidationStatus, Reason),
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// A record from the answer section of a response.
+type AnyRecord<'a> = Record<
+    ParsedDname<&'a Bytes>,
+    MasterRecordData<Bytes, ParsedDname<&'a Bytes>>,
+>;
+
+/// An RRSIG record from a response.
+type RrsigRecord<'a> =
+    Record<ParsedDname<&'a Bytes>, Rrsig<Bytes, ParsedDname<&'a Bytes>>>;
+
+/// Converts a message into one atop `Bytes`.
+fn to_bytes_message<Octets: AsRef<[u8]>>(
+    message: &Message<Octets>,
+) -> Option<Message<Bytes>> {
+    Message::from_octets(Bytes::copy_from_slice(message.as_slice())).ok()
+}
+
+/// Splits a record section into its RRsets and RRSIGs.
+#[allow(clippy::type_complexity)]
+fn rrsets(
+    section: RecordSection<&Bytes>,
+) -> Result<(Vec<Vec<AnyRecord<'_>>>, Vec<RrsigRecord<'_>>), ParseError> {
+    let mut rrsets: Vec<Vec<AnyRecord>> = Vec::new();
+    let mut rrsigs = Vec::new();
+    for record in section {
+        let record = record?;
+        if record.rtype() == Rtype::Rrsig {
+            if let Some(record) = record.to_record()? {
+                rrsigs.push(record);
+            }
+            continue;
+        }
+        let record: AnyRecord = match record.into_record()? {
+            Some(record) => record,
+            None => continue,
+        };
+        let rrset = rrsets.iter_mut().find(|rrset| {
+            rrset[0].owner().name_eq(record.owner())
+                && rrset[0].rtype() == record.rtype()
+                && rrset[0].class() == record.class()
+        });
+        match rrset {
+            Some(rrset) => rrset.push(record),
+            None => rrsets.push(vec![record]),
+        }
+    }
+    Ok((rrsets, rrsigs))
+}
+
+/// Returns the RRset of a given type for `owner` and its signatures.
+#[allow(clippy::type_complexity)]
+fn signed_rrset<'a, Data>(
+    section: RecordSection<&'a Bytes>,
+    owner: &Dname<Bytes>,
+) -> Result<
+    (
+        Vec<Record<ParsedDname<&'a Bytes>, Data>>,
+        Vec<Rrsig<Bytes, ParsedDname<&'a Bytes>>>,
+    ),
+    ParseError,
+>
+where
+    Data: ParseRecordData<&'a Bytes> + crate::base::rdata::RtypeRecordData,
+{
+    let mut records = Vec::new();
+    let mut sigs = Vec::new();
+    for record in section {
+        let record = record?;
+        if !record.owner().name_eq(owner) {
+            continue;
+        }
+        if record.rtype() == Rtype::Rrsig {
+            if let Some(record) =
+                record.to_record::<Rrsig<Bytes, ParsedDname<&Bytes>>>()?
+            {
+                if record.data().type_covered() == Data::RTYPE {
+                    sigs.push(record.into_data())
+                }
+            }
+        } else if let Some(record) = record.to_record::<Data>()? {
+            records.push(record)
+        }
+    }
+    Ok((records, sigs))
+}
+
+/// Returns the signer of the proof of non-existence for `name`.
+///
+/// This is the longest signer name of the signatures over SOA, NSEC, or
+/// NSEC3 records in the authority section that is an ancestor of `name`.
+fn denial_signer(
+    message: &Message<Bytes>,
+    name: &Dname<Bytes>,
+) -> Option<Dname<Bytes>> {
+    let mut res: Option<Dname<Bytes>> = None;
+    for record in message
+        .authority()
+        .ok()?
+        .limit_to::<Rrsig<Bytes, ParsedDname<&Bytes>>>()
+    {
+        let record = match record {
+            Ok(record) => record,
+            Err(_) => continue,
+        };
+        let sig = record.data();
+        if !matches!(
+            sig.type_covered(),
+            Rtype::Soa | Rtype::Nsec | Rtype::Nsec3
+        ) || !name.ends_with(sig.signer_name())
+        {
+            continue;
+        }
+        let longer = match res {
+            Some(ref res) => {
+                sig.signer_name().label_count() > res.label_count()
+            }
+            None => true,
+        };
+        if longer {
+            res = Some(sig.signer_name().to_bytes())
+        }
+    }
+    res
+}
+
+/// Returns the outcome of checking a proof of non-existence for `name`.
+fn denial_outcome(
+    name: Dname<Bytes>,
+    res: Result<Denial, DenialError>,
+) -> Outcome {
+    match res {
+        Ok(Denial::OptOut) => Outcome::insecure(),
+        Ok(_) => Outcome::secure(),
+        Err(err) if err.is_insecure() => Outcome::insecure(),
+        Err(err) => Outcome::failed(
+            ValidationStatus::Bogus,
+            Reason::Denial(name, err),
+        ),
+    }
+}
+
+/// Returns the smallest TTL of the authority section of a message.
+fn authority_ttl(message: &Message<Bytes>) -> u32 {
+    message
+        .authority()
+        .ok()
+        .and_then(|authority| {
+            authority
+                .filter_map(Result::ok)
+                .map(|record| record.ttl())
+                .min()
+        })
+        .unwrap_or(0)
+}
+
+/// Returns the smallest TTL of a set of records.
+fn min_ttl<N, D>(records: &[Record<N, D>]) -> u32 {
+    records.iter().map(Record::ttl).min().unwrap_or(0)
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::charstr::CharStr;
+    use crate::base::iana::{Class, DigestAlg, Nsec3HashAlg, SecAlg};
+    use crate::base::message_builder::MessageBuilder;
+    use crate::rdata::rfc4034::{RtypeBitmap, RtypeBitmapBuilder};
+    use crate::rdata::rfc5155::nsec3_hash;
+    use crate::rdata::{Nsec, Nsec3, A};
+    use crate::resolv::stub::Answer as StubAnswer;
+    use crate::validate::anchor::TrustAnchor;
+    use crate::validate::{DnskeyExt, RrsigExt};
+    use futures::future;
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+    use tokio::runtime;
+
+    type AnyData = MasterRecordData<Bytes, Dname<Bytes>>;
+    type TestRecord = Record<Dname<Bytes>, AnyData>;
+
+    fn name(s: &str) -> Dname<Bytes> {
+        if s == "." {
+            Dname::root_bytes()
+        } else {
+            Dname::from_str(s).unwrap()
+        }
+    }
+
+    fn record(owner: &str, data: impl Into<AnyData>) -> TestRecord {
+        Record::new(name(owner), Class::In, 3600, data.into())
+    }
+
+    fn a(owner: &str, last: u8) -> TestRecord {
+        record(owner, A::new(Ipv4Addr::new(192, 0, 2, last)))
+    }
+
+    fn types(types: &[Rtype]) -> RtypeBitmap<Bytes> {
+        let mut builder = RtypeBitmapBuilder::new_vec();
+        for rtype in types {
+            builder.add(*rtype).unwrap();
+        }
+        let bitmap = builder.finalize();
+        RtypeBitmap::from_octets(Bytes::copy_from_slice(bitmap.as_slice()))
+            .unwrap()
+    }
+
+    /// A zone signing key.
+    struct Key {
+        apex: Dname<Bytes>,
+        dnskey: Dnskey<Bytes>,
+        pair: Ed25519KeyPair,
+    }
+
+    impl Key {
+        fn new(apex: &str) -> Self {
+            let pkcs8 =
+                Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+            let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+            let dnskey = Dnskey::new(
+                257,
+                3,
+                SecAlg::Ed25519,
+                Bytes::copy_from_slice(pair.public_key().as_ref()),
+            );
+            Key {
+                apex: name(apex),
+                dnskey,
+                pair,
+            }
+        }
+
+        fn dnskey(&self) -> TestRecord {
+            Record::new(
+                self.apex.clone(),
+                Class::In,
+                3600,
+                self.dnskey.clone().into(),
+            )
+        }
+
+        fn ds(&self) -> TestRecord {
+            let digest =
+                self.dnskey.digest(&self.apex, DigestAlg::Sha256).unwrap();
+            Record::new(
+                self.apex.clone(),
+                Class::In,
+                3600,
+                Ds::new(
+                    self.dnskey.key_tag(),
+                    SecAlg::Ed25519,
+                    DigestAlg::Sha256,
+                    Bytes::copy_from_slice(digest.as_ref()),
+                )
+                .into(),
+            )
+        }
+
+        /// Returns the RRSIG record for an RRset.
+        fn sign(&self, rrset: &[TestRecord]) -> TestRecord {
+            let first = &rrset[0];
+            let now = Serial::now();
+            let rrsig = |signature| {
+                Rrsig::new(
+                    first.rtype(),
+                    SecAlg::Ed25519,
+                    first.owner().rrsig_label_count(),
+                    first.ttl(),
+                    now.add(3600),
+                    Serial::from(u32::from(now).wrapping_sub(3600)),
+                    self.dnskey.key_tag(),
+                    self.apex.clone(),
+                    signature,
+                )
+            };
+            let mut data = Vec::new();
+            rrsig(Bytes::new())
+                .signed_data(&mut data, &mut rrset.to_vec())
+                .unwrap();
+            let signature = self.pair.sign(&data);
+            Record::new(
+                first.owner().clone(),
+                Class::In,
+                first.ttl(),
+                rrsig(Bytes::copy_from_slice(signature.as_ref())).into(),
+            )
+        }
+
+        /// Returns an RRset together with its signature.
+        fn signed(&self, mut rrset: Vec<TestRecord>) -> Vec<TestRecord> {
+            let sig = self.sign(&rrset);
+            rrset.push(sig);
+            rrset
+        }
+    }
+
+    /// A resolver answering from a fixed set of responses.
+    #[derive(Default)]
+    struct MockResolver {
+        responses: HashMap<(Dname<Bytes>, Rtype), Message<Bytes>>,
+    }
+
+    impl MockResolver {
+        fn insert(
+            &mut self,
+            qname: &str,
+            qtype: Rtype,
+            answer: Vec<TestRecord>,
+            authority: Vec<TestRecord>,
+        ) {
+            let mut msg = MessageBuilder::new_bytes();
+            msg.header_mut().set_qr(true);
+            let mut msg = msg.question();
+            msg.push((name(qname), qtype)).unwrap();
+            let mut msg = msg.answer();
+            for record in answer {
+                msg.push(record).unwrap();
+            }
+            let mut msg = msg.authority();
+            for record in authority {
+                msg.push(record).unwrap();
+            }
+            self.responses
+                .insert((name(qname), qtype), msg.into_message());
+        }
+    }
+
+    impl<'a> Resolver for &'a MockResolver {
+        type Octets = Bytes;
+        type Answer = StubAnswer;
+        type Query = future::Ready<Result<StubAnswer, QueryError>>;
+
+        fn query<N, Q>(&self, question: Q) -> Self::Query
+        where
+            N: ToDname,
+            Q: Into<Question<N>>,
+        {
+            let question = question.into();
+            let key = (question.qname().to_bytes(), question.qtype());
+            future::ready(match self.responses.get(&key) {
+                Some(message) => Ok(message.clone().into()),
+                None => Err(QueryError::NoServers),
+            })
+        }
+    }
+
+    /// Creates a resolver for a small signed hierarchy.
+    ///
+    /// The root zone is signed and anchored. Below it are:
+    ///
+    /// * `secure.`, a signed zone with a DS record in the root,
+    /// * `insecure.`, an unsigned delegation proven via NSEC,
+    /// * `optout.`, an unsigned delegation in an NSEC3 opt-out span,
+    /// * `nods.`, a signed zone without a DS record or proof of its absence,
+    /// * `child.`, a signed zone whose DS NODATA answer is signed by the
+    ///   zone itself.
+    fn resolver() -> (MockResolver, TrustAnchors) {
+        let root = Key::new(".");
+        let secure = Key::new("secure.");
+        let nods = Key::new("nods.");
+        let child = Key::new("child.");
+        let mut res = MockResolver::default();
+
+        // The root zone.
+        res.insert(
+            ".",
+            Rtype::Dnskey,
+            root.signed(vec![root.dnskey()]),
+            vec![],
+        );
+
+        // secure.
+        res.insert(
+            "secure.",
+            Rtype::Ds,
+            root.signed(vec![secure.ds()]),
+            vec![],
+        );
+        res.insert(
+            "secure.",
+            Rtype::Dnskey,
+            secure.signed(vec![secure.dnskey()]),
+            vec![],
+        );
+        res.insert(
+            "www.secure.",
+            Rtype::A,
+            secure.signed(vec![a("www.secure.", 1)]),
+            vec![],
+        );
+        let sig = secure.sign(&[a("bad.secure.", 1)]);
+        res.insert(
+            "bad.secure.",
+            Rtype::A,
+            vec![a("bad.secure.", 2), sig],
+            vec![],
+        );
+
+        // insecure.
+        res.insert(
+            "insecure.",
+            Rtype::Ds,
+            vec![],
+            root.signed(vec![record(
+                "insecure.",
+                Nsec::new(
+                    name("nods."),
+                    types(&[Rtype::Ns, Rtype::Rrsig, Rtype::Nsec]),
+                ),
+            )]),
+        );
+        res.insert(
+            "www.insecure.",
+            Rtype::A,
+            vec![a("www.insecure.", 1)],
+            vec![],
+        );
+
+        // optout.
+        let hash = |owner: &str| {
+            nsec3_hash(&name(owner), Nsec3HashAlg::Sha1, 0, b"").unwrap()
+        };
+        let mut chain = vec![
+            (hash("."), types(&[Rtype::Soa, Rtype::Ns, Rtype::Dnskey])),
+            (hash("secure."), types(&[Rtype::Ns, Rtype::Ds])),
+        ];
+        chain
+            .sort_by(|left, right| left.0.as_slice().cmp(right.0.as_slice()));
+        let mut authority = Vec::new();
+        for i in 0..chain.len() {
+            let next = &chain[(i + 1) % chain.len()].0;
+            authority.extend(root.signed(vec![record(
+                &format!("{}.", chain[i].0),
+                Nsec3::new(
+                    Nsec3HashAlg::Sha1,
+                    1,
+                    0,
+                    CharStr::empty(),
+                    CharStr::from_octets(Bytes::copy_from_slice(
+                        next.as_slice(),
+                    ))
+                    .unwrap(),
+                    chain[i].1.clone(),
+                ),
+            )]));
+        }
+        res.insert("optout.", Rtype::Ds, vec![], authority);
+        res.insert(
+            "www.optout.",
+            Rtype::A,
+            vec![a("www.optout.", 1)],
+            vec![],
+        );
+
+        // nods.
+        res.insert("nods.", Rtype::Ds, vec![], vec![]);
+        res.insert(
+            "www.nods.",
+            Rtype::A,
+            nods.signed(vec![a("www.nods.", 1)]),
+            vec![],
+        );
+
+        // child.
+        res.insert(
+            "child.",
+            Rtype::Ds,
+            vec![],
+            child.signed(vec![record(
+                "child.",
+                Nsec::new(
+                    name("www.child."),
+                    types(&[
+                        Rtype::Soa,
+                        Rtype::Ns,
+                        Rtype::Dnskey,
+                        Rtype::Rrsig,
+                        Rtype::Nsec,
+                    ]),
+                ),
+            )]),
+        );
+        res.insert(
+            "child.",
+            Rtype::Dnskey,
+            child.signed(vec![child.dnskey()]),
+            vec![],
+        );
+        res.insert(
+            "www.child.",
+            Rtype::A,
+            child.signed(vec![a("www.child.", 1)]),
+            vec![],
+        );
+
+        let mut anchor = TrustAnchor::new(Dname::root_vec());
+        anchor.add_dnskey(Dnskey::new(
+            257,
+            3,
+            SecAlg::Ed25519,
+            root.dnskey.public_key().as_ref().into(),
+        ));
+        let mut anchors = TrustAnchors::new();
+        anchors.insert(anchor);
+        (res, anchors)
+    }
+
+    fn validate(qname: &str) -> Answer<StubAnswer> {
+        let (mock, anchors) = resolver();
+        let resolver = ValidatingResolver::with_anchors(&mock, anchors);
+        runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(resolver.query((name(qname), Rtype::A)))
+            .unwrap()
+    }
+
+    #[test]
+    fn secure() {
+        let answer = validate("www.secure.");
+        assert_eq!(answer.status(), ValidationStatus::Secure);
+        assert_eq!(answer.reason(), None);
+    }
+
+    #[test]
+    fn insecure() {
+        assert_eq!(
+            validate("www.insecure.").status(),
+            ValidationStatus::Insecure
+        );
+        assert_eq!(
+            validate("www.optout.").status(),
+            ValidationStatus::Insecure
+        );
+    }
+
+    #[test]
+    fn bogus() {
+        let answer = validate("bad.secure.");
+        assert_eq!(answer.status(), ValidationStatus::Bogus);
+        assert!(matches!(
+            answer.reason(),
+            Some(Reason::Rrset(_, Rtype::A, _))
+        ));
+
+        let answer = validate("www.nods.");
+        assert_eq!(answer.status(), ValidationStatus::Bogus);
+        assert_eq!(
+            answer.reason(),
+            Some(&Reason::MissingProof(name("nods.")))
+        );
+    }
+
+    #[test]
+    fn child_signed_ds_nodata() {
+        let answer = validate("www.child.");
+        assert_eq!(answer.status(), ValidationStatus::Bogus);
+        assert_eq!(
+            answer.reason(),
+            Some(&Reason::MissingProof(name("child.")))
+        );
+    }
+
+    #[test]
+    fn combine_outcomes() {
+        let bogus =
+            Outcome::failed(ValidationStatus::Bogus, Reason::Malformed);
+        let res = Outcome::secure()
+            .combine(Outcome::insecure())
+            .combine(bogus)
+            .combine(Outcome::failed(
+                ValidationStatus::Indeterminate,
+                Reason::ErrorResponse(Rcode::ServFail),
+            ));
+        assert_eq!(res.status, ValidationStatus::Bogus);
+        assert_eq!(res.reason, Some(Reason::Malformed));
+        assert_eq!(
+            Outcome::secure().combine(Outcome::insecure()).status,
+            ValidationStatus::Insecure
+        );
+    }
+
+    #[test]
+    fn zone_cache() {
+        let mut cache = ZoneCache::default();
+        let name = Dname::root_bytes();
+        cache.insert(name.clone(), ZoneState::Insecure, 60);
+        assert!(matches!(cache.get(&name), Some(ZoneState::Insecure)));
+        cache.insert(name.clone(), ZoneState::Insecure, 0);
+        assert!(cache.get(&name).is_none());
+    }
+}
@@ -1,156 +1,353 @@
 //! Signs a zone file.
+//!
+//! This is a simple zone signer built on the `sign` module. It reads an
+//! unsigned zone from a master file, adds the DNSKEY records for the given
+//! keys, creates either an NSEC or NSEC3 chain, signs all authoritative
+//! RRsets, and writes the signed zone.
+//!
+//! Keys are read either from BIND key file pairs or from files containing
+//! the private key in PKCS#8 format, DER or PEM encoded. If both key
+//! signing and zone signing keys are given, the key signing keys only sign
+//! the DNSKEY, CDS, and CDNSKEY RRsets. Otherwise all keys sign all RRsets.
 
-use std::io;
-use std::fs::File;
 use bytes::Bytes;
-use domain_core::name::Dname;
-use domain_core::rdata::MasterRecordData;
-use domain_core::master::reader::{Reader, ReaderItem};
-use domain_core::record::Record;
-use domain_core::serial::Serial;
-use domain_sign::ring;
-use domain_sign::sign::{FamilyName, SortedRecords};
-use ::ring::rand::SystemRandom;
-use unwrap::unwrap;
+use domain::base::charstr::CharStr;
+use domain::base::iana::SecAlg;
+use domain::base::name::Dname;
+use domain::base::record::Record;
+use domain::base::serial::Serial;
+use domain::master::reader::{Reader, ReaderItem};
+use domain::master::scan::Scanner;
+use domain::rdata::MasterRecordData;
+use domain::sign::bind;
+use domain::sign::key::{KeyRole, KeySet, SigningKey};
+use domain::sign::records::{FamilyName, Nsec3Config, SortedRecords};
+use domain::sign::ring::Key;
+use domain::utils::base64;
+use ring::rand::SystemRandom;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::process;
+use std::str::FromStr;
+
+const USAGE: &str = "\
+Usage: signzone [OPTIONS] <zonefile>
+
+Options:
+  -k <file>     add a zone signing key read from <file>
+  -K <file>     add a key signing key read from <file>
+  -a <alg>      algorithm of the keys that follow (default ECDSAP256SHA256)
+  -o <file>     write the signed zone to <file> instead of stdout
+  -i <time>     signature inception (default one hour ago)
+  -e <time>     signature expiration (default 30 days after inception)
+  -n            use NSEC3 instead of NSEC
+  -t <num>      number of additional NSEC3 iterations (default 0)
+  -s <salt>     NSEC3 salt in hex or '-' for no salt (default)
+  -p            set the NSEC3 opt-out flag
 
+Key files are either BIND key file pairs given by the name of one of the
+files or their common base name, or contain a private key in PKCS#8 format,
+DER or PEM encoded. For BIND key files, flags and algorithm are taken from
+the .key file and keys with the SEP flag are key signing keys. If both key
+signing and zone signing keys are given, key signing keys only sign the
+DNSKEY, CDS, and CDNSKEY RRsets. Otherwise, all keys sign all RRsets. Times
+are given either as seconds since the epoch or as YYYYMMDDHHmmSS.";
+
+/// The default validity period of signatures: 30 days.
+const VALIDITY: u32 = 30 * 24 * 3600;
+
+type Records =
+    SortedRecords<Dname<Bytes>, MasterRecordData<Bytes, Dname<Bytes>>>;
 
 fn main() {
-    let mut args = std::env::args();
-    let _ = unwrap!(args.next());
-    let infile = match args.next() {
-        Some(infile) => infile,
-        None => {
-            eprintln!("Usage: signzone <infile> [<outfile>]");
-            std::process::exit(1)
+    let options = match Options::from_args() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            process::exit(1)
         }
     };
-    let outfile = args.next();
-
-    if let Err(err) = sign_zone(infile, outfile) {
+    if let Err(err) = sign_zone(options) {
         eprintln!("{}", err);
-        std::process::exit(1)
+        process::exit(1)
     }
-        
 }
 
+//------------ Options -------------------------------------------------------
+
+/// The options given on the command line.
+struct Options {
+    zonefile: String,
+    outfile: Option<String>,
+    keys: Vec<KeyFile>,
+    inception: Serial,
+    expiration: Serial,
+    nsec3: Option<Nsec3Config<Bytes>>,
+}
 
-type Records = SortedRecords<Dname<Bytes>, MasterRecordData<Bytes, Dname<Bytes>>>;
+/// A key file and how to use the key.
+struct KeyFile {
+    path: String,
+    algorithm: SecAlg,
+    flags: u16,
+}
 
+impl Options {
+    fn from_args() -> Result<Self, String> {
+        let mut args = std::env::args().skip(1);
+        let mut zonefile = None;
+        let mut outfile = None;
+        let mut keys = Vec::new();
+        let mut algorithm = SecAlg::EcdsaP256Sha256;
+        let mut inception = None;
+        let mut expiration = None;
+        let mut nsec3 = false;
+        let mut config = Nsec3Config::default();
 
-fn sign_zone(infile: String, outfile: Option<String>) -> Result<(), io::Error> {
-    let rng = SystemRandom::new();
-    let key = match ring::Key::throwaway_13(256, &rng) {
-        Ok(key) => key,
-        Err(_) => {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "failed to create key"
-            ))
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {}", arg))
+            };
+            match arg.as_str() {
+                "-k" | "-K" => {
+                    keys.push(KeyFile {
+                        path: value()?,
+                        algorithm,
+                        flags: if arg == "-K" { 257 } else { 256 },
+                    });
+                }
+                "-a" => {
+                    let value = value()?;
+                    algorithm = SecAlg::from_str(&value).map_err(|_| {
+                        format!("invalid algorithm {}", value)
+                    })?;
+                }
+                "-o" => outfile = Some(value()?),
+                "-i" => inception = Some(parse_time(&value()?)?),
+                "-e" => expiration = Some(parse_time(&value()?)?),
+                "-n" => nsec3 = true,
+                "-t" => {
+                    let value = value()?;
+                    config.iterations =
+                        u16::from_str(&value).map_err(|_| {
+                            format!("invalid iterations {}", value)
+                        })?;
+                }
+                "-s" => config.salt = parse_salt(&value()?)?,
+                "-p" => config.opt_out = true,
+                _ if arg.starts_with('-') => {
+                    return Err(format!("unknown option {}", arg))
+                }
+                _ => {
+                    if zonefile.is_some() {
+                        return Err("more than one zone file given".into());
+                    }
+                    zonefile = Some(arg);
+                }
+            }
         }
-    };
-    let mut records = load_zone(infile)?;
-    let (apex, ttl) = find_apex(&records)?;
-    let nsecs = records.nsecs(&apex, ttl);
-    records.extend(nsecs.into_iter().map(Record::from_record));
-    match apex.dnskey(ttl, &key) {
-        Ok(record) => {
-            let _ = records.insert(Record::from_record(record));
+
+        let zonefile =
+            zonefile.ok_or_else(|| String::from("no zone file"))?;
+        if keys.is_empty() {
+            return Err("no keys given".into());
         }
-        Err(_) => {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Creating DNSKEY record failed."
-            ))
+        let inception = inception
+            .unwrap_or_else(|| Serial::from(Serial::now().into_int() - 3600));
+        let expiration =
+            expiration.unwrap_or_else(|| inception.add(VALIDITY));
+        if expiration <= inception {
+            return Err("expiration is not after inception".into());
         }
+        Ok(Options {
+            zonefile,
+            outfile,
+            keys,
+            inception,
+            expiration,
+            nsec3: if nsec3 { Some(config) } else { None },
+        })
+    }
+}
+
+fn parse_time(value: &str) -> Result<Serial, String> {
+    Serial::scan_rrsig(&mut Scanner::new(value))
+        .map_err(|_| format!("invalid time {}", value))
+}
+
+fn parse_salt(value: &str) -> Result<CharStr<Bytes>, String> {
+    if value == "-" {
+        return Ok(CharStr::empty());
+    }
+    CharStr::scan_hex(&mut Scanner::new(value))
+        .map_err(|_| format!("invalid salt {}", value))
+}
+
+//------------ Signing -------------------------------------------------------
+
+fn sign_zone(options: Options) -> Result<(), String> {
+    let rng = SystemRandom::new();
+    let keys = options
+        .keys
+        .iter()
+        .map(|file| {
+            let key = load_key(file, &rng)?;
+            let dnskey = key.dnskey().map_err(|_| {
+                format!("{}: creating DNSKEY record failed", file.path)
+            })?;
+            Ok((dnskey.is_secure_entry_point(), key))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let keys = key_set(keys);
+
+    let mut records = load_zone(&options.zonefile)?;
+    let (apex, ttl, nsec_ttl) = find_apex(&records)?;
+
+    for (_, key) in keys.iter() {
+        let dnskey = apex
+            .dnskey(ttl, key)
+            .map_err(|_| String::from("creating DNSKEY record failed"))?;
+        let _ = records.insert(Record::from_record(dnskey));
     }
-    let inception = Serial::now().sub(10);
-    let expiration = inception.add(2592000); // XXX 30 days
-    let rrsigs = match records.sign(&apex, expiration, inception, &key) {
-        Ok(rrsigs) => rrsigs,
-        Err(_) => {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Signing failed."
-            ))
+
+    match options.nsec3 {
+        Some(ref config) => {
+            let chain =
+                records.nsec3s(&apex, nsec_ttl, config).map_err(|err| {
+                    format!("creating NSEC3 chain failed: {}", err)
+                })?;
+            let _ = records.insert(Record::from_record(chain.nsec3param));
+            records.extend(chain.nsec3s.into_iter().map(Record::from_record));
         }
-    };
-    records.extend(rrsigs.into_iter().map(Record::from_record));
-    let _ds = match apex.ds(ttl, key) {
-        Ok(ds) => ds,
-        Err(_) => {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "Creating DS record failed."
-            ))
+        None => {
+            let nsecs = records.nsecs(&apex, nsec_ttl);
+            records.extend(nsecs.into_iter().map(Record::from_record));
         }
-    };
+    }
 
-    match outfile {
-        Some(path) => {
-            let mut file = File::create(path)?;
-            records.write(&mut file)?;
+    let rrsigs = records
+        .sign_with_keys(&apex, options.expiration, options.inception, &keys)
+        .map_err(|_| String::from("signing failed"))?;
+    records.extend(rrsigs.into_iter().map(Record::from_record));
+
+    match options.outfile {
+        Some(ref path) => {
+            let mut file = File::create(path)
+                .map_err(|err| format!("{}: {}", path, err))?;
+            records
+                .write(&mut file)
+                .map_err(|err| format!("{}: {}", path, err))?;
         }
         None => {
-            {
-                let stdout = io::stdout();
-                let mut stdout = stdout.lock();
-                records.write(&mut stdout)?;
-            }
-            println!("");
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            records.write(&mut stdout).map_err(|err| err.to_string())?;
         }
     }
-    //println!("{}", ds);
     Ok(())
 }
 
+/// Assigns the roles to the keys based on their SEP flag.
+///
+/// The keys are given together with their SEP flag. If there are only keys
+/// with or without the SEP flag, all keys become combined signing keys.
+fn key_set(keys: Vec<(bool, Key)>) -> KeySet<Key> {
+    let ksks = keys.iter().filter(|(sep, _)| *sep).count();
+    let split = ksks > 0 && ksks < keys.len();
+    keys.into_iter()
+        .map(|(sep, key)| {
+            let role = if !split {
+                KeyRole::Csk
+            } else if sep {
+                KeyRole::Ksk
+            } else {
+                KeyRole::Zsk
+            };
+            (role, key)
+        })
+        .collect()
+}
+
+fn load_key<'a>(
+    key: &KeyFile,
+    rng: &'a SystemRandom,
+) -> Result<Key<'a>, String> {
+    if is_bind_key(&key.path) {
+        let (public, private) = bind::load(&key.path)
+            .map_err(|err| format!("{}: {}", key.path, err))?;
+        return Key::from_bind(&public, &private, rng)
+            .map_err(|err| format!("{}: {}", key.path, err));
+    }
+
+    let mut data = Vec::new();
+    File::open(&key.path)
+        .and_then(|mut file| file.read_to_end(&mut data))
+        .map_err(|err| format!("{}: {}", key.path, err))?;
+
+    // PEM files have the base64 encoded DER data between the BEGIN and
+    // END lines.
+    let data = match std::str::from_utf8(&data) {
+        Ok(text) if text.starts_with("-----BEGIN") => {
+            let encoded: String = text
+                .lines()
+                .filter(|line| !line.starts_with("-----"))
+                .collect();
+            base64::decode(&encoded)
+                .map_err(|_| format!("{}: invalid PEM data", key.path))?
+                .to_vec()
+        }
+        _ => data,
+    };
+
+    Key::from_pkcs8(key.flags, key.algorithm, &data, rng).map_err(|_| {
+        format!("{}: invalid or unsupported {} key", key.path, key.algorithm)
+    })
+}
+
+/// Returns whether a path refers to a BIND key file pair.
+fn is_bind_key(path: &str) -> bool {
+    path.ends_with(".key")
+        || path.ends_with(".private")
+        || Path::new(&format!("{}.private", path)).exists()
+}
 
-fn load_zone(infile: String) -> Result<Records, io::Error> {
-    let reader = Reader::open(infile)?;
-    let mut res = SortedRecords::new();
+fn load_zone(path: &str) -> Result<Records, String> {
+    let reader =
+        Reader::open(path).map_err(|err| format!("{}: {}", path, err))?;
+    let mut res = Vec::new();
     for item in reader {
         match item {
-            Ok(ReaderItem::Record(record)) => {
-                let _ = res.insert(record);
-            }
-            Ok(ReaderItem::Include {..}) => {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    "$include not supported"
-                ))
-            }
-            Ok(ReaderItem::Control {name, ..}) => {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("${} not supported", name)
-                ))
+            Ok(ReaderItem::Record(record)) => res.push(record),
+            Ok(ReaderItem::Include { .. }) => {
+                return Err(format!("{}: $INCLUDE not supported", path))
             }
-            Err(err) => {
-                return Err(io::Error::new(
-                    io::ErrorKind::Other,
-                    format!("{}", err)
-                ))
+            Ok(ReaderItem::Control { name, .. }) => {
+                return Err(format!("{}: ${} not supported", path, name))
             }
+            Err(err) => return Err(format!("{}: {}", path, err)),
         }
     }
-    Ok(res)
+    Ok(res.into_iter().collect())
 }
 
+/// Returns the apex of the zone and the TTLs for DNSKEY and NSEC records.
+///
+/// The DNSKEY records get the TTL of the SOA record. NSEC and NSEC3 records
+/// get the smaller of the SOA TTL and its minimum field as described in
+/// RFC 9077.
 fn find_apex(
-    records: &Records
-) -> Result<(FamilyName<Dname<Bytes>>, u32), io::Error> {
+    records: &Records,
+) -> Result<(FamilyName<Dname<Bytes>>, u32, u32), String> {
     let soa = match records.find_soa() {
         Some(soa) => soa,
-        None => {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                "cannot find SOA record"
-            ))
-        }
+        None => return Err("cannot find SOA record".into()),
     };
-    let ttl = match *soa.first().data() {
+    let ttl = soa.ttl();
+    let minimum = match *soa.first().data() {
         MasterRecordData::Soa(ref soa) => soa.minimum(),
-        _ => unreachable!()
+        _ => unreachable!(),
     };
-    Ok((soa.family_name().cloned(), ttl))
+    Ok((soa.family_name().cloned(), ttl, ttl.min(minimum)))
 }
-
@@ -0,0 +1,551 @@
+//! Key files in the format used by BIND.
+//!
+//! BIND and a number of other tools such as ldns store DNSSEC keys in a
+//! pair of files. Both files share the base name `K<zone>+<alg>+<tag>`
+//! where `<zone>` is the apex of the zone the key is for, `<alg>` is the
+//! algorithm number as three decimal digits, and `<tag>` is the key tag as
+//! five decimal digits. The `.key` file contains the public key as a
+//! DNSKEY record in master file format. The `.private` file contains the
+//! private key as a list of named fields.
+//!
+//! This module provides [`PublicKeyFile`] and [`PrivateKeyFile`] for
+//! reading and writing the content of these two files. They don’t deal
+//! with any actual cryptography – creating a signing key from the files is
+//! left to the signing backends such as [`Key::from_bind`] for _ring._
+//!
+//! [`Key::from_bind`]: super::ring::Key::from_bind
+#![cfg(feature = "bytes")]
+#![cfg_attr(docsrs, doc(cfg(all(feature = "sign", feature = "bytes"))))]
+
+use crate::base::iana::SecAlg;
+use crate::base::name::{Dname, ToDname};
+use crate::rdata::Dnskey;
+use crate::utils::base64;
+use core::str::FromStr;
+use std::borrow::ToOwned;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::string::{String, ToString};
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ Module Configuration ------------------------------------------
+
+/// The fields of a private key file that contain binary data.
+const BINARY_FIELDS: &[&str] = &[
+    "Modulus",
+    "PublicExponent",
+    "PrivateExponent",
+    "Prime1",
+    "Prime2",
+    "Exponent1",
+    "Exponent2",
+    "Coefficient",
+    "PrivateKey",
+];
+
+/// The format version we write into private key files.
+const FORMAT_VERSION: &str = "v1.3";
+
+//------------ Functions -----------------------------------------------------
+
+/// Returns the base name of the key files for a key.
+///
+/// The name has the form `K<zone>+<alg>+<tag>` with the zone name given as
+/// an absolute name.
+pub fn base_name(
+    owner: &impl ToDname,
+    algorithm: SecAlg,
+    key_tag: u16,
+) -> String {
+    format!(
+        "K{}+{:03}+{:05}",
+        absolute_name(owner),
+        algorithm.to_int(),
+        key_tag
+    )
+}
+
+/// Reads a key file pair.
+///
+/// The `path` is the path of either of the two files or their common base
+/// name without an extension.
+pub fn load(
+    path: impl AsRef<Path>,
+) -> Result<(PublicKeyFile, PrivateKeyFile), io::Error> {
+    let path = path.as_ref();
+    let base = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("key") | Some("private") => path.with_extension(""),
+        _ => path.into(),
+    };
+    let public = read_to_string(&base, "key")?;
+    let private = read_to_string(&base, "private")?;
+    let public = PublicKeyFile::from_str(&public).map_err(invalid_data)?;
+    let private = PrivateKeyFile::from_str(&private).map_err(invalid_data)?;
+    if public.dnskey().algorithm() != private.algorithm() {
+        return Err(invalid_data(KeyFileError::Mismatch));
+    }
+    Ok((public, private))
+}
+
+/// Writes a key file pair into the directory `dir`.
+///
+/// The names of the files are derived from the public key via
+/// [`base_name`]. On Unix systems, the private key file is only readable
+/// by its owner.
+///
+/// Returns the path of the files without extension.
+pub fn save(
+    dir: impl AsRef<Path>,
+    public: &PublicKeyFile,
+    private: &PrivateKeyFile,
+) -> Result<PathBuf, io::Error> {
+    let base = dir.as_ref().join(base_name(
+        public.owner(),
+        public.dnskey().algorithm(),
+        public.dnskey().key_tag(),
+    ));
+    write!(create(&base, "key", false)?, "{}", public)?;
+    write!(create(&base, "private", true)?, "{}", private)?;
+    Ok(base)
+}
+
+fn read_to_string(base: &Path, ext: &str) -> Result<String, io::Error> {
+    let mut path = base.as_os_str().to_owned();
+    path.push(".");
+    path.push(ext);
+    fs::read_to_string(path)
+}
+
+fn create(base: &Path, ext: &str, private: bool) -> Result<File, io::Error> {
+    let mut path = base.as_os_str().to_owned();
+    path.push(".");
+    path.push(ext);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        if private {
+            options.mode(0o600);
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+    options.open(path)
+}
+
+/// Returns a name in master file format with the trailing dot.
+fn absolute_name(name: &impl ToDname) -> String {
+    let name = name.to_cow().to_string();
+    if name.is_empty() || name == "." {
+        ".".into()
+    } else {
+        name + "."
+    }
+}
+
+fn invalid_data(err: KeyFileError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+//------------ PublicKeyFile -------------------------------------------------
+
+/// The content of a `.key` file.
+///
+/// The file contains a single DNSKEY record in master file format and
+/// possibly some comments.
+#[derive(Clone, Debug)]
+pub struct PublicKeyFile {
+    /// The owner name of the DNSKEY record, i.e., the zone apex.
+    owner: Dname<Vec<u8>>,
+
+    /// The DNSKEY record data.
+    dnskey: Dnskey<Vec<u8>>,
+}
+
+impl PublicKeyFile {
+    /// Creates a new value from the owner name and DNSKEY record data.
+    pub fn new(owner: Dname<Vec<u8>>, dnskey: Dnskey<Vec<u8>>) -> Self {
+        PublicKeyFile { owner, dnskey }
+    }
+
+    /// Returns the owner name of the DNSKEY record.
+    pub fn owner(&self) -> &Dname<Vec<u8>> {
+        &self.owner
+    }
+
+    /// Returns the DNSKEY record data.
+    pub fn dnskey(&self) -> &Dnskey<Vec<u8>> {
+        &self.dnskey
+    }
+
+    /// Converts the value into its owner name and DNSKEY record data.
+    pub fn into_parts(self) -> (Dname<Vec<u8>>, Dnskey<Vec<u8>>) {
+        (self.owner, self.dnskey)
+    }
+}
+
+//--- FromStr
+
+impl FromStr for PublicKeyFile {
+    type Err = KeyFileError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        for line in s.lines() {
+            let line = match line.find(';') {
+                Some(pos) => &line[..pos],
+                None => line,
+            };
+            let mut tokens = line
+                .split_whitespace()
+                .filter(|token| *token != "(" && *token != ")");
+            let owner = match tokens.next() {
+                Some(owner) => owner,
+                None => continue,
+            };
+            let owner =
+                Dname::from_str(owner).map_err(|_| KeyFileError::Syntax)?;
+
+            // Skip over the optional TTL and class.
+            let mut tokens = tokens
+                .skip_while(|token| !token.eq_ignore_ascii_case("DNSKEY"));
+            if tokens.next().is_none() {
+                return Err(KeyFileError::Syntax);
+            }
+            let flags =
+                tokens.next().and_then(|flags| u16::from_str(flags).ok());
+            let protocol =
+                tokens.next().and_then(|proto| u8::from_str(proto).ok());
+            // SecAlg::from_str doesn’t resolve numbers to known
+            // algorithms, so try those first.
+            let algorithm =
+                tokens.next().and_then(|alg| match u8::from_str(alg) {
+                    Ok(alg) => Some(SecAlg::from_int(alg)),
+                    Err(_) => SecAlg::from_str(alg).ok(),
+                });
+            let (flags, protocol, algorithm) =
+                match (flags, protocol, algorithm) {
+                    (Some(flags), Some(protocol), Some(algorithm)) => {
+                        (flags, protocol, algorithm)
+                    }
+                    _ => return Err(KeyFileError::Syntax),
+                };
+            let key: String = tokens.collect();
+            let key =
+                base64::decode(&key).map_err(|_| KeyFileError::Syntax)?;
+            return Ok(PublicKeyFile::new(
+                owner,
+                Dnskey::new(flags, protocol, algorithm, key.to_vec()),
+            ));
+        }
+        Err(KeyFileError::Syntax)
+    }
+}
+
+//--- Display
+
+impl fmt::Display for PublicKeyFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = if self.dnskey.is_secure_entry_point() {
+            "key-signing"
+        } else {
+            "zone-signing"
+        };
+        writeln!(
+            f,
+            "; This is a {} key, keyid {}, for {}.",
+            kind,
+            self.dnskey.key_tag(),
+            self.owner
+        )?;
+        write!(
+            f,
+            "{} IN DNSKEY {} {} {} ",
+            absolute_name(&self.owner),
+            self.dnskey.flags(),
+            self.dnskey.protocol(),
+            self.dnskey.algorithm().to_int()
+        )?;
+        base64::display(self.dnskey.public_key(), f)?;
+        writeln!(f)
+    }
+}
+
+//------------ PrivateKeyFile ------------------------------------------------
+
+/// The content of a `.private` file.
+///
+/// The file consists of lines of the form `<name>: <value>`. The first line
+/// gives the format version and the second one the algorithm. The remaining
+/// fields depend on the algorithm. For ECDSA and Ed25519 keys, there is a
+/// single binary field `PrivateKey`, RSA keys are split into multiple
+/// fields. In addition, the file may contain timing metadata such as
+/// `Created` or `Activate` which is kept as text.
+#[derive(Clone)]
+pub struct PrivateKeyFile {
+    /// The algorithm of the key.
+    algorithm: SecAlg,
+
+    /// The binary fields in the order they appear in the file.
+    fields: Vec<(String, Vec<u8>)>,
+
+    /// All other fields.
+    metadata: Vec<(String, String)>,
+}
+
+impl PrivateKeyFile {
+    /// Creates a new, empty value for the given algorithm.
+    pub fn new(algorithm: SecAlg) -> Self {
+        PrivateKeyFile {
+            algorithm,
+            fields: Vec::new(),
+            metadata: Vec::new(),
+        }
+    }
+
+    /// Returns the algorithm of the key.
+    pub fn algorithm(&self) -> SecAlg {
+        self.algorithm
+    }
+
+    /// Returns the content of the binary field `name`.
+    pub fn get(&self, name: &str) -> Option<&[u8]> {
+        self.fields
+            .iter()
+            .find(|item| item.0 == name)
+            .map(|item| item.1.as_slice())
+    }
+
+    /// Returns the `PrivateKey` field used by ECDSA and Ed25519 keys.
+    pub fn private_key(&self) -> Option<&[u8]> {
+        self.get("PrivateKey")
+    }
+
+    /// Sets the binary field `name` to `value`.
+    pub fn set(&mut self, name: &str, value: Vec<u8>) {
+        match self.fields.iter_mut().find(|item| item.0 == name) {
+            Some(item) => item.1 = value,
+            None => self.fields.push((name.into(), value)),
+        }
+    }
+
+    /// Returns the metadata field `name`.
+    pub fn metadata(&self, name: &str) -> Option<&str> {
+        self.metadata
+            .iter()
+            .find(|item| item.0 == name)
+            .map(|item| item.1.as_str())
+    }
+
+    /// Sets the metadata field `name` to `value`.
+    pub fn set_metadata(&mut self, name: &str, value: String) {
+        match self.metadata.iter_mut().find(|item| item.0 == name) {
+            Some(item) => item.1 = value,
+            None => self.metadata.push((name.into(), value)),
+        }
+    }
+}
+
+//--- FromStr
+
+impl FromStr for PrivateKeyFile {
+    type Err = KeyFileError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| match line.find(':') {
+                Some(pos) => Ok((&line[..pos], line[pos + 1..].trim())),
+                None => Err(KeyFileError::Syntax),
+            });
+
+        match lines.next() {
+            Some(Ok(("Private-key-format", version))) => {
+                if !version.starts_with("v1.") {
+                    return Err(KeyFileError::UnsupportedFormat);
+                }
+            }
+            _ => return Err(KeyFileError::Syntax),
+        }
+        let mut res = match lines.next() {
+            Some(Ok(("Algorithm", value))) => {
+                let value = value.split_whitespace().next().unwrap_or("");
+                let algorithm =
+                    u8::from_str(value).map_err(|_| KeyFileError::Syntax)?;
+                PrivateKeyFile::new(SecAlg::from_int(algorithm))
+            }
+            _ => return Err(KeyFileError::Syntax),
+        };
+        for line in lines {
+            let (name, value) = line?;
+            if BINARY_FIELDS.contains(&name) {
+                let value = base64::decode(value)
+                    .map_err(|_| KeyFileError::Syntax)?;
+                res.set(name, value.to_vec());
+            } else {
+                res.set_metadata(name, value.into());
+            }
+        }
+        Ok(res)
+    }
+}
+
+//--- Display and Debug
+
+impl fmt::Display for PrivateKeyFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Private-key-format: {}", FORMAT_VERSION)?;
+        writeln!(
+            f,
+            "Algorithm: {} ({})",
+            self.algorithm.to_int(),
+            self.algorithm
+        )?;
+        for (name, value) in &self.fields {
+            write!(f, "{}: ", name)?;
+            base64::display(value, f)?;
+            writeln!(f)?;
+        }
+        for (name, value) in &self.metadata {
+            writeln!(f, "{}: {}", name, value)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for PrivateKeyFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Let’s not accidentally leak the private key into logs.
+        f.debug_struct("PrivateKeyFile")
+            .field("algorithm", &self.algorithm)
+            .field("metadata", &self.metadata)
+            .finish()
+    }
+}
+
+//============ Error Types ===================================================
+
+//------------ KeyFileError --------------------------------------------------
+
+/// A key file could not be used.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyFileError {
+    /// The content of a file was malformed.
+    Syntax,
+
+    /// The private key file has an unsupported format version.
+    UnsupportedFormat,
+
+    /// The algorithm of the key is not supported.
+    UnsupportedAlgorithm,
+
+    /// A required field is missing from the private key file.
+    MissingField(&'static str),
+
+    /// The public and private key don’t belong together.
+    Mismatch,
+
+    /// The key was rejected by the signing backend.
+    BadKey,
+}
+
+//--- Display and Error
+
+impl fmt::Display for KeyFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KeyFileError::Syntax => f.write_str("malformed key file"),
+            KeyFileError::UnsupportedFormat => {
+                f.write_str("unsupported private key format")
+            }
+            KeyFileError::UnsupportedAlgorithm => {
+                f.write_str("unsupported key algorithm")
+            }
+            KeyFileError::MissingField(name) => {
+                write!(f, "missing field '{}' in private key file", name)
+            }
+            KeyFileError::Mismatch => {
+                f.write_str("public and private key don’t match")
+            }
+            KeyFileError::BadKey => f.write_str("invalid key"),
+        }
+    }
+}
+
+impl error::Error for KeyFileError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // A key from the BIND test suite.
+    const PUBLIC: &str = "\
+; This is a zone-signing key, keyid 23136, for example.
+; Created: 20220325120000 (Fri Mar 25 12:00:00 2022)
+example. 3600 IN DNSKEY 256 3 13 \
+    Jlzq2UmhU1gUc3CbyhB8HJQfwtNsAq/p6zPHrFfWBS6yYp94Ktdt6Hg9 \
+    u5wr3G8bISdPHRiUTJ0PJ9HD7KtjOA==
+";
+
+    const PRIVATE: &str = "\
+Private-key-format: v1.3
+Algorithm: 13 (ECDSAP256SHA256)
+PrivateKey: GU6SnQ/Ou+xC5RumuIUIuJZteXT2z0O/ok1s38Et6mQ=
+Created: 20220325120000
+Publish: 20220325120000
+Activate: 20220325120000
+";
+
+    #[test]
+    fn parse_and_display() {
+        let public = PublicKeyFile::from_str(PUBLIC).unwrap();
+        assert_eq!(public.owner().to_string(), "example");
+        assert_eq!(public.dnskey().flags(), 256);
+        assert_eq!(public.dnskey().algorithm(), SecAlg::EcdsaP256Sha256);
+        assert_eq!(public.dnskey().public_key().len(), 64);
+        assert_eq!(
+            PublicKeyFile::from_str(&public.to_string())
+                .unwrap()
+                .dnskey(),
+            public.dnskey()
+        );
+
+        let private = PrivateKeyFile::from_str(PRIVATE).unwrap();
+        assert_eq!(private.algorithm(), SecAlg::EcdsaP256Sha256);
+        assert_eq!(private.private_key().unwrap().len(), 32);
+        assert_eq!(private.metadata("Created"), Some("20220325120000"));
+        assert_eq!(private.to_string(), PRIVATE);
+
+        assert_eq!(
+            base_name(
+                public.owner(),
+                public.dnskey().algorithm(),
+                public.dnskey().key_tag()
+            ),
+            format!("Kexample.+013+{:05}", public.dnskey().key_tag())
+        );
+    }
+
+    #[test]
+    fn bad_files() {
+        assert_eq!(
+            PrivateKeyFile::from_str("Private-key-format: v2.0\n").err(),
+            Some(KeyFileError::UnsupportedFormat)
+        );
+        assert_eq!(
+            PrivateKeyFile::from_str("Algorithm: 13\n").err(),
+            Some(KeyFileError::Syntax)
+        );
+        assert_eq!(
+            PublicKeyFile::from_str("; just a comment\n").err(),
+            Some(KeyFileError::Syntax)
+        );
+    }
+}
@@ -1,6 +1,8 @@
-use crate::base::iana::SecAlg;
+use crate::base::iana::{Rtype, SecAlg};
 use crate::base::name::ToDname;
 use crate::rdata::{Dnskey, Ds};
+use std::iter::FromIterator;
+use std::vec::Vec;
 
 pub trait SigningKey {
     type Octets: AsRef<[u8]>;
@@ -51,3 +53,115 @@ impl<'a, K: SigningKey> SigningKey for &'a K {
         (*self).sign(data)
     }
 }
+
+//------------ KeyRole -------------------------------------------------------
+
+/// The role of a key when signing a zone.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum KeyRole {
+    /// A key signing key.
+    ///
+    /// The key only signs the DNSKEY, CDS, and CDNSKEY RRsets at the apex.
+    Ksk,
+
+    /// A zone signing key.
+    ///
+    /// The key signs all RRsets except those signed by a key signing key.
+    Zsk,
+
+    /// A combined signing key.
+    ///
+    /// The key signs all RRsets.
+    Csk,
+}
+
+impl KeyRole {
+    /// Returns whether a key with this role signs an RRset.
+    ///
+    /// The RRset has the record type `rtype`. If `apex` is `true`, it is
+    /// at the apex of the zone.
+    pub fn signs(self, rtype: Rtype, apex: bool) -> bool {
+        let key_rrset = apex
+            && matches!(rtype, Rtype::Dnskey | Rtype::Cds | Rtype::Cdnskey);
+        match self {
+            KeyRole::Ksk => key_rrset,
+            KeyRole::Zsk => !key_rrset,
+            KeyRole::Csk => true,
+        }
+    }
+}
+
+//------------ KeySet --------------------------------------------------------
+
+/// A set of keys for signing a zone, each with its role.
+///
+/// There can be any number of keys for each role, e.g., while rolling over
+/// keys.
+#[derive(Clone, Debug)]
+pub struct KeySet<K> {
+    keys: Vec<(KeyRole, K)>,
+}
+
+impl<K> KeySet<K> {
+    /// Creates a new, empty key set.
+    pub fn new() -> Self {
+        KeySet { keys: Vec::new() }
+    }
+
+    /// Adds a key with the given role.
+    pub fn add(&mut self, role: KeyRole, key: K) {
+        self.keys.push((role, key))
+    }
+
+    /// Adds a key signing key.
+    pub fn add_ksk(&mut self, key: K) {
+        self.add(KeyRole::Ksk, key)
+    }
+
+    /// Adds a zone signing key.
+    pub fn add_zsk(&mut self, key: K) {
+        self.add(KeyRole::Zsk, key)
+    }
+
+    /// Adds a combined signing key.
+    pub fn add_csk(&mut self, key: K) {
+        self.add(KeyRole::Csk, key)
+    }
+
+    /// Returns whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Returns the number of keys in the set.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns an iterator over the keys and their roles.
+    pub fn iter(&self) -> impl Iterator<Item = (KeyRole, &K)> {
+        self.keys.iter().map(|(role, key)| (*role, key))
+    }
+
+    /// Returns an iterator over the keys with the given role.
+    pub fn keys(&self, role: KeyRole) -> impl Iterator<Item = &K> {
+        self.keys
+            .iter()
+            .filter(move |item| item.0 == role)
+            .map(|item| &item.1)
+    }
+}
+
+impl<K> Default for KeySet<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K> FromIterator<(KeyRole, K)> for KeySet<K> {
+    fn from_iter<T: IntoIterator<Item = (KeyRole, K)>>(iter: T) -> Self {
+        KeySet {
+            keys: iter.into_iter().collect(),
+        }
+    }
+}
@@ -1,25 +1,225 @@
 //! Key and Signer using OpenSSL.
+//!
+//! In addition to the algorithms supported by the [ring] backend, this
+//! module supports RSA/SHA-1 and Ed448 as well as RSA keys of all sizes
+//! OpenSSL accepts. DSA is not supported as RFC 8624 forbids signing with
+//! it.
+//!
+//! The module also provides [`PublicKey`] for checking signatures against a
+//! DNSKEY record with OpenSSL.
+//!
+//! [ring]: super::ring
 #![cfg(feature = "openssl")]
 #![cfg_attr(docsrs, doc(cfg(feature = "openssl")))]
 
-use std::vec::Vec;
-use openssl::error::ErrorStack;
-use openssl::hash::MessageDigest;
-use openssl::pkey::{PKey, Private};
-use openssl::sha::sha256;
-use openssl::sign::Signer as OpenSslSigner;
-use unwrap::unwrap;
-use crate::base::iana::DigestAlg;
+#[cfg(feature = "bytes")]
+use super::bind::{KeyFileError, PrivateKeyFile, PublicKeyFile};
+use super::key::SigningKey;
+use crate::base::iana::{DigestAlg, SecAlg};
+#[cfg(feature = "bytes")]
+use crate::base::name::Dname;
 use crate::base::name::ToDname;
 use crate::base::octets::Compose;
-use crate::rdata::{Ds, Dnskey};
-use super::key::SigningKey;
+use crate::rdata::{Dnskey, Ds};
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcGroup, EcKey, EcPoint, PointConversionForm};
+use openssl::ecdsa::EcdsaSig;
+use openssl::error::ErrorStack;
+use openssl::hash::{hash, MessageDigest};
+use openssl::nid::Nid;
+use openssl::pkey::{HasPublic, Id, PKey, PKeyRef, Private, Public};
+use openssl::rsa::Rsa;
+use openssl::sign::{Signer as OpenSslSigner, Verifier};
+use std::vec::Vec;
+use std::{error, fmt};
 
+//------------ Key -----------------------------------------------------------
 
+/// A signing key using OpenSSL.
 pub struct Key {
     dnskey: Dnskey<Vec<u8>>,
     key: PKey<Private>,
-    digest: MessageDigest,
+}
+
+impl Key {
+    /// Creates a key from an OpenSSL private key.
+    ///
+    /// The key will be used with the DNSSEC algorithm `algorithm` and its
+    /// DNSKEY record will have the given `flags`. The type of the key has to
+    /// fit the algorithm.
+    pub fn from_pkey(
+        flags: u16,
+        algorithm: SecAlg,
+        key: PKey<Private>,
+    ) -> Result<Self, KeyError> {
+        let public_key = dnskey_public_key(algorithm, &key)?;
+        Ok(Key {
+            dnskey: Dnskey::new(flags, 3, algorithm, public_key),
+            key,
+        })
+    }
+
+    /// Creates a key from a DER encoded private key in PKCS#8 format.
+    pub fn from_pkcs8(
+        flags: u16,
+        algorithm: SecAlg,
+        pkcs8: &[u8],
+    ) -> Result<Self, KeyError> {
+        Self::from_pkey(
+            flags,
+            algorithm,
+            PKey::private_key_from_pkcs8(pkcs8)?,
+        )
+    }
+
+    /// Creates a key from a PEM encoded private key.
+    pub fn from_pem(
+        flags: u16,
+        algorithm: SecAlg,
+        pem: &[u8],
+    ) -> Result<Self, KeyError> {
+        Self::from_pkey(flags, algorithm, PKey::private_key_from_pem(pem)?)
+    }
+
+    /// Generates a new key.
+    ///
+    /// RSA keys will have 2048 bits. Use [`generate_rsa`][Self::generate_rsa]
+    /// for other sizes.
+    pub fn generate(algorithm: SecAlg, flags: u16) -> Result<Self, KeyError> {
+        let key = match key_id(algorithm)? {
+            Id::RSA => return Self::generate_rsa(algorithm, 2048, flags),
+            Id::ED25519 => PKey::generate_ed25519()?,
+            Id::ED448 => PKey::generate_ed448()?,
+            _ => {
+                let group = ec_group(algorithm)?;
+                PKey::from_ec_key(EcKey::generate(&group)?)?
+            }
+        };
+        Self::from_pkey(flags, algorithm, key)
+    }
+
+    /// Generates a new RSA key with the given number of bits.
+    pub fn generate_rsa(
+        algorithm: SecAlg,
+        bits: u32,
+        flags: u16,
+    ) -> Result<Self, KeyError> {
+        if key_id(algorithm)? != Id::RSA {
+            return Err(KeyError::Mismatch);
+        }
+        let key = PKey::from_rsa(Rsa::generate(bits)?)?;
+        Self::from_pkey(flags, algorithm, key)
+    }
+
+    /// Creates a key from the content of a BIND key file pair.
+    ///
+    /// The function checks that the private and public key belong together.
+    #[cfg(feature = "bytes")]
+    pub fn from_bind(
+        public: &PublicKeyFile,
+        private: &PrivateKeyFile,
+    ) -> Result<Self, KeyFileError> {
+        let dnskey = public.dnskey();
+        let algorithm = dnskey.algorithm();
+        if algorithm != private.algorithm() {
+            return Err(KeyFileError::Mismatch);
+        }
+        let id = key_id(algorithm)
+            .map_err(|_| KeyFileError::UnsupportedAlgorithm)?;
+        let key = bind_pkey(id, dnskey, private)?;
+        let key = Self::from_pkey(dnskey.flags(), algorithm, key)
+            .map_err(|_| KeyFileError::BadKey)?;
+        if key.dnskey != *dnskey {
+            return Err(KeyFileError::BadKey);
+        }
+        Ok(key)
+    }
+
+    /// Returns the content of the BIND key file pair for the key.
+    ///
+    /// The `owner` is the apex of the zone the key is used for.
+    #[cfg(feature = "bytes")]
+    pub fn to_bind(
+        &self,
+        owner: Dname<Vec<u8>>,
+    ) -> Result<(PublicKeyFile, PrivateKeyFile), ErrorStack> {
+        let mut private = PrivateKeyFile::new(self.dnskey.algorithm());
+        match self.key.id() {
+            Id::RSA => {
+                let rsa = self.key.rsa()?;
+                let fields = [
+                    Some(rsa.n()),
+                    Some(rsa.e()),
+                    Some(rsa.d()),
+                    rsa.p(),
+                    rsa.q(),
+                    rsa.dmp1(),
+                    rsa.dmq1(),
+                    rsa.iqmp(),
+                ];
+                for (name, value) in RSA_FIELDS.iter().zip(fields.iter()) {
+                    if let Some(value) = value {
+                        private.set(name, value.to_vec());
+                    }
+                }
+            }
+            Id::EC => {
+                let ec = self.key.ec_key()?;
+                let len = ec_field_len(self.dnskey.algorithm());
+                private
+                    .set("PrivateKey", ec.private_key().to_vec_padded(len)?);
+            }
+            _ => private.set("PrivateKey", self.key.raw_private_key()?),
+        }
+        Ok((PublicKeyFile::new(owner, self.dnskey.clone()), private))
+    }
+
+    /// Returns the private key in PKCS#8 format.
+    pub fn to_pkcs8(&self) -> Result<Vec<u8>, ErrorStack> {
+        self.key.private_key_to_pkcs8()
+    }
+
+    /// Returns a reference to the underlying OpenSSL key.
+    pub fn pkey(&self) -> &PKeyRef<Private> {
+        &self.key
+    }
+
+    /// Returns the DS record for the key using the given digest algorithm.
+    ///
+    /// The `owner` is the apex of the zone the key is used for. The
+    /// [`SigningKey::ds`] method always uses SHA-256.
+    pub fn ds_with_digest<N: ToDname>(
+        &self,
+        owner: N,
+        digest_type: DigestAlg,
+    ) -> Result<Ds<Vec<u8>>, KeyError> {
+        let digest = match digest_type {
+            DigestAlg::Sha1 => MessageDigest::sha1(),
+            DigestAlg::Sha256 => MessageDigest::sha256(),
+            DigestAlg::Sha384 => MessageDigest::sha384(),
+            _ => return Err(KeyError::UnsupportedAlgorithm),
+        };
+        self.ds_digest(owner, digest_type, digest)
+            .map_err(Into::into)
+    }
+
+    /// Creates the DS record using an OpenSSL message digest.
+    fn ds_digest<N: ToDname>(
+        &self,
+        owner: N,
+        digest_type: DigestAlg,
+        digest: MessageDigest,
+    ) -> Result<Ds<Vec<u8>>, ErrorStack> {
+        let mut buf = Vec::new();
+        owner.compose_canonical(&mut buf).unwrap();
+        self.dnskey.compose_canonical(&mut buf).unwrap();
+        Ok(Ds::new(
+            self.dnskey.key_tag(),
+            self.dnskey.algorithm(),
+            digest_type,
+            hash(digest, &buf)?.to_vec(),
+        ))
+    }
 }
 
 impl SigningKey for Key {
@@ -33,26 +233,524 @@ impl SigningKey for Key {
 
     fn ds<N: ToDname>(
         &self,
-        owner: N
+        owner: N,
     ) -> Result<Ds<Self::Octets>, Self::Error> {
-        let mut buf = Vec::new();
-        unwrap!(owner.compose_canonical(&mut buf));
-        unwrap!(self.dnskey.compose_canonical(&mut buf));
-        let digest = Vec::from(sha256(&buf).as_ref());
-        Ok(Ds::new(
-            self.key_tag()?,
-            self.dnskey.algorithm(),
-            DigestAlg::Sha256,
-            digest,
-        ))
+        self.ds_digest(owner, DigestAlg::Sha256, MessageDigest::sha256())
     }
 
     fn sign(&self, data: &[u8]) -> Result<Self::Signature, Self::Error> {
-        let mut signer = OpenSslSigner::new(
-            self.digest, &self.key
-        )?;
-        signer.update(data)?;
-        signer.sign_to_vec()
+        let algorithm = self.dnskey.algorithm();
+        let mut signer = match message_digest(algorithm) {
+            Some(digest) => OpenSslSigner::new(digest, &self.key)?,
+            None => OpenSslSigner::new_without_digest(&self.key)?,
+        };
+        let signature = signer.sign_oneshot_to_vec(data)?;
+        if self.key.id() == Id::EC {
+            // OpenSSL produces DER encoded signatures but DNSSEC wants the
+            // plain concatenation of r and s. See RFC 6605, section 4.
+            let signature = EcdsaSig::from_der(&signature)?;
+            let len = ec_field_len(algorithm);
+            let mut res = signature.r().to_vec_padded(len)?;
+            res.extend_from_slice(&signature.s().to_vec_padded(len)?);
+            Ok(res)
+        } else {
+            Ok(signature)
+        }
     }
 }
 
+//------------ PublicKey -----------------------------------------------------
+
+/// A public key for checking signatures using OpenSSL.
+pub struct PublicKey {
+    algorithm: SecAlg,
+    key: PKey<Public>,
+}
+
+impl PublicKey {
+    /// Creates the public key of a DNSKEY record.
+    pub fn from_dnskey<Octets: AsRef<[u8]>>(
+        dnskey: &Dnskey<Octets>,
+    ) -> Result<Self, KeyError> {
+        let algorithm = dnskey.algorithm();
+        let public_key = dnskey.public_key().as_ref();
+        let key = match key_id(algorithm)? {
+            Id::RSA => {
+                let (exponent, modulus) =
+                    rsa_components(public_key).ok_or(KeyError::BadKey)?;
+                PKey::from_rsa(Rsa::from_public_components(
+                    BigNum::from_slice(modulus)?,
+                    BigNum::from_slice(exponent)?,
+                )?)?
+            }
+            Id::EC => {
+                let group = ec_group(algorithm)?;
+                let mut point = Vec::with_capacity(public_key.len() + 1);
+                point.push(4);
+                point.extend_from_slice(public_key);
+                let mut ctx = BigNumContext::new()?;
+                let point = EcPoint::from_bytes(&group, &point, &mut ctx)
+                    .map_err(|_| KeyError::BadKey)?;
+                PKey::from_ec_key(EcKey::from_public_key(&group, &point)?)?
+            }
+            id => PKey::public_key_from_raw_bytes(public_key, id)
+                .map_err(|_| KeyError::BadKey)?,
+        };
+        Ok(PublicKey { algorithm, key })
+    }
+
+    /// Checks a signature over the given data.
+    ///
+    /// Returns `Ok(true)` if the signature is valid and `Ok(false)` if it
+    /// isn’t.
+    pub fn verify(
+        &self,
+        data: &[u8],
+        signature: &[u8],
+    ) -> Result<bool, ErrorStack> {
+        let mut verifier = match message_digest(self.algorithm) {
+            Some(digest) => Verifier::new(digest, &self.key)?,
+            None => Verifier::new_without_digest(&self.key)?,
+        };
+        if self.key.id() == Id::EC {
+            let len = ec_field_len(self.algorithm) as usize;
+            if signature.len() != 2 * len {
+                return Ok(false);
+            }
+            let signature = EcdsaSig::from_private_components(
+                BigNum::from_slice(&signature[..len])?,
+                BigNum::from_slice(&signature[len..])?,
+            )?;
+            verifier.verify_oneshot(&signature.to_der()?, data)
+        } else {
+            verifier.verify_oneshot(signature, data)
+        }
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// The names of the RSA key fields in BIND private key files.
+#[cfg(feature = "bytes")]
+const RSA_FIELDS: &[&str] = &[
+    "Modulus",
+    "PublicExponent",
+    "PrivateExponent",
+    "Prime1",
+    "Prime2",
+    "Exponent1",
+    "Exponent2",
+    "Coefficient",
+];
+
+/// Returns the OpenSSL key type for a DNSSEC algorithm.
+fn key_id(algorithm: SecAlg) -> Result<Id, KeyError> {
+    match algorithm {
+        SecAlg::RsaSha1
+        | SecAlg::RsaSha1Nsec3Sha1
+        | SecAlg::RsaSha256
+        | SecAlg::RsaSha512 => Ok(Id::RSA),
+        SecAlg::EcdsaP256Sha256 | SecAlg::EcdsaP384Sha384 => Ok(Id::EC),
+        SecAlg::Ed25519 => Ok(Id::ED25519),
+        SecAlg::Ed448 => Ok(Id::ED448),
+        _ => Err(KeyError::UnsupportedAlgorithm),
+    }
+}
+
+/// Returns the message digest for a DNSSEC algorithm.
+///
+/// Returns `None` for the EdDSA algorithms which hash internally.
+fn message_digest(algorithm: SecAlg) -> Option<MessageDigest> {
+    match algorithm {
+        SecAlg::RsaSha1 | SecAlg::RsaSha1Nsec3Sha1 => {
+            Some(MessageDigest::sha1())
+        }
+        SecAlg::RsaSha256 | SecAlg::EcdsaP256Sha256 => {
+            Some(MessageDigest::sha256())
+        }
+        SecAlg::EcdsaP384Sha384 => Some(MessageDigest::sha384()),
+        SecAlg::RsaSha512 => Some(MessageDigest::sha512()),
+        _ => None,
+    }
+}
+
+/// Returns the curve for an ECDSA algorithm.
+fn ec_group(algorithm: SecAlg) -> Result<EcGroup, KeyError> {
+    let nid = match algorithm {
+        SecAlg::EcdsaP256Sha256 => Nid::X9_62_PRIME256V1,
+        SecAlg::EcdsaP384Sha384 => Nid::SECP384R1,
+        _ => return Err(KeyError::UnsupportedAlgorithm),
+    };
+    Ok(EcGroup::from_curve_name(nid)?)
+}
+
+/// Returns the length in octets of integers for an ECDSA algorithm.
+fn ec_field_len(algorithm: SecAlg) -> i32 {
+    if algorithm == SecAlg::EcdsaP384Sha384 {
+        48
+    } else {
+        32
+    }
+}
+
+/// Returns the public key of a key as used in the DNSKEY record.
+fn dnskey_public_key<T: HasPublic>(
+    algorithm: SecAlg,
+    key: &PKeyRef<T>,
+) -> Result<Vec<u8>, KeyError> {
+    let id = key_id(algorithm)?;
+    if key.id() != id {
+        return Err(KeyError::Mismatch);
+    }
+    match id {
+        Id::RSA => {
+            let rsa = key.rsa()?;
+            let exponent = rsa.e().to_vec();
+            let modulus = rsa.n().to_vec();
+            let mut res =
+                Vec::with_capacity(exponent.len() + modulus.len() + 3);
+            if exponent.len() < 256 {
+                res.push(exponent.len() as u8);
+            } else {
+                res.push(0);
+                res.extend_from_slice(&(exponent.len() as u16).to_be_bytes());
+            }
+            res.extend_from_slice(&exponent);
+            res.extend_from_slice(&modulus);
+            Ok(res)
+        }
+        Id::EC => {
+            let ec = key.ec_key()?;
+            if ec.group().curve_name() != ec_group(algorithm)?.curve_name() {
+                return Err(KeyError::Mismatch);
+            }
+            let mut ctx = BigNumContext::new()?;
+            let mut res = ec.public_key().to_bytes(
+                ec.group(),
+                PointConversionForm::UNCOMPRESSED,
+                &mut ctx,
+            )?;
+            // DNSKEY records don’t include the leading 0x04 octet.
+            res.remove(0);
+            Ok(res)
+        }
+        _ => Ok(key.raw_public_key()?),
+    }
+}
+
+/// Splits an RSA public key from a DNSKEY into exponent and modulus.
+///
+/// The format is defined in section 2 of RFC 3110.
+fn rsa_components(key: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (len, key) = match *key.first()? {
+        0 => {
+            let len = u16::from_be_bytes([*key.get(1)?, *key.get(2)?]);
+            (usize::from(len), &key[3..])
+        }
+        len => (usize::from(len), &key[1..]),
+    };
+    if key.len() <= len {
+        return None;
+    }
+    Some(key.split_at(len))
+}
+
+/// Creates an OpenSSL private key from a BIND key file pair.
+///
+/// The public key is taken from the DNSKEY record where necessary. The
+/// consistency of the key is checked.
+#[cfg(feature = "bytes")]
+fn bind_pkey(
+    id: Id,
+    dnskey: &Dnskey<Vec<u8>>,
+    private: &PrivateKeyFile,
+) -> Result<PKey<Private>, KeyFileError> {
+    let field = |name| {
+        private
+            .get(name)
+            .ok_or(KeyFileError::MissingField(name))
+            .and_then(|value| {
+                BigNum::from_slice(value).map_err(|_| KeyFileError::BadKey)
+            })
+    };
+    let res = match id {
+        Id::RSA => {
+            let rsa = Rsa::from_private_components(
+                field(RSA_FIELDS[0])?,
+                field(RSA_FIELDS[1])?,
+                field(RSA_FIELDS[2])?,
+                field(RSA_FIELDS[3])?,
+                field(RSA_FIELDS[4])?,
+                field(RSA_FIELDS[5])?,
+                field(RSA_FIELDS[6])?,
+                field(RSA_FIELDS[7])?,
+            )
+            .map_err(|_| KeyFileError::BadKey)?;
+            if !rsa.check_key().unwrap_or(false) {
+                return Err(KeyFileError::BadKey);
+            }
+            PKey::from_rsa(rsa)
+        }
+        Id::EC => {
+            let group = ec_group(dnskey.algorithm())
+                .map_err(|_| KeyFileError::BadKey)?;
+            let private_key = field("PrivateKey")?;
+            ec_private_key(&group, &private_key, dnskey.public_key())
+                .and_then(PKey::from_ec_key)
+        }
+        id => {
+            let private_key = private
+                .private_key()
+                .ok_or(KeyFileError::MissingField("PrivateKey"))?;
+            PKey::private_key_from_raw_bytes(private_key, id)
+        }
+    };
+    res.map_err(|_| KeyFileError::BadKey)
+}
+
+/// Creates an EC key from the private key and the DNSKEY public key.
+#[cfg(feature = "bytes")]
+fn ec_private_key(
+    group: &EcGroup,
+    private_key: &BigNum,
+    public_key: &[u8],
+) -> Result<EcKey<Private>, ErrorStack> {
+    let mut point = vec![4];
+    point.extend_from_slice(public_key);
+    let mut ctx = BigNumContext::new()?;
+    let public_key = EcPoint::from_bytes(group, &point, &mut ctx)?;
+    let key =
+        EcKey::from_private_components(group, private_key, &public_key)?;
+    key.check_key()?;
+    Ok(key)
+}
+
+//------------ KeyError ------------------------------------------------------
+
+/// An error happened while creating a key.
+#[derive(Clone, Debug)]
+pub enum KeyError {
+    /// The algorithm is not supported.
+    UnsupportedAlgorithm,
+
+    /// The key doesn’t fit the algorithm.
+    Mismatch,
+
+    /// The public key is malformed.
+    BadKey,
+
+    /// OpenSSL reported an error.
+    Openssl(ErrorStack),
+}
+
+impl From<ErrorStack> for KeyError {
+    fn from(err: ErrorStack) -> Self {
+        KeyError::Openssl(err)
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KeyError::UnsupportedAlgorithm => {
+                f.write_str("unsupported algorithm")
+            }
+            KeyError::Mismatch => {
+                f.write_str("key doesn’t match the algorithm")
+            }
+            KeyError::BadKey => f.write_str("malformed public key"),
+            KeyError::Openssl(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for KeyError {}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sign_and_verify(key: &Key) {
+        let sig = key.sign(b"message").unwrap();
+        let public = PublicKey::from_dnskey(&key.dnskey).unwrap();
+        assert!(public.verify(b"message", &sig).unwrap());
+        assert!(!public.verify(b"massage", &sig).unwrap());
+    }
+
+    #[test]
+    fn algorithms() {
+        for &algorithm in &[
+            SecAlg::RsaSha1,
+            SecAlg::RsaSha1Nsec3Sha1,
+            SecAlg::RsaSha256,
+            SecAlg::RsaSha512,
+            SecAlg::EcdsaP256Sha256,
+            SecAlg::EcdsaP384Sha384,
+            SecAlg::Ed25519,
+            SecAlg::Ed448,
+        ] {
+            let key = Key::generate(algorithm, 256).unwrap();
+            assert_eq!(key.algorithm().unwrap(), algorithm);
+            sign_and_verify(&key);
+        }
+        assert!(matches!(
+            Key::generate(SecAlg::Dsa, 256),
+            Err(KeyError::UnsupportedAlgorithm)
+        ));
+    }
+
+    #[test]
+    fn rsa_sizes() {
+        let key = Key::generate_rsa(SecAlg::RsaSha256, 1024, 256).unwrap();
+        assert_eq!(key.sign(b"message").unwrap().len(), 128);
+        sign_and_verify(&key);
+    }
+
+    #[test]
+    fn ds_digests() {
+        let owner = crate::base::name::Dname::root_vec();
+        let key = Key::generate(SecAlg::EcdsaP256Sha256, 257).unwrap();
+        for &(digest_type, len) in &[
+            (DigestAlg::Sha1, 20),
+            (DigestAlg::Sha256, 32),
+            (DigestAlg::Sha384, 48),
+        ] {
+            let ds = key.ds_with_digest(&owner, digest_type).unwrap();
+            assert_eq!(ds.digest_type(), digest_type);
+            assert_eq!(ds.digest().len(), len);
+        }
+        assert_eq!(
+            key.ds(&owner).unwrap(),
+            key.ds_with_digest(&owner, DigestAlg::Sha256).unwrap()
+        );
+        assert!(matches!(
+            key.ds_with_digest(&owner, DigestAlg::Gost),
+            Err(KeyError::UnsupportedAlgorithm)
+        ));
+    }
+
+    #[cfg(feature = "validate")]
+    #[test]
+    fn validate_interop() {
+        use crate::base::iana::{Class, Rtype};
+        use crate::base::record::Record;
+        use crate::base::serial::Serial;
+        use crate::rdata::{Rrsig, A};
+        use crate::validate::{supports_algorithm, RrsigExt};
+        use std::net::Ipv4Addr;
+
+        // Ed448 and small RSA keys aren’t supported by ring.
+        assert!(supports_algorithm(SecAlg::Ed448));
+        let keys = [
+            Key::generate(SecAlg::Ed448, 256).unwrap(),
+            Key::generate_rsa(SecAlg::RsaSha256, 512, 256).unwrap(),
+        ];
+        for key in &keys {
+            let owner = crate::base::name::Dname::root_vec();
+            let rrsig = |signature| {
+                Rrsig::new(
+                    Rtype::A,
+                    key.algorithm().unwrap(),
+                    0,
+                    3600,
+                    Serial(2),
+                    Serial(1),
+                    key.key_tag().unwrap(),
+                    owner.clone(),
+                    signature,
+                )
+            };
+            let mut records = [Record::new(
+                owner.clone(),
+                Class::In,
+                3600,
+                A::new(Ipv4Addr::new(192, 0, 2, 1)),
+            )];
+            let mut data = Vec::new();
+            rrsig(Vec::new())
+                .signed_data(&mut data, &mut records)
+                .unwrap();
+            let rrsig = rrsig(key.sign(&data).unwrap());
+            assert!(rrsig.verify_signed_data(&key.dnskey, &data).is_ok());
+            data.push(0);
+            assert!(rrsig.verify_signed_data(&key.dnskey, &data).is_err());
+        }
+    }
+
+    #[test]
+    fn mismatch() {
+        let key = Key::generate(SecAlg::EcdsaP384Sha384, 256).unwrap();
+        let pkcs8 = key.to_pkcs8().unwrap();
+        assert!(Key::from_pkcs8(256, SecAlg::EcdsaP384Sha384, &pkcs8).is_ok());
+        assert!(matches!(
+            Key::from_pkcs8(256, SecAlg::EcdsaP256Sha256, &pkcs8),
+            Err(KeyError::Mismatch)
+        ));
+        assert!(matches!(
+            Key::from_pkcs8(256, SecAlg::Ed25519, &pkcs8),
+            Err(KeyError::Mismatch)
+        ));
+    }
+
+    #[cfg(feature = "bytes")]
+    #[test]
+    fn bind_roundtrip() {
+        use core::str::FromStr;
+        use std::string::ToString;
+
+        for &algorithm in &[
+            SecAlg::RsaSha256,
+            SecAlg::EcdsaP256Sha256,
+            SecAlg::EcdsaP384Sha384,
+            SecAlg::Ed25519,
+            SecAlg::Ed448,
+        ] {
+            let key = Key::generate(algorithm, 257).unwrap();
+            let owner = Dname::from_str("example.com").unwrap();
+            let (public, private) = key.to_bind(owner).unwrap();
+            let public =
+                PublicKeyFile::from_str(&public.to_string()).unwrap();
+            let private =
+                PrivateKeyFile::from_str(&private.to_string()).unwrap();
+            let loaded = Key::from_bind(&public, &private).unwrap();
+            assert_eq!(loaded.dnskey, key.dnskey);
+            sign_and_verify(&loaded);
+        }
+
+        let owner: Dname<Vec<u8>> = Dname::from_str("example.com").unwrap();
+        let one = Key::generate(SecAlg::EcdsaP256Sha256, 256).unwrap();
+        let two = Key::generate(SecAlg::EcdsaP256Sha256, 256).unwrap();
+        let (public, _) = one.to_bind(owner.clone()).unwrap();
+        let (_, private) = two.to_bind(owner).unwrap();
+        assert_eq!(
+            Key::from_bind(&public, &private).err(),
+            Some(KeyFileError::BadKey)
+        );
+    }
+
+    #[cfg(feature = "ring")]
+    #[test]
+    fn ring_interop() {
+        use ring::signature::{
+            UnparsedPublicKey, ECDSA_P256_SHA256_FIXED,
+            ECDSA_P384_SHA384_FIXED,
+        };
+
+        for &(algorithm, verify) in &[
+            (SecAlg::EcdsaP256Sha256, &ECDSA_P256_SHA256_FIXED),
+            (SecAlg::EcdsaP384Sha384, &ECDSA_P384_SHA384_FIXED),
+        ] {
+            let key = Key::generate(algorithm, 256).unwrap();
+            let sig = key.sign(b"message").unwrap();
+            let mut public_key = vec![4];
+            public_key.extend_from_slice(key.dnskey.public_key());
+            UnparsedPublicKey::new(verify, &public_key)
+                .verify(b"message", &sig)
+                .unwrap();
+        }
+    }
+}
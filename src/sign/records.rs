@@ -1,15 +1,29 @@
 //! Actual signing.
 
-use super::key::SigningKey;
+use super::key::{KeyRole, KeySet, SigningKey};
+use crate::base::charstr::CharStr;
 use crate::base::cmp::CanonicalOrd;
-use crate::base::iana::{Class, Rtype};
+use crate::base::iana::{Class, DigestAlg, Nsec3HashAlg, Rtype, SecAlg};
+use crate::base::name::Dname;
 use crate::base::name::ToDname;
+#[cfg(feature = "ring")]
+use crate::base::octets::OctetsBuilder;
 use crate::base::octets::{Compose, EmptyBuilder, FromBuilder};
 use crate::base::rdata::RecordData;
 use crate::base::record::Record;
 use crate::base::serial::Serial;
+#[cfg(feature = "ring")]
+use crate::rdata::rfc4034::dnskey_digest;
 use crate::rdata::rfc4034::{ProtoRrsig, RtypeBitmap};
-use crate::rdata::{Dnskey, Ds, Nsec, Rrsig};
+#[cfg(feature = "ring")]
+use crate::rdata::rfc5155::{nsec3_hash, Nsec3Hash, Nsec3HashError};
+use crate::rdata::{
+    AllRecordData, Cdnskey, Cds, Dnskey, Ds, MasterRecordData, Nsec, Nsec3,
+    Nsec3param, Rrsig,
+};
+use core::cmp::Ordering;
+use rand::Rng;
+use std::collections::HashSet;
 use std::iter::FromIterator;
 use std::vec::Vec;
 use std::{fmt, io, slice};
@@ -20,16 +34,46 @@ use std::{fmt, io, slice};
 #[derive(Clone)]
 pub struct SortedRecords<N, D> {
     records: Vec<Record<N, D>>,
+
+    /// The RRsets changed via `insert` since the last re-signing.
+    changed: HashSet<(Dname<Vec<u8>>, Class, Rtype)>,
 }
 
 impl<N, D> SortedRecords<N, D> {
     pub fn new() -> Self {
         SortedRecords {
             records: Vec::new(),
+            changed: HashSet::new(),
         }
     }
 
+    /// Inserts a record.
+    ///
+    /// Unless the record is an RRSIG record, its RRset is marked as changed
+    /// so that [`resign`][Self::resign] replaces the RRset’s signatures.
+    /// Records added via `extend` or `collect` are not marked, which makes
+    /// those the way to load an already signed zone.
     pub fn insert(&mut self, record: Record<N, D>) -> Result<(), Record<N, D>>
+    where
+        N: ToDname,
+        D: RecordData + CanonicalOrd,
+    {
+        let rtype = record.rtype();
+        if rtype != Rtype::Rrsig {
+            self.changed.insert((
+                record.owner().to_vec(),
+                record.class(),
+                rtype,
+            ));
+        }
+        self.insert_unchanged(record)
+    }
+
+    /// Inserts a record without marking its RRset as changed.
+    fn insert_unchanged(
+        &mut self,
+        record: Record<N, D>,
+    ) -> Result<(), Record<N, D>>
     where
         N: ToDname,
         D: RecordData + CanonicalOrd,
@@ -46,6 +90,15 @@ impl<N, D> SortedRecords<N, D> {
         }
     }
 
+    /// Returns whether an RRset has been changed via `insert`.
+    fn is_changed(&self, owner: &N, class: Class, rtype: Rtype) -> bool
+    where
+        N: ToDname,
+    {
+        !self.changed.is_empty()
+            && self.changed.contains(&(owner.to_vec(), class, rtype))
+    }
+
     pub fn families(&self) -> RecordsIter<N, D> {
         RecordsIter::new(&self.records)
     }
@@ -67,6 +120,70 @@ impl<N, D> SortedRecords<N, D> {
         None
     }
 
+    /// Removes an RRset and all RRSIG records covering it.
+    ///
+    /// Returns the removed records of the RRset without the RRSIG records.
+    /// Use this method before inserting the new content of a changed RRset
+    /// so that [`resign`][Self::resign] knows to sign it again.
+    pub fn remove_rrset<NN: ToDname>(
+        &mut self,
+        owner: &NN,
+        class: Class,
+        rtype: Rtype,
+    ) -> Vec<Record<N, D>>
+    where
+        N: ToDname,
+        D: RecordData + MaybeRrsig,
+    {
+        // Find the start of the family. The closure never returns `Equal`,
+        // so we always get the position of the first record.
+        let start = self
+            .records
+            .binary_search_by(|record| {
+                match record.class().cmp(&class) {
+                    Ordering::Equal => {}
+                    res => return res,
+                }
+                match record.owner().name_cmp(owner) {
+                    Ordering::Equal => Ordering::Greater,
+                    res => res,
+                }
+            })
+            .unwrap_or_else(|idx| idx);
+
+        let mut res = Vec::new();
+        let mut idx = start;
+        while let Some(record) = self.records.get(idx) {
+            if record.class() != class || !record.owner().name_eq(owner) {
+                break;
+            }
+            if record.rtype() == rtype {
+                res.push(self.records.remove(idx));
+            } else if record
+                .data()
+                .as_rrsig()
+                .map(|sig| sig.type_covered() == rtype)
+                .unwrap_or(false)
+            {
+                self.records.remove(idx);
+            } else {
+                idx += 1;
+            }
+        }
+        res
+    }
+
+    /// Retains only the records for which `op` returns `true`.
+    pub fn retain<F>(&mut self, op: F)
+    where
+        F: FnMut(&Record<N, D>) -> bool,
+    {
+        self.records.retain(op)
+    }
+
+    /// Signs all RRsets of the zone with a key.
+    ///
+    /// Returns the RRSIG records.
     #[allow(clippy::type_complexity)]
     pub fn sign<Octets, Key, ApexName>(
         &self,
@@ -83,6 +200,69 @@ impl<N, D> SortedRecords<N, D> {
         ApexName: ToDname + Clone,
     {
         let mut res = Vec::new();
+        self.sign_role(
+            apex,
+            expiration,
+            inception,
+            key,
+            KeyRole::Csk,
+            &mut res,
+        )?;
+        Ok(res)
+    }
+
+    /// Signs the zone with a set of keys.
+    ///
+    /// Each key only signs the RRsets its role calls for. Key signing keys
+    /// sign the DNSKEY, CDS, and CDNSKEY RRsets at the apex, zone signing
+    /// keys sign everything else, and combined signing keys sign all
+    /// RRsets.
+    ///
+    /// Since section 2.2 of RFC 4035 requires each RRset to be signed with
+    /// every algorithm present in the DNSKEY RRset, both roles should be
+    /// filled with keys of the same algorithms.
+    ///
+    /// Returns the RRSIG records.
+    #[allow(clippy::type_complexity)]
+    pub fn sign_with_keys<Octets, Key, ApexName>(
+        &self,
+        apex: &FamilyName<ApexName>,
+        expiration: Serial,
+        inception: Serial,
+        keys: &KeySet<Key>,
+    ) -> Result<Vec<Record<N, Rrsig<Octets, ApexName>>>, Key::Error>
+    where
+        N: ToDname + Clone,
+        D: RecordData,
+        Key: SigningKey,
+        Octets: From<Key::Signature>,
+        ApexName: ToDname + Clone,
+    {
+        let mut res = Vec::new();
+        for (role, key) in keys.iter() {
+            self.sign_role(apex, expiration, inception, key, role, &mut res)?;
+        }
+        Ok(res)
+    }
+
+    /// Signs the RRsets a key with the given role is responsible for.
+    #[allow(clippy::type_complexity)]
+    fn sign_role<Octets, Key, ApexName>(
+        &self,
+        apex: &FamilyName<ApexName>,
+        expiration: Serial,
+        inception: Serial,
+        key: Key,
+        role: KeyRole,
+        res: &mut Vec<Record<N, Rrsig<Octets, ApexName>>>,
+    ) -> Result<(), Key::Error>
+    where
+        N: ToDname + Clone,
+        D: RecordData,
+        Key: SigningKey,
+        Octets: From<Key::Signature>,
+        ApexName: ToDname + Clone,
+    {
         let mut buf = Vec::new();
 
         // The owner name of a zone cut if we currently are at or below one.
@@ -120,6 +300,8 @@ impl<N, D> SortedRecords<N, D> {
                 None
             };
 
+            let at_apex = name.eq(apex);
+
             for rrset in family.rrsets() {
                 if cut.is_some() {
                     // If we are at a zone cut, we only sign DS and NSEC
@@ -137,6 +319,11 @@ impl<N, D> SortedRecords<N, D> {
                     }
                 }
 
+                // Leave RRsets to keys with a different role.
+                if !role.signs(rrset.rtype(), at_apex) {
+                    continue;
+                }
+
                 // Create the signature.
                 buf.clear();
                 let rrsig = ProtoRrsig::new(
@@ -163,9 +350,301 @@ impl<N, D> SortedRecords<N, D> {
                 ));
             }
         }
+        Ok(())
+    }
+
+    /// Incrementally re-signs the zone with a key.
+    ///
+    /// The method expects a zone that has been signed with `key` before,
+    /// e.g., via [`sign`][Self::sign]. Existing RRSIG records created by
+    /// the key are kept as long as they cover an RRset that still needs
+    /// signing and don’t expire within the refresh window of `config`.
+    /// All other RRSIG records of the key are removed. RRsets without a
+    /// usable signature are signed and the new RRSIG records added.
+    /// RRSIG records created by other keys are left alone.
+    ///
+    /// Changes to an RRset are not detected by looking at its signature.
+    /// Instead, RRsets that received records via [`insert`][Self::insert]
+    /// since the last re-signing are signed again. A changed RRset should
+    /// also first be removed via [`remove_rrset`][Self::remove_rrset],
+    /// which also removes its signatures, before adding its new content.
+    /// Signatures of changed RRsets by keys not used here are left in
+    /// place, so all keys of the zone should be used at once via
+    /// [`resign_with_keys`][Self::resign_with_keys].
+    ///
+    /// The expiration time of new signatures is reduced by a random
+    /// amount of up to `config.jitter` seconds so that signatures created
+    /// together don’t all expire at the same time.
+    ///
+    /// The method fails with [`ResignError::InvalidConfig`] if the validity
+    /// period or the refresh window of `config` exceed 2^31 - 1 seconds.
+    pub fn resign<Octets, Key, ApexName>(
+        &mut self,
+        apex: &FamilyName<ApexName>,
+        config: &ResignConfig,
+        key: Key,
+    ) -> Result<ResignStats, ResignError<Key::Error>>
+    where
+        N: ToDname + Clone,
+        D: RecordData
+            + CanonicalOrd
+            + MaybeRrsig
+            + From<Rrsig<Octets, ApexName>>,
+        Key: SigningKey,
+        Octets: From<Key::Signature>,
+        ApexName: ToDname + Clone,
+    {
+        let mut stale = Vec::new();
+        let mut rrsigs = Vec::new();
+        let stats = self.resign_role(
+            apex,
+            config,
+            key,
+            KeyRole::Csk,
+            &mut stale,
+            &mut rrsigs,
+        )?;
+        self.finish_resign(stale, rrsigs);
+        Ok(stats)
+    }
+
+    /// Incrementally re-signs the zone with a set of keys.
+    ///
+    /// This works like [`resign`][Self::resign] for each key in the set
+    /// except that each key only signs the RRsets its role calls for as
+    /// described for [`sign_with_keys`][Self::sign_with_keys]. Signatures
+    /// of a key for RRsets not covered by its role are removed.
+    pub fn resign_with_keys<Octets, Key, ApexName>(
+        &mut self,
+        apex: &FamilyName<ApexName>,
+        config: &ResignConfig,
+        keys: &KeySet<Key>,
+    ) -> Result<ResignStats, ResignError<Key::Error>>
+    where
+        N: ToDname + Clone,
+        D: RecordData
+            + CanonicalOrd
+            + MaybeRrsig
+            + From<Rrsig<Octets, ApexName>>,
+        Key: SigningKey,
+        Octets: From<Key::Signature>,
+        ApexName: ToDname + Clone,
+    {
+        let mut res = ResignStats::default();
+        let mut stale = Vec::new();
+        let mut rrsigs = Vec::new();
+        for (role, key) in keys.iter() {
+            let stats = self.resign_role(
+                apex,
+                config,
+                key,
+                role,
+                &mut stale,
+                &mut rrsigs,
+            )?;
+            res.kept += stats.kept;
+            res.signed += stats.signed;
+            res.removed += stats.removed;
+        }
+        self.finish_resign(stale, rrsigs);
         Ok(res)
     }
 
+    /// Re-signs the RRsets a key with the given role is responsible for.
+    ///
+    /// Adds the indexes of the key’s RRSIG records to remove to `stale` and
+    /// the new RRSIG records to `rrsigs`.
+    #[allow(clippy::type_complexity)]
+    fn resign_role<Octets, Key, ApexName>(
+        &self,
+        apex: &FamilyName<ApexName>,
+        config: &ResignConfig,
+        key: Key,
+        role: KeyRole,
+        stale: &mut Vec<usize>,
+        rrsigs: &mut Vec<Record<N, Rrsig<Octets, ApexName>>>,
+    ) -> Result<ResignStats, ResignError<Key::Error>>
+    where
+        N: ToDname + Clone,
+        D: RecordData
+            + CanonicalOrd
+            + MaybeRrsig
+            + From<Rrsig<Octets, ApexName>>,
+        Key: SigningKey,
+        Octets: From<Key::Signature>,
+        ApexName: ToDname + Clone,
+    {
+        if !config.is_valid() {
+            return Err(ResignError::InvalidConfig);
+        }
+        let algorithm = key.algorithm().map_err(ResignError::Signing)?;
+        let key_tag = key.key_tag().map_err(ResignError::Signing)?;
+        let mut rng = rand::thread_rng();
+        let mut stats = ResignStats::default();
+        let mut buf = Vec::new();
+
+        // The owner name of a zone cut if we currently are at or below one.
+        let mut cut: Option<FamilyName<N>> = None;
+
+        let mut families = self.families();
+        families.skip_before(apex);
+
+        // The index of the first record of the next family.
+        let mut next = self.records.len() - families.slice.len();
+
+        for family in families {
+            let start = next;
+            next += family.slice.len();
+
+            if !family.is_in_zone(apex) {
+                break;
+            }
+
+            // Our signatures in this family with their record index.
+            let mut own: Vec<_> = family
+                .records()
+                .enumerate()
+                .filter_map(|(idx, record)| {
+                    record
+                        .data()
+                        .as_rrsig()
+                        .filter(|sig| {
+                            sig.algorithm() == algorithm
+                                && sig.key_tag() == key_tag
+                                && sig.signer_name().name_eq(apex.owner())
+                        })
+                        .map(|sig| (start + idx, sig, false))
+                })
+                .collect();
+
+            // Below a zone cut, none of our signatures are needed.
+            let occluded = match cut {
+                Some(ref cut) => family.owner().ends_with(cut.owner()),
+                None => false,
+            };
+            if occluded {
+                stats.removed += own.len();
+                stale.extend(own.iter().map(|item| item.0));
+                continue;
+            }
+
+            let name = family.family_name().cloned();
+            cut = if family.is_zone_cut(apex) {
+                Some(name.clone())
+            } else {
+                None
+            };
+            let labels = name.owner().rrsig_label_count();
+            let at_apex = name.eq(apex);
+
+            for rrset in family.rrsets() {
+                // Same rules as in `sign`.
+                if cut.is_some() {
+                    if rrset.rtype() != Rtype::Ds
+                        && rrset.rtype() != Rtype::Nsec
+                    {
+                        continue;
+                    }
+                } else if rrset.rtype() == Rtype::Rrsig {
+                    continue;
+                }
+                if !role.signs(rrset.rtype(), at_apex) {
+                    continue;
+                }
+
+                // Signatures of a changed RRset are never usable.
+                let changed = self.is_changed(
+                    name.owner(),
+                    name.class(),
+                    rrset.rtype(),
+                );
+                let usable = own.iter_mut().find(|(_, sig, keep)| {
+                    !changed
+                        && !keep
+                        && sig.type_covered() == rrset.rtype()
+                        && sig.original_ttl() == rrset.ttl()
+                        && sig.labels() == labels
+                        && config.is_fresh(sig)
+                });
+                if let Some(item) = usable {
+                    item.2 = true;
+                    stats.kept += 1;
+                    continue;
+                }
+
+                let jitter = if config.jitter > 0 {
+                    rng.gen_range(0..=config.jitter)
+                } else {
+                    0
+                };
+                buf.clear();
+                let rrsig = ProtoRrsig::new(
+                    rrset.rtype(),
+                    algorithm,
+                    labels,
+                    rrset.ttl(),
+                    config.now.add(config.validity.saturating_sub(jitter)),
+                    config.inception,
+                    key_tag,
+                    apex.owner().clone(),
+                );
+                rrsig.compose_canonical(&mut buf).unwrap();
+                for record in rrset.iter() {
+                    record.compose_canonical(&mut buf).unwrap();
+                }
+                let signature =
+                    key.sign(&buf).map_err(ResignError::Signing)?;
+                rrsigs.push(Record::new(
+                    name.owner().clone(),
+                    name.class(),
+                    rrset.ttl(),
+                    rrsig.into_rrsig(signature.into()),
+                ));
+                stats.signed += 1;
+            }
+
+            let len = stale.len();
+            stale
+                .extend(own.iter().filter(|item| !item.2).map(|item| item.0));
+            stats.removed += stale.len() - len;
+        }
+        Ok(stats)
+    }
+
+    /// Applies the result of re-signing with one or more keys.
+    ///
+    /// Removes the records at the indexes in `stale`, adds the new RRSIG
+    /// records, and forgets which RRsets have changed.
+    fn finish_resign<Octets, ApexName>(
+        &mut self,
+        mut stale: Vec<usize>,
+        rrsigs: Vec<Record<N, Rrsig<Octets, ApexName>>>,
+    ) where
+        N: ToDname,
+        D: RecordData + CanonicalOrd + From<Rrsig<Octets, ApexName>>,
+    {
+        if !stale.is_empty() {
+            stale.sort_unstable();
+            stale.dedup();
+            let mut stale = stale.into_iter().peekable();
+            let mut idx = 0;
+            self.records.retain(|_| {
+                let keep = stale.peek() != Some(&idx);
+                if !keep {
+                    stale.next();
+                }
+                idx += 1;
+                keep
+            });
+        }
+        if !rrsigs.is_empty() {
+            self.records
+                .extend(rrsigs.into_iter().map(Record::from_record));
+            self.records.sort_by(|a, b| a.canonical_cmp(b));
+        }
+        self.changed.clear();
+    }
+
     pub fn nsecs<Octets, ApexName>(
         &self,
         apex: &FamilyName<ApexName>,
@@ -230,8 +709,10 @@ impl<N, D> SortedRecords<N, D> {
             }
 
             let mut bitmap = RtypeBitmap::<Octets>::builder();
-            // Assume there’s gonna be an RRSIG.
+            // Assume there’s gonna be an RRSIG. The NSEC record itself
+            // isn’t in the family yet.
             bitmap.add(Rtype::Rrsig).unwrap();
+            bitmap.add(Rtype::Nsec).unwrap();
             for rrset in family.rrsets() {
                 bitmap.add(rrset.rtype()).unwrap()
             }
@@ -246,6 +727,152 @@ impl<N, D> SortedRecords<N, D> {
         res
     }
 
+    /// Generates the NSEC3 chain for the zone starting at `apex`.
+    ///
+    /// The records of the chain are created using the hash algorithm,
+    /// iteration count, and salt given in `config`. Their type bitmaps
+    /// reflect the types present at the original owner names. Empty
+    /// non-terminals receive records with empty bitmaps. If opt-out is
+    /// enabled, delegations without DS records are left out of the chain.
+    ///
+    /// Because the record for the apex lists the NSEC3PARAM type, the
+    /// returned value also contains the NSEC3PARAM record that needs to be
+    /// added to the apex. All records receive the given `ttl` which should
+    /// be the minimum field of the zone’s SOA record.
+    #[cfg(feature = "ring")]
+    pub fn nsec3s<Octets, ApexName>(
+        &self,
+        apex: &FamilyName<ApexName>,
+        ttl: u32,
+        config: &Nsec3Config<Octets>,
+    ) -> Result<Nsec3Records<ApexName, Octets>, Nsec3HashError>
+    where
+        N: ToDname + Clone,
+        D: RecordData,
+        Octets: FromBuilder + Clone,
+        Octets::Builder: EmptyBuilder,
+        ApexName: ToDname + Clone,
+    {
+        // The hashes of the names we have processed and the data for the
+        // NSEC3 record for each of them.
+        let mut seen = HashSet::new();
+        let mut hashes: Vec<(Nsec3Hash, RtypeBitmap<Octets>)> = Vec::new();
+
+        // The owner name of a zone cut if we currently are at or below one.
+        let mut cut: Option<FamilyName<N>> = None;
+
+        let mut families = self.families();
+
+        // Since the records are ordered, the first family is the apex --
+        // we can skip everything before that.
+        families.skip_before(apex);
+
+        for family in families {
+            // If the owner is out of zone, we have moved out of our zone and
+            // are done.
+            if !family.is_in_zone(apex) {
+                break;
+            }
+
+            // If the family is below a zone cut, we must ignore it.
+            if let Some(ref cut) = cut {
+                if family.owner().ends_with(cut.owner()) {
+                    continue;
+                }
+            }
+
+            // If the family only contains NSEC3 records from an earlier
+            // run, we ignore it, too.
+            if family.records().all(|record| {
+                record.rtype() == Rtype::Nsec3
+                    || record.rtype() == Rtype::Rrsig
+            }) {
+                continue;
+            }
+
+            let name = family.family_name().cloned();
+            let is_apex = family.family_name() == *apex;
+            cut = if family.is_zone_cut(apex) {
+                Some(name)
+            } else {
+                None
+            };
+            let has_ds = family.records().any(|r| r.rtype() == Rtype::Ds);
+
+            // Unsigned delegations are left out with opt-out.
+            if cut.is_some() && config.opt_out && !has_ds {
+                continue;
+            }
+
+            let mut bitmap = RtypeBitmap::<Octets>::builder();
+            if cut.is_none() || has_ds {
+                bitmap.add(Rtype::Rrsig).unwrap();
+            }
+            if is_apex {
+                bitmap.add(Rtype::Nsec3param).unwrap();
+            }
+            for rrset in family.rrsets() {
+                if rrset.rtype() != Rtype::Rrsig
+                    && rrset.rtype() != Rtype::Nsec3
+                {
+                    bitmap.add(rrset.rtype()).unwrap()
+                }
+            }
+            let hash = config.hash(family.owner())?;
+            seen.insert(hash.clone());
+            hashes.push((hash, bitmap.finalize()));
+
+            // Add empty non-terminals between the apex and the name. We can
+            // stop at the first ancestor we have already seen since the
+            // records are ordered and everything above it has been taken
+            // care of already.
+            let owner: Dname<Vec<u8>> = family
+                .owner()
+                .to_dname()
+                .map_err(|_| Nsec3HashError::LongName)?;
+            for ancestor in owner.iter_suffixes().skip(1) {
+                if !ancestor.ends_with(apex.owner())
+                    || ancestor.name_eq(apex.owner())
+                {
+                    break;
+                }
+                let hash = config.hash(&ancestor)?;
+                if !seen.insert(hash.clone()) {
+                    break;
+                }
+                hashes.push((
+                    hash,
+                    RtypeBitmap::<Octets>::builder().finalize(),
+                ));
+            }
+        }
+
+        hashes.sort_by(|left, right| left.0.cmp(&right.0));
+        let mut nsec3s = Vec::with_capacity(hashes.len());
+        for (i, (hash, bitmap)) in hashes.iter().enumerate() {
+            let next = &hashes[(i + 1) % hashes.len()].0;
+            nsec3s.push(Record::new(
+                hash.to_owner_name(apex.owner())?,
+                apex.class(),
+                ttl,
+                Nsec3::new(
+                    config.algorithm,
+                    config.flags(),
+                    config.iterations,
+                    config.salt.clone(),
+                    CharStr::from_octets(octets_from_slice(next.as_slice())?)
+                        .map_err(|_| Nsec3HashError::LongName)?,
+                    bitmap.clone(),
+                ),
+            ));
+        }
+
+        Ok(Nsec3Records {
+            nsec3s,
+            nsec3param: apex.clone().into_record(ttl, config.nsec3param()),
+        })
+    }
+
     pub fn write<W>(&self, target: &mut W) -> Result<(), io::Error>
     where
         N: fmt::Display,
@@ -272,7 +899,10 @@ where
 {
     fn from(mut src: Vec<Record<N, D>>) -> Self {
         src.sort_by(CanonicalOrd::canonical_cmp);
-        SortedRecords { records: src }
+        SortedRecords {
+            records: src,
+            changed: HashSet::new(),
+        }
     }
 }
 
@@ -281,12 +911,18 @@ where
     N: ToDname,
     D: RecordData + CanonicalOrd,
 {
+    /// Collects the records, dropping duplicates.
+    ///
+    /// The records are sorted once at the end rather than inserted one by
+    /// one, which makes this the fastest way to load a large zone.
     fn from_iter<T: IntoIterator<Item = Record<N, D>>>(iter: T) -> Self {
-        let mut res = Self::new();
-        for item in iter {
-            let _ = res.insert(item);
+        let mut records: Vec<_> = iter.into_iter().collect();
+        records.sort_by(CanonicalOrd::canonical_cmp);
+        records.dedup_by(|a, b| a.canonical_cmp(b) == Ordering::Equal);
+        SortedRecords {
+            records,
+            changed: HashSet::new(),
         }
-        res
     }
 }
 
@@ -297,11 +933,282 @@ where
 {
     fn extend<T: IntoIterator<Item = Record<N, D>>>(&mut self, iter: T) {
         for item in iter {
-            let _ = self.insert(item);
+            let _ = self.insert_unchanged(item);
+        }
+    }
+}
+
+//------------ ResignConfig --------------------------------------------------
+
+/// Configuration for incrementally re-signing a zone.
+///
+/// See [`SortedRecords::resign`] for how these values are used.
+#[derive(Clone, Copy, Debug)]
+pub struct ResignConfig {
+    /// The current time.
+    pub now: Serial,
+
+    /// The inception time of new signatures.
+    pub inception: Serial,
+
+    /// The validity period of new signatures in seconds.
+    ///
+    /// The expiration time of new signatures is this many seconds after
+    /// `now`, minus jitter. Because of serial number arithmetic, the value
+    /// must not exceed 2^31 - 1.
+    pub validity: u32,
+
+    /// The refresh window in seconds.
+    ///
+    /// Signatures expiring within this many seconds after `now` are
+    /// replaced. The value must not exceed 2^31 - 1.
+    pub refresh: u32,
+
+    /// The maximum jitter in seconds.
+    ///
+    /// The expiration time of new signatures is reduced by a random value
+    /// up to this many seconds.
+    pub jitter: u32,
+}
+
+impl ResignConfig {
+    /// Creates a new configuration for the current time.
+    ///
+    /// The inception time of new signatures is set to an hour ago to allow
+    /// for clock skew. The jitter is set to one tenth of the refresh
+    /// window.
+    pub fn new(validity: u32, refresh: u32) -> Self {
+        let now = Serial::now();
+        ResignConfig {
+            now,
+            inception: Serial(now.into_int().wrapping_sub(3600)),
+            validity,
+            refresh,
+            jitter: refresh / 10,
+        }
+    }
+
+    /// Returns whether the time spans can be added to serial numbers.
+    fn is_valid(&self) -> bool {
+        const MAX_DELTA: u32 = 0x7FFF_FFFF;
+        self.validity <= MAX_DELTA && self.refresh <= MAX_DELTA
+    }
+
+    /// Returns whether an existing signature can be kept.
+    fn is_fresh<O, N>(&self, rrsig: &Rrsig<O, N>) -> bool {
+        rrsig.inception() <= self.now
+            && self.now.add(self.refresh) < rrsig.expiration()
+    }
+}
+
+//------------ ResignError ---------------------------------------------------
+
+/// Incrementally re-signing a zone failed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ResignError<E> {
+    /// The validity period or refresh window of the configuration is too
+    /// large.
+    InvalidConfig,
+
+    /// A key failed to create a signature.
+    Signing(E),
+}
+
+//--- Display and Error
+
+impl<E: fmt::Display> fmt::Display for ResignError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ResignError::InvalidConfig => {
+                f.write_str("validity period or refresh window too large")
+            }
+            ResignError::Signing(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> std::error::Error for ResignError<E> {}
+
+//------------ ResignStats ---------------------------------------------------
+
+/// What happened during incremental re-signing.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ResignStats {
+    /// The number of signatures that were kept.
+    pub kept: usize,
+
+    /// The number of new signatures.
+    pub signed: usize,
+
+    /// The number of signatures that were removed.
+    pub removed: usize,
+}
+
+//------------ MaybeRrsig ----------------------------------------------------
+
+/// Record data that may be an RRSIG record.
+///
+/// This is used by [`SortedRecords`] to find existing signatures.
+pub trait MaybeRrsig {
+    /// The octets type of the RRSIG record data.
+    type Octets;
+
+    /// The domain name type of the RRSIG record data.
+    type Name: ToDname;
+
+    /// Returns the RRSIG record data if the data is one.
+    fn as_rrsig(&self) -> Option<&Rrsig<Self::Octets, Self::Name>>;
+}
+
+impl<O, N: ToDname> MaybeRrsig for Rrsig<O, N> {
+    type Octets = O;
+    type Name = N;
+
+    fn as_rrsig(&self) -> Option<&Rrsig<O, N>> {
+        Some(self)
+    }
+}
+
+impl<O, N: ToDname> MaybeRrsig for MasterRecordData<O, N> {
+    type Octets = O;
+    type Name = N;
+
+    fn as_rrsig(&self) -> Option<&Rrsig<O, N>> {
+        match *self {
+            MasterRecordData::Rrsig(ref rrsig) => Some(rrsig),
+            _ => None,
         }
     }
 }
 
+impl<O, N: ToDname> MaybeRrsig for AllRecordData<O, N> {
+    type Octets = O;
+    type Name = N;
+
+    fn as_rrsig(&self) -> Option<&Rrsig<O, N>> {
+        match *self {
+            AllRecordData::Rrsig(ref rrsig) => Some(rrsig),
+            _ => None,
+        }
+    }
+}
+
+//------------ Nsec3Config ---------------------------------------------------
+
+/// The parameters for generating an NSEC3 chain.
+///
+/// The default value uses SHA-1 without additional iterations, an empty
+/// salt, and no opt-out as recommended by [RFC 9276].
+///
+/// [RFC 9276]: https://tools.ietf.org/html/rfc9276
+#[derive(Clone)]
+pub struct Nsec3Config<Octets> {
+    /// The hash algorithm.
+    pub algorithm: Nsec3HashAlg,
+
+    /// The number of additional hash iterations.
+    pub iterations: u16,
+
+    /// The salt appended to the name before hashing.
+    pub salt: CharStr<Octets>,
+
+    /// Whether to leave unsigned delegations out of the chain.
+    pub opt_out: bool,
+}
+
+impl<Octets> Nsec3Config<Octets> {
+    /// Creates a new config using SHA-1 and the given parameters.
+    pub fn new(
+        iterations: u16,
+        salt: CharStr<Octets>,
+        opt_out: bool,
+    ) -> Self {
+        Nsec3Config {
+            algorithm: Nsec3HashAlg::Sha1,
+            iterations,
+            salt,
+            opt_out,
+        }
+    }
+
+    /// Returns the flags field for the NSEC3 records.
+    pub fn flags(&self) -> u8 {
+        if self.opt_out {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// Returns the NSEC3PARAM record data for the config.
+    ///
+    /// The flags field of the record is always zero. See RFC 5155, section
+    /// 4.1.2.
+    pub fn nsec3param(&self) -> Nsec3param<Octets>
+    where
+        Octets: Clone,
+    {
+        Nsec3param::new(self.algorithm, 0, self.iterations, self.salt.clone())
+    }
+
+    /// Returns the hash of `name`.
+    #[cfg(feature = "ring")]
+    fn hash(&self, name: &impl ToDname) -> Result<Nsec3Hash, Nsec3HashError>
+    where
+        Octets: AsRef<[u8]>,
+    {
+        nsec3_hash(name, self.algorithm, self.iterations, self.salt.as_ref())
+    }
+}
+
+impl<Octets: From<&'static [u8]>> Default for Nsec3Config<Octets> {
+    fn default() -> Self {
+        Self::new(0, CharStr::empty(), false)
+    }
+}
+
+//------------ Nsec3Records --------------------------------------------------
+
+/// The records of an NSEC3 chain.
+#[derive(Clone)]
+pub struct Nsec3Records<N, Octets> {
+    /// The NSEC3 records in the order of their hashed owner names.
+    pub nsec3s: Vec<Record<Dname<Octets>, Nsec3<Octets>>>,
+
+    /// The NSEC3PARAM record for the apex.
+    pub nsec3param: Record<N, Nsec3param<Octets>>,
+}
+
+/// Creates an octets value from a slice.
+#[cfg(feature = "ring")]
+fn octets_from_slice<Octets>(slice: &[u8]) -> Result<Octets, Nsec3HashError>
+where
+    Octets: FromBuilder,
+    Octets::Builder: EmptyBuilder,
+{
+    let mut builder = Octets::Builder::empty();
+    builder
+        .append_slice(slice)
+        .map_err(|_| Nsec3HashError::LongName)?;
+    Ok(builder.freeze())
+}
+
+//------------ UnsupportedDigest ---------------------------------------------
+
+/// The digest algorithm for a DS or CDS record is not supported.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct UnsupportedDigest;
+
+//--- Display and Error
+
+impl fmt::Display for UnsupportedDigest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("unsupported digest algorithm")
+    }
+}
+
+impl std::error::Error for UnsupportedDigest {}
+
 //------------ Family --------------------------------------------------------
 
 /// A set of records with the same owner name and class.
@@ -362,7 +1269,8 @@ pub struct FamilyName<N> {
 }
 
 impl<N> FamilyName<N> {
-    fn new(owner: N, class: Class) -> Self {
+    /// Creates a new family name from an owner name and class.
+    pub fn new(owner: N, class: Class) -> Self {
         FamilyName { owner, class }
     }
 
@@ -404,6 +1312,158 @@ impl<N> FamilyName<N> {
         key.ds(&self.owner)
             .map(|ds| self.clone().into_record(ttl, ds))
     }
+
+    /// Creates a DS record for a DNSKEY using the given digest algorithm.
+    #[cfg(feature = "ring")]
+    pub fn ds_from_dnskey<Octets: AsRef<[u8]>>(
+        &self,
+        ttl: u32,
+        dnskey: &Dnskey<Octets>,
+        digest_type: DigestAlg,
+    ) -> Result<Record<N, Ds<Vec<u8>>>, UnsupportedDigest>
+    where
+        N: ToDname + Clone,
+    {
+        let digest = dnskey_digest(&self.owner, dnskey, digest_type)
+            .ok_or(UnsupportedDigest)?
+            .as_ref()
+            .into();
+        Ok(self.clone().into_record(
+            ttl,
+            Ds::new(
+                dnskey.key_tag(),
+                dnskey.algorithm(),
+                digest_type,
+                digest,
+            ),
+        ))
+    }
+
+    /// Creates a CDNSKEY record for a key.
+    pub fn cdnskey<K: SigningKey, Octets: From<K::Octets>>(
+        &self,
+        ttl: u32,
+        key: K,
+    ) -> Result<Record<N, Cdnskey<Octets>>, K::Error>
+    where
+        N: Clone,
+    {
+        key.dnskey()
+            .map(|dnskey| self.cdnskey_from_dnskey(ttl, dnskey.convert()))
+    }
+
+    /// Creates a CDNSKEY record from a DNSKEY record’s data.
+    pub fn cdnskey_from_dnskey<Octets>(
+        &self,
+        ttl: u32,
+        dnskey: Dnskey<Octets>,
+    ) -> Record<N, Cdnskey<Octets>>
+    where
+        N: Clone,
+    {
+        self.clone().into_record(
+            ttl,
+            Cdnskey::new(
+                dnskey.flags(),
+                dnskey.protocol(),
+                dnskey.algorithm(),
+                dnskey.into_public_key(),
+            ),
+        )
+    }
+
+    /// Creates a CDS record for a key.
+    ///
+    /// The digest algorithm is the one used by the key’s
+    /// [`ds`][SigningKey::ds] method.
+    pub fn cds<K: SigningKey, Octets: From<K::Octets>>(
+        &self,
+        ttl: u32,
+        key: K,
+    ) -> Result<Record<N, Cds<Octets>>, K::Error>
+    where
+        N: ToDname + Clone,
+    {
+        key.ds(&self.owner).map(|ds| {
+            self.clone().into_record(
+                ttl,
+                Cds::new(
+                    ds.key_tag(),
+                    ds.algorithm(),
+                    ds.digest_type(),
+                    ds.into_digest().into(),
+                ),
+            )
+        })
+    }
+
+    /// Creates a CDS record for a DNSKEY using the given digest algorithm.
+    #[cfg(feature = "ring")]
+    pub fn cds_from_dnskey<Octets: AsRef<[u8]>>(
+        &self,
+        ttl: u32,
+        dnskey: &Dnskey<Octets>,
+        digest_type: DigestAlg,
+    ) -> Result<Record<N, Cds<Vec<u8>>>, UnsupportedDigest>
+    where
+        N: ToDname + Clone,
+    {
+        let digest = dnskey_digest(&self.owner, dnskey, digest_type)
+            .ok_or(UnsupportedDigest)?
+            .as_ref()
+            .into();
+        Ok(self.clone().into_record(
+            ttl,
+            Cds::new(
+                dnskey.key_tag(),
+                dnskey.algorithm(),
+                digest_type,
+                digest,
+            ),
+        ))
+    }
+
+    /// Creates a CDS record asking the parent to remove all DS records.
+    ///
+    /// This is the record `0 0 0 00` defined in [RFC 8078, Section 4].
+    /// Together with the record from [`cdnskey_delete`][Self::cdnskey_delete],
+    /// it signals that the zone is to become insecure.
+    ///
+    /// [RFC 8078, Section 4]: https://tools.ietf.org/html/rfc8078#section-4
+    pub fn cds_delete<Octets>(&self, ttl: u32) -> Record<N, Cds<Octets>>
+    where
+        N: Clone,
+        Octets: From<&'static [u8]>,
+    {
+        self.clone().into_record(
+            ttl,
+            Cds::new(
+                0,
+                SecAlg::DeleteDs,
+                DigestAlg::from_int(0),
+                b"\0".as_ref().into(),
+            ),
+        )
+    }
+
+    /// Creates a CDNSKEY record asking the parent to remove all DS records.
+    ///
+    /// This is the record `0 3 0 AA==` defined in [RFC 8078, Section 4].
+    ///
+    /// [RFC 8078, Section 4]: https://tools.ietf.org/html/rfc8078#section-4
+    pub fn cdnskey_delete<Octets>(
+        &self,
+        ttl: u32,
+    ) -> Record<N, Cdnskey<Octets>>
+    where
+        N: Clone,
+        Octets: From<&'static [u8]>,
+    {
+        self.clone().into_record(
+            ttl,
+            Cdnskey::new(0, 3, SecAlg::DeleteDs, b"\0".as_ref().into()),
+        )
+    }
 }
 
 impl<'a, N: Clone> FamilyName<&'a N> {
@@ -605,3 +1665,419 @@ where
         Some(Rrset::new(res))
     }
 }
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::SecAlg;
+    use crate::base::rdata::UnknownRecordData;
+    use crate::rdata::{Ns, A};
+    use core::str::FromStr;
+    use std::string::{String, ToString};
+
+    type TestRecord = Record<Dname<Vec<u8>>, UnknownRecordData<Vec<u8>>>;
+
+    fn record(owner: &str, rtype: Rtype) -> TestRecord {
+        Record::new(
+            Dname::from_str(owner).unwrap(),
+            Class::In,
+            3600,
+            UnknownRecordData::from_octets(rtype, Vec::new()),
+        )
+    }
+
+    #[cfg(feature = "ring")]
+    fn owners(
+        records: &Nsec3Records<Dname<Vec<u8>>, Vec<u8>>,
+    ) -> Vec<String> {
+        records
+            .nsec3s
+            .iter()
+            .map(|record| format!("{}", record.owner()))
+            .collect()
+    }
+
+    #[cfg(feature = "ring")]
+    #[test]
+    fn nsec3_chain() {
+        // A subset of the zone from RFC 5155, Appendix A.
+        let records: SortedRecords<_, _> = vec![
+            record("example", Rtype::Soa),
+            record("example", Rtype::Ns),
+            record("a.example", Rtype::Ns),
+            record("ns1.a.example", Rtype::A),
+            record("ai.example", Rtype::A),
+            record("ns1.example", Rtype::A),
+            record("x.y.w.example", Rtype::Mx),
+        ]
+        .into();
+        let apex =
+            FamilyName::new(Dname::from_str("example").unwrap(), Class::In);
+        let config = Nsec3Config::new(
+            12,
+            CharStr::from_octets(vec![0xaa, 0xbb, 0xcc, 0xdd]).unwrap(),
+            false,
+        );
+
+        let chain = records.nsec3s(&apex, 3600, &config).unwrap();
+        assert_eq!(
+            owners(&chain),
+            [
+                "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom.example",
+                "2t7b4g4vsa5smi47k61mv5bv1a22bojr.example",
+                "2vptu5timamqttgl4luu9kg21e0aor3s.example",
+                "35mthgpgcu1qg68fab165klnsnk3dpvl.example",
+                "gjeqe526plbf1g8mklp59enfd789njgi.example",
+                "ji6neoaepv8b5o6k4ev33abha8ht9fgc.example",
+                "k8udemvp1j2f7eg6jebps17vp3n8i58h.example",
+            ]
+        );
+        let apex_nsec3 = chain.nsec3s[0].data();
+        assert!(apex_nsec3.types().contains(Rtype::Nsec3param));
+        assert!(apex_nsec3.types().contains(Rtype::Soa));
+        assert!(!apex_nsec3.types().contains(Rtype::Nsec3));
+        let mut next = String::new();
+        crate::utils::base32::display_hex(
+            apex_nsec3.next_owner().as_slice(),
+            &mut next,
+        )
+        .unwrap();
+        assert_eq!(next, "2T7B4G4VSA5SMI47K61MV5BV1A22BOJR");
+        let delegation = chain.nsec3s[3].data();
+        assert!(delegation.types().contains(Rtype::Ns));
+        assert!(!delegation.types().contains(Rtype::Rrsig));
+        let empty = chain.nsec3s[6].data();
+        assert_eq!(empty.types().iter().count(), 0);
+        assert_eq!(chain.nsec3param.data().flags(), 0);
+
+        let config = Nsec3Config {
+            opt_out: true,
+            ..config
+        };
+        let chain = records.nsec3s(&apex, 3600, &config).unwrap();
+        assert_eq!(chain.nsec3s.len(), 6);
+        assert!(chain.nsec3s.iter().all(|record| record.data().opt_out()));
+    }
+
+    //--- Incremental signing
+
+    type ZoneData = MasterRecordData<Vec<u8>, Dname<Vec<u8>>>;
+
+    /// A key that produces dummy signatures.
+    struct TestKey(u8);
+
+    impl SigningKey for TestKey {
+        type Octets = Vec<u8>;
+        type Signature = Vec<u8>;
+        type Error = ();
+
+        fn dnskey(&self) -> Result<Dnskey<Vec<u8>>, ()> {
+            Ok(Dnskey::new(256, 3, SecAlg::EcdsaP256Sha256, vec![self.0]))
+        }
+
+        fn ds<N: ToDname>(&self, _owner: N) -> Result<Ds<Vec<u8>>, ()> {
+            Err(())
+        }
+
+        fn sign(&self, _data: &[u8]) -> Result<Vec<u8>, ()> {
+            Ok(vec![0; 64])
+        }
+    }
+
+    fn zone_record(
+        owner: &str,
+        data: ZoneData,
+    ) -> Record<Dname<Vec<u8>>, ZoneData> {
+        Record::new(Dname::from_str(owner).unwrap(), Class::In, 3600, data)
+    }
+
+    fn a(owner: &str, addr: &str) -> Record<Dname<Vec<u8>>, ZoneData> {
+        zone_record(owner, A::new(addr.parse().unwrap()).into())
+    }
+
+    fn ns(owner: &str, target: &str) -> Record<Dname<Vec<u8>>, ZoneData> {
+        let target: Dname<Vec<u8>> = Dname::from_str(target).unwrap();
+        zone_record(owner, Ns::new(target).into())
+    }
+
+    fn rrsigs(
+        records: &SortedRecords<Dname<Vec<u8>>, ZoneData>,
+    ) -> Vec<(String, Rtype, Serial)> {
+        records
+            .records
+            .iter()
+            .filter_map(|record| {
+                record.data().as_rrsig().map(|sig| {
+                    (
+                        record.owner().to_string(),
+                        sig.type_covered(),
+                        sig.expiration(),
+                    )
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn resign() {
+        let mut records: SortedRecords<_, _> = vec![
+            a("example", "192.0.2.1"),
+            a("www.example", "192.0.2.2"),
+            ns("sub.example", "ns.sub.example"),
+            a("ns.sub.example", "192.0.2.3"),
+        ]
+        .into();
+        let apex =
+            FamilyName::new(Dname::from_str("example").unwrap(), Class::In);
+        let mut config = ResignConfig::new(86400, 3600);
+        config.jitter = 0;
+
+        // Time spans that can’t be added to a serial are rejected.
+        let mut invalid = config;
+        invalid.refresh = u32::MAX;
+        assert_eq!(
+            records.resign(&apex, &invalid, TestKey(1)),
+            Err(ResignError::InvalidConfig)
+        );
+        let mut invalid = config;
+        invalid.validity = 0x8000_0000;
+        assert_eq!(
+            records.resign(&apex, &invalid, TestKey(1)),
+            Err(ResignError::InvalidConfig)
+        );
+
+        // Initial signing only signs the authoritative RRsets.
+        let stats = records.resign(&apex, &config, TestKey(1)).unwrap();
+        assert_eq!(
+            stats,
+            ResignStats {
+                kept: 0,
+                signed: 2,
+                removed: 0
+            }
+        );
+        let expiration = config.now.add(86400);
+        assert_eq!(
+            rrsigs(&records),
+            [
+                ("example".into(), Rtype::A, expiration),
+                ("www.example".into(), Rtype::A, expiration),
+            ]
+        );
+
+        // Nothing changed, nothing to do.
+        let stats = records.resign(&apex, &config, TestKey(1)).unwrap();
+        assert_eq!(
+            stats,
+            ResignStats {
+                kept: 2,
+                signed: 0,
+                removed: 0
+            }
+        );
+
+        // A changed RRset is signed again.
+        let www = Dname::<Vec<u8>>::from_str("www.example").unwrap();
+        let removed = records.remove_rrset(&www, Class::In, Rtype::A);
+        assert_eq!(removed, [a("www.example", "192.0.2.2")]);
+        records.insert(a("www.example", "192.0.2.4")).unwrap();
+        assert_eq!(rrsigs(&records).len(), 1);
+        let stats = records.resign(&apex, &config, TestKey(1)).unwrap();
+        assert_eq!(
+            stats,
+            ResignStats {
+                kept: 1,
+                signed: 1,
+                removed: 0
+            }
+        );
+        assert_eq!(rrsigs(&records).len(), 2);
+
+        // A changed TTL is noticed, too.
+        let removed = records.remove_rrset(&www, Class::In, Rtype::A);
+        for mut record in removed {
+            record.set_ttl(60);
+            records.insert(record).unwrap();
+        }
+        let stats = records.resign(&apex, &config, TestKey(1)).unwrap();
+        assert_eq!(stats.signed, 1);
+
+        // So is a record inserted into a signed RRset.
+        records.insert(a("www.example", "192.0.2.5")).unwrap();
+        let stats = records.resign(&apex, &config, TestKey(1)).unwrap();
+        assert_eq!(
+            stats,
+            ResignStats {
+                kept: 1,
+                signed: 1,
+                removed: 1
+            }
+        );
+        let stats = records.resign(&apex, &config, TestKey(1)).unwrap();
+        assert_eq!(stats.kept, 2);
+
+        // Signatures within the refresh window are replaced, with jitter.
+        config.now = config.now.add(86400 - 3000);
+        config.jitter = 600;
+        let stats = records.resign(&apex, &config, TestKey(1)).unwrap();
+        assert_eq!(
+            stats,
+            ResignStats {
+                kept: 0,
+                signed: 2,
+                removed: 2
+            }
+        );
+        for (_, _, sig_expiration) in rrsigs(&records) {
+            assert!(sig_expiration <= config.now.add(86400));
+            assert!(sig_expiration >= config.now.add(86400 - 600));
+        }
+
+        // Signatures of records that became occluded are removed.
+        records.insert(ns("www.example", "ns.sub.example")).unwrap();
+        records.insert(a("deep.www.example", "192.0.2.5")).unwrap();
+        let stats = records.resign(&apex, &config, TestKey(1)).unwrap();
+        assert_eq!(
+            stats,
+            ResignStats {
+                kept: 1,
+                signed: 0,
+                removed: 1
+            }
+        );
+        assert_eq!(rrsigs(&records).len(), 1);
+    }
+
+    #[test]
+    fn key_roles() {
+        let dnskey = TestKey(1).dnskey().unwrap();
+        let mut records: SortedRecords<_, _> = vec![
+            a("example", "192.0.2.1"),
+            zone_record("example", dnskey.into()),
+            a("www.example", "192.0.2.2"),
+        ]
+        .into();
+        let apex =
+            FamilyName::new(Dname::from_str("example").unwrap(), Class::In);
+        let mut keys = KeySet::new();
+        keys.add_ksk(TestKey(1));
+        keys.add_zsk(TestKey(2));
+        keys.add_zsk(TestKey(3));
+
+        let signed: Vec<_> = records
+            .sign_with_keys::<Vec<u8>, _, _>(
+                &apex,
+                Serial(2000),
+                Serial(1000),
+                &keys,
+            )
+            .unwrap()
+            .into_iter()
+            .map(|record| {
+                (
+                    record.owner().to_string(),
+                    record.data().type_covered(),
+                    record.data().key_tag(),
+                )
+            })
+            .collect();
+        let ksk = TestKey(1).key_tag().unwrap();
+        let zsk1 = TestKey(2).key_tag().unwrap();
+        let zsk2 = TestKey(3).key_tag().unwrap();
+        assert_eq!(
+            signed,
+            [
+                ("example".into(), Rtype::Dnskey, ksk),
+                ("example".into(), Rtype::A, zsk1),
+                ("www.example".into(), Rtype::A, zsk1),
+                ("example".into(), Rtype::A, zsk2),
+                ("www.example".into(), Rtype::A, zsk2),
+            ]
+        );
+
+        let mut config = ResignConfig::new(86400, 3600);
+        config.jitter = 0;
+        let stats = records.resign_with_keys(&apex, &config, &keys).unwrap();
+        assert_eq!(stats.signed, 5);
+        let stats = records.resign_with_keys(&apex, &config, &keys).unwrap();
+        assert_eq!(
+            stats,
+            ResignStats {
+                kept: 5,
+                signed: 0,
+                removed: 0
+            }
+        );
+
+        // Turning the KSK into a CSK makes it sign everything.
+        let mut keys = KeySet::new();
+        keys.add_csk(TestKey(1));
+        let stats = records.resign_with_keys(&apex, &config, &keys).unwrap();
+        assert_eq!(
+            stats,
+            ResignStats {
+                kept: 1,
+                signed: 2,
+                removed: 0
+            }
+        );
+    }
+
+    //--- CDS and CDNSKEY
+
+    #[cfg(feature = "ring")]
+    #[test]
+    fn cds_from_dnskey() {
+        // The example from RFC 4034, section 5.4.
+        let dnskey = Dnskey::new(
+            256,
+            3,
+            SecAlg::RsaSha1,
+            crate::utils::base64::decode(
+                "AQOeiiR0GOMYkDshWoSKz9XzfwJr1AYtsmx3TGkJaNXVbfi/\
+                 2pHm822aJ5iI9BMzNXxeYCmZDRD99WYwYqUSdjMmmAphXdvx\
+                 egXd/M5+X7OrzKBaMbCVdFLUUh6DhweJBjEVv5f2wwjM9Xzc\
+                 nOf+EPbtG9DMBmADjFDc2w/rljwvFw==",
+            )
+            .unwrap(),
+        );
+        let apex = FamilyName::new(
+            Dname::<Vec<u8>>::from_str("dskey.example.com").unwrap(),
+            Class::In,
+        );
+        let cds = apex
+            .cds_from_dnskey(86400, &dnskey, DigestAlg::Sha1)
+            .unwrap();
+        assert_eq!(
+            cds.data().to_string(),
+            "60485 RSASHA1 1 2bb183af5f22588179a53b0a98631fad1a292118"
+        );
+        let ds = apex
+            .ds_from_dnskey(86400, &dnskey, DigestAlg::Sha1)
+            .unwrap();
+        assert_eq!(ds.data().digest(), cds.data().digest());
+        assert_eq!(
+            apex.cds_from_dnskey(86400, &dnskey, DigestAlg::Gost),
+            Err(UnsupportedDigest)
+        );
+
+        let cdnskey = apex.cdnskey_from_dnskey(86400, dnskey.clone());
+        assert_eq!(cdnskey.data().public_key(), dnskey.public_key());
+        assert_eq!(cdnskey.data().flags(), 256);
+    }
+
+    #[test]
+    fn cds_delete() {
+        let apex = FamilyName::new(
+            Dname::<Vec<u8>>::from_str("example.com").unwrap(),
+            Class::In,
+        );
+        let cds = apex.cds_delete::<Vec<u8>>(3600);
+        assert_eq!(cds.data().algorithm(), SecAlg::DeleteDs);
+        assert_eq!(cds.data().to_string(), "0 DELETE 0 00");
+        let cdnskey = apex.cdnskey_delete::<Vec<u8>>(3600);
+        assert_eq!(cdnskey.data().to_string(), "0 3 DELETE AA==");
+    }
+}
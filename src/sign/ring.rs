@@ -2,8 +2,12 @@
 #![cfg(feature = "ring")]
 #![cfg_attr(docsrs, doc(cfg(feature = "ring")))]
 
+#[cfg(feature = "bytes")]
+use super::bind::{KeyFileError, PrivateKeyFile, PublicKeyFile};
 use super::key::SigningKey;
 use crate::base::iana::{DigestAlg, SecAlg};
+#[cfg(feature = "bytes")]
+use crate::base::name::Dname;
 use crate::base::name::ToDname;
 use crate::base::octets::Compose;
 use crate::rdata::{Dnskey, Ds};
@@ -13,18 +17,28 @@ use ring::digest;
 use ring::error::Unspecified;
 use ring::rand::SecureRandom;
 use ring::signature::{
-    EcdsaKeyPair, Ed25519KeyPair, KeyPair, RsaEncoding, RsaKeyPair,
-    Signature as RingSignature, ECDSA_P256_SHA256_FIXED_SIGNING,
+    EcdsaKeyPair, EcdsaSigningAlgorithm, Ed25519KeyPair, KeyPair,
+    RsaEncoding, RsaKeyPair, Signature as RingSignature,
+    ECDSA_P256_SHA256_FIXED_SIGNING, ECDSA_P384_SHA384_FIXED_SIGNING,
+    RSA_PKCS1_SHA256, RSA_PKCS1_SHA512,
 };
 use std::vec::Vec;
 
 pub struct Key<'a> {
     dnskey: Dnskey<Vec<u8>>,
     key: RingKey,
+
+    /// The private key for exporting the key.
+    ///
+    /// For ECDSA and Ed25519, this is the raw private key. For RSA, it is
+    /// the DER encoded RSAPrivateKey structure of RFC 8017.
+    #[cfg_attr(not(feature = "bytes"), allow(dead_code))]
+    secret: Vec<u8>,
+
     rng: &'a dyn SecureRandom,
 }
 
-#[allow(dead_code, clippy::large_enum_variant)]
+#[allow(clippy::large_enum_variant)]
 enum RingKey {
     Ecdsa(EcdsaKeyPair),
     Ed25519(Ed25519KeyPair),
@@ -32,30 +46,157 @@ enum RingKey {
 }
 
 impl<'a> Key<'a> {
-    pub fn throwaway_13(
+    /// Generates a new key.
+    ///
+    /// The key will be used with the DNSSEC algorithm `algorithm` and its
+    /// DNSKEY record will have the given `flags`. The supported algorithms
+    /// are those listed for [`from_pkcs8`][Self::from_pkcs8] except for the
+    /// RSA algorithms since ring cannot generate RSA keys.
+    pub fn generate(
+        algorithm: SecAlg,
         flags: u16,
         rng: &'a dyn SecureRandom,
     ) -> Result<Self, Unspecified> {
-        let pkcs8 = EcdsaKeyPair::generate_pkcs8(
-            &ECDSA_P256_SHA256_FIXED_SIGNING,
+        let pkcs8 = match algorithm {
+            SecAlg::Ed25519 => Ed25519KeyPair::generate_pkcs8(rng)?,
+            _ => EcdsaKeyPair::generate_pkcs8(ecdsa_alg(algorithm)?, rng)?,
+        };
+        Self::from_pkcs8(flags, algorithm, pkcs8.as_ref(), rng)
+    }
+
+    /// Creates a key from a private key in PKCS#8 format.
+    ///
+    /// The key will be used with the DNSSEC algorithm `algorithm` and its
+    /// DNSKEY record will have the given `flags`. Currently,
+    /// `SecAlg::RsaSha256`, `SecAlg::RsaSha512`, `SecAlg::EcdsaP256Sha256`,
+    /// `SecAlg::EcdsaP384Sha384`, and `SecAlg::Ed25519` are supported.
+    pub fn from_pkcs8(
+        flags: u16,
+        algorithm: SecAlg,
+        pkcs8: &[u8],
+        rng: &'a dyn SecureRandom,
+    ) -> Result<Self, Unspecified> {
+        let secret =
+            pkcs8_private_key(pkcs8, algorithm).ok_or(Unspecified)?;
+        let (key, public_key) = match algorithm {
+            SecAlg::Ed25519 => {
+                let keypair =
+                    Ed25519KeyPair::from_pkcs8_maybe_unchecked(pkcs8)
+                        .map_err(|_| Unspecified)?;
+                let public_key = keypair.public_key().as_ref().into();
+                (RingKey::Ed25519(keypair), public_key)
+            }
+            SecAlg::RsaSha256 | SecAlg::RsaSha512 => {
+                let keypair =
+                    RsaKeyPair::from_pkcs8(pkcs8).map_err(|_| Unspecified)?;
+                let public_key = rsa_public_key(&keypair);
+                (RingKey::Rsa(keypair, rsa_encoding(algorithm)?), public_key)
+            }
+            _ => {
+                let keypair =
+                    EcdsaKeyPair::from_pkcs8(ecdsa_alg(algorithm)?, pkcs8)
+                        .map_err(|_| Unspecified)?;
+                // The DNSKEY doesn’t include the leading 0x04 octet.
+                let public_key = keypair.public_key().as_ref()[1..].into();
+                (RingKey::Ecdsa(keypair), public_key)
+            }
+        };
+        Ok(Key {
+            dnskey: Dnskey::new(flags, 3, algorithm, public_key),
+            key,
+            secret: secret.into(),
             rng,
-        )?;
-        let keypair = EcdsaKeyPair::from_pkcs8(
-            &ECDSA_P256_SHA256_FIXED_SIGNING,
-            pkcs8.as_ref(),
-        )?;
-        let public_key = keypair.public_key().as_ref()[1..].into();
+        })
+    }
+
+    /// Creates a key from the content of a BIND key file pair.
+    ///
+    /// The supported algorithms are those listed for
+    /// [`from_pkcs8`][Self::from_pkcs8]. The function checks that the
+    /// private and public key belong together.
+    #[cfg(feature = "bytes")]
+    pub fn from_bind(
+        public: &PublicKeyFile,
+        private: &PrivateKeyFile,
+        rng: &'a dyn SecureRandom,
+    ) -> Result<Self, KeyFileError> {
+        let dnskey = public.dnskey();
+        if dnskey.algorithm() != private.algorithm() {
+            return Err(KeyFileError::Mismatch);
+        }
+        let (key, secret) = match dnskey.algorithm() {
+            SecAlg::RsaSha256 | SecAlg::RsaSha512 => {
+                let der = rsa_der(private)?;
+                let keypair = RsaKeyPair::from_der(&der)
+                    .map_err(|_| KeyFileError::BadKey)?;
+                if rsa_public_key(&keypair) != *dnskey.public_key() {
+                    return Err(KeyFileError::BadKey);
+                }
+                let encoding = rsa_encoding(dnskey.algorithm())
+                    .map_err(|_| KeyFileError::UnsupportedAlgorithm)?;
+                (RingKey::Rsa(keypair, encoding), der)
+            }
+            SecAlg::Ed25519 => {
+                let secret = bind_private_key(private)?;
+                let keypair = Ed25519KeyPair::from_seed_and_public_key(
+                    secret,
+                    dnskey.public_key(),
+                )
+                .map_err(|_| KeyFileError::BadKey)?;
+                (RingKey::Ed25519(keypair), secret.into())
+            }
+            algorithm => {
+                let alg = ecdsa_alg(algorithm)
+                    .map_err(|_| KeyFileError::UnsupportedAlgorithm)?;
+                let secret = bind_private_key(private)?;
+                let mut public_key =
+                    Vec::with_capacity(dnskey.public_key().len() + 1);
+                public_key.push(4);
+                public_key.extend_from_slice(dnskey.public_key());
+                let keypair = EcdsaKeyPair::from_private_key_and_public_key(
+                    alg,
+                    secret,
+                    &public_key,
+                )
+                .map_err(|_| KeyFileError::BadKey)?;
+                (RingKey::Ecdsa(keypair), secret.into())
+            }
+        };
         Ok(Key {
-            dnskey: Dnskey::new(
-                flags,
-                3,
-                SecAlg::EcdsaP256Sha256,
-                public_key,
-            ),
-            key: RingKey::Ecdsa(keypair),
+            dnskey: dnskey.clone(),
+            key,
+            secret,
             rng,
         })
     }
+
+    /// Returns the content of the BIND key file pair for the key.
+    ///
+    /// The `owner` is the apex of the zone the key is used for.
+    #[cfg(feature = "bytes")]
+    pub fn to_bind(
+        &self,
+        owner: Dname<Vec<u8>>,
+    ) -> (PublicKeyFile, PrivateKeyFile) {
+        let mut private = PrivateKeyFile::new(self.dnskey.algorithm());
+        match self.key {
+            RingKey::Rsa(..) => {
+                let mut der = rsa_der_fields(&self.secret);
+                for name in RSA_FIELDS {
+                    private.set(name, der.next().unwrap_or_default().into())
+                }
+            }
+            _ => private.set("PrivateKey", self.secret.clone()),
+        }
+        (PublicKeyFile::new(owner, self.dnskey.clone()), private)
+    }
+
+    pub fn throwaway_13(
+        flags: u16,
+        rng: &'a dyn SecureRandom,
+    ) -> Result<Self, Unspecified> {
+        Self::generate(SecAlg::EcdsaP256Sha256, flags, rng)
+    }
 }
 
 impl<'a> SigningKey for Key<'a> {
@@ -99,6 +240,184 @@ impl<'a> SigningKey for Key<'a> {
     }
 }
 
+//------------ Helper Functions ----------------------------------------------
+
+/// The names of the RSA key fields in BIND private key files.
+///
+/// The fields are in the order of the integers in the RSAPrivateKey
+/// structure.
+#[cfg(feature = "bytes")]
+const RSA_FIELDS: &[&str] = &[
+    "Modulus",
+    "PublicExponent",
+    "PrivateExponent",
+    "Prime1",
+    "Prime2",
+    "Exponent1",
+    "Exponent2",
+    "Coefficient",
+];
+
+/// Returns the ring RSA encoding for a DNSSEC algorithm.
+fn rsa_encoding(
+    algorithm: SecAlg,
+) -> Result<&'static dyn RsaEncoding, Unspecified> {
+    match algorithm {
+        SecAlg::RsaSha256 => Ok(&RSA_PKCS1_SHA256),
+        SecAlg::RsaSha512 => Ok(&RSA_PKCS1_SHA512),
+        _ => Err(Unspecified),
+    }
+}
+
+/// Returns the DNSKEY public key of an RSA key pair.
+///
+/// The format is defined in section 2 of RFC 3110.
+fn rsa_public_key(keypair: &RsaKeyPair) -> Vec<u8> {
+    let exponent = keypair.public_key().exponent();
+    let exponent = exponent.big_endian_without_leading_zero();
+    let modulus = keypair.public_key().modulus();
+    let modulus = modulus.big_endian_without_leading_zero();
+    let mut res = Vec::with_capacity(exponent.len() + modulus.len() + 3);
+    if exponent.len() < 256 {
+        res.push(exponent.len() as u8);
+    } else {
+        res.push(0);
+        res.extend_from_slice(&(exponent.len() as u16).to_be_bytes());
+    }
+    res.extend_from_slice(exponent);
+    res.extend_from_slice(modulus);
+    res
+}
+
+/// Returns the raw private key of an ECDSA or Ed25519 private key file.
+#[cfg(feature = "bytes")]
+fn bind_private_key(private: &PrivateKeyFile) -> Result<&[u8], KeyFileError> {
+    private
+        .private_key()
+        .ok_or(KeyFileError::MissingField("PrivateKey"))
+}
+
+/// Creates the DER encoded RSAPrivateKey from a BIND private key file.
+#[cfg(feature = "bytes")]
+fn rsa_der(private: &PrivateKeyFile) -> Result<Vec<u8>, KeyFileError> {
+    let mut content = vec![0x02, 0x01, 0x00];
+    for name in RSA_FIELDS {
+        let value =
+            private.get(name).ok_or(KeyFileError::MissingField(name))?;
+        let start = value.iter().position(|&x| x != 0).unwrap_or(value.len());
+        let value = &value[start..];
+        if value.first().map(|&x| x & 0x80 != 0).unwrap_or(true) {
+            der_push(&mut content, 0x02, &[&[0], value])
+        } else {
+            der_push(&mut content, 0x02, &[value])
+        }
+    }
+    let mut res = Vec::new();
+    der_push(&mut res, 0x30, &[&content]);
+    Ok(res)
+}
+
+/// Returns an iterator over the integers of a DER encoded RSAPrivateKey.
+///
+/// The version is skipped and the integers are returned without leading
+/// zeros.
+#[cfg(feature = "bytes")]
+fn rsa_der_fields(der: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut content = der_next(der, 0x30)
+        .and_then(|(content, _)| der_next(content, 0x02))
+        .map(|(_, rest)| rest)
+        .unwrap_or_default();
+    core::iter::from_fn(move || {
+        let (value, rest) = der_next(content, 0x02)?;
+        content = rest;
+        let start = value.iter().position(|&x| x != 0).unwrap_or(value.len());
+        Some(&value[start..])
+    })
+}
+
+/// Returns the ring ECDSA algorithm for a DNSSEC algorithm.
+fn ecdsa_alg(
+    algorithm: SecAlg,
+) -> Result<&'static EcdsaSigningAlgorithm, Unspecified> {
+    match algorithm {
+        SecAlg::EcdsaP256Sha256 => Ok(&ECDSA_P256_SHA256_FIXED_SIGNING),
+        SecAlg::EcdsaP384Sha384 => Ok(&ECDSA_P384_SHA384_FIXED_SIGNING),
+        _ => Err(Unspecified),
+    }
+}
+
+/// Extracts the raw private key from a PKCS#8 document.
+///
+/// For ECDSA keys, this is the private key of the embedded ECPrivateKey
+/// structure defined in RFC 5915. For Ed25519 keys, it is the seed of the
+/// CurvePrivateKey defined in RFC 8410. For RSA keys, it is the complete
+/// RSAPrivateKey structure of RFC 8017.
+fn pkcs8_private_key(pkcs8: &[u8], algorithm: SecAlg) -> Option<&[u8]> {
+    let (info, _) = der_next(pkcs8, 0x30)?;
+    let (_version, rest) = der_next(info, 0x02)?;
+    let (_algorithm, rest) = der_next(rest, 0x30)?;
+    let (key, _) = der_next(rest, 0x04)?;
+    if algorithm == SecAlg::Ed25519 {
+        der_next(key, 0x04).map(|(seed, _)| seed)
+    } else if rsa_encoding(algorithm).is_ok() {
+        Some(key)
+    } else {
+        let (key, _) = der_next(key, 0x30)?;
+        let (_version, rest) = der_next(key, 0x02)?;
+        der_next(rest, 0x04).map(|(key, _)| key)
+    }
+}
+
+/// Splits off a DER encoded value with the given tag.
+///
+/// Returns the content of the value and the remaining data.
+fn der_next(data: &[u8], tag: u8) -> Option<(&[u8], &[u8])> {
+    if *data.first()? != tag {
+        return None;
+    }
+    let first = *data.get(1)?;
+    let (len, start) = if first < 0x80 {
+        (usize::from(first), 2)
+    } else {
+        let count = usize::from(first & 0x7f);
+        if count == 0 || count > 2 {
+            return None;
+        }
+        let mut len = 0;
+        for i in 0..count {
+            len = (len << 8) | usize::from(*data.get(2 + i)?);
+        }
+        (len, 2 + count)
+    };
+    let end = start.checked_add(len)?;
+    if end > data.len() {
+        return None;
+    }
+    Some((&data[start..end], &data[end..]))
+}
+
+/// Appends a DER encoded value with the given tag and content.
+///
+/// The content is given as a sequence of parts for convenience.
+#[cfg(feature = "bytes")]
+fn der_push(target: &mut Vec<u8>, tag: u8, content: &[&[u8]]) {
+    let len: usize = content.iter().map(|part| part.len()).sum();
+    target.push(tag);
+    if len < 0x80 {
+        target.push(len as u8);
+    } else if len < 0x100 {
+        target.extend_from_slice(&[0x81, len as u8]);
+    } else {
+        target.push(0x82);
+        target.extend_from_slice(&(len as u16).to_be_bytes());
+    }
+    for part in content {
+        target.extend_from_slice(part);
+    }
+}
+
+//------------ Signature -----------------------------------------------------
+
 pub struct Signature(SignatureInner);
 
 enum SignatureInner {
@@ -134,3 +453,155 @@ impl From<Signature> for Bytes {
         }
     }
 }
+
+impl From<Signature> for Vec<u8> {
+    fn from(sig: Signature) -> Self {
+        match sig.0 {
+            SignatureInner::Sig(sig) => sig.as_ref().into(),
+            SignatureInner::Vec(sig) => sig,
+        }
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(all(test, feature = "bytes"))]
+mod test {
+    use super::*;
+    use core::str::FromStr;
+    use ring::rand::SystemRandom;
+    use ring::signature::{
+        RsaPublicKeyComponents, UnparsedPublicKey, VerificationAlgorithm,
+        ECDSA_P256_SHA256_FIXED, ECDSA_P384_SHA384_FIXED, ED25519,
+        RSA_PKCS1_2048_8192_SHA256,
+    };
+    use std::string::ToString;
+
+    fn roundtrip(
+        algorithm: SecAlg,
+        verify: &'static dyn VerificationAlgorithm,
+    ) {
+        let rng = SystemRandom::new();
+        let key = Key::generate(algorithm, 257, &rng).unwrap();
+        let owner: Dname<Vec<u8>> = Dname::from_str("example.com").unwrap();
+        let (public, private) = key.to_bind(owner);
+
+        let public = PublicKeyFile::from_str(&public.to_string()).unwrap();
+        let private = PrivateKeyFile::from_str(&private.to_string()).unwrap();
+        let loaded = Key::from_bind(&public, &private, &rng).unwrap();
+        assert_eq!(loaded.dnskey().unwrap(), key.dnskey().unwrap());
+
+        let sig = loaded.sign(b"message").unwrap();
+        let mut public_key = Vec::new();
+        if algorithm != SecAlg::Ed25519 {
+            public_key.push(4);
+        }
+        public_key.extend_from_slice(key.dnskey.public_key());
+        UnparsedPublicKey::new(verify, &public_key)
+            .verify(b"message", sig.as_ref())
+            .unwrap();
+    }
+
+    #[test]
+    fn bind_roundtrip() {
+        roundtrip(SecAlg::EcdsaP256Sha256, &ECDSA_P256_SHA256_FIXED);
+        roundtrip(SecAlg::EcdsaP384Sha384, &ECDSA_P384_SHA384_FIXED);
+        roundtrip(SecAlg::Ed25519, &ED25519);
+    }
+
+    const RSA_PUBLIC: &str = "example.com. IN DNSKEY 256 3 8 \
+        AwEAAdnZqyAWTjUVAS0qKA//kplmUzecmLs8R6Td8EKR1EtWXvl4vB1oClw0w6EU\
+        X7/wJ1aA+YWvZoplKpood5Aoa9J3jJt6cysyrTr/qlD3pr39HQum9QNqeeB4orE0\
+        4pG1fDfJ7hnJ0l3EWbmz6eUy/tOqKpFaSv8607KAVcred8XIcRGKk5yQN+dqKaR/\
+        02lQup+6fBuKrr42YhQbA2P15mt0Y847v68Q84TzVuIjG6A/DKqjpkLYCzfao9VC\
+        lLqxFtvr0VtMAXLik5jCzqlso5LFpuDZRL4K/lQufLW2iVJOb4BaKT6HurhFC2CP\
+        HHMAGPdYSx4BsvGu7cl6Oh3Njq0=";
+
+    const RSA_PRIVATE: &str = "\
+        Private-key-format: v1.3\n\
+        Algorithm: 8 (RSASHA256)\n\
+        Modulus: \
+        2dmrIBZONRUBLSooD/+SmWZTN5yYuzxHpN3wQpHUS1Ze+Xi8HWgKXDTDoRRfv/An\
+        VoD5ha9mimUqmih3kChr0neMm3pzKzKtOv+qUPemvf0dC6b1A2p54HiisTTikbV8\
+        N8nuGcnSXcRZubPp5TL+06oqkVpK/zrTsoBVyt53xchxEYqTnJA352oppH/TaVC6\
+        n7p8G4quvjZiFBsDY/Xma3Rjzju/rxDzhPNW4iMboD8MqqOmQtgLN9qj1UKUurEW\
+        2+vRW0wBcuKTmMLOqWyjksWm4NlEvgr+VC58tbaJUk5vgFopPoe6uEULYI8ccwAY\
+        91hLHgGy8a7tyXo6Hc2OrQ==\n\
+        PublicExponent: AQAB\n\
+        PrivateExponent: \
+        TkaD0lexailN7uiC1JzEFBJMPGMsDbqvE3Ibbg/v89Ho9kKHdRl4/4CF+k7TyKeF\
+        uvNEtOA9O+T11cZMj6O2ej0ybHsm/pKK4/QRQl55rzJPSWyIBN4xRJKUApA6CJjp\
+        NTJ9Nw7pY3+1raATnqMgJPWzrd5/rA17G1w5d1dQOeQN5ydAhNtNvdnUPQmx/oT4\
+        HMQAQ5/t3caCdsydKGO5R2VRkeEuKCtxHEgRQY+QvR7dw6DRVlVqOuXLclncOHMR\
+        tcYFKEGR+2+QhaMiN7G9Pm4NsLKQQIiKxlm4Okz5Sz9CENpvNm7pmZuNHIexdK+C\
+        PQZLp1XuRug08zs8xX0fkQ==\n\
+        Prime1: \
+        7DMIw2po+1TYqqKiOK8FRBj2cQruLSv1h/KrqkuANNhmw0AX6fA8X+9X9V8tqp9u\
+        tTxdw+hf6NspepFYvXcQoQd6cWbeaFPd3y2bSAyFbCFPuUxb0593D+oRYbiIgY95\
+        TkH+zvvLh5wmEcfmMNkDNwMy3JwlhaLkCjSWbU44N08=\n\
+        Prime2: \
+        7BzaWoXHMSqvkMMCAy2LAqlsMObGq8b/UQyzy37QvAVDBF0O6v7Hxx4uvK7p76te\
+        a9eGDvqY1E1MP77rjWaCj+l+K9WRMEQuidVDW7SPUmcIIB+6cSEXZVwbHtvmdRTi\
+        Sq8nNmtIvrFwLTaBBbt2Fr1OFVgNmkYRguApVWQyW0M=\n\
+        Exponent1: \
+        Giw1hmHeLCtkrtPCogFS1g7mD/SkG8FJPb04JHClTjXEfVI21CdYqVC+MGRRjRyJ\
+        ie+cfD7dkdHnMLu2V5g/8XgAq1w4vNq+NSK6mCs7BZZTXTcG3dZHvS20q11lUuXx\
+        DSjm8UyZavd0iHfcHAGQB6KFGkj7J2m7Vx4opQgL1qU=\n\
+        Exponent2: \
+        aycU+SvpICkNEBdP3H1YrtaQoQi6dM6UpAWtutZMiGei6GILGR6TL4xFDaAbzobp\
+        DOv8jOOxY/fIeM1+POeerQz8E4ySnWLz891y+jVRLcZQa7O6ewjXg0pvjGujhNL7\
+        d4VRuGKr2s7P59TqbN2MU/mJbCpP0gmmfMyaNYSJTd0=\n\
+        Coefficient: \
+        lX28BL6UN+YxSFXayH9X5XFqvinzORR+mCO7KrjZoPkNLWbxuQ/Mfw3WD2HIYBL4\
+        XDQKjq+BcboE/PszjON6tAiXT0Siys7YEoxv7lnN7mgU9c6l5/emAZPWzJsXWGwQ\
+        df0Uki+8cdwxveFntfhaJMnILgWEaGKfuWFZRTDj4Yk=\n";
+
+    #[test]
+    fn bind_rsa() {
+        let rng = SystemRandom::new();
+        let public = PublicKeyFile::from_str(RSA_PUBLIC).unwrap();
+        let private = PrivateKeyFile::from_str(RSA_PRIVATE).unwrap();
+        let key = Key::from_bind(&public, &private, &rng).unwrap();
+        assert_eq!(
+            key.dnskey().unwrap().key_tag(),
+            public.dnskey().key_tag()
+        );
+
+        let (_, exported) = key.to_bind(public.owner().clone());
+        assert_eq!(exported.to_string(), RSA_PRIVATE);
+
+        let sig = key.sign(b"message").unwrap();
+        let modulus = private.get("Modulus").unwrap();
+        let exponent = private.get("PublicExponent").unwrap();
+        RsaPublicKeyComponents {
+            n: modulus,
+            e: exponent,
+        }
+        .verify(&RSA_PKCS1_2048_8192_SHA256, b"message", sig.as_ref())
+        .unwrap();
+
+        let mut bad = PrivateKeyFile::new(SecAlg::RsaSha256);
+        for name in RSA_FIELDS {
+            bad.set(name, private.get(name).unwrap().into());
+        }
+        bad.set("Prime1", private.get("Prime2").unwrap().into());
+        assert_eq!(
+            Key::from_bind(&public, &bad, &rng).err(),
+            Some(KeyFileError::BadKey)
+        );
+    }
+
+    #[test]
+    fn bind_mismatch() {
+        let rng = SystemRandom::new();
+        let owner: Dname<Vec<u8>> = Dname::from_str("example.com").unwrap();
+        let one = Key::generate(SecAlg::EcdsaP256Sha256, 256, &rng).unwrap();
+        let two = Key::generate(SecAlg::EcdsaP256Sha256, 256, &rng).unwrap();
+        let (public, _) = one.to_bind(owner.clone());
+        let (_, private) = two.to_bind(owner);
+        assert_eq!(
+            Key::from_bind(&public, &private, &rng).err(),
+            Some(KeyFileError::BadKey)
+        );
+    }
+}
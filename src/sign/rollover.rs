@@ -0,0 +1,1042 @@
+//! Key rollovers.
+//!
+//! This module helps with changing the keys of a zone over time as described
+//! in [RFC 6781] and [RFC 7583].
+//!
+//! A [`KeyManager`] keeps all keys of a zone together with their role and a
+//! [`KeyTiming`] that says when the key’s DNSKEY record is published, when
+//! the key is used for signing, and when the parent zone should have a DS
+//! record for it. Given the current time, the manager determines the
+//! DNSKEY, CDS, and CDNSKEY RRsets of the zone as well as the set of keys
+//! to sign with.
+//!
+//! The manager can also start a rollover by adding a new key and adjusting
+//! the timing of the old keys according to one of the well-known rollover
+//! methods. The delays used for this are provided via [`RolloverParams`].
+//! All the caller needs to do is to regularly call
+//! [`update_records`][KeyManager::update_records] and re-sign the zone with
+//! the keys returned by [`signing_keys`][KeyManager::signing_keys]. The time
+//! when this next becomes necessary is available via
+//! [`next_event`][KeyManager::next_event].
+//!
+//! Note that the manager cannot know whether the parent has actually
+//! updated its DS records. It only publishes the CDS and CDNSKEY records
+//! defined in [RFC 7344] and assumes the parent zone follows them within
+//! the delays given in [`RolloverParams`].
+//!
+//! [RFC 6781]: https://tools.ietf.org/html/rfc6781
+//! [RFC 7344]: https://tools.ietf.org/html/rfc7344
+//! [RFC 7583]: https://tools.ietf.org/html/rfc7583
+
+use super::key::{KeyRole, KeySet, SigningKey};
+use super::records::{FamilyName, MaybeRrsig, SortedRecords};
+use crate::base::cmp::CanonicalOrd;
+use crate::base::iana::Rtype;
+use crate::base::name::ToDname;
+use crate::base::rdata::RecordData;
+use crate::base::record::Record;
+use crate::base::serial::Serial;
+use crate::rdata::{Cdnskey, Cds, Dnskey};
+use std::vec::Vec;
+
+//------------ KeyTiming -----------------------------------------------------
+
+/// The points in time when a key changes state.
+///
+/// Each state is entered at its start time and left at its end time. If
+/// the start time is `None`, the state is never entered. If the end time is
+/// `None`, the state is never left once entered.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct KeyTiming {
+    /// When the DNSKEY record is added to the zone.
+    pub publish: Option<Serial>,
+
+    /// When the key starts signing.
+    pub active: Option<Serial>,
+
+    /// When the key stops signing.
+    pub retire: Option<Serial>,
+
+    /// When the DNSKEY record is removed from the zone.
+    pub remove: Option<Serial>,
+
+    /// When the parent should start having a DS record for the key.
+    ///
+    /// From this time on, CDS and CDNSKEY records for the key are published
+    /// if it is a key signing or combined signing key.
+    pub ds_publish: Option<Serial>,
+
+    /// When the parent should stop having a DS record for the key.
+    pub ds_remove: Option<Serial>,
+}
+
+impl KeyTiming {
+    /// Creates the timing for a key that is used right away.
+    ///
+    /// The key is published, signs, and has its DS record published at
+    /// `now` and stays that way until a rollover.
+    pub fn starting(now: Serial) -> Self {
+        KeyTiming {
+            publish: Some(now),
+            active: Some(now),
+            ds_publish: Some(now),
+            ..Default::default()
+        }
+    }
+
+    /// Returns whether the DNSKEY record is in the zone at `now`.
+    pub fn is_published(&self, now: Serial) -> bool {
+        in_state(self.publish, self.remove, now)
+    }
+
+    /// Returns whether the key signs at `now`.
+    pub fn is_active(&self, now: Serial) -> bool {
+        in_state(self.active, self.retire, now)
+    }
+
+    /// Returns whether the parent should have a DS record at `now`.
+    pub fn has_ds(&self, now: Serial) -> bool {
+        in_state(self.ds_publish, self.ds_remove, now)
+    }
+
+    /// Returns whether the key has left all its states for good at `now`.
+    pub fn is_finished(&self, now: Serial) -> bool {
+        has_ended(self.publish, self.remove, now)
+            && has_ended(self.active, self.retire, now)
+            && has_ended(self.ds_publish, self.ds_remove, now)
+    }
+
+    /// Returns the first point in time after `now` when a state changes.
+    pub fn next_event(&self, now: Serial) -> Option<Serial> {
+        [
+            self.publish,
+            self.active,
+            self.retire,
+            self.remove,
+            self.ds_publish,
+            self.ds_remove,
+        ]
+        .iter()
+        .filter_map(|time| *time)
+        .filter(|time| *time > now)
+        .fold(None, |res, time| match res {
+            Some(res) if res < time => Some(res),
+            _ => Some(time),
+        })
+    }
+}
+
+/// Returns whether a state with the given start and end is current.
+fn in_state(start: Option<Serial>, end: Option<Serial>, now: Serial) -> bool {
+    match start {
+        Some(start) if start <= now => {
+            end.map(|end| now < end).unwrap_or(true)
+        }
+        _ => false,
+    }
+}
+
+/// Returns whether a state with the given start and end is over for good.
+fn has_ended(
+    start: Option<Serial>,
+    end: Option<Serial>,
+    now: Serial,
+) -> bool {
+    match (start, end) {
+        (None, _) => true,
+        (Some(_), Some(end)) => end <= now,
+        (Some(_), None) => false,
+    }
+}
+
+/// Moves the end of a state to `time` unless it already ends earlier.
+fn end_by(end: &mut Option<Serial>, time: Serial) {
+    match *end {
+        Some(end) if end <= time => {}
+        _ => *end = Some(time),
+    }
+}
+
+//------------ ManagedKey ----------------------------------------------------
+
+/// A key with its role and timing.
+#[derive(Clone, Debug)]
+pub struct ManagedKey<K> {
+    key: K,
+    role: KeyRole,
+    timing: KeyTiming,
+}
+
+impl<K> ManagedKey<K> {
+    /// Returns a reference to the key.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Returns the role of the key.
+    pub fn role(&self) -> KeyRole {
+        self.role
+    }
+
+    /// Returns the timing of the key.
+    pub fn timing(&self) -> &KeyTiming {
+        &self.timing
+    }
+
+    /// Returns a mutable reference to the timing of the key.
+    pub fn timing_mut(&mut self) -> &mut KeyTiming {
+        &mut self.timing
+    }
+
+    /// Returns whether CDS and CDNSKEY records are published at `now`.
+    pub fn publishes_ds(&self, now: Serial) -> bool {
+        self.role != KeyRole::Zsk && self.timing.has_ds(now)
+    }
+
+    /// Returns whether the key isn’t needed anymore at `now`.
+    ///
+    /// The DS timing of a zone signing key is ignored.
+    pub fn is_finished(&self, now: Serial) -> bool {
+        if self.role == KeyRole::Zsk {
+            has_ended(self.timing.publish, self.timing.remove, now)
+                && has_ended(self.timing.active, self.timing.retire, now)
+        } else {
+            self.timing.is_finished(now)
+        }
+    }
+}
+
+//------------ KeyManager ----------------------------------------------------
+
+/// The keys of a zone and their timing.
+///
+/// Keys are identified by their index which is returned when adding them.
+/// Indexes stay the same until [`purge`][Self::purge] is called.
+#[derive(Clone, Debug)]
+pub struct KeyManager<K> {
+    keys: Vec<ManagedKey<K>>,
+}
+
+impl<K> KeyManager<K> {
+    /// Creates a new manager without any keys.
+    pub fn new() -> Self {
+        KeyManager { keys: Vec::new() }
+    }
+
+    /// Adds a key with the given role and timing.
+    ///
+    /// Returns the index of the key.
+    pub fn add(&mut self, role: KeyRole, key: K, timing: KeyTiming) -> usize {
+        self.keys.push(ManagedKey { key, role, timing });
+        self.keys.len() - 1
+    }
+
+    /// Returns the key with the given index.
+    pub fn get(&self, idx: usize) -> Option<&ManagedKey<K>> {
+        self.keys.get(idx)
+    }
+
+    /// Returns a mutable reference to the key with the given index.
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut ManagedKey<K>> {
+        self.keys.get_mut(idx)
+    }
+
+    /// Returns an iterator over all keys.
+    pub fn iter(&self) -> impl Iterator<Item = &ManagedKey<K>> {
+        self.keys.iter()
+    }
+
+    /// Returns the number of keys.
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Returns whether there are no keys.
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Removes all keys that have finished at `now`.
+    ///
+    /// This changes the indexes of the remaining keys.
+    pub fn purge(&mut self, now: Serial) {
+        self.keys.retain(|key| !key.is_finished(now))
+    }
+
+    /// Returns the keys to sign the zone with at `now`.
+    pub fn signing_keys(&self, now: Serial) -> KeySet<&K> {
+        self.keys
+            .iter()
+            .filter(|key| key.timing.is_active(now))
+            .map(|key| (key.role, &key.key))
+            .collect()
+    }
+
+    /// Returns the first point in time after `now` when anything changes.
+    ///
+    /// The zone needs to be updated and re-signed at that time.
+    pub fn next_event(&self, now: Serial) -> Option<Serial> {
+        self.keys
+            .iter()
+            .filter_map(|key| key.timing.next_event(now))
+            .fold(None, |res, time| match res {
+                Some(res) if res < time => Some(res),
+                _ => Some(time),
+            })
+    }
+
+    //--- Rollovers
+
+    /// Starts a pre-publish zone signing key rollover.
+    ///
+    /// The new key is published at `now` but only starts signing once its
+    /// DNSKEY record has reached all caches. At the same time, the old key
+    /// with index `old` stops signing. It is removed once all signatures
+    /// made by it have expired from caches.
+    ///
+    /// Returns the index of the new key.
+    ///
+    /// See [RFC 6781, Section 4.1.1.1].
+    ///
+    /// # Panics
+    ///
+    /// The method panics if there is no key with index `old`.
+    ///
+    /// [RFC 6781, Section 4.1.1.1]: https://tools.ietf.org/html/rfc6781#section-4.1.1.1
+    pub fn start_zsk_pre_publish(
+        &mut self,
+        old: usize,
+        key: K,
+        params: &RolloverParams,
+        now: Serial,
+    ) -> usize {
+        let active = now.add(params.propagation + params.dnskey_ttl);
+        let remove = active
+            .add(params.signing_delay + params.propagation + params.max_ttl);
+        let timing = &mut self.keys[old].timing;
+        end_by(&mut timing.retire, active);
+        end_by(&mut timing.remove, remove);
+        self.add(
+            KeyRole::Zsk,
+            key,
+            KeyTiming {
+                publish: Some(now),
+                active: Some(active),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Starts a double-signature zone signing key rollover.
+    ///
+    /// The new key is published and signs from `now` on. The old key with
+    /// index `old` is removed and stops signing once the zone signed with
+    /// the new key has reached all caches.
+    ///
+    /// Returns the index of the new key.
+    ///
+    /// See [RFC 6781, Section 4.1.1.2].
+    ///
+    /// # Panics
+    ///
+    /// The method panics if there is no key with index `old`.
+    ///
+    /// [RFC 6781, Section 4.1.1.2]: https://tools.ietf.org/html/rfc6781#section-4.1.1.2
+    pub fn start_zsk_double_signature(
+        &mut self,
+        old: usize,
+        key: K,
+        params: &RolloverParams,
+        now: Serial,
+    ) -> usize {
+        let remove = now.add(
+            params.signing_delay
+                + params.propagation
+                + params.dnskey_ttl.max(params.max_ttl),
+        );
+        let timing = &mut self.keys[old].timing;
+        end_by(&mut timing.retire, remove);
+        end_by(&mut timing.remove, remove);
+        self.add(
+            KeyRole::Zsk,
+            key,
+            KeyTiming {
+                publish: Some(now),
+                active: Some(now),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Starts a double-DS key signing key rollover.
+    ///
+    /// The DS record for the new key is requested from the parent at `now`.
+    /// Once it has reached all caches, the new key replaces the old key
+    /// with index `old` in the DNSKEY RRset. Finally, the DS record for the
+    /// old key is withdrawn once the new DNSKEY RRset has reached all
+    /// caches.
+    ///
+    /// If the old key is a combined signing key, the new key takes over
+    /// signing the whole zone. Its DNSKEY record is therefore published at
+    /// `now`, too, and it only starts signing once both its DS and DNSKEY
+    /// records have reached all caches. The old key stops signing at that
+    /// time and is removed together with its DS record once all
+    /// signatures made by it have expired from caches.
+    ///
+    /// The new key gets the role of the old key. Returns its index.
+    ///
+    /// See [RFC 6781, Section 4.1.2] and [RFC 7583, Section 3.3.3].
+    ///
+    /// # Panics
+    ///
+    /// The method panics if there is no key with index `old` or if that
+    /// key is a zone signing key.
+    ///
+    /// [RFC 6781, Section 4.1.2]: https://tools.ietf.org/html/rfc6781#section-4.1.2
+    /// [RFC 7583, Section 3.3.3]: https://tools.ietf.org/html/rfc7583#section-3.3.3
+    pub fn start_ksk_double_ds(
+        &mut self,
+        old: usize,
+        key: K,
+        params: &RolloverParams,
+        now: Serial,
+    ) -> usize {
+        match self.keys[old].role {
+            KeyRole::Ksk => self.ksk_double_ds(old, key, params, now),
+            KeyRole::Csk => self.csk_double_ds(old, key, params, now),
+            KeyRole::Zsk => {
+                panic!("double-DS rollover of a zone signing key")
+            }
+        }
+    }
+
+    /// Performs a double-DS rollover of a key signing key.
+    fn ksk_double_ds(
+        &mut self,
+        old: usize,
+        key: K,
+        params: &RolloverParams,
+        now: Serial,
+    ) -> usize {
+        let swap = now.add(params.parent_propagation + params.ds_ttl);
+        let ds_remove = swap.add(params.propagation + params.dnskey_ttl);
+        let timing = &mut self.keys[old].timing;
+        end_by(&mut timing.retire, swap);
+        end_by(&mut timing.remove, swap);
+        if timing.ds_publish.is_none() {
+            timing.ds_publish = Some(now);
+        }
+        end_by(&mut timing.ds_remove, ds_remove);
+        self.add(
+            KeyRole::Ksk,
+            key,
+            KeyTiming {
+                publish: Some(swap),
+                active: Some(swap),
+                ds_publish: Some(now),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Performs a double-DS rollover of a combined signing key.
+    fn csk_double_ds(
+        &mut self,
+        old: usize,
+        key: K,
+        params: &RolloverParams,
+        now: Serial,
+    ) -> usize {
+        let swap = now.add(
+            (params.parent_propagation + params.ds_ttl)
+                .max(params.propagation + params.dnskey_ttl),
+        );
+        let remove = swap.add(
+            params.signing_delay
+                + params.propagation
+                + params.dnskey_ttl.max(params.max_ttl),
+        );
+        let timing = &mut self.keys[old].timing;
+        end_by(&mut timing.retire, swap);
+        end_by(&mut timing.remove, remove);
+        if timing.ds_publish.is_none() {
+            timing.ds_publish = Some(now);
+        }
+        end_by(&mut timing.ds_remove, remove);
+        self.add(
+            KeyRole::Csk,
+            key,
+            KeyTiming {
+                publish: Some(now),
+                active: Some(swap),
+                ds_publish: Some(now),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Starts an algorithm rollover.
+    ///
+    /// All keys that haven’t finished at `now` are replaced by the keys
+    /// given in `keys` using the conservative approach of
+    /// [RFC 6781, Section 4.1.4]: The new keys start signing at `now` and
+    /// are published once these signatures have reached all caches. After
+    /// the new DNSKEY RRset has reached all caches, the DS records are
+    /// swapped. Once the new DS records have reached all caches, the old
+    /// keys are removed and, after that change has reached all caches, stop
+    /// signing.
+    ///
+    /// Returns the indexes of the new keys.
+    ///
+    /// [RFC 6781, Section 4.1.4]: https://tools.ietf.org/html/rfc6781#section-4.1.4
+    pub fn start_algorithm_rollover<I>(
+        &mut self,
+        keys: I,
+        params: &RolloverParams,
+        now: Serial,
+    ) -> Vec<usize>
+    where
+        I: IntoIterator<Item = (KeyRole, K)>,
+    {
+        let publish = now
+            .add(params.signing_delay + params.propagation + params.max_ttl);
+        let ds_swap = publish.add(params.propagation + params.dnskey_ttl);
+        let remove = ds_swap.add(params.parent_propagation + params.ds_ttl);
+        let retire = remove.add(params.propagation + params.dnskey_ttl);
+        for key in &mut self.keys {
+            if key.is_finished(now) {
+                continue;
+            }
+            end_by(&mut key.timing.remove, remove);
+            end_by(&mut key.timing.retire, retire);
+            end_by(&mut key.timing.ds_remove, ds_swap);
+        }
+        keys.into_iter()
+            .map(|(role, key)| {
+                self.add(
+                    role,
+                    key,
+                    KeyTiming {
+                        publish: Some(publish),
+                        active: Some(now),
+                        ds_publish: Some(ds_swap),
+                        ..Default::default()
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+impl<K: SigningKey> KeyManager<K> {
+    /// Returns the DNSKEY, CDS, and CDNSKEY records of the zone at `now`.
+    ///
+    /// The records are created for the apex `apex` with a TTL of `ttl`.
+    pub fn key_records<N, Octets>(
+        &self,
+        apex: &FamilyName<N>,
+        ttl: u32,
+        now: Serial,
+    ) -> Result<KeyRecords<N, Octets>, K::Error>
+    where
+        N: ToDname + Clone,
+        Octets: From<K::Octets>,
+    {
+        let mut res = KeyRecords {
+            dnskeys: Vec::new(),
+            cds: Vec::new(),
+            cdnskeys: Vec::new(),
+        };
+        for key in &self.keys {
+            if key.timing.is_published(now) {
+                res.dnskeys.push(apex.dnskey(ttl, &key.key)?);
+            }
+            if key.publishes_ds(now) {
+                res.cdnskeys.push(apex.cdnskey(ttl, &key.key)?);
+                res.cds.push(apex.cds(ttl, &key.key)?);
+            }
+        }
+        Ok(res)
+    }
+
+    /// Updates the key records of a zone to their state at `now`.
+    ///
+    /// Replaces the DNSKEY, CDS, and CDNSKEY RRsets at the apex with those
+    /// returned by [`key_records`][Self::key_records] and removes all RRSIG
+    /// records made by managed keys that don’t sign at `now` anymore. The
+    /// zone needs to be re-signed afterwards, e.g., via
+    /// [`SortedRecords::resign_with_keys`].
+    pub fn update_records<N, D, Octets>(
+        &self,
+        records: &mut SortedRecords<N, D>,
+        apex: &FamilyName<N>,
+        ttl: u32,
+        now: Serial,
+    ) -> Result<(), K::Error>
+    where
+        N: ToDname + Clone,
+        D: RecordData
+            + CanonicalOrd
+            + MaybeRrsig
+            + From<Dnskey<Octets>>
+            + From<Cds<Octets>>
+            + From<Cdnskey<Octets>>,
+        Octets: From<K::Octets>,
+    {
+        let new = self.key_records::<N, Octets>(apex, ttl, now)?;
+        for rtype in &[Rtype::Dnskey, Rtype::Cds, Rtype::Cdnskey] {
+            records.remove_rrset(apex.owner(), apex.class(), *rtype);
+        }
+        records.extend(new.dnskeys.into_iter().map(Record::from_record));
+        records.extend(new.cds.into_iter().map(Record::from_record));
+        records.extend(new.cdnskeys.into_iter().map(Record::from_record));
+
+        // Keys are identified by algorithm and key tag. Since key tags can
+        // collide, signatures matching an active key are always kept.
+        let mut active = Vec::new();
+        let mut retired = Vec::new();
+        for key in &self.keys {
+            let id = (key.key.algorithm()?, key.key.key_tag()?);
+            if key.timing.is_active(now) {
+                active.push(id)
+            } else {
+                retired.push(id)
+            }
+        }
+        retired.retain(|id| !active.contains(id));
+        if !retired.is_empty() {
+            records.retain(|record| {
+                record
+                    .data()
+                    .as_rrsig()
+                    .map(|sig| {
+                        !retired.contains(&(sig.algorithm(), sig.key_tag()))
+                    })
+                    .unwrap_or(true)
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<K> Default for KeyManager<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+//------------ KeyRecords ----------------------------------------------------
+
+/// The key related records at the apex of a zone.
+#[derive(Clone)]
+pub struct KeyRecords<N, Octets> {
+    /// The DNSKEY records.
+    pub dnskeys: Vec<Record<N, Dnskey<Octets>>>,
+
+    /// The CDS records.
+    pub cds: Vec<Record<N, Cds<Octets>>>,
+
+    /// The CDNSKEY records.
+    pub cdnskeys: Vec<Record<N, Cdnskey<Octets>>>,
+}
+
+//------------ RolloverParams ------------------------------------------------
+
+/// The delays used when planning a rollover.
+///
+/// All values are in seconds. The names follow the terminology of
+/// [RFC 7583].
+///
+/// [RFC 7583]: https://tools.ietf.org/html/rfc7583
+#[derive(Clone, Copy, Debug)]
+pub struct RolloverParams {
+    /// The TTL of the DNSKEY RRset.
+    pub dnskey_ttl: u32,
+
+    /// The TTL of the DS RRset in the parent zone.
+    pub ds_ttl: u32,
+
+    /// The largest TTL of any RRset in the zone.
+    pub max_ttl: u32,
+
+    /// The time it takes a change to reach all authoritative servers.
+    ///
+    /// This is called _Dprp_ in RFC 7583.
+    pub propagation: u32,
+
+    /// The time it takes a DS change to be published by the parent.
+    ///
+    /// This includes both the time the parent takes to pick up the change
+    /// and the propagation delay of the parent zone.
+    pub parent_propagation: u32,
+
+    /// The time it takes to re-sign the whole zone with a new key.
+    ///
+    /// This is called _Dsgn_ in RFC 7583.
+    pub signing_delay: u32,
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Class, DigestAlg, SecAlg};
+    use crate::base::name::Dname;
+    use crate::rdata::{Ds, MasterRecordData, Rrsig, A};
+    use core::str::FromStr;
+
+    type Name = Dname<Vec<u8>>;
+    type ZoneData = MasterRecordData<Vec<u8>, Name>;
+
+    /// A key with dummy key material and signatures.
+    #[derive(Debug)]
+    struct TestKey {
+        id: u8,
+        flags: u16,
+        algorithm: SecAlg,
+    }
+
+    impl TestKey {
+        fn ksk(id: u8) -> Self {
+            TestKey {
+                id,
+                flags: 257,
+                algorithm: SecAlg::EcdsaP256Sha256,
+            }
+        }
+
+        fn zsk(id: u8) -> Self {
+            TestKey {
+                id,
+                flags: 256,
+                algorithm: SecAlg::EcdsaP256Sha256,
+            }
+        }
+    }
+
+    impl SigningKey for TestKey {
+        type Octets = Vec<u8>;
+        type Signature = Vec<u8>;
+        type Error = ();
+
+        fn dnskey(&self) -> Result<Dnskey<Vec<u8>>, ()> {
+            Ok(Dnskey::new(self.flags, 3, self.algorithm, vec![self.id]))
+        }
+
+        fn ds<N: ToDname>(&self, _owner: N) -> Result<Ds<Vec<u8>>, ()> {
+            Ok(Ds::new(
+                self.key_tag()?,
+                self.algorithm,
+                DigestAlg::Sha256,
+                vec![self.id],
+            ))
+        }
+
+        fn sign(&self, _data: &[u8]) -> Result<Vec<u8>, ()> {
+            Ok(vec![0; 64])
+        }
+    }
+
+    fn apex() -> FamilyName<Name> {
+        FamilyName::new(Name::from_str("example.com").unwrap(), Class::In)
+    }
+
+    fn params() -> RolloverParams {
+        RolloverParams {
+            dnskey_ttl: 3600,
+            ds_ttl: 7200,
+            max_ttl: 86400,
+            propagation: 300,
+            parent_propagation: 3600,
+            signing_delay: 0,
+        }
+    }
+
+    /// Returns the ids of the published, signing, and DS keys at `now`.
+    fn state(
+        keys: &KeyManager<TestKey>,
+        now: u32,
+    ) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let now = Serial(now);
+        let records =
+            keys.key_records::<_, Vec<u8>>(&apex(), 3600, now).unwrap();
+        assert_eq!(records.cds.len(), records.cdnskeys.len());
+        (
+            records
+                .dnskeys
+                .iter()
+                .map(|record| record.data().public_key()[0])
+                .collect(),
+            keys.signing_keys(now)
+                .iter()
+                .map(|(_, key)| key.id)
+                .collect(),
+            records
+                .cdnskeys
+                .iter()
+                .map(|record| record.data().public_key()[0])
+                .collect(),
+        )
+    }
+
+    fn initial() -> KeyManager<TestKey> {
+        let mut keys = KeyManager::new();
+        keys.add(
+            KeyRole::Ksk,
+            TestKey::ksk(1),
+            KeyTiming::starting(Serial(0)),
+        );
+        keys.add(
+            KeyRole::Zsk,
+            TestKey::zsk(2),
+            KeyTiming::starting(Serial(0)),
+        );
+        keys
+    }
+
+    #[test]
+    fn zsk_pre_publish() {
+        let mut keys = initial();
+        assert_eq!(state(&keys, 100), (vec![1, 2], vec![1, 2], vec![1]));
+        assert_eq!(keys.next_event(Serial(100)), None);
+
+        keys.start_zsk_pre_publish(
+            1,
+            TestKey::zsk(3),
+            &params(),
+            Serial(1000),
+        );
+        let active = 1000 + 300 + 3600;
+        let remove = active + 300 + 86400;
+        assert_eq!(keys.next_event(Serial(1000)), Some(Serial(active)));
+        assert_eq!(state(&keys, 1000), (vec![1, 2, 3], vec![1, 2], vec![1]));
+        assert_eq!(
+            state(&keys, active),
+            (vec![1, 2, 3], vec![1, 3], vec![1])
+        );
+        assert_eq!(keys.next_event(Serial(active)), Some(Serial(remove)));
+        assert_eq!(state(&keys, remove), (vec![1, 3], vec![1, 3], vec![1]));
+        assert_eq!(keys.next_event(Serial(remove)), None);
+
+        keys.purge(Serial(remove));
+        assert_eq!(keys.len(), 2);
+    }
+
+    #[test]
+    fn zsk_double_signature() {
+        let mut keys = initial();
+        keys.start_zsk_double_signature(
+            1,
+            TestKey::zsk(3),
+            &params(),
+            Serial(1000),
+        );
+        let remove = 1000 + 300 + 86400;
+        assert_eq!(
+            state(&keys, 1000),
+            (vec![1, 2, 3], vec![1, 2, 3], vec![1])
+        );
+        assert_eq!(keys.next_event(Serial(1000)), Some(Serial(remove)));
+        assert_eq!(state(&keys, remove), (vec![1, 3], vec![1, 3], vec![1]));
+    }
+
+    #[test]
+    fn ksk_double_ds() {
+        let mut keys = initial();
+        keys.start_ksk_double_ds(0, TestKey::ksk(3), &params(), Serial(1000));
+        let swap = 1000 + 3600 + 7200;
+        let ds_remove = swap + 300 + 3600;
+        assert_eq!(state(&keys, 1000), (vec![1, 2], vec![1, 2], vec![1, 3]));
+        assert_eq!(keys.next_event(Serial(1000)), Some(Serial(swap)));
+        assert_eq!(state(&keys, swap), (vec![2, 3], vec![2, 3], vec![1, 3]));
+        assert_eq!(keys.next_event(Serial(swap)), Some(Serial(ds_remove)));
+        assert_eq!(
+            state(&keys, ds_remove),
+            (vec![2, 3], vec![2, 3], vec![3])
+        );
+        assert_eq!(keys.get(2).unwrap().role(), KeyRole::Ksk);
+
+        keys.purge(Serial(ds_remove));
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys.get(0).unwrap().key().id, 2);
+    }
+
+    #[test]
+    fn csk_double_ds() {
+        let mut keys = KeyManager::new();
+        keys.add(
+            KeyRole::Csk,
+            TestKey::ksk(1),
+            KeyTiming::starting(Serial(0)),
+        );
+        keys.start_ksk_double_ds(0, TestKey::ksk(3), &params(), Serial(1000));
+        let swap = 1000 + 3600 + 7200;
+        let remove = swap + 300 + 86400;
+
+        // The new key is published right away but only signs once its
+        // DNSKEY and DS records have reached all caches.
+        assert_eq!(state(&keys, 1000), (vec![1, 3], vec![1], vec![1, 3]));
+        assert_eq!(keys.next_event(Serial(1000)), Some(Serial(swap)));
+        assert_eq!(state(&keys, swap), (vec![1, 3], vec![3], vec![1, 3]));
+        assert_eq!(keys.next_event(Serial(swap)), Some(Serial(remove)));
+        assert_eq!(state(&keys, remove), (vec![3], vec![3], vec![3]));
+        assert_eq!(keys.get(1).unwrap().role(), KeyRole::Csk);
+    }
+
+    #[test]
+    #[should_panic]
+    fn zsk_double_ds() {
+        let mut keys = initial();
+        keys.start_ksk_double_ds(1, TestKey::zsk(3), &params(), Serial(1000));
+    }
+
+    #[test]
+    fn algorithm_rollover() {
+        let mut keys = initial();
+        let new = keys.start_algorithm_rollover(
+            vec![
+                (
+                    KeyRole::Ksk,
+                    TestKey {
+                        algorithm: SecAlg::Ed25519,
+                        ..TestKey::ksk(3)
+                    },
+                ),
+                (
+                    KeyRole::Zsk,
+                    TestKey {
+                        algorithm: SecAlg::Ed25519,
+                        ..TestKey::zsk(4)
+                    },
+                ),
+            ],
+            &params(),
+            Serial(1000),
+        );
+        assert_eq!(new, [2, 3]);
+
+        let publish = 1000 + 300 + 86400;
+        let ds_swap = publish + 300 + 3600;
+        let remove = ds_swap + 3600 + 7200;
+        let retire = remove + 300 + 3600;
+        assert_eq!(
+            state(&keys, 1000),
+            (vec![1, 2], vec![1, 2, 3, 4], vec![1])
+        );
+        assert_eq!(
+            state(&keys, publish),
+            (vec![1, 2, 3, 4], vec![1, 2, 3, 4], vec![1])
+        );
+        assert_eq!(
+            state(&keys, ds_swap),
+            (vec![1, 2, 3, 4], vec![1, 2, 3, 4], vec![3])
+        );
+        assert_eq!(
+            state(&keys, remove),
+            (vec![3, 4], vec![1, 2, 3, 4], vec![3])
+        );
+        assert_eq!(state(&keys, retire), (vec![3, 4], vec![3, 4], vec![3]));
+        assert_eq!(keys.next_event(Serial(retire)), None);
+    }
+
+    #[test]
+    fn update_records() {
+        let apex = apex();
+        let mut keys = initial();
+        let mut records = SortedRecords::<Name, ZoneData>::new();
+        records
+            .insert(apex.clone().into_record(
+                3600,
+                A::new("192.0.2.1".parse().unwrap()).into(),
+            ))
+            .unwrap();
+        keys.update_records::<_, _, Vec<u8>>(
+            &mut records,
+            &apex,
+            3600,
+            Serial(0),
+        )
+        .unwrap();
+
+        // Add a signature for the A record by the ZSK.
+        let zsk = TestKey::zsk(2);
+        records
+            .insert(
+                apex.clone().into_record(
+                    3600,
+                    Rrsig::new(
+                        Rtype::A,
+                        zsk.algorithm,
+                        2,
+                        3600,
+                        Serial(1000000),
+                        Serial(0),
+                        zsk.key_tag().unwrap(),
+                        apex.owner().clone(),
+                        vec![0; 64],
+                    )
+                    .into(),
+                ),
+            )
+            .unwrap();
+
+        let rtypes = |records: &SortedRecords<Name, ZoneData>| {
+            records
+                .rrsets()
+                .map(|rrset| (rrset.rtype(), rrset.iter().count()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            rtypes(&records),
+            [
+                (Rtype::A, 1),
+                (Rtype::Rrsig, 1),
+                (Rtype::Dnskey, 2),
+                (Rtype::Cds, 1),
+                (Rtype::Cdnskey, 1),
+            ]
+        );
+
+        keys.start_zsk_double_signature(
+            1,
+            TestKey::zsk(3),
+            &params(),
+            Serial(1000),
+        );
+        keys.update_records::<_, _, Vec<u8>>(
+            &mut records,
+            &apex,
+            3600,
+            Serial(1000),
+        )
+        .unwrap();
+        assert_eq!(
+            rtypes(&records),
+            [
+                (Rtype::A, 1),
+                (Rtype::Rrsig, 1),
+                (Rtype::Dnskey, 3),
+                (Rtype::Cds, 1),
+                (Rtype::Cdnskey, 1),
+            ]
+        );
+
+        keys.update_records::<_, _, Vec<u8>>(
+            &mut records,
+            &apex,
+            3600,
+            Serial(1000 + 300 + 86400),
+        )
+        .unwrap();
+        assert_eq!(
+            rtypes(&records),
+            [
+                (Rtype::A, 1),
+                (Rtype::Dnskey, 2),
+                (Rtype::Cds, 1),
+                (Rtype::Cdnskey, 1)
+            ]
+        );
+    }
+}
@@ -0,0 +1,1633 @@
+//! Trust anchors.
+//!
+//! A trust anchor is a DS or DNSKEY record for a zone whose authenticity
+//! has been established out of band. Validation of a chain of trust starts
+//! at such an anchor. Typically, the only anchor needed is the one for the
+//! root zone which is available via [`TrustAnchor::root`].
+//!
+//! The anchors used by a validator are kept in a [`TrustAnchors`] store.
+//! It can be loaded from the root anchor XML file published by IANA as
+//! described in [RFC 7958] or from files containing DS or DNSKEY records in
+//! master file format. The store keeps its anchors up to date by following
+//! the key signing keys of the anchored zones as described in [RFC 5011]:
+//! whenever a validated DNSKEY RRset is passed to [`TrustAnchors::update`],
+//! new keys are added after a hold-down time and revoked keys are removed.
+//! The resulting state can be written out and later read back in again.
+//!
+//! [RFC 5011]: https://tools.ietf.org/html/rfc5011
+//! [RFC 7958]: https://tools.ietf.org/html/rfc7958
+
+use super::rrset::{ds_matches_key, verify_dnskeys_with, RrsetError};
+use super::RrsigExt;
+use crate::base::iana::{DigestAlg, Rtype, SecAlg};
+use crate::base::name::{Dname, ToDname};
+use crate::base::record::Record;
+use crate::base::serial::Serial;
+use crate::rdata::{Dnskey, Ds, Rrsig};
+use crate::utils::base64;
+use core::str::FromStr;
+use std::borrow::ToOwned;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+#[cfg(feature = "bytes")]
+use std::string::String;
+use std::vec::Vec;
+use std::{cmp, error, fmt, slice};
+
+//------------ Module Configuration ------------------------------------------
+
+/// The default add and remove hold-down time in seconds.
+///
+/// This is 30 days as required by [RFC 5011, Section 2.4.1].
+///
+/// [RFC 5011, Section 2.4.1]: https://tools.ietf.org/html/rfc5011#section-2.4.1
+const DEFAULT_HOLD_DOWN: u32 = 30 * 86400;
+
+/// The flag of a DNSKEY record marking a key as revoked.
+const REVOKE_FLAG: u16 = 0x0080;
+
+//------------ TrustAnchor ---------------------------------------------------
+
+/// A trust anchor for a zone.
+///
+/// The anchor consists of the apex name of the zone and a set of DS and
+/// DNSKEY records. A DNSKEY RRset for the zone is trusted if it is signed by
+/// one of the keys either contained in the anchor directly or referred to by
+/// one of its DS records.
+///
+/// In addition, the anchor tracks the key signing keys it has seen in the
+/// zone’s DNSKEY RRset according to [RFC 5011]. These keys are available
+/// via [`keys`][Self::keys]. Once a key is tracked, it is trusted only if
+/// its state says so, regardless of the DS and DNSKEY records.
+///
+/// [RFC 5011]: https://tools.ietf.org/html/rfc5011
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TrustAnchor {
+    /// The apex of the zone.
+    owner: Dname<Vec<u8>>,
+
+    /// The DS records of the anchor.
+    ds: Vec<Ds<Vec<u8>>>,
+
+    /// The DNSKEY records of the anchor.
+    dnskeys: Vec<Dnskey<Vec<u8>>>,
+
+    /// The keys tracked via RFC 5011.
+    keys: Vec<AnchorKey>,
+}
+
+impl TrustAnchor {
+    /// Creates a new, empty trust anchor for the given zone.
+    pub fn new(owner: Dname<Vec<u8>>) -> Self {
+        TrustAnchor {
+            owner,
+            ds: Vec::new(),
+            dnskeys: Vec::new(),
+            keys: Vec::new(),
+        }
+    }
+
+    /// Creates the trust anchor for the root zone.
+    ///
+    /// The anchor contains the DS records for the root zone KSKs published
+    /// by IANA, i.e., KSK-2017 with key tag 20326 and KSK-2024 with key tag
+    /// 38696.
+    pub fn root() -> Self {
+        let mut res = Self::new(Dname::root_vec());
+        res.add_ds(Ds::new(
+            20326,
+            SecAlg::RsaSha256,
+            DigestAlg::Sha256,
+            [
+                0xe0, 0x6d, 0x44, 0xb8, 0x0b, 0x8f, 0x1d, 0x39, 0xa9, 0x5c,
+                0x0b, 0x0d, 0x7c, 0x65, 0xd0, 0x84, 0x58, 0xe8, 0x80, 0x40,
+                0x9b, 0xbc, 0x68, 0x34, 0x57, 0x10, 0x42, 0x37, 0xc7, 0xf8,
+                0xec, 0x8d,
+            ]
+            .as_ref()
+            .into(),
+        ));
+        res.add_ds(Ds::new(
+            38696,
+            SecAlg::RsaSha256,
+            DigestAlg::Sha256,
+            [
+                0x68, 0x3d, 0x2d, 0x0a, 0xcb, 0x8c, 0x9b, 0x71, 0x2a, 0x19,
+                0x48, 0xb2, 0x7f, 0x74, 0x12, 0x19, 0x29, 0x8d, 0x0a, 0x45,
+                0x0d, 0x61, 0x2c, 0x48, 0x3a, 0xf4, 0x44, 0xa4, 0xc0, 0xfb,
+                0x2b, 0x16,
+            ]
+            .as_ref()
+            .into(),
+        ));
+        res
+    }
+
+    /// Returns the apex name of the zone of the anchor.
+    pub fn owner(&self) -> &Dname<Vec<u8>> {
+        &self.owner
+    }
+
+    /// Returns the DS records of the anchor.
+    pub fn ds(&self) -> &[Ds<Vec<u8>>] {
+        &self.ds
+    }
+
+    /// Returns the DNSKEY records of the anchor.
+    pub fn dnskeys(&self) -> &[Dnskey<Vec<u8>>] {
+        &self.dnskeys
+    }
+
+    /// Returns the keys tracked by the anchor.
+    pub fn keys(&self) -> &[AnchorKey] {
+        &self.keys
+    }
+
+    /// Returns whether the anchor contains neither DS nor DNSKEY records.
+    ///
+    /// An anchor without any records can still have tracked keys.
+    pub fn is_empty(&self) -> bool {
+        self.ds.is_empty() && self.dnskeys.is_empty()
+    }
+
+    /// Adds a DS record to the anchor.
+    pub fn add_ds(&mut self, ds: Ds<Vec<u8>>) {
+        if !self.ds.contains(&ds) {
+            self.ds.push(ds)
+        }
+    }
+
+    /// Adds a DNSKEY record to the anchor.
+    pub fn add_dnskey(&mut self, key: Dnskey<Vec<u8>>) {
+        if !self.dnskeys.contains(&key) {
+            self.dnskeys.push(key)
+        }
+    }
+
+    /// Returns whether the anchor covers the given name.
+    ///
+    /// This is the case if the name is at or below the apex of the anchor.
+    pub fn covers(&self, name: &impl ToDname) -> bool {
+        name.ends_with(&self.owner)
+    }
+
+    /// Adds a tracked key to the anchor.
+    ///
+    /// If there already is a tracked key for the same public key, it is
+    /// replaced.
+    pub fn add_key(&mut self, key: AnchorKey) {
+        match self.keys.iter_mut().find(|item| item.matches(&key.key)) {
+            Some(item) => *item = key,
+            None => self.keys.push(key),
+        }
+    }
+
+    /// Returns whether `key` is trusted by the anchor.
+    ///
+    /// A revoked key is never trusted. If the key is tracked, it is trusted
+    /// if its state is [`KeyState::Valid`] or [`KeyState::Missing`].
+    /// Otherwise it is trusted if it is one of the anchor’s DNSKEY records
+    /// or is referred to by one of its DS records.
+    pub fn trusts_key<Octets: AsRef<[u8]>>(
+        &self,
+        key: &Dnskey<Octets>,
+    ) -> bool {
+        if key.is_revoked() {
+            return false;
+        }
+        match self.keys.iter().find(|item| item.matches(key)) {
+            Some(item) => item.state.is_trusted(),
+            None => self.configured(key),
+        }
+    }
+
+    /// Returns whether `key` is trusted via the DS or DNSKEY records.
+    fn configured<Octets: AsRef<[u8]>>(&self, key: &Dnskey<Octets>) -> bool {
+        self.dnskeys.iter().any(|item| item == key)
+            || self
+                .ds
+                .iter()
+                .any(|ds| ds_matches_key(ds, &self.owner, key))
+    }
+
+    /// Removes the DS and DNSKEY records referring to `key`.
+    fn remove_configured(&mut self, key: &Dnskey<Vec<u8>>) {
+        let owner = &self.owner;
+        self.ds.retain(|ds| !ds_matches_key(ds, owner, key));
+        self.dnskeys.retain(|item| item != key);
+    }
+
+    /// Verifies the DNSKEY RRset of the anchor’s zone.
+    ///
+    /// The RRset is valid if it has been signed by one of the keys trusted
+    /// by the anchor and the signature is valid at `now`. The owner of the
+    /// records needs to be the apex of the anchor.
+    ///
+    /// Upon success, returns the signature that verified the RRset.
+    pub fn verify_dnskeys<'s, N, Octets, SigOcts, SigName>(
+        &self,
+        dnskeys: &mut [Record<N, Dnskey<Octets>>],
+        rrsigs: impl IntoIterator<Item = &'s Rrsig<SigOcts, SigName>>,
+        now: Serial,
+    ) -> Result<&'s Rrsig<SigOcts, SigName>, RrsetError>
+    where
+        N: ToDname,
+        Octets: AsRef<[u8]> + Clone,
+        SigOcts: AsRef<[u8]> + 's,
+        SigName: ToDname + 's,
+    {
+        verify_dnskeys_with(dnskeys, rrsigs, now, |owner, key| {
+            owner.name_eq(&self.owner) && self.trusts_key(key)
+        })
+    }
+
+    /// Updates the anchor from the zone’s DNSKEY RRset.
+    ///
+    /// The RRset is first verified using the keys currently trusted by the
+    /// anchor as via [`verify_dnskeys`][Self::verify_dnskeys]. If that
+    /// succeeds, the tracked keys are updated according to the state
+    /// machine of [RFC 5011, Section 4]:
+    ///
+    /// * A key signing key that isn’t tracked yet is added in state
+    ///   [`KeyState::AddPend`] unless it is trusted by the anchor’s DS or
+    ///   DNSKEY records already in which case it becomes
+    ///   [`KeyState::Valid`] right away.
+    /// * A pending key is dropped again if it disappears from the RRset and
+    ///   becomes valid once the add hold-down time has passed. The hold-down
+    ///   time is the greater of `hold_down.add` and the original TTL of the
+    ///   RRset.
+    /// * A valid key becomes [`KeyState::Missing`] if it disappears from the
+    ///   RRset and valid again if it reappears.
+    /// * A valid or missing key with the REVOKE bit set becomes
+    ///   [`KeyState::Revoked`] if it has signed the RRset itself. It is
+    ///   dropped once the remove hold-down time `hold_down.remove` has
+    ///   passed. The key is also removed from the anchor’s DS and DNSKEY
+    ///   records so that it is never trusted again. Should it reappear
+    ///   without the REVOKE bit, it is treated as a new key.
+    ///
+    /// Upon success, returns the signature that verified the RRset.
+    ///
+    /// [RFC 5011, Section 4]: https://tools.ietf.org/html/rfc5011#section-4
+    pub fn update<'s, N, Octets, SigOcts, SigName>(
+        &mut self,
+        dnskeys: &mut [Record<N, Dnskey<Octets>>],
+        rrsigs: &'s [Rrsig<SigOcts, SigName>],
+        now: Serial,
+        hold_down: HoldDown,
+    ) -> Result<&'s Rrsig<SigOcts, SigName>, RrsetError>
+    where
+        N: ToDname,
+        Octets: AsRef<[u8]> + Clone,
+        SigOcts: AsRef<[u8]> + 's,
+        SigName: ToDname + 's,
+    {
+        let sig = self.verify_dnskeys(dnskeys, rrsigs, now)?;
+        let add_hold_down = cmp::min(
+            cmp::max(hold_down.add, sig.original_ttl()),
+            0x7FFF_FFFF,
+        );
+
+        // Process revocations first.
+        let revoked: Vec<_> = dnskeys
+            .iter()
+            .map(Record::data)
+            .filter(|key| key.is_secure_entry_point() && key.is_revoked())
+            .map(owned_key)
+            .collect();
+        for key in revoked {
+            let trusted =
+                match self.keys.iter().find(|item| item.matches(&key)) {
+                    Some(item) => item.state.is_trusted(),
+                    None => self.configured(&unrevoked(&key)),
+                };
+            if trusted
+                && is_self_signed(&self.owner, &key, dnskeys, rrsigs, now)
+            {
+                self.remove_configured(&unrevoked(&key));
+                self.add_key(AnchorKey::new(
+                    key,
+                    KeyState::Revoked,
+                    now,
+                    hold_down.remove,
+                ));
+            }
+        }
+
+        // Now update the tracked keys based on which keys are present.
+        let present: Vec<_> = dnskeys
+            .iter()
+            .map(Record::data)
+            .filter(|key| {
+                key.is_zsk()
+                    && key.is_secure_entry_point()
+                    && !key.is_revoked()
+                    && key.protocol() == 3
+            })
+            .map(owned_key)
+            .collect();
+        self.keys.retain(|item| match item.state {
+            KeyState::AddPend => present.iter().any(|key| item.matches(key)),
+            KeyState::Revoked => !item.hold_down_passed(now),
+            _ => true,
+        });
+        for item in &mut self.keys {
+            let is_present = present.iter().any(|key| item.matches(key));
+            let state = match item.state {
+                KeyState::AddPend if item.hold_down_passed(now) => {
+                    KeyState::Valid
+                }
+                KeyState::Valid if !is_present => KeyState::Missing,
+                KeyState::Missing if is_present => KeyState::Valid,
+                _ => continue,
+            };
+            *item = AnchorKey::new(item.key.clone(), state, now, 0);
+        }
+        for key in present {
+            if self.keys.iter().any(|item| item.matches(&key)) {
+                continue;
+            }
+            let item = if self.configured(&key) {
+                AnchorKey::new(key, KeyState::Valid, now, 0)
+            } else {
+                AnchorKey::new(key, KeyState::AddPend, now, add_hold_down)
+            };
+            self.keys.push(item);
+        }
+        Ok(sig)
+    }
+
+    /// Merges the content of another anchor for the same zone into `self`.
+    fn merge(&mut self, other: TrustAnchor) {
+        for ds in other.ds {
+            self.add_ds(ds)
+        }
+        for key in other.dnskeys {
+            self.add_dnskey(key)
+        }
+        for key in other.keys {
+            self.add_key(key)
+        }
+    }
+
+    /// Writes the anchor in the format used by [`TrustAnchors`].
+    fn write_records(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let owner = if self.owner.is_root() {
+            ".".into()
+        } else {
+            format!("{}.", self.owner)
+        };
+        for ds in &self.ds {
+            write!(
+                f,
+                "{} DS {} {} {} ",
+                owner,
+                ds.key_tag(),
+                ds.algorithm().to_int(),
+                ds.digest_type().to_int()
+            )?;
+            for ch in ds.digest() {
+                write!(f, "{:02X}", ch)?;
+            }
+            writeln!(f)?;
+        }
+        for key in &self.dnskeys {
+            write_dnskey(f, &owner, key)?;
+            writeln!(f)?;
+        }
+        for key in &self.keys {
+            write_dnskey(f, &owner, &key.key)?;
+            write!(
+                f,
+                " ;state={} changed={} hold-down={}",
+                key.state, key.changed, key.hold_down
+            )?;
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+//------------ AnchorKey -----------------------------------------------------
+
+/// A key signing key tracked by a trust anchor.
+///
+/// In addition to the DNSKEY record data of the key, the value contains
+/// the key’s state as defined in [RFC 5011], the time of the last state
+/// change, and, for pending and revoked keys, the hold-down time after
+/// which the key moves on to the next state.
+///
+/// [RFC 5011]: https://tools.ietf.org/html/rfc5011
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AnchorKey {
+    /// The DNSKEY record data of the key.
+    key: Dnskey<Vec<u8>>,
+
+    /// The state of the key.
+    state: KeyState,
+
+    /// The time the key entered its current state.
+    changed: Serial,
+
+    /// The hold-down time in seconds, counting from `changed`.
+    hold_down: u32,
+}
+
+impl AnchorKey {
+    /// Creates a new tracked key from its components.
+    ///
+    /// # Panics
+    ///
+    /// The method panics if `hold_down` is greater than `2^31 - 1`.
+    pub fn new(
+        key: Dnskey<Vec<u8>>,
+        state: KeyState,
+        changed: Serial,
+        hold_down: u32,
+    ) -> Self {
+        assert!(hold_down <= 0x7FFF_FFFF);
+        AnchorKey {
+            key,
+            state,
+            changed,
+            hold_down,
+        }
+    }
+
+    /// Returns the DNSKEY record data of the key.
+    pub fn key(&self) -> &Dnskey<Vec<u8>> {
+        &self.key
+    }
+
+    /// Returns the state of the key.
+    pub fn state(&self) -> KeyState {
+        self.state
+    }
+
+    /// Returns the time the key entered its current state.
+    pub fn changed(&self) -> Serial {
+        self.changed
+    }
+
+    /// Returns the hold-down time of the key in seconds.
+    pub fn hold_down(&self) -> u32 {
+        self.hold_down
+    }
+
+    /// Returns when the hold-down time of the key ends.
+    ///
+    /// Returns `None` if the key is neither pending nor revoked.
+    pub fn hold_down_end(&self) -> Option<Serial> {
+        match self.state {
+            KeyState::AddPend | KeyState::Revoked => {
+                Some(self.changed.add(self.hold_down))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns whether the hold-down time has passed at `now`.
+    fn hold_down_passed(&self, now: Serial) -> bool {
+        match self.hold_down_end() {
+            Some(end) => end <= now,
+            None => false,
+        }
+    }
+
+    /// Returns whether `key` is the same key as this one.
+    ///
+    /// The REVOKE bit is ignored for the comparison.
+    fn matches<Octets: AsRef<[u8]>>(&self, key: &Dnskey<Octets>) -> bool {
+        self.key.flags() | REVOKE_FLAG == key.flags() | REVOKE_FLAG
+            && self.key.protocol() == key.protocol()
+            && self.key.algorithm() == key.algorithm()
+            && self.key.public_key().as_slice() == key.public_key().as_ref()
+    }
+}
+
+//------------ KeyState ------------------------------------------------------
+
+/// The state of a key tracked by a trust anchor.
+///
+/// The states are those defined in [RFC 5011, Section 4]. The states
+/// _Start_ and _Removed_ are not represented since keys in these states
+/// aren’t tracked.
+///
+/// [RFC 5011, Section 4]: https://tools.ietf.org/html/rfc5011#section-4
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum KeyState {
+    /// The key has been seen but the add hold-down time hasn’t passed yet.
+    AddPend,
+
+    /// The key is a trusted key.
+    Valid,
+
+    /// The key is trusted but has disappeared from the DNSKEY RRset.
+    Missing,
+
+    /// The key has been revoked and must not be used any more.
+    Revoked,
+}
+
+impl KeyState {
+    /// Returns whether a key in this state is trusted.
+    pub fn is_trusted(self) -> bool {
+        matches!(self, KeyState::Valid | KeyState::Missing)
+    }
+}
+
+//--- FromStr and Display
+
+impl FromStr for KeyState {
+    type Err = AnchorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("ADDPEND") {
+            Ok(KeyState::AddPend)
+        } else if s.eq_ignore_ascii_case("VALID") {
+            Ok(KeyState::Valid)
+        } else if s.eq_ignore_ascii_case("MISSING") {
+            Ok(KeyState::Missing)
+        } else if s.eq_ignore_ascii_case("REVOKED") {
+            Ok(KeyState::Revoked)
+        } else {
+            Err(AnchorError::Syntax)
+        }
+    }
+}
+
+impl fmt::Display for KeyState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            KeyState::AddPend => "ADDPEND",
+            KeyState::Valid => "VALID",
+            KeyState::Missing => "MISSING",
+            KeyState::Revoked => "REVOKED",
+        })
+    }
+}
+
+//------------ HoldDown ------------------------------------------------------
+
+/// The hold-down times used for updating trust anchors.
+///
+/// Both times default to 30 days.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct HoldDown {
+    /// The time in seconds before a new key becomes trusted.
+    pub add: u32,
+
+    /// The time in seconds before a revoked key is forgotten.
+    pub remove: u32,
+}
+
+impl Default for HoldDown {
+    fn default() -> Self {
+        HoldDown {
+            add: DEFAULT_HOLD_DOWN,
+            remove: DEFAULT_HOLD_DOWN,
+        }
+    }
+}
+
+//------------ TrustAnchors --------------------------------------------------
+
+/// A set of trust anchors.
+///
+/// The store keeps at most one [`TrustAnchor`] per zone. Anchors can be
+/// added directly, from the IANA root anchor XML file via
+/// [`add_iana_xml`][Self::add_iana_xml], or, if the `bytes` feature is
+/// enabled, from DS and DNSKEY records in master file format via
+/// [`add_records`][Self::add_records].
+///
+/// The anchors are kept up to date according to [RFC 5011] by passing each
+/// DNSKEY RRset for the apex of an anchor to [`update`][Self::update]. The
+/// [`Display`][fmt::Display] implementation writes the complete state of
+/// the store as DS and DNSKEY records with the state of tracked keys in a
+/// comment. It can be read back in via `add_records` or, more conveniently,
+/// written to and loaded from a file via [`save`][Self::save] and
+/// [`load`][Self::load].
+///
+/// [RFC 5011]: https://tools.ietf.org/html/rfc5011
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TrustAnchors {
+    /// The anchors.
+    anchors: Vec<TrustAnchor>,
+
+    /// The hold-down times for updates.
+    hold_down: HoldDown,
+}
+
+impl TrustAnchors {
+    /// Creates a new, empty store.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Creates a store containing only the root anchor.
+    ///
+    /// See [`TrustAnchor::root`] for the anchor.
+    pub fn root() -> Self {
+        TrustAnchor::root().into()
+    }
+
+    /// Creates a store from the content of the IANA root anchor XML file.
+    ///
+    /// See [`add_iana_xml`][Self::add_iana_xml] for details.
+    pub fn from_iana_xml(
+        xml: &str,
+        now: Serial,
+    ) -> Result<Self, AnchorError> {
+        let mut res = Self::new();
+        res.add_iana_xml(xml, now)?;
+        Ok(res)
+    }
+
+    /// Returns the hold-down times used by the store.
+    pub fn hold_down(&self) -> HoldDown {
+        self.hold_down
+    }
+
+    /// Sets the hold-down times used by the store.
+    pub fn set_hold_down(&mut self, hold_down: HoldDown) {
+        self.hold_down = hold_down
+    }
+
+    /// Returns the number of anchors in the store.
+    pub fn len(&self) -> usize {
+        self.anchors.len()
+    }
+
+    /// Returns whether the store is empty.
+    pub fn is_empty(&self) -> bool {
+        self.anchors.is_empty()
+    }
+
+    /// Returns an iterator over the anchors in the store.
+    pub fn iter(&self) -> slice::Iter<'_, TrustAnchor> {
+        self.anchors.iter()
+    }
+
+    /// Adds an anchor to the store.
+    ///
+    /// If there already is an anchor for the same zone, the records and
+    /// tracked keys of `anchor` are merged into it.
+    pub fn insert(&mut self, anchor: TrustAnchor) {
+        match self
+            .anchors
+            .iter_mut()
+            .find(|item| item.owner.name_eq(&anchor.owner))
+        {
+            Some(item) => item.merge(anchor),
+            None => self.anchors.push(anchor),
+        }
+    }
+
+    /// Returns the anchor for the zone with the given apex.
+    pub fn get(&self, apex: &impl ToDname) -> Option<&TrustAnchor> {
+        self.anchors.iter().find(|item| item.owner.name_eq(apex))
+    }
+
+    /// Returns the closest anchor covering `name`.
+    pub fn find(&self, name: &impl ToDname) -> Option<&TrustAnchor> {
+        self.anchors
+            .iter()
+            .filter(|anchor| anchor.covers(name))
+            .max_by_key(|anchor| anchor.owner.label_count())
+    }
+
+    /// Verifies a DNSKEY RRset and updates the matching anchor.
+    ///
+    /// The owner of the records must be the apex of one of the anchors.
+    /// See [`TrustAnchor::update`] for details. The store’s hold-down times
+    /// are used.
+    pub fn update<'s, N, Octets, SigOcts, SigName>(
+        &mut self,
+        dnskeys: &mut [Record<N, Dnskey<Octets>>],
+        rrsigs: &'s [Rrsig<SigOcts, SigName>],
+        now: Serial,
+    ) -> Result<&'s Rrsig<SigOcts, SigName>, RrsetError>
+    where
+        N: ToDname,
+        Octets: AsRef<[u8]> + Clone,
+        SigOcts: AsRef<[u8]> + 's,
+        SigName: ToDname + 's,
+    {
+        let hold_down = self.hold_down;
+        let anchor = match dnskeys.first() {
+            Some(record) => self
+                .anchors
+                .iter_mut()
+                .find(|item| item.owner.name_eq(record.owner())),
+            None => None,
+        };
+        match anchor {
+            Some(anchor) => anchor.update(dnskeys, rrsigs, now, hold_down),
+            None => Err(RrsetError::NoKey),
+        }
+    }
+
+    /// Adds the anchor from the content of the IANA root anchor XML file.
+    ///
+    /// The format of the file is defined in [RFC 7958]. Only those digests
+    /// whose validity period includes `now` are added as DS records. The
+    /// XML is not checked for well-formedness beyond what is necessary to
+    /// find the relevant elements.
+    ///
+    /// [RFC 7958]: https://tools.ietf.org/html/rfc7958
+    pub fn add_iana_xml(
+        &mut self,
+        xml: &str,
+        now: Serial,
+    ) -> Result<(), AnchorError> {
+        let (_, body, _) =
+            xml_element(xml, "TrustAnchor").ok_or(AnchorError::Syntax)?;
+        let (_, zone, _) =
+            xml_element(body, "Zone").ok_or(AnchorError::Syntax)?;
+        let mut anchor = TrustAnchor::new(parse_name(zone.trim())?);
+        let mut rest = body;
+        while let Some((attrs, content, tail)) =
+            xml_element(rest, "KeyDigest")
+        {
+            rest = tail;
+            let valid_from = xml_attr(attrs, "validFrom")
+                .and_then(parse_datetime)
+                .ok_or(AnchorError::Syntax)?;
+            let valid_until = match xml_attr(attrs, "validUntil") {
+                Some(value) => {
+                    Some(parse_datetime(value).ok_or(AnchorError::Syntax)?)
+                }
+                None => None,
+            };
+            if valid_from > now
+                || valid_until.map(|until| until <= now).unwrap_or(false)
+            {
+                continue;
+            }
+            let key_tag = xml_value(content, "KeyTag")?;
+            let algorithm = xml_value(content, "Algorithm")?;
+            let digest_type = xml_value(content, "DigestType")?;
+            let digest = xml_element(content, "Digest")
+                .and_then(|(_, digest, _)| decode_hex(digest))
+                .ok_or(AnchorError::Syntax)?;
+            anchor.add_ds(Ds::new(
+                key_tag,
+                SecAlg::from_int(algorithm),
+                DigestAlg::from_int(digest_type),
+                digest,
+            ));
+        }
+        if anchor.is_empty() {
+            return Err(AnchorError::NoAnchor);
+        }
+        self.insert(anchor);
+        Ok(())
+    }
+
+    /// Writes the store into a file.
+    ///
+    /// The file is first written under a temporary name and then renamed
+    /// so that an existing file is replaced atomically.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), io::Error> {
+        let path = path.as_ref();
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let mut file = File::create(&tmp)?;
+        write!(file, "{}", self)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl TrustAnchors {
+    /// Loads a store from a file.
+    ///
+    /// See [`add_file`][Self::add_file] for the supported formats.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, io::Error> {
+        let mut res = Self::new();
+        res.add_file(path)?;
+        Ok(res)
+    }
+
+    /// Adds the anchors from a file.
+    ///
+    /// If the file starts with a `<`, it is considered to be an IANA root
+    /// anchor XML file and read via [`add_iana_xml`][Self::add_iana_xml]
+    /// using the current time. Otherwise it is read via
+    /// [`add_records`][Self::add_records].
+    pub fn add_file(
+        &mut self,
+        path: impl AsRef<Path>,
+    ) -> Result<(), io::Error> {
+        let content = fs::read_to_string(path)?;
+        let res = if content.trim_start().starts_with('<') {
+            self.add_iana_xml(&content, Serial::now())
+        } else {
+            self.add_records(&content)
+        };
+        res.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Adds anchors from DS and DNSKEY records in master file format.
+    ///
+    /// Each record needs to have an explicit owner name which is taken as
+    /// the apex of the anchor. TTL and class are optional and ignored.
+    /// Records may span multiple lines using parentheses. A `$TTL` control
+    /// entry is ignored, all other control entries and records of other
+    /// types are rejected.
+    ///
+    /// A DNSKEY record whose comment contains a `state` entry is added as a
+    /// tracked key. Such comments have the form
+    /// `;state=<state> changed=<time> hold-down=<seconds>` and are written
+    /// by the [`Display`][fmt::Display] implementation of the store.
+    pub fn add_records(&mut self, s: &str) -> Result<(), AnchorError> {
+        let mut tokens = Vec::new();
+        let mut comment = String::new();
+        let mut depth = 0usize;
+        for line in s.lines() {
+            let (data, note) = match line.find(';') {
+                Some(pos) => (&line[..pos], &line[pos + 1..]),
+                None => (line, ""),
+            };
+            for token in data.split(|ch: char| {
+                ch.is_whitespace() || ch == '(' || ch == ')'
+            }) {
+                if !token.is_empty() {
+                    tokens.push(token)
+                }
+            }
+            for ch in data.chars() {
+                if ch == '(' {
+                    depth += 1
+                } else if ch == ')' {
+                    depth =
+                        depth.checked_sub(1).ok_or(AnchorError::Syntax)?;
+                }
+            }
+            comment.push_str(note);
+            comment.push(' ');
+            if depth == 0 {
+                if !tokens.is_empty() {
+                    self.add_record(&tokens, &comment)?;
+                    tokens.clear();
+                }
+                comment.clear();
+            }
+        }
+        if depth != 0 {
+            return Err(AnchorError::Syntax);
+        }
+        Ok(())
+    }
+
+    /// Adds a single record given as its tokens and its comment.
+    fn add_record(
+        &mut self,
+        tokens: &[&str],
+        comment: &str,
+    ) -> Result<(), AnchorError> {
+        if tokens[0].starts_with('$') {
+            if tokens[0].eq_ignore_ascii_case("$TTL") {
+                return Ok(());
+            }
+            return Err(AnchorError::UnexpectedRecord);
+        }
+        let owner = parse_name(tokens[0])?;
+        let pos = tokens
+            .iter()
+            .position(|token| {
+                token.eq_ignore_ascii_case("DS")
+                    || token.eq_ignore_ascii_case("DNSKEY")
+            })
+            .ok_or(AnchorError::UnexpectedRecord)?;
+        let mut anchor = TrustAnchor::new(owner);
+        let mut fields = tokens[pos + 1..].iter().copied();
+        if tokens[pos].eq_ignore_ascii_case("DS") {
+            let key_tag = parse_field(fields.next())?;
+            let algorithm = parse_algorithm(fields.next())?;
+            let digest_type = match fields.next().map(u8::from_str) {
+                Some(Ok(value)) => DigestAlg::from_int(value),
+                _ => return Err(AnchorError::Syntax),
+            };
+            let digest: String = fields.collect();
+            let digest = decode_hex(&digest).ok_or(AnchorError::Syntax)?;
+            anchor.add_ds(Ds::new(key_tag, algorithm, digest_type, digest));
+        } else {
+            let flags = parse_field(fields.next())?;
+            let protocol = parse_field(fields.next())?;
+            let algorithm = parse_algorithm(fields.next())?;
+            let key: String = fields.collect();
+            let key =
+                base64::decode(&key).map_err(|_| AnchorError::Syntax)?;
+            let key = Dnskey::new(flags, protocol, algorithm, key.to_vec());
+            match parse_state(comment)? {
+                Some((state, changed, hold_down)) => anchor
+                    .add_key(AnchorKey::new(key, state, changed, hold_down)),
+                None => anchor.add_dnskey(key),
+            }
+        }
+        self.insert(anchor);
+        Ok(())
+    }
+}
+
+//--- From
+
+impl From<TrustAnchor> for TrustAnchors {
+    fn from(anchor: TrustAnchor) -> Self {
+        let mut res = Self::new();
+        res.insert(anchor);
+        res
+    }
+}
+
+impl From<Vec<TrustAnchor>> for TrustAnchors {
+    fn from(anchors: Vec<TrustAnchor>) -> Self {
+        let mut res = Self::new();
+        for anchor in anchors {
+            res.insert(anchor)
+        }
+        res
+    }
+}
+
+//--- FromStr
+
+#[cfg(feature = "bytes")]
+impl FromStr for TrustAnchors {
+    type Err = AnchorError;
+
+    /// Reads a store from DS and DNSKEY records.
+    ///
+    /// See [`add_records`][Self::add_records] for the format.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut res = Self::new();
+        res.add_records(s)?;
+        Ok(res)
+    }
+}
+
+//--- IntoIterator
+
+impl<'a> IntoIterator for &'a TrustAnchors {
+    type Item = &'a TrustAnchor;
+    type IntoIter = slice::Iter<'a, TrustAnchor>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+//--- Display
+
+impl fmt::Display for TrustAnchors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "; Trust anchors for DNSSEC validation.")?;
+        for anchor in &self.anchors {
+            anchor.write_records(f)?;
+        }
+        Ok(())
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Converts a DNSKEY record data into one using a vec.
+fn owned_key<Octets: AsRef<[u8]>>(key: &Dnskey<Octets>) -> Dnskey<Vec<u8>> {
+    Dnskey::new(
+        key.flags(),
+        key.protocol(),
+        key.algorithm(),
+        key.public_key().as_ref().into(),
+    )
+}
+
+/// Returns the given key with the REVOKE bit cleared.
+fn unrevoked(key: &Dnskey<Vec<u8>>) -> Dnskey<Vec<u8>> {
+    Dnskey::new(
+        key.flags() & !REVOKE_FLAG,
+        key.protocol(),
+        key.algorithm(),
+        key.public_key().clone(),
+    )
+}
+
+/// Returns whether the DNSKEY RRset has been signed by `key`.
+///
+/// This is used to check that a revoked key has revoked itself as required
+/// by [RFC 5011, Section 2.1]. Since revoked keys are otherwise not to be
+/// used for verification, we need to do this separately.
+///
+/// [RFC 5011, Section 2.1]: https://tools.ietf.org/html/rfc5011#section-2.1
+fn is_self_signed<N, Octets, SigOcts, SigName>(
+    owner: &Dname<Vec<u8>>,
+    key: &Dnskey<Vec<u8>>,
+    dnskeys: &mut [Record<N, Dnskey<Octets>>],
+    rrsigs: &[Rrsig<SigOcts, SigName>],
+    now: Serial,
+) -> bool
+where
+    N: ToDname,
+    Octets: AsRef<[u8]>,
+    SigOcts: AsRef<[u8]>,
+    SigName: ToDname,
+{
+    rrsigs.iter().any(|sig| {
+        if sig.type_covered() != Rtype::Dnskey
+            || !sig.signer_name().name_eq(owner)
+            || sig.algorithm() != key.algorithm()
+            || sig.key_tag() != key.key_tag()
+            || !(sig.inception() <= now && now <= sig.expiration())
+        {
+            return false;
+        }
+        let mut buf = Vec::new();
+        sig.signed_data(&mut buf, dnskeys).is_ok()
+            && sig.verify_signed_data(key, &buf).is_ok()
+    })
+}
+
+/// Writes the owner and data of a DNSKEY record.
+fn write_dnskey(
+    f: &mut fmt::Formatter,
+    owner: &str,
+    key: &Dnskey<Vec<u8>>,
+) -> fmt::Result {
+    write!(
+        f,
+        "{} DNSKEY {} {} {} ",
+        owner,
+        key.flags(),
+        key.protocol(),
+        key.algorithm().to_int()
+    )?;
+    base64::display(key.public_key(), f)
+}
+
+/// Finds the first element `name` in `xml`.
+///
+/// Returns the attributes of the start tag, the content of the element,
+/// and the remainder of `xml` after the element.
+fn xml_element<'a>(
+    xml: &'a str,
+    name: &str,
+) -> Option<(&'a str, &'a str, &'a str)> {
+    let mut pos = 0;
+    loop {
+        let start = pos + xml[pos..].find('<')? + 1;
+        pos = start;
+        let tag = &xml[start..];
+        if !tag.starts_with(name) {
+            continue;
+        }
+        let tag = &tag[name.len()..];
+        match tag.chars().next() {
+            Some(ch) if ch == '>' || ch == '/' || ch.is_whitespace() => {}
+            _ => continue,
+        }
+        let end = tag.find('>')?;
+        if tag[..end].ends_with('/') {
+            return Some((&tag[..end - 1], "", &tag[end + 1..]));
+        }
+        let attrs = &tag[..end];
+        let tag = &tag[end + 1..];
+        let close = format!("</{}>", name);
+        let end = tag.find(&close)?;
+        return Some((attrs, &tag[..end], &tag[end + close.len()..]));
+    }
+}
+
+/// Returns the value of the attribute `name`.
+fn xml_attr<'a>(attrs: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attrs;
+    loop {
+        let pos = rest.find(name)?;
+        let before = rest[..pos].chars().next_back();
+        let tail = rest[pos + name.len()..].trim_start();
+        rest = &rest[pos + name.len()..];
+        if !before.map(char::is_whitespace).unwrap_or(false)
+            || !tail.starts_with('=')
+        {
+            continue;
+        }
+        let tail = tail[1..].trim_start();
+        let quote = tail.chars().next()?;
+        if quote != '"' && quote != '\'' {
+            return None;
+        }
+        let tail = &tail[1..];
+        return tail.find(quote).map(|end| &tail[..end]);
+    }
+}
+
+/// Returns the content of the element `name` parsed into a value.
+fn xml_value<T: FromStr>(xml: &str, name: &str) -> Result<T, AnchorError> {
+    xml_element(xml, name)
+        .and_then(|(_, value, _)| T::from_str(value.trim()).ok())
+        .ok_or(AnchorError::Syntax)
+}
+
+/// Parses an RFC 3339 date and time into a Unix time.
+///
+/// Fractional seconds are accepted but ignored.
+fn parse_datetime(s: &str) -> Option<Serial> {
+    fn num(s: &str) -> Option<i64> {
+        if s.is_empty() || !s.bytes().all(|ch| ch.is_ascii_digit()) {
+            return None;
+        }
+        i64::from_str(s).ok()
+    }
+
+    // All valid values are ASCII, which makes slicing by byte safe.
+    if s.len() < 20 || !s.is_ascii() {
+        return None;
+    }
+    let (datetime, zone) = s.split_at(19);
+    let bytes = datetime.as_bytes();
+    if bytes[4] != b'-'
+        || bytes[7] != b'-'
+        || (bytes[10] != b'T' && bytes[10] != b't' && bytes[10] != b' ')
+        || bytes[13] != b':'
+        || bytes[16] != b':'
+    {
+        return None;
+    }
+    let year = num(&datetime[0..4])?;
+    let month = num(&datetime[5..7])?;
+    let day = num(&datetime[8..10])?;
+    let hour = num(&datetime[11..13])?;
+    let minute = num(&datetime[14..16])?;
+    let second = num(&datetime[17..19])?;
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 60
+    {
+        return None;
+    }
+
+    let zone = match zone.strip_prefix('.') {
+        Some(zone) => zone.trim_start_matches(|ch: char| ch.is_ascii_digit()),
+        None => zone,
+    };
+    let offset = if zone == "Z" || zone == "z" {
+        0
+    } else {
+        let sign = match zone.chars().next()? {
+            '+' => 1,
+            '-' => -1,
+            _ => return None,
+        };
+        if zone.len() != 6 || &zone[3..4] != ":" {
+            return None;
+        }
+        sign * (num(&zone[1..3])? * 3600 + num(&zone[4..6])? * 60)
+    };
+
+    // Days since the epoch via the algorithm from
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let yoe = year - era * 400;
+    let doy = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146_097 + doe - 719_468;
+
+    let time = days * 86400 + hour * 3600 + minute * 60 + second - offset;
+    if time < 0 || time > i64::from(u32::MAX) {
+        return None;
+    }
+    Some(Serial(time as u32))
+}
+
+/// Parses a domain name.
+///
+/// Unlike `Dname::from_str`, this accepts a single dot as the root name.
+fn parse_name(s: &str) -> Result<Dname<Vec<u8>>, AnchorError> {
+    if s == "." {
+        Ok(Dname::root_vec())
+    } else {
+        Dname::from_str(s).map_err(|_| AnchorError::Syntax)
+    }
+}
+
+/// Decodes a hex string, ignoring any white space.
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    let digits: Vec<_> = s
+        .chars()
+        .filter(|ch| !ch.is_whitespace())
+        .map(|ch| ch.to_digit(16))
+        .collect::<Option<_>>()?;
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+    Some(
+        digits
+            .chunks(2)
+            .map(|pair| (pair[0] << 4 | pair[1]) as u8)
+            .collect(),
+    )
+}
+
+/// Parses a numeric field of a record.
+#[cfg(feature = "bytes")]
+fn parse_field<T: FromStr>(field: Option<&str>) -> Result<T, AnchorError> {
+    field
+        .and_then(|field| T::from_str(field).ok())
+        .ok_or(AnchorError::Syntax)
+}
+
+/// Parses the algorithm field of a record.
+#[cfg(feature = "bytes")]
+fn parse_algorithm(field: Option<&str>) -> Result<SecAlg, AnchorError> {
+    // SecAlg::from_str doesn’t resolve numbers to known algorithms, so try
+    // those first.
+    let field = field.ok_or(AnchorError::Syntax)?;
+    match u8::from_str(field) {
+        Ok(alg) => Ok(SecAlg::from_int(alg)),
+        Err(_) => SecAlg::from_str(field).map_err(|_| AnchorError::Syntax),
+    }
+}
+
+/// Parses the state of a tracked key from the comment of its record.
+#[cfg(feature = "bytes")]
+fn parse_state(
+    comment: &str,
+) -> Result<Option<(KeyState, Serial, u32)>, AnchorError> {
+    let mut state = None;
+    let mut changed = None;
+    let mut hold_down = 0;
+    for item in comment.split_whitespace() {
+        let mut item = item.splitn(2, '=');
+        let (key, value) = match (item.next(), item.next()) {
+            (Some(key), Some(value)) => (key, value),
+            _ => continue,
+        };
+        match key {
+            "state" => state = Some(KeyState::from_str(value)?),
+            "changed" => changed = Some(parse_field(Some(value))?),
+            "hold-down" => hold_down = parse_field(Some(value))?,
+            _ => {}
+        }
+    }
+    match (state, changed) {
+        (Some(state), Some(changed)) if hold_down <= 0x7FFF_FFFF => {
+            Ok(Some((state, changed, hold_down)))
+        }
+        (None, _) => Ok(None),
+        _ => Err(AnchorError::Syntax),
+    }
+}
+
+//============ Error Types ===================================================
+
+//------------ AnchorError ---------------------------------------------------
+
+/// Trust anchors could not be read.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum AnchorError {
+    /// The data was malformed.
+    Syntax,
+
+    /// There was a record other than a DS or DNSKEY record.
+    UnexpectedRecord,
+
+    /// The data did not contain a currently valid anchor.
+    NoAnchor,
+}
+
+//--- Display and Error
+
+impl fmt::Display for AnchorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AnchorError::Syntax => f.write_str("malformed trust anchor"),
+            AnchorError::UnexpectedRecord => {
+                f.write_str("unexpected record in trust anchor file")
+            }
+            AnchorError::NoAnchor => f.write_str("no valid trust anchor"),
+        }
+    }
+}
+
+impl error::Error for AnchorError {}
+
+//============ Test ==========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::base64;
+    use std::str::FromStr;
+    use std::string::ToString;
+
+    #[test]
+    fn root_trusts_ksk_2017() {
+        let key = Dnskey::new(
+            257,
+            3,
+            SecAlg::RsaSha256,
+            Vec::from(
+                base64::decode(
+                    "AwEAAaz/tAm8yTn4Mfeh5eyI96WSVexTBAvkMgJzkKTOiW1vkIbzxeF3+/\
+                    4RgWOq7HrxRixHlFlExOLAJr5emLvN7SWXgnLh4+B5xQlNVz8Og8kvArMt\
+                    NROxVQuCaSnIDdD5LKyWbRd2n9WGe2R8PzgCmr3EgVLrjyBxWezF0jLHwV\
+                    N8efS3rCj/EWgvIWgb9tarpVUDK/b58Da+sqqls3eNbuv7pr+eoZG+SrDK\
+                    6nWeL3c6H5Apxz7LjVc1uTIdsIXxuOLYA4/ilBmSVIzuDWfdRUfhHdY6+c\
+                    n8HFRm+2hM8AnXGXws9555KrUB5qihylGa8subX2Nn6UwNR1AkUTV74bU=",
+                )
+                .unwrap()
+                .as_ref(),
+            ),
+        );
+        let anchor = TrustAnchor::root();
+        assert!(anchor.trusts_key(&key));
+        assert!(anchor
+            .covers(&Dname::<Vec<u8>>::from_str("nlnetlabs.nl").unwrap()));
+
+        let mut anchor =
+            TrustAnchor::new(Dname::from_str("example.com").unwrap());
+        assert!(!anchor.trusts_key(&key));
+        anchor.add_dnskey(key.clone());
+        assert!(anchor.trusts_key(&key));
+        assert!(!anchor.covers(&Dname::<Vec<u8>>::from_str("nl").unwrap()));
+    }
+
+    const ROOT_ANCHORS: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<TrustAnchor id="38FD26FB-D2A5-4BCD-9A75-BB9A7A8C5D5D" source="http://data.iana.org/root-anchors/root-anchors.xml">
+<Zone>.</Zone>
+<KeyDigest id="Kjqmt7v" validFrom="2010-07-15T00:00:00+00:00" validUntil="2019-01-11T00:00:00+00:00">
+<KeyTag>19036</KeyTag>
+<Algorithm>8</Algorithm>
+<DigestType>2</DigestType>
+<Digest>49AAC11D7B6F6446702E54A1607371607A1A41855200FD2CE1CDDE32F24E8FB5</Digest>
+</KeyDigest>
+<KeyDigest id="Klajeyz" validFrom="2017-02-02T00:00:00+00:00">
+<KeyTag>20326</KeyTag>
+<Algorithm>8</Algorithm>
+<DigestType>2</DigestType>
+<Digest>E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D</Digest>
+</KeyDigest>
+<KeyDigest id="Kmyv6jo" validFrom="2024-07-18T00:00:00+00:00">
+<KeyTag>38696</KeyTag>
+<Algorithm>8</Algorithm>
+<DigestType>2</DigestType>
+<Digest>683D2D0ACB8C9B712A1948B27F741219298D0A450D612C483AF444A4C0FB2B16</Digest>
+</KeyDigest>
+</TrustAnchor>
+"#;
+
+    #[test]
+    fn iana_xml() {
+        assert_eq!(
+            parse_datetime("2017-02-02T00:00:00+00:00"),
+            Some(Serial(1_485_993_600))
+        );
+        assert_eq!(
+            parse_datetime("2017-02-02T01:30:00.25+01:30"),
+            Some(Serial(1_485_993_600))
+        );
+        assert_eq!(parse_datetime("2017-02-02 00:00:00"), None);
+        assert_eq!(parse_datetime("2017-02-02T00:00:00+0é:0"), None);
+        assert_eq!(parse_datetime("2017-02-02T00:00:00.é"), None);
+        assert_eq!(parse_datetime("2é17-02-02T00:00:00Z"), None);
+
+        let anchors =
+            TrustAnchors::from_iana_xml(ROOT_ANCHORS, Serial(1_500_000_000))
+                .unwrap();
+        let root = anchors.get(&Dname::root_slice()).unwrap();
+        assert_eq!(
+            root.ds().iter().map(Ds::key_tag).collect::<Vec<_>>(),
+            [19036, 20326]
+        );
+
+        let anchors =
+            TrustAnchors::from_iana_xml(ROOT_ANCHORS, Serial(1_735_689_600))
+                .unwrap();
+        assert_eq!(anchors, TrustAnchors::root());
+
+        assert_eq!(
+            TrustAnchors::from_iana_xml(ROOT_ANCHORS, Serial(1_200_000_000)),
+            Err(AnchorError::NoAnchor)
+        );
+        assert_eq!(
+            TrustAnchors::from_iana_xml("<TrustAnchor>", Serial(0)),
+            Err(AnchorError::Syntax)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "bytes")]
+    fn records() {
+        // The example from RFC 4034, section 5.4.
+        let mut anchors = TrustAnchors::from_str(
+            "; Example trust anchors.\n\
+            $TTL 86400\n\
+            dskey.example.com. 86400 IN DNSKEY 256 3 5 ( AQOeiiR0GOMYkDshWoSKz9Xz\n\
+                fwJr1AYtsmx3TGkJaNXVbfi/ 2pHm822aJ5iI9BMzNXxeYCmZ\n\
+                DRD99WYwYqUSdjMmmAphXdvx egXd/M5+X7OrzKBaMbCVdFLU\n\
+                Uh6DhweJBjEVv5f2wwjM9Xzc nOf+EPbtG9DMBmADjFDc2w/r\n\
+                ljwvFw==\n\
+                ) ;  key id = 60485\n\
+            dskey.example.com. 86400 IN DS 60485 5 1 ( 2BB183AF5F22588179A53B0A\n\
+                98631FAD1A292118 )\n",
+        )
+        .unwrap();
+        assert_eq!(anchors.len(), 1);
+        let owner = Dname::<Vec<u8>>::from_str("dskey.example.com").unwrap();
+        let anchor = anchors.get(&owner).unwrap();
+        assert_eq!(anchor.ds().len(), 1);
+        assert_eq!(anchor.dnskeys().len(), 1);
+        assert_eq!(anchor.dnskeys()[0].key_tag(), 60485);
+        assert!(anchor.trusts_key(&anchor.dnskeys()[0]));
+
+        let key = Dnskey::new(257, 3, SecAlg::EcdsaP256Sha256, vec![1; 64]);
+        let mut tracked = TrustAnchor::new(owner.clone());
+        tracked.add_key(AnchorKey::new(
+            key.clone(),
+            KeyState::AddPend,
+            Serial(1000),
+            2000,
+        ));
+        anchors.insert(tracked);
+        anchors.insert(TrustAnchor::root());
+        assert_eq!(anchors.len(), 2);
+        assert!(!anchors.get(&owner).unwrap().trusts_key(&key));
+        assert_eq!(
+            anchors
+                .find(
+                    &Dname::<Vec<u8>>::from_str("www.dskey.example.com")
+                        .unwrap()
+                )
+                .unwrap()
+                .owner(),
+            &owner
+        );
+        assert_eq!(
+            TrustAnchors::from_str(&anchors.to_string()).unwrap(),
+            anchors
+        );
+
+        assert_eq!(
+            TrustAnchors::from_str("example.com. IN A 192.0.2.1"),
+            Err(AnchorError::UnexpectedRecord)
+        );
+        assert_eq!(
+            TrustAnchors::from_str("example.com. DS 60485 5 1 ( 2BB1"),
+            Err(AnchorError::Syntax)
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "sign")]
+    fn rfc5011() {
+        use crate::base::iana::Class;
+        use crate::sign::key::SigningKey;
+        use crate::sign::records::{FamilyName, SortedRecords};
+        use crate::sign::ring::Key;
+        use ring::rand::SystemRandom;
+        use ring::signature::{
+            EcdsaKeyPair, ECDSA_P256_SHA256_FIXED_SIGNING,
+        };
+
+        type Rrset = Vec<Record<Dname<Vec<u8>>, Dnskey<Vec<u8>>>>;
+        type Rrsigs = Vec<Rrsig<Vec<u8>, Dname<Vec<u8>>>>;
+
+        fn rrset(keys: &[&Key], signers: &[&Key]) -> (Rrset, Rrsigs) {
+            let apex = FamilyName::new(
+                Dname::<Vec<u8>>::from_str("example.com").unwrap(),
+                Class::In,
+            );
+            let mut records = SortedRecords::new();
+            let mut rrset = Vec::new();
+            for key in keys {
+                let record = Record::new(
+                    apex.owner().clone(),
+                    Class::In,
+                    3600,
+                    key.dnskey().unwrap(),
+                );
+                records.insert(record.clone()).unwrap();
+                rrset.push(record);
+            }
+            let mut rrsigs = Vec::new();
+            for key in signers {
+                rrsigs.extend(
+                    records
+                        .sign::<Vec<u8>, _, _>(
+                            &apex,
+                            Serial(1_000_000),
+                            Serial(1),
+                            *key,
+                        )
+                        .unwrap()
+                        .into_iter()
+                        .map(Record::into_data),
+                );
+            }
+            (rrset, rrsigs)
+        }
+
+        let rng = SystemRandom::new();
+        let pkcs8 = EcdsaKeyPair::generate_pkcs8(
+            &ECDSA_P256_SHA256_FIXED_SIGNING,
+            &rng,
+        )
+        .unwrap();
+        let alg = SecAlg::EcdsaP256Sha256;
+        let ksk1 = Key::from_pkcs8(257, alg, pkcs8.as_ref(), &rng).unwrap();
+        let revoked =
+            Key::from_pkcs8(257 | REVOKE_FLAG, alg, pkcs8.as_ref(), &rng)
+                .unwrap();
+        let ksk2 = Key::throwaway_13(257, &rng).unwrap();
+        let key1 = ksk1.dnskey().unwrap();
+        let key2 = ksk2.dnskey().unwrap();
+
+        let owner = Dname::<Vec<u8>>::from_str("example.com").unwrap();
+        let mut anchor = TrustAnchor::new(owner.clone());
+        anchor.add_ds(ksk1.ds(&owner).unwrap());
+        let mut anchors = TrustAnchors::from(anchor);
+        anchors.set_hold_down(HoldDown {
+            add: 100,
+            remove: 200,
+        });
+        let mut update = |keys: &[&Key], signers: &[&Key], now: u32| {
+            let (mut rrset, rrsigs) = rrset(keys, signers);
+            let res = anchors.update(&mut rrset, &rrsigs, Serial(now));
+            let anchor = anchors.get(&owner).unwrap();
+            let states = anchor
+                .keys()
+                .iter()
+                .map(|item| (item.key().key_tag(), item.state()))
+                .collect::<Vec<_>>();
+            (res.is_ok(), states, anchor.clone())
+        };
+        let tag1 = key1.key_tag();
+        let tag2 = key2.key_tag();
+        let revoked_tag = revoked.dnskey().unwrap().key_tag();
+
+        // An RRset not signed by a trusted key is rejected.
+        assert_eq!(update(&[&ksk2], &[&ksk2], 500).0, false);
+
+        // The key referred to by the DS becomes valid right away.
+        let (ok, states, _) = update(&[&ksk1], &[&ksk1], 1000);
+        assert!(ok);
+        assert_eq!(states, [(tag1, KeyState::Valid)]);
+
+        // A new key is pending and forgotten if it disappears again.
+        let (_, states, anchor) = update(&[&ksk1, &ksk2], &[&ksk1], 2000);
+        assert_eq!(
+            states,
+            [(tag1, KeyState::Valid), (tag2, KeyState::AddPend)]
+        );
+        assert!(!anchor.trusts_key(&key2));
+        assert_eq!(anchor.keys()[1].hold_down_end(), Some(Serial(5600)));
+        let (_, states, _) = update(&[&ksk1], &[&ksk1], 3000);
+        assert_eq!(states, [(tag1, KeyState::Valid)]);
+
+        // The hold-down time is the original TTL of the RRset.
+        let (_, states, _) = update(&[&ksk1, &ksk2], &[&ksk1], 4000);
+        assert_eq!(states[1], (tag2, KeyState::AddPend));
+        let (_, states, _) = update(&[&ksk1, &ksk2], &[&ksk1], 7599);
+        assert_eq!(states[1], (tag2, KeyState::AddPend));
+        let (_, states, anchor) = update(&[&ksk1, &ksk2], &[&ksk1], 7600);
+        assert_eq!(states[1], (tag2, KeyState::Valid));
+        assert!(anchor.trusts_key(&key2));
+
+        // A revoked key not signing the RRset is merely missing.
+        let (_, states, anchor) = update(&[&revoked, &ksk2], &[&ksk2], 8000);
+        assert_eq!(
+            states,
+            [(tag1, KeyState::Missing), (tag2, KeyState::Valid)]
+        );
+        assert!(anchor.trusts_key(&key1));
+
+        // A self-signed revoked key is revoked and removed after the
+        // hold-down time.
+        let (_, states, anchor) =
+            update(&[&revoked, &ksk2], &[&ksk2, &revoked], 8100);
+        assert_eq!(
+            states,
+            [(revoked_tag, KeyState::Revoked), (tag2, KeyState::Valid)]
+        );
+        assert!(!anchor.trusts_key(&key1));
+        assert!(anchor.ds().is_empty());
+        assert_eq!(anchor.keys()[0].hold_down_end(), Some(Serial(8300)));
+        assert_eq!(update(&[&ksk1], &[&ksk1], 8200).0, false);
+        let (_, states, anchor) = update(&[&ksk2], &[&ksk2], 8300);
+        assert_eq!(states, [(tag2, KeyState::Valid)]);
+        assert!(!anchor.trusts_key(&key1));
+
+        // A revoked key published again without the REVOKE bit is a new
+        // key and not trusted.
+        assert_eq!(update(&[&ksk1], &[&ksk1], 8400).0, false);
+        let (_, states, anchor) = update(&[&ksk1, &ksk2], &[&ksk2], 8500);
+        assert_eq!(
+            states,
+            [(tag2, KeyState::Valid), (tag1, KeyState::AddPend)]
+        );
+        assert!(!anchor.trusts_key(&key1));
+    }
+}
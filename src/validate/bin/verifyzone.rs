@@ -0,0 +1,112 @@
+//! Verifies a signed zone file.
+//!
+//! This is a simple zone verifier built on the `validate::zone` module. It
+//! reads a signed zone from a master file and checks the signatures of all
+//! authoritative RRsets against the DNSKEY records at the apex as well as
+//! the NSEC or NSEC3 chain. Every problem found is printed on a line of its
+//! own. The exit code is 0 if the zone is fine and 1 otherwise.
+
+use bytes::Bytes;
+use domain::base::name::Dname;
+use domain::base::serial::Serial;
+use domain::master::reader::{Reader, ReaderItem};
+use domain::master::scan::Scanner;
+use domain::rdata::MasterRecordData;
+use domain::sign::records::SortedRecords;
+use domain::validate::zone::verify_zone;
+use std::process;
+
+const USAGE: &str = "\
+Usage: verifyzone [OPTIONS] <zonefile>
+
+Options:
+  -t <time>     check signatures at <time> instead of now
+
+Times are given either as seconds since the epoch or as YYYYMMDDHHmmSS.";
+
+type Records =
+    SortedRecords<Dname<Bytes>, MasterRecordData<Bytes, Dname<Bytes>>>;
+
+fn main() {
+    let (zonefile, now) = match from_args() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            process::exit(1)
+        }
+    };
+    match verify(&zonefile, now) {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+/// Returns the zone file and the time from the command line.
+fn from_args() -> Result<(String, Serial), String> {
+    let mut args = std::env::args().skip(1);
+    let mut zonefile = None;
+    let mut now = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-t" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("missing value for {}", arg))?;
+                now = Some(
+                    Serial::scan_rrsig(&mut Scanner::new(value.as_str()))
+                        .map_err(|_| format!("invalid time {}", value))?,
+                );
+            }
+            _ if arg.starts_with('-') => {
+                return Err(format!("unknown option {}", arg))
+            }
+            _ => {
+                if zonefile.is_some() {
+                    return Err("more than one zone file given".into());
+                }
+                zonefile = Some(arg);
+            }
+        }
+    }
+    let zonefile = zonefile.ok_or_else(|| String::from("no zone file"))?;
+    Ok((zonefile, now.unwrap_or_else(Serial::now)))
+}
+
+/// Verifies the zone and prints all problems.
+///
+/// Returns whether the zone is fine.
+fn verify(path: &str, now: Serial) -> Result<bool, String> {
+    let records = load_zone(path)?;
+    let apex = match records.find_soa() {
+        Some(soa) => soa.family_name().cloned(),
+        None => return Err("cannot find SOA record".into()),
+    };
+    let problems = verify_zone(&records, &apex, now);
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    Ok(problems.is_empty())
+}
+
+fn load_zone(path: &str) -> Result<Records, String> {
+    let reader =
+        Reader::open(path).map_err(|err| format!("{}: {}", path, err))?;
+    let mut res = Vec::new();
+    for item in reader {
+        match item {
+            Ok(ReaderItem::Record(record)) => res.push(record),
+            Ok(ReaderItem::Include { .. }) => {
+                return Err(format!("{}: $INCLUDE not supported", path))
+            }
+            Ok(ReaderItem::Control { name, .. }) => {
+                return Err(format!("{}: ${} not supported", path, name))
+            }
+            Err(err) => return Err(format!("{}: {}", path, err)),
+        }
+    }
+    Ok(res.into_iter().collect())
+}
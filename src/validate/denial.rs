@@ -0,0 +1,956 @@
+//! Authenticated denial of existence.
+//!
+//! Negative answers – responses saying that a name doesn’t exist or that it
+//! has no records of the requested type – are authenticated in DNSSEC via
+//! NSEC or NSEC3 records that are included in the authority section of the
+//! response. This module contains functions that check these proofs.
+//!
+//! [`verify_denial`] checks the proof for a negative answer, i.e., a
+//! response with either the NXDOMAIN response code or an empty answer.
+//! [`verify_wildcard`] checks the proof necessary for a positive answer
+//! that has been synthesized from a wildcard: that there is no closer match
+//! for the query name.
+//!
+//! Both functions verify the signatures of the NSEC or NSEC3 records using
+//! the validated keys of the zone and then return a [`Denial`] describing
+//! what exactly has been proven or a [`DenialError`] if the proof failed.
+//!
+//! NSEC proofs are checked as described in [RFC 4035] and [RFC 6840]. NSEC3
+//! proofs, including opt-out, are checked as described in [RFC 5155].
+//!
+//! [RFC 4035]: https://tools.ietf.org/html/rfc4035
+//! [RFC 5155]: https://tools.ietf.org/html/rfc5155
+//! [RFC 6840]: https://tools.ietf.org/html/rfc6840
+
+use super::rrset::{verify_rrset, RrsetError};
+use crate::base::cmp::CanonicalOrd;
+use crate::base::iana::{Nsec3HashAlg, Rcode, Rtype};
+use crate::base::message::{Message, RecordSection};
+use crate::base::name::{Dname, ParsedDname, ToDname};
+use crate::base::octets::{Compose, OctetsRef, ParseError};
+use crate::base::rdata::{ParseRecordData, RecordData, RtypeRecordData};
+use crate::base::record::Record;
+use crate::base::serial::Serial;
+use crate::rdata::rfc4034::RtypeBitmap;
+use crate::rdata::rfc5155::nsec3_hash;
+use crate::rdata::{Dnskey, Nsec, Nsec3, Rrsig};
+use crate::utils::base32;
+use core::cmp::Ordering;
+use core::slice;
+use std::string::{String, ToString};
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ Module Configuration ------------------------------------------
+
+/// The maximum number of additional NSEC3 hash iterations we accept.
+///
+/// Proofs using NSEC3 records with a higher iteration count are rejected
+/// with [`DenialError::ExcessiveIterations`] and should be treated as
+/// insecure. See [RFC 9276, Section 3.2].
+///
+/// [RFC 9276, Section 3.2]: https://tools.ietf.org/html/rfc9276#section-3.2
+pub const MAX_NSEC3_ITERATIONS: u16 = 150;
+
+//------------ verify_denial -------------------------------------------------
+
+/// Verifies the proof of non-existence contained in a negative answer.
+///
+/// The `message` is the response to a query for `qname` and `qtype`. If
+/// its answer section contained a CNAME or DNAME chain, `qname` should be
+/// the last name of that chain. The response code of the message needs to
+/// be either NXDOMAIN, in which case a proof that `qname` doesn’t exist is
+/// required, or NOERROR, in which case a proof that `qname` has no records
+/// of type `qtype` is required.
+///
+/// The NSEC or NSEC3 records in the authority section of the message must
+/// have been signed by `zone` with one of the validated `keys`. The
+/// signatures need to be valid at `now`.
+pub fn verify_denial<Octets, N, Z, KeyOcts>(
+    message: &Message<Octets>,
+    qname: &N,
+    qtype: Rtype,
+    zone: &Z,
+    keys: &[Dnskey<KeyOcts>],
+    now: Serial,
+) -> Result<Denial, DenialError>
+where
+    Octets: AsRef<[u8]>,
+    N: ToDname,
+    Z: ToDname,
+    KeyOcts: AsRef<[u8]>,
+{
+    let nxdomain = match message.header().rcode() {
+        Rcode::NXDomain => true,
+        Rcode::NoError => false,
+        _ => return Err(DenialError::NoProof),
+    };
+    if !qname.ends_with(zone) {
+        return Err(DenialError::NoProof);
+    }
+    let qname = to_vec_name(qname)?;
+    match DenialRecords::collect(message, zone, keys, now)? {
+        DenialRecords::Nsec(records) => {
+            nsec_denial(&records, &qname, qtype, nxdomain)
+        }
+        DenialRecords::Nsec3(set) => set.denial(&qname, qtype, nxdomain),
+    }
+}
+
+//------------ verify_wildcard -----------------------------------------------
+
+/// Verifies the proof that a positive answer was correctly synthesized.
+///
+/// If an RRset in an answer has been synthesized from a wildcard, the
+/// response needs to contain a proof that there is no closer match for the
+/// query name. The RRset in question is owned by `qname` and the label
+/// count of the RRSIG that verified it is `labels`.
+///
+/// The NSEC or NSEC3 records in the authority section of the message must
+/// have been signed by `zone` with one of the validated `keys`. The
+/// signatures need to be valid at `now`.
+///
+/// Upon success, the function returns [`Denial::WildcardExpansion`].
+pub fn verify_wildcard<Octets, N, Z, KeyOcts>(
+    message: &Message<Octets>,
+    qname: &N,
+    labels: u8,
+    zone: &Z,
+    keys: &[Dnskey<KeyOcts>],
+    now: Serial,
+) -> Result<Denial, DenialError>
+where
+    Octets: AsRef<[u8]>,
+    N: ToDname,
+    Z: ToDname,
+    KeyOcts: AsRef<[u8]>,
+{
+    if !qname.ends_with(zone) {
+        return Err(DenialError::NoProof);
+    }
+    let qname = to_vec_name(qname)?;
+
+    // The next closer name is the source of synthesis with the wildcard
+    // label replaced by the next label of the query name. The RRSIG label
+    // count doesn’t include the root label.
+    let skip = match qname.label_count().checked_sub(usize::from(labels) + 2)
+    {
+        Some(skip) => skip,
+        None => return Err(DenialError::NoProof),
+    };
+    let next_closer = to_vec_name(&qname.iter_suffixes().nth(skip).unwrap())?;
+
+    let covered = match DenialRecords::collect(message, zone, keys, now)? {
+        DenialRecords::Nsec(records) => {
+            records.iter().any(|nsec| nsec.covers(&qname))
+        }
+        DenialRecords::Nsec3(set) => set.find_cover(&next_closer).is_some(),
+    };
+    if covered {
+        Ok(Denial::WildcardExpansion)
+    } else {
+        Err(DenialError::NoProof)
+    }
+}
+
+//------------ Denial --------------------------------------------------------
+
+/// What a proof of non-existence has proven.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Denial {
+    /// The query name does not exist.
+    NxDomain,
+
+    /// The query name exists but has no records of the query type.
+    NoData,
+
+    /// The query name is matched by a wildcard without the query type.
+    WildcardNoData,
+
+    /// The answer has correctly been synthesized from a wildcard.
+    WildcardExpansion,
+
+    /// The query name is an unsigned delegation.
+    ///
+    /// This is returned when proving that there are no DS records for a
+    /// name that has NS records. It means that the delegated zone is
+    /// insecure.
+    InsecureDelegation,
+
+    /// The query name is covered by an NSEC3 record with opt-out set.
+    ///
+    /// The name may be an unsigned delegation that has been left out of the
+    /// NSEC3 chain. The answer has to be treated as insecure.
+    OptOut,
+}
+
+//------------ DenialRecords -------------------------------------------------
+
+/// The verified NSEC or NSEC3 records from a response.
+enum DenialRecords {
+    Nsec(Vec<NsecRecord>),
+    Nsec3(Nsec3Set),
+}
+
+impl DenialRecords {
+    /// Collects and verifies the records from the authority section.
+    fn collect<Octets, Z, KeyOcts>(
+        message: &Message<Octets>,
+        zone: &Z,
+        keys: &[Dnskey<KeyOcts>],
+        now: Serial,
+    ) -> Result<Self, DenialError>
+    where
+        Octets: AsRef<[u8]>,
+        Z: ToDname,
+        KeyOcts: AsRef<[u8]>,
+    {
+        let message = message.for_slice();
+        let authority = message.authority()?;
+
+        let nsec3s =
+            signed_records::<_, Nsec3<_>, _, _>(authority, zone, keys, now)?;
+        if !nsec3s.is_empty() {
+            return Nsec3Set::from_records(&nsec3s, zone).map(Self::Nsec3);
+        }
+
+        let nsecs = signed_records::<_, Nsec<_, ParsedDname<_>>, _, _>(
+            authority, zone, keys, now,
+        )?;
+        if nsecs.is_empty() {
+            return Err(DenialError::NoRecords);
+        }
+        nsecs
+            .iter()
+            .map(NsecRecord::from_record)
+            .collect::<Result<_, _>>()
+            .map(Self::Nsec)
+    }
+}
+
+//------------ NsecRecord ----------------------------------------------------
+
+/// The relevant content of a verified NSEC record.
+struct NsecRecord {
+    owner: Dname<Vec<u8>>,
+    next: Dname<Vec<u8>>,
+    types: RtypeBitmap<Vec<u8>>,
+}
+
+impl NsecRecord {
+    fn from_record<N, O, NN>(
+        record: &Record<N, Nsec<O, NN>>,
+    ) -> Result<Self, DenialError>
+    where
+        N: ToDname,
+        O: AsRef<[u8]>,
+        NN: ToDname,
+    {
+        Ok(NsecRecord {
+            owner: to_vec_name(record.owner())?,
+            next: to_vec_name(record.data().next_name())?,
+            types: to_vec_bitmap(record.data().types())?,
+        })
+    }
+
+    /// Returns whether the record proves that `name` doesn’t exist.
+    fn covers(&self, name: &Dname<Vec<u8>>) -> bool {
+        if self.owner.name_cmp(name) != Ordering::Less {
+            return false;
+        }
+
+        // An NSEC record at a delegation or DNAME can’t be used to prove
+        // anything about names below it. See RFC 6840, section 4.1.
+        if name.ends_with(&self.owner)
+            && (self.types.contains(Rtype::Dname)
+                || (self.types.contains(Rtype::Ns)
+                    && !self.types.contains(Rtype::Soa)))
+        {
+            return false;
+        }
+
+        // The last NSEC record points back to the apex.
+        if self.next.name_cmp(&self.owner) == Ordering::Greater {
+            name.name_cmp(&self.next) == Ordering::Less
+        } else {
+            true
+        }
+    }
+}
+
+/// Checks a negative answer proven via NSEC records.
+fn nsec_denial(
+    records: &[NsecRecord],
+    qname: &Dname<Vec<u8>>,
+    qtype: Rtype,
+    nxdomain: bool,
+) -> Result<Denial, DenialError> {
+    if let Some(nsec) = records.iter().find(|nsec| nsec.owner.name_eq(qname))
+    {
+        if nxdomain {
+            return Err(DenialError::NoProof);
+        }
+        return nodata_types(&nsec.types, qtype, Denial::NoData);
+    }
+
+    let cover = match records.iter().find(|nsec| nsec.covers(qname)) {
+        Some(cover) => cover,
+        None => return Err(DenialError::NoProof),
+    };
+
+    // If the next name is below the query name, the query name is an
+    // empty non-terminal. See RFC 7129, section 3.2. It exists, so this
+    // can’t prove an NXDOMAIN.
+    if cover.next.ends_with(qname) && !cover.next.name_eq(qname) {
+        if nxdomain {
+            return Err(DenialError::NoProof);
+        }
+        return Ok(Denial::NoData);
+    }
+
+    // The closest encloser is the longest ancestor of the query name
+    // shared with either end of the covering NSEC record.
+    let owner_ancestor = common_ancestor(qname, &cover.owner)?;
+    let next_ancestor = common_ancestor(qname, &cover.next)?;
+    let closest_encloser =
+        if owner_ancestor.label_count() >= next_ancestor.label_count() {
+            owner_ancestor
+        } else {
+            next_ancestor
+        };
+    let wildcard = wildcard_name(&closest_encloser)?;
+
+    if nxdomain {
+        if records.iter().any(|nsec| nsec.covers(&wildcard)) {
+            Ok(Denial::NxDomain)
+        } else {
+            Err(DenialError::NoProof)
+        }
+    } else {
+        match records.iter().find(|nsec| nsec.owner.name_eq(&wildcard)) {
+            Some(nsec) => {
+                nodata_types(&nsec.types, qtype, Denial::WildcardNoData)
+            }
+            None => Err(DenialError::NoProof),
+        }
+    }
+}
+
+//------------ Nsec3Set ------------------------------------------------------
+
+/// The relevant content of a verified NSEC3 record.
+struct Nsec3Record {
+    /// The hashed owner name in lowercase base32hex.
+    owner: String,
+
+    /// The next hashed owner name in lowercase base32hex.
+    next: String,
+
+    /// Whether the opt-out flag is set.
+    opt_out: bool,
+
+    /// The types at the original owner name.
+    types: RtypeBitmap<Vec<u8>>,
+}
+
+impl Nsec3Record {
+    /// Returns whether the record covers the given hash.
+    fn covers(&self, hash: &str) -> bool {
+        if self.owner.as_str() < self.next.as_str() {
+            self.owner.as_str() < hash && hash < self.next.as_str()
+        } else {
+            // The last record in the chain.
+            self.owner.as_str() < hash || hash < self.next.as_str()
+        }
+    }
+}
+
+/// The NSEC3 records from a response sharing the same parameters.
+struct Nsec3Set {
+    zone: Dname<Vec<u8>>,
+    algorithm: Nsec3HashAlg,
+    iterations: u16,
+    salt: Vec<u8>,
+    records: Vec<Nsec3Record>,
+}
+
+impl Nsec3Set {
+    fn from_records<N, O, Z>(
+        records: &[Record<N, Nsec3<O>>],
+        zone: &Z,
+    ) -> Result<Self, DenialError>
+    where
+        N: ToDname,
+        O: AsRef<[u8]>,
+        Z: ToDname,
+    {
+        let first = records[0].data();
+        let mut res = Nsec3Set {
+            zone: to_vec_name(zone)?,
+            algorithm: first.hash_algorithm(),
+            iterations: first.iterations(),
+            salt: first.salt().as_ref().into(),
+            records: Vec::new(),
+        };
+        if res.iterations > MAX_NSEC3_ITERATIONS {
+            return Err(DenialError::ExcessiveIterations);
+        }
+        if nsec3_hash(&res.zone, res.algorithm, 0, &[]).is_err() {
+            return Err(DenialError::UnsupportedHash);
+        }
+
+        for record in records {
+            let data = record.data();
+
+            // Records with different parameters are to be ignored. See
+            // RFC 5155, section 8.2.
+            if data.hash_algorithm() != res.algorithm
+                || data.iterations() != res.iterations
+                || data.salt().as_ref() != res.salt.as_slice()
+            {
+                continue;
+            }
+
+            // The owner needs to be a hash label directly below the apex.
+            let mut labels = record.owner().iter_labels();
+            let label = match labels.next() {
+                Some(label) => label,
+                None => continue,
+            };
+            let owner = to_vec_name(record.owner())?;
+            match owner.iter_suffixes().nth(1) {
+                Some(parent) if parent.name_eq(&res.zone) => {}
+                _ => continue,
+            }
+
+            let mut owner =
+                String::from_utf8_lossy(label.as_slice()).into_owned();
+            owner.make_ascii_lowercase();
+            res.records.push(Nsec3Record {
+                owner,
+                next: encode_hash(data.next_owner().as_ref()),
+                opt_out: data.opt_out(),
+                types: to_vec_bitmap(data.types())?,
+            });
+        }
+        Ok(res)
+    }
+
+    /// Returns the hash of `name` in lowercase base32hex.
+    fn hash(&self, name: &impl ToDname) -> String {
+        // We have checked that the algorithm is supported when creating
+        // the set.
+        nsec3_hash(name, self.algorithm, self.iterations, &self.salt)
+            .unwrap()
+            .to_string()
+    }
+
+    /// Returns the record matching `name`.
+    fn find_match(&self, name: &impl ToDname) -> Option<&Nsec3Record> {
+        let hash = self.hash(name);
+        self.records.iter().find(|record| record.owner == hash)
+    }
+
+    /// Returns the record covering `name`.
+    fn find_cover(&self, name: &impl ToDname) -> Option<&Nsec3Record> {
+        let hash = self.hash(name);
+        self.records.iter().find(|record| record.covers(&hash))
+    }
+
+    /// Finds the closest provable encloser of `qname`.
+    ///
+    /// Returns the closest encloser and the record covering the next
+    /// closer name. See RFC 5155, section 8.3.
+    fn closest_encloser(
+        &self,
+        qname: &Dname<Vec<u8>>,
+    ) -> Result<(Dname<Vec<u8>>, &Nsec3Record), DenialError> {
+        let mut next_closer = None;
+        for name in qname.iter_suffixes() {
+            if !name.ends_with(&self.zone) {
+                break;
+            }
+            if let Some(record) = self.find_match(&name) {
+                let next_closer = match next_closer {
+                    Some(next_closer) => next_closer,
+                    None => return Err(DenialError::NoProof),
+                };
+                if record.types.contains(Rtype::Dname)
+                    || (record.types.contains(Rtype::Ns)
+                        && !record.types.contains(Rtype::Soa))
+                {
+                    return Err(DenialError::NoProof);
+                }
+                return match self.find_cover(&next_closer) {
+                    Some(cover) => Ok((to_vec_name(&name)?, cover)),
+                    None => Err(DenialError::NoProof),
+                };
+            }
+            next_closer = Some(name);
+        }
+        Err(DenialError::NoProof)
+    }
+
+    /// Checks a negative answer.
+    fn denial(
+        &self,
+        qname: &Dname<Vec<u8>>,
+        qtype: Rtype,
+        nxdomain: bool,
+    ) -> Result<Denial, DenialError> {
+        if !nxdomain {
+            if let Some(record) = self.find_match(qname) {
+                return nodata_types(&record.types, qtype, Denial::NoData);
+            }
+        }
+
+        let (closest_encloser, cover) = self.closest_encloser(qname)?;
+        if nxdomain {
+            let wildcard = wildcard_name(&closest_encloser)?;
+            if self.find_cover(&wildcard).is_none() {
+                return Err(DenialError::NoProof);
+            }
+            return if cover.opt_out {
+                Ok(Denial::OptOut)
+            } else {
+                Ok(Denial::NxDomain)
+            };
+        }
+
+        // A DS query for a name in an opt-out span. See RFC 5155,
+        // section 8.6.
+        if qtype == Rtype::Ds && cover.opt_out {
+            return Ok(Denial::OptOut);
+        }
+
+        let wildcard = wildcard_name(&closest_encloser)?;
+        match self.find_match(&wildcard) {
+            Some(record) => {
+                nodata_types(&record.types, qtype, Denial::WildcardNoData)
+            }
+            None => Err(DenialError::NoProof),
+        }
+    }
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Collects and verifies the records of a given type from a section.
+///
+/// Only records at or below `zone` are considered. Each record is verified
+/// separately using its own signatures.
+fn signed_records<Ref, Data, Z, KeyOcts>(
+    section: RecordSection<Ref>,
+    zone: &Z,
+    keys: &[Dnskey<KeyOcts>],
+    now: Serial,
+) -> Result<Vec<Record<ParsedDname<Ref>, Data>>, DenialError>
+where
+    Ref: OctetsRef,
+    Data: ParseRecordData<Ref>
+        + RtypeRecordData
+        + RecordData
+        + CanonicalOrd
+        + Compose,
+    Z: ToDname,
+    KeyOcts: AsRef<[u8]>,
+{
+    let mut records = Vec::new();
+    let mut sigs = Vec::new();
+    for record in section {
+        let record = record?;
+        if !record.owner().ends_with(zone) {
+            continue;
+        }
+        if record.rtype() == Rtype::Rrsig {
+            if let Some(sig) =
+                record.to_record::<Rrsig<Ref::Range, ParsedDname<Ref>>>()?
+            {
+                if sig.data().type_covered() == Data::RTYPE {
+                    sigs.push(sig)
+                }
+            }
+        } else if let Some(record) = record.to_record::<Data>()? {
+            records.push(record)
+        }
+    }
+    for record in &mut records {
+        let owner = *record.owner();
+        let owner_sigs = sigs
+            .iter()
+            .filter(|sig| sig.owner().name_eq(&owner))
+            .map(Record::data);
+        verify_rrset(zone, slice::from_mut(record), owner_sigs, keys, now)?;
+    }
+    Ok(records)
+}
+
+/// Checks the types of a matching NSEC or NSEC3 record for a NODATA proof.
+///
+/// Returns `success` if the types prove the absence of `qtype`.
+fn nodata_types(
+    types: &RtypeBitmap<Vec<u8>>,
+    qtype: Rtype,
+    success: Denial,
+) -> Result<Denial, DenialError> {
+    if types.contains(qtype) || types.contains(Rtype::Cname) {
+        return Err(DenialError::NoProof);
+    }
+    if qtype == Rtype::Ds {
+        // The record must come from the parent side of a delegation.
+        if types.contains(Rtype::Soa) {
+            return Err(DenialError::NoProof);
+        }
+        if types.contains(Rtype::Ns) {
+            return Ok(Denial::InsecureDelegation);
+        }
+    } else if types.contains(Rtype::Ns) && !types.contains(Rtype::Soa) {
+        // The parent side of a delegation can’t prove anything about the
+        // child zone’s records.
+        return Err(DenialError::NoProof);
+    }
+    Ok(success)
+}
+
+/// Returns the longest common ancestor of two names.
+fn common_ancestor(
+    left: &Dname<Vec<u8>>,
+    right: &Dname<Vec<u8>>,
+) -> Result<Dname<Vec<u8>>, DenialError> {
+    match left.iter_suffixes().find(|name| right.ends_with(name)) {
+        Some(name) => to_vec_name(&name),
+        None => Ok(Dname::root_vec()),
+    }
+}
+
+/// Returns the wildcard name directly below `name`.
+fn wildcard_name(
+    name: &Dname<Vec<u8>>,
+) -> Result<Dname<Vec<u8>>, DenialError> {
+    let mut octets = Vec::with_capacity(name.len() + 2);
+    octets.extend_from_slice(b"\x01*");
+    octets.extend_from_slice(name.as_slice());
+    Dname::from_octets(octets).map_err(|_| DenialError::Malformed)
+}
+
+/// Converts a name into a name atop a vec.
+fn to_vec_name(name: &impl ToDname) -> Result<Dname<Vec<u8>>, DenialError> {
+    name.to_dname().map_err(|_| DenialError::Malformed)
+}
+
+/// Converts a type bitmap into a bitmap atop a vec.
+fn to_vec_bitmap<O: AsRef<[u8]>>(
+    types: &RtypeBitmap<O>,
+) -> Result<RtypeBitmap<Vec<u8>>, DenialError> {
+    RtypeBitmap::from_octets(types.as_slice().into())
+        .map_err(|_| DenialError::Malformed)
+}
+
+/// Encodes a hash value as lowercase base32hex.
+fn encode_hash(hash: &[u8]) -> String {
+    let mut res = String::new();
+    base32::display_hex(hash, &mut res).unwrap();
+    res.make_ascii_lowercase();
+    res
+}
+
+//============ Error Types ===================================================
+
+//------------ DenialError ---------------------------------------------------
+
+/// A proof of non-existence could not be verified.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DenialError {
+    /// The response contains neither NSEC nor NSEC3 records.
+    NoRecords,
+
+    /// The NSEC or NSEC3 records could not be verified.
+    Rrset(RrsetError),
+
+    /// The NSEC or NSEC3 records do not prove the non-existence.
+    NoProof,
+
+    /// The NSEC3 records use an unsupported hash algorithm.
+    UnsupportedHash,
+
+    /// The NSEC3 records use too many hash iterations.
+    ExcessiveIterations,
+
+    /// The response was malformed.
+    Malformed,
+}
+
+impl DenialError {
+    /// Returns whether the answer should be treated as insecure.
+    ///
+    /// This is the case if the proof uses NSEC3 parameters that we do not
+    /// support. See [RFC 5155, Section 8.1] and [RFC 9276, Section 3.2].
+    ///
+    /// [RFC 5155, Section 8.1]: https://tools.ietf.org/html/rfc5155#section-8.1
+    /// [RFC 9276, Section 3.2]: https://tools.ietf.org/html/rfc9276#section-3.2
+    pub fn is_insecure(self) -> bool {
+        matches!(
+            self,
+            DenialError::UnsupportedHash | DenialError::ExcessiveIterations
+        )
+    }
+}
+
+//--- From
+
+impl From<RrsetError> for DenialError {
+    fn from(err: RrsetError) -> Self {
+        DenialError::Rrset(err)
+    }
+}
+
+impl From<ParseError> for DenialError {
+    fn from(_: ParseError) -> Self {
+        DenialError::Malformed
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for DenialError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DenialError::NoRecords => f.write_str("no NSEC or NSEC3 records"),
+            DenialError::Rrset(err) => write!(f, "{}", err),
+            DenialError::NoProof => f.write_str("missing proof"),
+            DenialError::UnsupportedHash => {
+                f.write_str("unsupported NSEC3 hash algorithm")
+            }
+            DenialError::ExcessiveIterations => {
+                f.write_str("too many NSEC3 iterations")
+            }
+            DenialError::Malformed => f.write_str("malformed response"),
+        }
+    }
+}
+
+impl error::Error for DenialError {}
+
+//============ Test ==========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rdata::rfc4034::RtypeBitmapBuilder;
+    use std::str::FromStr;
+
+    fn name(s: &str) -> Dname<Vec<u8>> {
+        Dname::from_str(s).unwrap()
+    }
+
+    fn types(types: &[Rtype]) -> RtypeBitmap<Vec<u8>> {
+        let mut builder = RtypeBitmapBuilder::new_vec();
+        for rtype in types {
+            builder.add(*rtype).unwrap();
+        }
+        builder.finalize()
+    }
+
+    /// The names of the zone from RFC 5155, Appendix A.
+    #[allow(clippy::type_complexity)]
+    fn example_zone() -> Vec<(&'static str, Vec<Rtype>)> {
+        use Rtype::*;
+        vec![
+            ("example.", vec![Ns, Soa, Mx, Rrsig, Dnskey, Nsec3param]),
+            ("a.example.", vec![Ns, Ds, Rrsig]),
+            ("ai.example.", vec![A, Hinfo, Rrsig, Aaaa]),
+            ("ns1.example.", vec![A, Rrsig]),
+            ("ns2.example.", vec![A, Rrsig]),
+            ("w.example.", vec![]),
+            ("*.w.example.", vec![Mx, Rrsig]),
+            ("x.w.example.", vec![Mx, Rrsig]),
+            ("y.w.example.", vec![]),
+            ("x.y.w.example.", vec![Mx, Rrsig]),
+            ("xx.example.", vec![A, Hinfo, Rrsig, Aaaa]),
+        ]
+    }
+
+    /// Returns the NSEC chain for the example zone.
+    ///
+    /// Unlike NSEC3, NSEC has no records for empty non-terminals.
+    fn nsec_chain() -> Vec<NsecRecord> {
+        let zone = example_zone();
+        let mut names: Vec<_> = zone
+            .iter()
+            .filter(|(_, rtypes)| !rtypes.is_empty())
+            .map(|&(owner, ref rtypes)| {
+                let mut rtypes = rtypes.clone();
+                rtypes.push(Rtype::Nsec);
+                (name(owner), rtypes)
+            })
+            .collect();
+        names.sort_by(|left, right| left.0.name_cmp(&right.0));
+        (0..names.len())
+            .map(|i| NsecRecord {
+                owner: names[i].0.clone(),
+                next: names[(i + 1) % names.len()].0.clone(),
+                types: types(&names[i].1),
+            })
+            .collect()
+    }
+
+    fn nsec3_chain(opt_out: bool) -> Nsec3Set {
+        let mut set = Nsec3Set {
+            zone: name("example."),
+            algorithm: Nsec3HashAlg::Sha1,
+            iterations: 12,
+            salt: vec![0xaa, 0xbb, 0xcc, 0xdd],
+            records: Vec::new(),
+        };
+        let mut hashes: Vec<_> = example_zone()
+            .into_iter()
+            .map(|(owner, rtypes)| (set.hash(&name(owner)), rtypes))
+            .collect();
+        hashes.sort();
+        set.records = (0..hashes.len())
+            .map(|i| Nsec3Record {
+                owner: hashes[i].0.clone(),
+                next: hashes[(i + 1) % hashes.len()].0.clone(),
+                opt_out,
+                types: types(&hashes[i].1),
+            })
+            .collect();
+        set
+    }
+
+    #[test]
+    fn nsec_covers() {
+        let chain = nsec_chain();
+        let apex = chain.iter().find(|nsec| nsec.owner == name("example."));
+        let apex = apex.unwrap();
+        assert!(apex.covers(&name("0.example.")));
+        assert!(!apex.covers(&name("b.example.")));
+        let delegation =
+            chain.iter().find(|nsec| nsec.owner == name("a.example."));
+        assert!(!delegation.unwrap().covers(&name("b.a.example.")));
+        let last =
+            chain.iter().find(|nsec| nsec.owner == name("xx.example."));
+        assert!(last.unwrap().covers(&name("z.example.")));
+    }
+
+    #[test]
+    fn nsec_denials() {
+        let chain = nsec_chain();
+        assert_eq!(
+            nsec_denial(&chain, &name("b.example."), Rtype::A, true),
+            Ok(Denial::NxDomain)
+        );
+        assert_eq!(
+            nsec_denial(&chain, &name("b.example."), Rtype::A, false),
+            Err(DenialError::NoProof)
+        );
+        assert_eq!(
+            nsec_denial(&chain, &name("ns1.example."), Rtype::Mx, false),
+            Ok(Denial::NoData)
+        );
+        assert_eq!(
+            nsec_denial(&chain, &name("ns1.example."), Rtype::A, false),
+            Err(DenialError::NoProof)
+        );
+        assert_eq!(
+            nsec_denial(&chain, &name("y.w.example."), Rtype::A, false),
+            Ok(Denial::NoData)
+        );
+        assert_eq!(
+            nsec_denial(&chain, &name("y.w.example."), Rtype::A, true),
+            Err(DenialError::NoProof)
+        );
+        assert_eq!(
+            nsec_denial(&chain, &name("w.example."), Rtype::A, false),
+            Ok(Denial::NoData)
+        );
+        assert_eq!(
+            nsec_denial(&chain, &name("w.example."), Rtype::A, true),
+            Err(DenialError::NoProof)
+        );
+        assert_eq!(
+            nsec_denial(&chain, &name("a.z.w.example."), Rtype::A, false),
+            Ok(Denial::WildcardNoData)
+        );
+        assert_eq!(
+            nsec_denial(&chain, &name("a.z.w.example."), Rtype::A, true),
+            Err(DenialError::NoProof)
+        );
+        assert_eq!(
+            nsec_denial(&chain, &name("a.example."), Rtype::Ds, false),
+            Err(DenialError::NoProof)
+        );
+        assert_eq!(
+            nsec_denial(&chain, &name("a.example."), Rtype::A, false),
+            Err(DenialError::NoProof)
+        );
+    }
+
+    #[test]
+    fn nsec3_denials() {
+        let chain = nsec3_chain(false);
+        assert_eq!(
+            chain.denial(&name("a.c.x.w.example."), Rtype::A, true),
+            Ok(Denial::NxDomain)
+        );
+        assert_eq!(
+            chain.denial(&name("ns1.example."), Rtype::Mx, false),
+            Ok(Denial::NoData)
+        );
+        assert_eq!(
+            chain.denial(&name("y.w.example."), Rtype::A, false),
+            Ok(Denial::NoData)
+        );
+        assert_eq!(
+            chain.denial(&name("a.z.w.example."), Rtype::Aaaa, false),
+            Ok(Denial::WildcardNoData)
+        );
+        assert_eq!(
+            chain.denial(&name("a.z.w.example."), Rtype::Mx, false),
+            Err(DenialError::NoProof)
+        );
+        assert_eq!(
+            chain.denial(&name("ns1.example."), Rtype::A, false),
+            Err(DenialError::NoProof)
+        );
+        assert_eq!(
+            chain.denial(&name("x.w.example."), Rtype::A, true),
+            Err(DenialError::NoProof)
+        );
+        assert_eq!(
+            chain.denial(&name("b.a.example."), Rtype::A, true),
+            Err(DenialError::NoProof)
+        );
+        assert_eq!(
+            chain.denial(&name("b.example."), Rtype::Ds, false),
+            Err(DenialError::NoProof)
+        );
+
+        let chain = nsec3_chain(true);
+        assert_eq!(
+            chain.denial(&name("b.example."), Rtype::Ds, false),
+            Ok(Denial::OptOut)
+        );
+        assert_eq!(
+            chain.denial(&name("a.c.x.w.example."), Rtype::A, true),
+            Ok(Denial::OptOut)
+        );
+    }
+
+    #[test]
+    fn nsec3_hashes() {
+        // The hashes from RFC 5155, Appendix A.
+        let chain = nsec3_chain(false);
+        assert_eq!(
+            chain.hash(&name("example.")),
+            "0p9mhaveqvm6t7vbl5lop2u3t2rp3tom"
+        );
+        assert_eq!(
+            chain.hash(&name("x.w.example.")),
+            "b4um86eghhds6nea196smvmlo4ors995"
+        );
+        assert!(chain.find_match(&name("x.y.w.example.")).is_some());
+        assert!(chain.find_match(&name("c.x.w.example.")).is_none());
+        assert!(chain.find_cover(&name("c.x.w.example.")).is_some());
+    }
+}
@@ -1,6 +1,11 @@
 //! DNSSEC validation.
 //!
 //! **This module is experimental and likely to change significantly.**
+//!
+//! Signatures are verified using ring. If the `openssl` *and* `sign`
+//! features are enabled, too, signatures ring refuses, i.e., Ed448 and RSA
+//! keys shorter than 1024 bits, are verified using OpenSSL via
+//! `sign::openssl` instead of being treated as unsupported.
 #![cfg(feature = "validate")]
 #![cfg_attr(docsrs, doc(cfg(feature = "validate")))]
 
@@ -10,11 +15,17 @@ use crate::base::name::ToDname;
 use crate::base::octets::{Compose, OctetsBuilder, ShortBuf};
 use crate::base::rdata::RecordData;
 use crate::base::record::Record;
+use crate::rdata::rfc4034::dnskey_digest;
 use crate::rdata::{Dnskey, Rrsig};
 use ring::{digest, signature};
 use std::vec::Vec;
 use std::{error, fmt};
 
+pub mod anchor;
+pub mod denial;
+pub mod rrset;
+pub mod zone;
+
 //------------ Dnskey --------------------------------------------------------
 
 /// Extensions for DNSKEY record type.
@@ -71,23 +82,8 @@ where
         dname: &N,
         algorithm: DigestAlg,
     ) -> Result<digest::Digest, AlgorithmError> {
-        let mut buf: Vec<u8> = Vec::new();
-        dname.compose_canonical(&mut buf).unwrap();
-        self.compose_canonical(&mut buf).unwrap();
-
-        let mut ctx = match algorithm {
-            DigestAlg::Sha1 => {
-                digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY)
-            }
-            DigestAlg::Sha256 => digest::Context::new(&digest::SHA256),
-            DigestAlg::Sha384 => digest::Context::new(&digest::SHA384),
-            _ => {
-                return Err(AlgorithmError::Unsupported);
-            }
-        };
-
-        ctx.update(&buf);
-        Ok(ctx.finish())
+        dnskey_digest(dname, self, algorithm)
+            .ok_or(AlgorithmError::Unsupported)
     }
 }
 
@@ -230,9 +226,15 @@ impl<Octets: AsRef<[u8]>, Name: Compose> RrsigExt for Rrsig<Octets, Name> {
                     _ => unreachable!(),
                 };
 
-                // Check for minimum supported key size
+                // Check for minimum supported key size. Smaller keys can
+                // only be checked via OpenSSL.
                 if self.signature().as_ref().len() < min_bytes {
-                    return Err(AlgorithmError::Unsupported);
+                    return verify_fallback(
+                        self.algorithm(),
+                        dnskey,
+                        signed_data,
+                        signature,
+                    );
                 }
 
                 // The key isn't available in either PEM or DER, so use the
@@ -271,11 +273,86 @@ impl<Octets: AsRef<[u8]>, Name: Compose> RrsigExt for Rrsig<Octets, Name> {
                     .verify(signed_data, signature)
                     .map_err(|_| AlgorithmError::BadSig)
             }
-            _ => Err(AlgorithmError::Unsupported),
+            algorithm => {
+                verify_fallback(algorithm, dnskey, signed_data, signature)
+            }
         }
     }
 }
 
+/// Verifies a signature ring doesn’t support using OpenSSL.
+///
+/// This covers Ed448 and RSA keys smaller than ring accepts. It uses the
+/// public key support of `sign::openssl` and thus needs the `sign` feature
+/// in addition to `openssl`.
+#[cfg(all(feature = "openssl", feature = "sign"))]
+fn verify_fallback(
+    algorithm: SecAlg,
+    dnskey: &Dnskey<impl AsRef<[u8]>>,
+    signed_data: &[u8],
+    signature: &[u8],
+) -> Result<(), AlgorithmError> {
+    use crate::sign::openssl::{KeyError, PublicKey};
+
+    if dnskey.algorithm() != algorithm {
+        return Err(AlgorithmError::InvalidData);
+    }
+    let key = PublicKey::from_dnskey(dnskey).map_err(|err| match err {
+        KeyError::UnsupportedAlgorithm => AlgorithmError::Unsupported,
+        _ => AlgorithmError::InvalidData,
+    })?;
+    match key.verify(signed_data, signature) {
+        Ok(true) => Ok(()),
+        _ => Err(AlgorithmError::BadSig),
+    }
+}
+
+/// Without OpenSSL, signatures ring doesn’t support can’t be verified.
+#[cfg(not(all(feature = "openssl", feature = "sign")))]
+fn verify_fallback(
+    _algorithm: SecAlg,
+    _dnskey: &Dnskey<impl AsRef<[u8]>>,
+    _signed_data: &[u8],
+    _signature: &[u8],
+) -> Result<(), AlgorithmError> {
+    Err(AlgorithmError::Unsupported)
+}
+
+//------------ Supported Algorithms ------------------------------------------
+
+/// Returns whether signatures with the given algorithm can be verified.
+///
+/// Zones signed only with unsupported algorithms are to be treated as
+/// unsigned. See [RFC 4035, Section 5.2]. If the `openssl` and `sign`
+/// features are enabled, Ed448 is supported, too.
+///
+/// [RFC 4035, Section 5.2]: https://tools.ietf.org/html/rfc4035#section-5.2
+pub fn supports_algorithm(algorithm: SecAlg) -> bool {
+    if cfg!(all(feature = "openssl", feature = "sign"))
+        && algorithm == SecAlg::Ed448
+    {
+        return true;
+    }
+    matches!(
+        algorithm,
+        SecAlg::RsaSha1
+            | SecAlg::RsaSha1Nsec3Sha1
+            | SecAlg::RsaSha256
+            | SecAlg::RsaSha512
+            | SecAlg::EcdsaP256Sha256
+            | SecAlg::EcdsaP384Sha384
+            | SecAlg::Ed25519
+    )
+}
+
+/// Returns whether DS records with the given digest type can be checked.
+pub fn supports_digest(algorithm: DigestAlg) -> bool {
+    matches!(
+        algorithm,
+        DigestAlg::Sha1 | DigestAlg::Sha256 | DigestAlg::Sha384
+    )
+}
+
 /// Return the RSA exponent and modulus components from DNSKEY record data.
 fn rsa_exponent_modulus(
     dnskey: &Dnskey<impl AsRef<[u8]>>,
@@ -301,6 +378,76 @@ fn rsa_exponent_modulus(
     Ok(public_key[pos..].split_at(exp_len))
 }
 
+//------------ ValidationStatus ----------------------------------------------
+
+/// The outcome of validating data.
+///
+/// The four states are defined in [RFC 4035, Section 4.3].
+///
+/// [RFC 4035, Section 4.3]: https://tools.ietf.org/html/rfc4035#section-4.3
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ValidationStatus {
+    /// There is a chain of signed records from a trust anchor to the data.
+    Secure,
+
+    /// There is a chain of signed records from a trust anchor to a proof
+    /// that the data is not signed.
+    Insecure,
+
+    /// The data should be signed but validation failed.
+    ///
+    /// This may be because signatures are missing, have expired, or are
+    /// invalid, or because a required proof of non-existence is missing.
+    Bogus,
+
+    /// It could not be determined whether the data should be signed.
+    ///
+    /// This is the case if there is no trust anchor covering the data or
+    /// if the necessary records could not be retrieved.
+    Indeterminate,
+}
+
+impl ValidationStatus {
+    /// Returns whether the status is `Secure`.
+    pub fn is_secure(self) -> bool {
+        matches!(self, ValidationStatus::Secure)
+    }
+
+    /// Returns whether the status is `Bogus`.
+    pub fn is_bogus(self) -> bool {
+        matches!(self, ValidationStatus::Bogus)
+    }
+
+    /// Combines the status of two pieces of data into an overall status.
+    ///
+    /// The result is the weaker of the two where `Bogus` is weaker than
+    /// `Indeterminate` which is weaker than `Insecure` which is weaker than
+    /// `Secure`.
+    pub fn combine(self, other: Self) -> Self {
+        use self::ValidationStatus::*;
+
+        match (self, other) {
+            (Bogus, _) | (_, Bogus) => Bogus,
+            (Indeterminate, _) | (_, Indeterminate) => Indeterminate,
+            (Insecure, _) | (_, Insecure) => Insecure,
+            (Secure, Secure) => Secure,
+        }
+    }
+}
+
+//--- Display
+
+impl fmt::Display for ValidationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ValidationStatus::Secure => "secure",
+            ValidationStatus::Insecure => "insecure",
+            ValidationStatus::Bogus => "bogus",
+            ValidationStatus::Indeterminate => "indeterminate",
+        })
+    }
+}
+
 //============ Error Types ===================================================
 
 //------------ AlgorithmError ------------------------------------------------
@@ -0,0 +1,370 @@
+//! Verification of signed RRsets.
+//!
+//! This module contains the building blocks for authenticating the records
+//! of a response: [`verify_rrset`] checks an RRset against its RRSIGs using
+//! a set of trusted DNSKEYs while [`verify_dnskeys`] checks the DNSKEY RRset
+//! of a zone against the DS records from its parent.
+
+use super::{DnskeyExt, RrsigExt};
+use crate::base::cmp::CanonicalOrd;
+use crate::base::name::ToDname;
+use crate::base::octets::Compose;
+use crate::base::rdata::RecordData;
+use crate::base::record::Record;
+use crate::base::serial::Serial;
+use crate::rdata::{Dnskey, Ds, Rrsig};
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ verify_rrset --------------------------------------------------
+
+/// Verifies an RRset using the given RRSIGs and DNSKEYs.
+///
+/// The records in `rrset` must all have the same owner, class, and type.
+/// They will be re-ordered into canonical order as part of the process.
+/// Only those of the `rrsigs` that cover the type of the RRset and have
+/// been created by `signer` are considered. `keys` are the trusted DNSKEYs
+/// of the zone `signer`. A signature is only accepted if `now` falls into
+/// its validity period.
+///
+/// Upon success, returns the signature that verified the RRset. If its
+/// label count is smaller than that of the owner name, the RRset has been
+/// synthesized from a wildcard and the caller needs to check that there is
+/// a proof of the non-existence of the query name.
+///
+/// If none of the signatures validates the RRset, returns the error of the
+/// signature that made it furthest along the way.
+pub fn verify_rrset<'s, N, D, SigOcts, SigName, KeyOcts>(
+    signer: &impl ToDname,
+    rrset: &mut [Record<N, D>],
+    rrsigs: impl IntoIterator<Item = &'s Rrsig<SigOcts, SigName>>,
+    keys: &[Dnskey<KeyOcts>],
+    now: Serial,
+) -> Result<&'s Rrsig<SigOcts, SigName>, RrsetError>
+where
+    N: ToDname,
+    D: RecordData + CanonicalOrd + Compose,
+    SigOcts: AsRef<[u8]> + 's,
+    SigName: ToDname + 's,
+    KeyOcts: AsRef<[u8]>,
+{
+    let (rtype, label_count) = match rrset.first() {
+        Some(record) => {
+            if !record.owner().ends_with(signer) {
+                return Err(RrsetError::NoSignature);
+            }
+            (record.rtype(), record.owner().rrsig_label_count())
+        }
+        None => return Err(RrsetError::NoSignature),
+    };
+
+    let mut err = RrsetError::NoSignature;
+    for rrsig in rrsigs {
+        if rrsig.type_covered() != rtype
+            || !rrsig.signer_name().name_eq(signer)
+            || rrsig.labels() > label_count
+        {
+            continue;
+        }
+        if !(rrsig.inception() <= now && now <= rrsig.expiration()) {
+            err = err.max(RrsetError::OutsideValidity);
+            continue;
+        }
+
+        let mut signed_data = None;
+        for key in keys {
+            if !key_may_sign(key)
+                || key.algorithm() != rrsig.algorithm()
+                || key.key_tag() != rrsig.key_tag()
+            {
+                continue;
+            }
+            if signed_data.is_none() {
+                let mut buf = Vec::new();
+                if rrsig.signed_data(&mut buf, rrset).is_err() {
+                    break;
+                }
+                signed_data = Some(buf);
+            }
+            let data = signed_data.as_ref().unwrap();
+            if rrsig.verify_signed_data(key, data).is_ok() {
+                return Ok(rrsig);
+            }
+        }
+        err = err.max(match signed_data {
+            Some(_) => RrsetError::BadSignature,
+            None => RrsetError::NoKey,
+        });
+    }
+    Err(err)
+}
+
+//------------ verify_dnskeys ------------------------------------------------
+
+/// Verifies the DNSKEY RRset of a zone using the DS records of its parent.
+///
+/// The DNSKEY RRset is considered valid if it has been signed by one of
+/// the keys in the set that is referred to by one of the DS records in
+/// `ds`. The RRset is passed as a slice of records so that it can be sorted
+/// canonically. The owner of these records is the apex of the zone. The
+/// `rrsigs` should be the RRSIGs for the RRset. As for all signatures, `now`
+/// needs to fall into their validity period.
+///
+/// Upon success, returns the signature that verified the RRset.
+pub fn verify_dnskeys<'s, N, Octets, SigOcts, SigName, DsOcts>(
+    dnskeys: &mut [Record<N, Dnskey<Octets>>],
+    rrsigs: impl IntoIterator<Item = &'s Rrsig<SigOcts, SigName>>,
+    ds: &[Ds<DsOcts>],
+    now: Serial,
+) -> Result<&'s Rrsig<SigOcts, SigName>, RrsetError>
+where
+    N: ToDname,
+    Octets: AsRef<[u8]> + Clone,
+    SigOcts: AsRef<[u8]> + 's,
+    SigName: ToDname + 's,
+    DsOcts: AsRef<[u8]>,
+{
+    verify_dnskeys_with(dnskeys, rrsigs, now, |owner, key| {
+        ds.iter().any(|ds| ds_matches_key(ds, owner, key))
+    })
+}
+
+/// Verifies a DNSKEY RRset using the keys approved by `trusted`.
+///
+/// This is the common part of [`verify_dnskeys`] and the verification via
+/// trust anchors.
+pub(super) fn verify_dnskeys_with<'s, N, Octets, SigOcts, SigName, F>(
+    dnskeys: &mut [Record<N, Dnskey<Octets>>],
+    rrsigs: impl IntoIterator<Item = &'s Rrsig<SigOcts, SigName>>,
+    now: Serial,
+    trusted: F,
+) -> Result<&'s Rrsig<SigOcts, SigName>, RrsetError>
+where
+    N: ToDname,
+    Octets: AsRef<[u8]> + Clone,
+    SigOcts: AsRef<[u8]> + 's,
+    SigName: ToDname + 's,
+    F: Fn(&N, &Dnskey<Octets>) -> bool,
+{
+    let keys: Vec<_> = dnskeys
+        .iter()
+        .filter(|record| trusted(record.owner(), record.data()))
+        .map(|record| record.data().clone())
+        .collect();
+    if keys.is_empty() {
+        return Err(RrsetError::NoKey);
+    }
+    let owner = match dnskeys.first() {
+        Some(record) => match record.owner().to_dname::<Vec<u8>>() {
+            Ok(owner) => owner,
+            Err(_) => return Err(RrsetError::NoKey),
+        },
+        None => return Err(RrsetError::NoKey),
+    };
+    verify_rrset(&owner, dnskeys, rrsigs, &keys, now)
+}
+
+/// Returns whether the DS record `ds` refers to `key` owned by `owner`.
+pub fn ds_matches_key<N, DsOcts, KeyOcts>(
+    ds: &Ds<DsOcts>,
+    owner: &N,
+    key: &Dnskey<KeyOcts>,
+) -> bool
+where
+    N: ToDname,
+    DsOcts: AsRef<[u8]>,
+    KeyOcts: AsRef<[u8]>,
+{
+    if ds.algorithm() != key.algorithm() || ds.key_tag() != key.key_tag() {
+        return false;
+    }
+    match key.digest(owner, ds.digest_type()) {
+        Ok(digest) => digest.as_ref() == ds.digest().as_ref(),
+        Err(_) => false,
+    }
+}
+
+/// Returns whether a key may be used to verify RRSIGs.
+///
+/// See [RFC 4034, Section 2.1.1] and [RFC 5011, Section 2.1].
+///
+/// [RFC 4034, Section 2.1.1]: https://tools.ietf.org/html/rfc4034#section-2.1.1
+/// [RFC 5011, Section 2.1]: https://tools.ietf.org/html/rfc5011#section-2.1
+fn key_may_sign<Octets: AsRef<[u8]>>(key: &Dnskey<Octets>) -> bool {
+    key.is_zsk() && !key.is_revoked() && key.protocol() == 3
+}
+
+//============ Error Types ===================================================
+
+//------------ RrsetError ----------------------------------------------------
+
+/// Verification of an RRset failed.
+///
+/// The variants are ordered by how far the verification got.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum RrsetError {
+    /// There was no signature by the signer for the RRset.
+    NoSignature,
+
+    /// The current time is outside the validity period of all signatures.
+    OutsideValidity,
+
+    /// None of the signatures was made by a trusted key.
+    NoKey,
+
+    /// None of the signatures could be verified.
+    BadSignature,
+}
+
+//--- Display and Error
+
+impl fmt::Display for RrsetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RrsetError::NoSignature => f.write_str("no signature"),
+            RrsetError::OutsideValidity => {
+                f.write_str("signature expired or not yet valid")
+            }
+            RrsetError::NoKey => f.write_str("no trusted key"),
+            RrsetError::BadSignature => f.write_str("bad signature"),
+        }
+    }
+}
+
+impl error::Error for RrsetError {}
+
+//============ Test ==========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Class, DigestAlg, Rtype, SecAlg};
+    use crate::base::name::Dname;
+    use crate::utils::base64;
+    use std::str::FromStr;
+    use std::string::String;
+
+    type Name = Dname<Vec<u8>>;
+
+    fn decode(s: &str) -> Vec<u8> {
+        let s: String = s.split_whitespace().collect();
+        base64::decode(&s).unwrap().as_ref().into()
+    }
+
+    // The root KSK and ZSK and the RRSIG over them from May 2019.
+    fn root_dnskeys(
+    ) -> (Vec<Record<Name, Dnskey<Vec<u8>>>>, Rrsig<Vec<u8>, Name>) {
+        let ksk = Dnskey::new(
+            257,
+            3,
+            SecAlg::RsaSha256,
+            decode(
+                "AwEAAaz/tAm8yTn4Mfeh5eyI96WSVexTBAvkMgJzkKTOiW1vkIbzxeF3+/
+                4RgWOq7HrxRixHlFlExOLAJr5emLvN7SWXgnLh4+B5xQlNVz8Og8kvArMt
+                NROxVQuCaSnIDdD5LKyWbRd2n9WGe2R8PzgCmr3EgVLrjyBxWezF0jLHwV
+                N8efS3rCj/EWgvIWgb9tarpVUDK/b58Da+sqqls3eNbuv7pr+eoZG+SrDK
+                6nWeL3c6H5Apxz7LjVc1uTIdsIXxuOLYA4/ilBmSVIzuDWfdRUfhHdY6+c
+                n8HFRm+2hM8AnXGXws9555KrUB5qihylGa8subX2Nn6UwNR1AkUTV74bU=",
+            ),
+        );
+        let zsk = Dnskey::new(
+            256,
+            3,
+            SecAlg::RsaSha256,
+            decode(
+                "AwEAAeVDC34GZILwsQJy97K2Fst4P3XYZrXLyrkausYzSqEjSUulgh+iLgH
+                g0y7FIF890+sIjXsk7KLJUmCOWfYWPorNKEOKLk5Zx/4M6D3IHZE3O3m/Ea
+                hrc28qQzmTLxiMZAW65MvR2UO3LxVtYOPBEBiDgAQD47x2JLsJYtavCzNL5
+                WiUk59OgvHmDqmcC7VXYBhK8V8Tic089XJgExGeplKWUt9yyc31ra1swJX5
+                1XsOaQz17+vyLVH8AZP26KvKFiZeoRbaq6vl+hc8HQnI2ug5rA2zoz3MsSQ
+                BvP1f/HvqsWxLqwXXKyDD1QM639U+XzVB8CYigyscRP22QCnwKIU=",
+            ),
+        );
+        let rrsig = Rrsig::new(
+            Rtype::Dnskey,
+            SecAlg::RsaSha256,
+            0,
+            172800,
+            1560211200.into(),
+            1558396800.into(),
+            20326,
+            Name::root(),
+            decode(
+                "otBkINZAQu7AvPKjr/xWIEE7+SoZtKgF8bzVynX6bfJMJuPay8jPvNmwXk
+                ZOdSoYlvFp0bk9JWJKCh8y5uoNfMFkN6OSrDkr3t0E+c8c0Mnmwkk5CETH3
+                Gqxthi0yyRX5T4VlHU06/Ks4zI+XAgl3FBpOc554ivdzez8YCjAIGx7Xgzz
+                ooEb7heMSlLc7S7/HNjw51TPRs4RxrAVcezieKCzPPpeWBhjE6R3oiSwrl0
+                SBD4/yplrDlr7UHs/Atcm3MSgemdyr2sOoOUkVQCVpcj3SQQezoD2tCM786
+                1CXEQdg5fjeHDtz285xHt5HJpA5cOcctRo4ihybfow/+V7AQ==",
+            ),
+        );
+        let records = vec![
+            Record::new(Name::root(), Class::In, 172800, ksk),
+            Record::new(Name::root(), Class::In, 172800, zsk),
+        ];
+        (records, rrsig)
+    }
+
+    fn root_ds() -> Ds<Vec<u8>> {
+        Ds::new(
+            20326,
+            SecAlg::RsaSha256,
+            DigestAlg::Sha256,
+            decode("4G1EuAuPHTmpXAsNfGXQhFjogECbvGg0VxBCN8f47I0="),
+        )
+    }
+
+    #[test]
+    fn dnskeys_with_ds() {
+        let (mut records, rrsig) = root_dnskeys();
+        let now = Serial(1559000000);
+        assert!(verify_dnskeys(
+            &mut records,
+            &[rrsig.clone()],
+            &[root_ds()],
+            now
+        )
+        .is_ok());
+        assert_eq!(
+            verify_dnskeys(
+                &mut records,
+                &[rrsig],
+                &[root_ds()],
+                Serial(1561000000)
+            ),
+            Err(RrsetError::OutsideValidity)
+        );
+    }
+
+    #[test]
+    fn dnskeys_wrong_ds() {
+        let (mut records, rrsig) = root_dnskeys();
+        let ds = Ds::new(
+            20326,
+            SecAlg::RsaSha256,
+            DigestAlg::Sha256,
+            vec![0u8; 32],
+        );
+        assert_eq!(
+            verify_dnskeys(&mut records, &[rrsig], &[ds], Serial(1559000000)),
+            Err(RrsetError::NoKey)
+        );
+    }
+
+    #[test]
+    fn rrset_wrong_signer() {
+        let (mut records, rrsig) = root_dnskeys();
+        let keys: Vec<_> = records.iter().map(|r| r.data().clone()).collect();
+        let signer = Name::from_str("example.").unwrap();
+        assert_eq!(
+            verify_rrset(
+                &signer,
+                &mut records,
+                &[rrsig],
+                &keys,
+                Serial(1559000000)
+            ),
+            Err(RrsetError::NoSignature)
+        );
+    }
+}
@@ -0,0 +1,989 @@
+//! Verification of complete signed zones.
+//!
+//! This module checks a signed zone before it is published. The function
+//! [`verify_zone`] takes all records of a zone as a [`SortedRecords`] and
+//! checks that
+//!
+//! * every authoritative RRset is signed by a DNSKEY from the apex with
+//!   every algorithm present in the DNSKEY RRset,
+//! * all signatures are valid at the given time,
+//! * no signatures are present for data that isn’t authoritative, and
+//! * the NSEC or NSEC3 chain covers all names of the zone in the right
+//!   order with type bitmaps matching the types present at each name.
+//!
+//! Rather than stopping at the first problem, all problems are collected
+//! and returned as a list of [`ZoneProblem`]s, each naming the owner and
+//! record type it was found at.
+#![cfg(feature = "sign")]
+#![cfg_attr(docsrs, doc(cfg(all(feature = "validate", feature = "sign"))))]
+
+use super::{supports_algorithm, RrsigExt};
+use crate::base::cmp::CanonicalOrd;
+use crate::base::iana::{Rtype, SecAlg};
+use crate::base::name::{Dname, ToDname};
+use crate::base::octets::Compose;
+use crate::base::rdata::RecordData;
+use crate::base::record::Record;
+use crate::base::serial::Serial;
+use crate::rdata::rfc4034::RtypeBitmap;
+use crate::rdata::rfc5155::nsec3_hash;
+use crate::rdata::{
+    AllRecordData, Dnskey, MasterRecordData, Nsec, Nsec3, Nsec3param, Rrsig,
+};
+use crate::sign::records::{Family, FamilyName, MaybeRrsig, SortedRecords};
+use crate::utils::base32;
+use std::collections::BTreeMap;
+use std::string::{String, ToString};
+use std::vec::Vec;
+use std::{error, fmt};
+
+//------------ verify_zone ---------------------------------------------------
+
+/// Verifies a signed zone.
+///
+/// The zone has its apex at `apex` and consists of those `records` at or
+/// below the apex. Signatures are checked against the DNSKEY records at
+/// the apex and must be valid at `now`.
+///
+/// Returns all problems found in the order of their owner names. If the
+/// returned list is empty, the zone is correctly signed.
+pub fn verify_zone<N, D, A>(
+    records: &SortedRecords<N, D>,
+    apex: &FamilyName<A>,
+    now: Serial,
+) -> Vec<ZoneProblem>
+where
+    N: ToDname + Clone,
+    D: ZoneRecordData + CanonicalOrd + Compose + Clone,
+    D::Octets: AsRef<[u8]>,
+    D::Name: Compose,
+    A: ToDname,
+{
+    let mut verifier = Verifier {
+        apex: apex.owner().to_vec(),
+        now,
+        keys: Vec::new(),
+        algorithms: Vec::new(),
+        problems: Vec::new(),
+    };
+    let families = zone_families(records, apex);
+    match families.first() {
+        Some((family, _)) if family.family_name() == *apex => {
+            verifier.load_keys(family);
+        }
+        _ => {
+            verifier.report(apex.owner(), Rtype::Soa, ProblemKind::NoApex);
+            return verifier.problems;
+        }
+    }
+
+    for (family, cut) in &families {
+        verifier.check_signatures(family, *cut);
+    }
+
+    let apex_family = &families[0].0;
+    let nsec3param = apex_family
+        .records()
+        .filter_map(|record| record.data().as_nsec3param())
+        .next();
+    if let Some(param) = nsec3param {
+        verifier.check_nsec3s(&families, param);
+    } else if apex_family
+        .records()
+        .any(|record| record.rtype() == Rtype::Nsec)
+    {
+        verifier.check_nsecs(&families);
+    } else {
+        verifier.report(apex.owner(), Rtype::Nsec, ProblemKind::NoDenial);
+    }
+
+    // The sort is stable, so problems at the same name keep their order.
+    let mut res = verifier.problems;
+    res.sort_by(|left, right| left.owner.name_cmp(&right.owner));
+    res
+}
+
+/// Returns the authoritative families of the zone.
+///
+/// Families below zone cuts are left out. The second element of each item
+/// states whether the family is a zone cut.
+fn zone_families<'a, N, D, A>(
+    records: &'a SortedRecords<N, D>,
+    apex: &FamilyName<A>,
+) -> Vec<(Family<'a, N, D>, bool)>
+where
+    N: ToDname,
+    D: RecordData,
+    A: ToDname,
+{
+    let mut res = Vec::new();
+    let mut cut: Option<&'a N> = None;
+    let mut families = records.families();
+    families.skip_before(apex);
+    for family in families {
+        if !family.is_in_zone(apex) {
+            break;
+        }
+        if let Some(cut) = cut {
+            if family.owner().ends_with(cut) {
+                continue;
+            }
+        }
+        let is_cut = family.is_zone_cut(apex);
+        cut = if is_cut {
+            Some(family.records().next().unwrap().owner())
+        } else {
+            None
+        };
+        res.push((family, is_cut));
+    }
+    res
+}
+
+//------------ Verifier ------------------------------------------------------
+
+/// The state of verifying a zone.
+struct Verifier<'a, O> {
+    /// The apex of the zone.
+    apex: Dname<Vec<u8>>,
+
+    /// The time signatures need to be valid at.
+    now: Serial,
+
+    /// The zone keys at the apex.
+    keys: Vec<&'a Dnskey<O>>,
+
+    /// The supported algorithms every RRset needs to be signed with.
+    algorithms: Vec<SecAlg>,
+
+    /// The problems found so far.
+    problems: Vec<ZoneProblem>,
+}
+
+impl<'a, O: AsRef<[u8]>> Verifier<'a, O> {
+    /// Adds a problem.
+    fn report(
+        &mut self,
+        owner: &impl ToDname,
+        rtype: Rtype,
+        kind: ProblemKind,
+    ) {
+        self.problems.push(ZoneProblem {
+            owner: owner.to_vec(),
+            rtype,
+            kind,
+        })
+    }
+
+    /// Collects the zone keys from the apex family.
+    fn load_keys<N, D>(&mut self, apex: &Family<'a, N, D>)
+    where
+        N: ToDname,
+        D: ZoneRecordData<Octets = O>,
+    {
+        for record in apex.records() {
+            let dnskey = match record.data().as_dnskey() {
+                Some(dnskey) => dnskey,
+                None => continue,
+            };
+            if dnskey.flags() & ZONE_KEY_FLAG == 0 || dnskey.protocol() != 3 {
+                continue;
+            }
+            self.keys.push(dnskey);
+            let algorithm = dnskey.algorithm();
+            if !supports_algorithm(algorithm) {
+                self.report(
+                    record.owner(),
+                    Rtype::Dnskey,
+                    ProblemKind::UnsupportedAlgorithm(algorithm),
+                );
+            } else if !self.algorithms.contains(&algorithm) {
+                self.algorithms.push(algorithm)
+            }
+        }
+        if self.keys.is_empty() {
+            let apex = self.apex.clone();
+            self.report(&apex, Rtype::Dnskey, ProblemKind::NoDnskey);
+        }
+    }
+
+    /// Checks the signatures of all RRsets of a family.
+    ///
+    /// If `cut` is `true`, the family is the parent side of a zone cut and
+    /// only its DS and NSEC RRsets are authoritative.
+    fn check_signatures<N, D>(&mut self, family: &Family<'a, N, D>, cut: bool)
+    where
+        N: ToDname + Clone,
+        D: ZoneRecordData<Octets = O> + CanonicalOrd + Compose + Clone,
+        D::Name: Compose,
+    {
+        let owner = family.owner();
+        let rrsigs: Vec<_> = family
+            .records()
+            .filter_map(|record| record.data().as_rrsig())
+            .collect();
+
+        for rrset in family.rrsets() {
+            let rtype = rrset.rtype();
+            if rtype == Rtype::Rrsig {
+                continue;
+            }
+            let mut sigs = rrsigs
+                .iter()
+                .filter(|sig| sig.type_covered() == rtype)
+                .peekable();
+            if cut && rtype != Rtype::Ds && rtype != Rtype::Nsec {
+                if sigs.peek().is_some() {
+                    self.report(
+                        owner,
+                        rtype,
+                        ProblemKind::UnexpectedSignature,
+                    );
+                }
+                continue;
+            }
+            if sigs.peek().is_none() {
+                self.report(owner, rtype, ProblemKind::Unsigned);
+                continue;
+            }
+
+            let mut records: Vec<_> = rrset.iter().cloned().collect();
+            let mut signed = Vec::new();
+            for sig in sigs {
+                if !supports_algorithm(sig.algorithm()) {
+                    continue;
+                }
+                match self.check_signature(sig, &mut records) {
+                    Ok(()) => signed.push(sig.algorithm()),
+                    Err(kind) => self.report(owner, rtype, kind),
+                }
+            }
+            for &algorithm in &self.algorithms {
+                if !signed.contains(&algorithm) {
+                    self.problems.push(ZoneProblem {
+                        owner: owner.to_vec(),
+                        rtype,
+                        kind: ProblemKind::MissingAlgorithm(algorithm),
+                    });
+                }
+            }
+        }
+
+        // Signatures for types that aren’t there.
+        for sig in &rrsigs {
+            let rtype = sig.type_covered();
+            if !family.records().any(|record| record.rtype() == rtype) {
+                self.report(owner, rtype, ProblemKind::UnexpectedSignature);
+            }
+        }
+    }
+
+    /// Checks a single signature over an RRset.
+    fn check_signature<N, D, SigName>(
+        &self,
+        sig: &Rrsig<O, SigName>,
+        records: &mut [Record<N, D>],
+    ) -> Result<(), ProblemKind>
+    where
+        N: ToDname,
+        D: RecordData + CanonicalOrd + Compose,
+        SigName: ToDname + Compose,
+    {
+        if !sig.signer_name().name_eq(&self.apex) {
+            return Err(ProblemKind::WrongSigner);
+        }
+        if sig.inception() > self.now {
+            return Err(ProblemKind::SignatureNotYetValid);
+        }
+        if sig.expiration() < self.now {
+            return Err(ProblemKind::SignatureExpired);
+        }
+        let mut signed_data = Vec::new();
+        if sig.signed_data(&mut signed_data, records).is_err() {
+            return Err(ProblemKind::BadSignature);
+        }
+        let mut res = ProblemKind::UnknownKey;
+        for key in &self.keys {
+            if key.algorithm() != sig.algorithm()
+                || key.key_tag() != sig.key_tag()
+            {
+                continue;
+            }
+            if sig.verify_signed_data(*key, &signed_data).is_ok() {
+                return Ok(());
+            }
+            res = ProblemKind::BadSignature;
+        }
+        Err(res)
+    }
+
+    /// Checks the NSEC chain of the zone.
+    fn check_nsecs<N, D>(&mut self, families: &[(Family<'a, N, D>, bool)])
+    where
+        N: ToDname,
+        D: ZoneRecordData<Octets = O>,
+    {
+        for (idx, (family, _)) in families.iter().enumerate() {
+            let owner = family.owner();
+            let nsec = match family
+                .records()
+                .filter_map(|record| record.data().as_nsec())
+                .next()
+            {
+                Some(nsec) => nsec,
+                None => {
+                    self.report(owner, Rtype::Nsec, ProblemKind::MissingNsec);
+                    continue;
+                }
+            };
+            let next_ok = match families.get(idx + 1) {
+                Some((next, _)) => nsec.next_name().name_eq(next.owner()),
+                None => nsec.next_name().name_eq(&self.apex),
+            };
+            if !next_ok {
+                self.report(owner, Rtype::Nsec, ProblemKind::WrongNext);
+            }
+            let mut types = Vec::new();
+            for record in family.records() {
+                if !types.contains(&record.rtype()) {
+                    types.push(record.rtype())
+                }
+            }
+            if !bitmap_matches(nsec.types(), &types) {
+                self.report(owner, Rtype::Nsec, ProblemKind::BitmapMismatch);
+            }
+        }
+    }
+
+    /// Checks the NSEC3 chain of the zone.
+    fn check_nsec3s<N, D>(
+        &mut self,
+        families: &[(Family<'a, N, D>, bool)],
+        param: &Nsec3param<O>,
+    ) where
+        N: ToDname,
+        D: ZoneRecordData<Octets = O>,
+    {
+        let algorithm = param.hash_algorithm();
+        let iterations = param.iterations();
+        let salt = param.salt().as_slice();
+        let hash = |name: &Dname<Vec<u8>>| {
+            nsec3_hash(name, algorithm, iterations, salt)
+                .map(|hash| hash.to_string())
+        };
+        if hash(&self.apex).is_err() {
+            let apex = self.apex.clone();
+            self.report(
+                &apex,
+                Rtype::Nsec3param,
+                ProblemKind::UnsupportedNsec3Hash,
+            );
+            return;
+        }
+
+        // The NSEC3 records we expect, keyed by their hash in lowercase
+        // base32hex, and the NSEC3 records present in the zone.
+        let mut expected = BTreeMap::<String, ExpectedNsec3>::new();
+        let mut nsec3s = Vec::new();
+        for (family, cut) in families {
+            let mut types = Vec::new();
+            for record in family.records() {
+                let rtype = record.rtype();
+                if let Some(nsec3) = record.data().as_nsec3() {
+                    nsec3s.push((record.owner(), nsec3));
+                    continue;
+                }
+                let nsec3_sig = record
+                    .data()
+                    .as_rrsig()
+                    .map(|sig| sig.type_covered() == Rtype::Nsec3)
+                    .unwrap_or(false);
+                if !nsec3_sig && !types.contains(&rtype) {
+                    types.push(rtype)
+                }
+            }
+            if types.is_empty() {
+                // A family holding only NSEC3 records.
+                continue;
+            }
+            let insecure = *cut && !types.contains(&Rtype::Ds);
+            let name = family.owner().to_vec();
+
+            // Empty non-terminals need an NSEC3 record, too, unless all
+            // names below them are insecure delegations and opt-out is
+            // used. See RFC 5155, section 7.1.
+            for ancestor in name.iter_suffixes().skip(1) {
+                if !ancestor.ends_with(&self.apex)
+                    || ancestor.name_eq(&self.apex)
+                {
+                    break;
+                }
+                let ancestor = ancestor.to_vec();
+                let hash = hash(&ancestor).unwrap();
+                if let Some(item) = expected.get_mut(&hash) {
+                    if insecure || !item.insecure || !item.types.is_empty() {
+                        break;
+                    }
+                    item.insecure = false;
+                    continue;
+                }
+                expected.insert(
+                    hash,
+                    ExpectedNsec3 {
+                        name: ancestor,
+                        types: Vec::new(),
+                        insecure,
+                        found: false,
+                    },
+                );
+            }
+            expected.insert(
+                hash(&name).unwrap(),
+                ExpectedNsec3 {
+                    name,
+                    types,
+                    insecure,
+                    found: false,
+                },
+            );
+        }
+
+        let opt_out = nsec3s.iter().any(|(_, nsec3)| nsec3.opt_out());
+        let labels: Vec<_> = nsec3s
+            .iter()
+            .map(|(owner, _)| hash_label(*owner, &self.apex))
+            .collect();
+        for (idx, &(owner, nsec3)) in nsec3s.iter().enumerate() {
+            if nsec3.hash_algorithm() != algorithm
+                || nsec3.iterations() != iterations
+                || nsec3.salt().as_slice() != salt
+            {
+                self.report(
+                    owner,
+                    Rtype::Nsec3,
+                    ProblemKind::Nsec3ParamMismatch,
+                );
+            }
+            let item = labels[idx]
+                .as_ref()
+                .and_then(|label| expected.get_mut(label));
+            let item = match item {
+                Some(item) => item,
+                None => {
+                    self.report(
+                        owner,
+                        Rtype::Nsec3,
+                        ProblemKind::UnexpectedNsec3,
+                    );
+                    continue;
+                }
+            };
+            item.found = true;
+            if !bitmap_matches(nsec3.types(), &item.types) {
+                self.report(owner, Rtype::Nsec3, ProblemKind::BitmapMismatch);
+            }
+            let next = encode_hash(nsec3.next_owner().as_slice());
+            if labels[(idx + 1) % labels.len()].as_ref() != Some(&next) {
+                self.report(owner, Rtype::Nsec3, ProblemKind::WrongNext);
+            }
+        }
+
+        for item in expected.values() {
+            // Insecure delegations may be left out with opt-out.
+            if item.found || (item.insecure && opt_out) {
+                continue;
+            }
+            self.problems.push(ZoneProblem {
+                owner: item.name.clone(),
+                rtype: Rtype::Nsec3,
+                kind: ProblemKind::MissingNsec3,
+            });
+        }
+    }
+}
+
+/// An NSEC3 record expected in the zone.
+struct ExpectedNsec3 {
+    /// The original owner name.
+    name: Dname<Vec<u8>>,
+
+    /// The types at the original owner name.
+    types: Vec<Rtype>,
+
+    /// Whether the name is an insecure delegation.
+    ///
+    /// This is also true for an empty non-terminal with only insecure
+    /// delegations below it.
+    insecure: bool,
+
+    /// Whether the record has been found.
+    found: bool,
+}
+
+//------------ Helper Functions ----------------------------------------------
+
+/// The zone key flag of a DNSKEY record. See RFC 4034, section 2.1.1.
+const ZONE_KEY_FLAG: u16 = 0x0100;
+
+/// Returns whether a type bitmap contains exactly the given types.
+fn bitmap_matches<O: AsRef<[u8]>>(
+    bitmap: &RtypeBitmap<O>,
+    types: &[Rtype],
+) -> bool {
+    bitmap.iter().count() == types.len()
+        && types.iter().all(|rtype| bitmap.contains(*rtype))
+}
+
+/// Returns the lowercase hash label of an NSEC3 owner name.
+///
+/// Returns `None` if the name isn’t directly below the apex.
+fn hash_label(owner: &impl ToDname, apex: &Dname<Vec<u8>>) -> Option<String> {
+    let owner = owner.to_vec();
+    match owner.iter_suffixes().nth(1) {
+        Some(parent) if parent.name_eq(apex) => {}
+        _ => return None,
+    }
+    let label = owner.iter().next()?;
+    let mut res = String::from_utf8_lossy(label.as_slice()).into_owned();
+    res.make_ascii_lowercase();
+    Some(res)
+}
+
+/// Encodes a hash value as lowercase base32hex.
+fn encode_hash(hash: &[u8]) -> String {
+    let mut res = String::new();
+    base32::display_hex(hash, &mut res).unwrap();
+    res.make_ascii_lowercase();
+    res
+}
+
+//------------ ZoneRecordData ------------------------------------------------
+
+/// Access to the record data types relevant for zone verification.
+pub trait ZoneRecordData: RecordData + MaybeRrsig {
+    /// Returns the DNSKEY record data if the data is one.
+    fn as_dnskey(&self) -> Option<&Dnskey<Self::Octets>>;
+
+    /// Returns the NSEC record data if the data is one.
+    fn as_nsec(&self) -> Option<&Nsec<Self::Octets, Self::Name>>;
+
+    /// Returns the NSEC3 record data if the data is one.
+    fn as_nsec3(&self) -> Option<&Nsec3<Self::Octets>>;
+
+    /// Returns the NSEC3PARAM record data if the data is one.
+    fn as_nsec3param(&self) -> Option<&Nsec3param<Self::Octets>>;
+}
+
+impl<O: AsRef<[u8]>, N: ToDname> ZoneRecordData for MasterRecordData<O, N> {
+    fn as_dnskey(&self) -> Option<&Dnskey<O>> {
+        match *self {
+            MasterRecordData::Dnskey(ref dnskey) => Some(dnskey),
+            _ => None,
+        }
+    }
+
+    fn as_nsec(&self) -> Option<&Nsec<O, N>> {
+        match *self {
+            MasterRecordData::Nsec(ref nsec) => Some(nsec),
+            _ => None,
+        }
+    }
+
+    fn as_nsec3(&self) -> Option<&Nsec3<O>> {
+        match *self {
+            MasterRecordData::Nsec3(ref nsec3) => Some(nsec3),
+            _ => None,
+        }
+    }
+
+    fn as_nsec3param(&self) -> Option<&Nsec3param<O>> {
+        match *self {
+            MasterRecordData::Nsec3param(ref param) => Some(param),
+            _ => None,
+        }
+    }
+}
+
+impl<O: AsRef<[u8]>, N: ToDname> ZoneRecordData for AllRecordData<O, N> {
+    fn as_dnskey(&self) -> Option<&Dnskey<O>> {
+        match *self {
+            AllRecordData::Dnskey(ref dnskey) => Some(dnskey),
+            _ => None,
+        }
+    }
+
+    fn as_nsec(&self) -> Option<&Nsec<O, N>> {
+        match *self {
+            AllRecordData::Nsec(ref nsec) => Some(nsec),
+            _ => None,
+        }
+    }
+
+    fn as_nsec3(&self) -> Option<&Nsec3<O>> {
+        match *self {
+            AllRecordData::Nsec3(ref nsec3) => Some(nsec3),
+            _ => None,
+        }
+    }
+
+    fn as_nsec3param(&self) -> Option<&Nsec3param<O>> {
+        match *self {
+            AllRecordData::Nsec3param(ref param) => Some(param),
+            _ => None,
+        }
+    }
+}
+
+//------------ ZoneProblem ---------------------------------------------------
+
+/// A problem found in a zone.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ZoneProblem {
+    owner: Dname<Vec<u8>>,
+    rtype: Rtype,
+    kind: ProblemKind,
+}
+
+impl ZoneProblem {
+    /// Returns the owner name the problem was found at.
+    pub fn owner(&self) -> &Dname<Vec<u8>> {
+        &self.owner
+    }
+
+    /// Returns the record type the problem was found for.
+    pub fn rtype(&self) -> Rtype {
+        self.rtype
+    }
+
+    /// Returns what kind of problem was found.
+    pub fn kind(&self) -> ProblemKind {
+        self.kind
+    }
+}
+
+//--- Display and Error
+
+impl fmt::Display for ZoneProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}: {}", self.owner, self.rtype, self.kind)
+    }
+}
+
+impl error::Error for ZoneProblem {}
+
+//------------ ProblemKind ---------------------------------------------------
+
+/// The kind of a problem found in a zone.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ProblemKind {
+    /// There are no records at the apex.
+    NoApex,
+
+    /// There are no zone keys at the apex.
+    NoDnskey,
+
+    /// A zone key uses an algorithm that can’t be verified.
+    UnsupportedAlgorithm(SecAlg),
+
+    /// An authoritative RRset has no signatures.
+    Unsigned,
+
+    /// An RRset has no valid signature with an algorithm of the zone keys.
+    MissingAlgorithm(SecAlg),
+
+    /// The signer name of a signature isn’t the apex.
+    WrongSigner,
+
+    /// A signature has expired.
+    SignatureExpired,
+
+    /// A signature isn’t valid yet.
+    SignatureNotYetValid,
+
+    /// A signature was made by a key not present at the apex.
+    UnknownKey,
+
+    /// A signature doesn’t verify.
+    BadSignature,
+
+    /// There are signatures for data that isn’t authoritative or missing.
+    UnexpectedSignature,
+
+    /// The zone has neither an NSEC chain nor an NSEC3PARAM record.
+    NoDenial,
+
+    /// An authoritative name has no NSEC record.
+    MissingNsec,
+
+    /// A name has no NSEC3 record.
+    MissingNsec3,
+
+    /// An NSEC3 record doesn’t belong to any name of the zone.
+    UnexpectedNsec3,
+
+    /// The next name of an NSEC or NSEC3 record is not the next name in
+    /// the chain.
+    WrongNext,
+
+    /// The type bitmap doesn’t match the types present at the name.
+    BitmapMismatch,
+
+    /// The parameters of an NSEC3 record differ from the NSEC3PARAM record.
+    Nsec3ParamMismatch,
+
+    /// The NSEC3 hash algorithm is not supported.
+    UnsupportedNsec3Hash,
+}
+
+//--- Display
+
+impl fmt::Display for ProblemKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ProblemKind::NoApex => f.write_str("no records at the apex"),
+            ProblemKind::NoDnskey => f.write_str("no zone keys"),
+            ProblemKind::UnsupportedAlgorithm(alg) => {
+                write!(f, "unsupported algorithm {}", alg)
+            }
+            ProblemKind::Unsigned => f.write_str("no signature"),
+            ProblemKind::MissingAlgorithm(alg) => {
+                write!(f, "no valid signature with algorithm {}", alg)
+            }
+            ProblemKind::WrongSigner => f.write_str("wrong signer name"),
+            ProblemKind::SignatureExpired => f.write_str("signature expired"),
+            ProblemKind::SignatureNotYetValid => {
+                f.write_str("signature not yet valid")
+            }
+            ProblemKind::UnknownKey => {
+                f.write_str("signature by unknown key")
+            }
+            ProblemKind::BadSignature => f.write_str("bad signature"),
+            ProblemKind::UnexpectedSignature => {
+                f.write_str("unexpected signature")
+            }
+            ProblemKind::NoDenial => f.write_str("no NSEC or NSEC3 chain"),
+            ProblemKind::MissingNsec => f.write_str("missing NSEC record"),
+            ProblemKind::MissingNsec3 => f.write_str("missing NSEC3 record"),
+            ProblemKind::UnexpectedNsec3 => {
+                f.write_str("NSEC3 record without a name")
+            }
+            ProblemKind::WrongNext => f.write_str("wrong next owner name"),
+            ProblemKind::BitmapMismatch => {
+                f.write_str("type bitmap doesn’t match types present")
+            }
+            ProblemKind::Nsec3ParamMismatch => {
+                f.write_str("NSEC3 parameters differ from NSEC3PARAM")
+            }
+            ProblemKind::UnsupportedNsec3Hash => {
+                f.write_str("unsupported NSEC3 hash algorithm")
+            }
+        }
+    }
+}
+
+//============ Testing =======================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::base::iana::{Class, Nsec3HashAlg};
+    use crate::rdata::{Ns, Soa, A};
+    use crate::sign::records::Nsec3Config;
+    use crate::sign::ring::Key;
+    use core::str::FromStr;
+    use ring::rand::SystemRandom;
+
+    type Name = Dname<Vec<u8>>;
+    type ZoneData = MasterRecordData<Vec<u8>, Name>;
+    type Zone = SortedRecords<Name, ZoneData>;
+
+    fn name(s: &str) -> Name {
+        Name::from_str(s).unwrap()
+    }
+
+    fn record(owner: &str, data: ZoneData) -> Record<Name, ZoneData> {
+        Record::new(name(owner), Class::In, 3600, data)
+    }
+
+    /// Creates an unsigned zone with a delegation.
+    fn zone() -> Zone {
+        let mut zone = Zone::new();
+        let soa = Soa::new(
+            name("ns.example.com"),
+            name("hostmaster.example.com"),
+            Serial(1),
+            3600,
+            600,
+            86400,
+            3600,
+        );
+        zone.insert(record("example.com", soa.into())).unwrap();
+        zone.insert(record(
+            "example.com",
+            Ns::new(name("ns.example.com")).into(),
+        ))
+        .unwrap();
+        for (owner, addr) in &[
+            ("ns.example.com", "192.0.2.1"),
+            ("www.a.example.com", "192.0.2.2"),
+            ("ns.sub.example.com", "192.0.2.3"),
+        ] {
+            zone.insert(record(owner, A::new(addr.parse().unwrap()).into()))
+                .unwrap();
+        }
+        zone.insert(record(
+            "sub.example.com",
+            Ns::new(name("ns.sub.example.com")).into(),
+        ))
+        .unwrap();
+        zone
+    }
+
+    fn apex() -> FamilyName<Name> {
+        FamilyName::new(name("example.com"), Class::In)
+    }
+
+    /// Signs the zone using either NSEC or NSEC3.
+    fn sign(zone: &mut Zone, nsec3: Option<Nsec3Config<Vec<u8>>>) {
+        let rng = SystemRandom::new();
+        let key = Key::throwaway_13(257, &rng).unwrap();
+        let apex = apex();
+        zone.insert(Record::from_record(
+            apex.dnskey::<_, Vec<u8>>(3600, &key).unwrap(),
+        ))
+        .unwrap();
+        match nsec3 {
+            Some(config) => {
+                let chain = zone.nsec3s(&apex, 3600, &config).unwrap();
+                zone.insert(Record::from_record(chain.nsec3param)).unwrap();
+                zone.extend(
+                    chain.nsec3s.into_iter().map(Record::from_record),
+                );
+            }
+            None => {
+                let nsecs = zone.nsecs::<Vec<u8>, _>(&apex, 3600);
+                zone.extend(nsecs.into_iter().map(Record::from_record));
+            }
+        }
+        let rrsigs = zone
+            .sign::<Vec<u8>, _, _>(&apex, Serial(2000), Serial(1000), &key)
+            .unwrap();
+        zone.extend(rrsigs.into_iter().map(Record::from_record));
+    }
+
+    fn problems(zone: &Zone, now: u32) -> Vec<(String, Rtype, ProblemKind)> {
+        verify_zone(zone, &apex(), Serial(now))
+            .into_iter()
+            .map(|item| (item.owner().to_string(), item.rtype(), item.kind()))
+            .collect()
+    }
+
+    #[test]
+    fn nsec_zone() {
+        let mut zone = zone();
+        sign(&mut zone, None);
+        assert_eq!(problems(&zone, 1500), []);
+
+        let expired = problems(&zone, 3000);
+        assert!(!expired.is_empty());
+        assert!(expired.iter().all(|item| matches!(
+            item.2,
+            ProblemKind::SignatureExpired | ProblemKind::MissingAlgorithm(_)
+        )));
+        assert!(problems(&zone, 500)
+            .iter()
+            .any(|item| item.2 == ProblemKind::SignatureNotYetValid));
+
+        // Change the A record but keep its signature, remove an NSEC
+        // record, and remove the signature of the NS RRset at the apex.
+        zone.retain(|record| {
+            record.rtype() != Rtype::A
+                || !record.owner().name_eq(&name("www.a.example.com"))
+        });
+        zone.insert(record(
+            "www.a.example.com",
+            A::new("192.0.2.9".parse().unwrap()).into(),
+        ))
+        .unwrap();
+        zone.remove_rrset(&name("ns.example.com"), Class::In, Rtype::Nsec);
+        zone.retain(|record| {
+            record
+                .data()
+                .as_rrsig()
+                .map(|sig| sig.type_covered() != Rtype::Ns)
+                .unwrap_or(true)
+        });
+        assert_eq!(
+            problems(&zone, 1500),
+            [
+                ("example.com".into(), Rtype::Ns, ProblemKind::Unsigned),
+                (
+                    "www.a.example.com".into(),
+                    Rtype::A,
+                    ProblemKind::BadSignature
+                ),
+                (
+                    "www.a.example.com".into(),
+                    Rtype::A,
+                    ProblemKind::MissingAlgorithm(SecAlg::EcdsaP256Sha256)
+                ),
+                (
+                    "ns.example.com".into(),
+                    Rtype::Nsec,
+                    ProblemKind::MissingNsec
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn nsec3_zone() {
+        let mut zone = zone();
+        sign(&mut zone, Some(Nsec3Config::default()));
+        assert_eq!(problems(&zone, 1500), []);
+
+        // With opt-out, an empty non-terminal with only insecure
+        // delegations below it has no NSEC3 record.
+        let mut opt_out = self::zone();
+        opt_out
+            .insert(record(
+                "sub.ent.example.com",
+                Ns::new(name("ns.example.com")).into(),
+            ))
+            .unwrap();
+        sign(
+            &mut opt_out,
+            Some(Nsec3Config {
+                opt_out: true,
+                ..Default::default()
+            }),
+        );
+        assert_eq!(problems(&opt_out, 1500), []);
+        let ent =
+            nsec3_hash(&name("ent.example.com"), Nsec3HashAlg::Sha1, 0, &[])
+                .unwrap()
+                .to_owner_name::<Vec<u8>, _>(&name("example.com"))
+                .unwrap();
+        assert!(opt_out.families().all(|family| family.owner() != &ent));
+
+        // Remove the NSEC3 record for a name.
+        let hashed = nsec3_hash(
+            &name("www.a.example.com"),
+            Nsec3HashAlg::Sha1,
+            0,
+            &[],
+        )
+        .unwrap()
+        .to_owner_name::<Vec<u8>, _>(&name("example.com"))
+        .unwrap();
+        zone.remove_rrset(&hashed, Class::In, Rtype::Nsec3);
+        let problems = problems(&zone, 1500);
+        assert_eq!(problems.len(), 2);
+        assert!(problems.contains(&(
+            "www.a.example.com".into(),
+            Rtype::Nsec3,
+            ProblemKind::MissingNsec3
+        )));
+        assert!(problems.iter().any(|item| item.2 == ProblemKind::WrongNext));
+    }
+}